@@ -0,0 +1,35 @@
+//! ベンチマークで共有する入力局面。
+
+use othello_complexity_rs::othello::{flip, get_moves, has_move, Board};
+
+/// 初期局面から `games` 局を再現可能な乱択（xorshift64）で終局まで打ち、現れた局面を順に返す
+/// （パスした局面は、パス前と手番を入れ替えた後の両方を含む）
+pub fn playout_boards(games: usize) -> Vec<Board> {
+    let mut state = 0x9E37_79B9_7F4A_7C15u64;
+    let mut boards = vec![];
+    for _ in 0..games {
+        let mut b = Board::initial();
+        loop {
+            boards.push(b);
+            let moves = get_moves(b.player, b.opponent);
+            if moves == 0 {
+                if !has_move(b.opponent, b.player) {
+                    break;
+                }
+                b = Board::new(b.opponent, b.player);
+                continue;
+            }
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            let mut m = moves;
+            for _ in 0..state % moves.count_ones() as u64 {
+                m &= m - 1;
+            }
+            let pos = m.trailing_zeros() as usize;
+            let flipped = flip(pos, b.player, b.opponent);
+            b = Board::new(b.opponent ^ flipped, b.player ^ flipped ^ (1u64 << pos));
+        }
+    }
+    boards
+}
@@ -0,0 +1,46 @@
+//! `flip`（方向ごとのシフト）と表引きの `flip_tabled` の比較。`cargo bench --bench flip` で実行する。
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use othello_complexity_rs::othello::{flip, flip_tabled, get_moves};
+
+mod common;
+
+/// 初期局面からの再現可能な乱択プレイアウトで現れる局面と、その合法手
+fn sample_moves(games: usize) -> Vec<(usize, u64, u64)> {
+    let mut samples = vec![];
+    for b in common::playout_boards(games) {
+        let mut m = get_moves(b.player, b.opponent);
+        while m != 0 {
+            samples.push((m.trailing_zeros() as usize, b.player, b.opponent));
+            m &= m - 1;
+        }
+    }
+    samples
+}
+
+fn bench_flip(c: &mut Criterion) {
+    let samples = sample_moves(100);
+    assert!(samples
+        .iter()
+        .all(|&(pos, p, o)| flip(pos, p, o) == flip_tabled(pos, p, o)));
+
+    let mut group = c.benchmark_group("flip");
+    group.throughput(Throughput::Elements(samples.len() as u64));
+    group.bench_function("shift", |bench| {
+        bench.iter(|| {
+            for &(pos, p, o) in &samples {
+                black_box(flip(black_box(pos), black_box(p), black_box(o)));
+            }
+        })
+    });
+    group.bench_function("tabled", |bench| {
+        bench.iter(|| {
+            for &(pos, p, o) in &samples {
+                black_box(flip_tabled(black_box(pos), black_box(p), black_box(o)));
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_flip);
+criterion_main!(benches);
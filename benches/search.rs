@@ -0,0 +1,97 @@
+//! 探索 1 ノードあたりの `unique()` を 1 回にまとめた効果の計測。`cargo bench --bench search` で実行する。
+//!
+//! - `pass_orientation`: パス後の向きも登録する局面で、`unique_with_swapped()` と `unique()` 2 回を比べる
+//! - `forward_search` / `retrospective_search`: 固定の探索のノード処理速度（ノード/秒）
+use std::collections::HashSet;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use othello_complexity_rs::othello::Board;
+use othello_complexity_rs::search::core::{retrospective_search, search, Btable, SearchResult};
+
+/// 順方向探索の石数
+const FORWARD_DISCS: i32 = 10;
+/// 逆方向探索のリーフの石数
+const LEAF_DISCS: i32 = 8;
+
+fn forward(discs: i32) -> (HashSet<[u64; 2]>, HashSet<[u64; 2]>) {
+    let mut searched = HashSet::new();
+    let mut leafnode = HashSet::new();
+    search(&Board::initial(), &mut searched, &mut leafnode, discs);
+    (searched, leafnode)
+}
+
+fn bench_pass_orientation(c: &mut Criterion) {
+    let (_, leafnode) = forward(FORWARD_DISCS);
+    let boards: Vec<Board> = leafnode.iter().map(|&[p, o]| Board::new(p, o)).collect();
+
+    let mut group = c.benchmark_group("pass_orientation");
+    group.throughput(Throughput::Elements(boards.len() as u64));
+    group.bench_function("unique_with_swapped", |bench| {
+        bench.iter(|| {
+            for b in &boards {
+                black_box(black_box(b).unique_with_swapped());
+            }
+        })
+    });
+    group.bench_function("unique_twice", |bench| {
+        bench.iter(|| {
+            for b in &boards {
+                let b = black_box(b);
+                black_box((b.unique(), Board::new(b.opponent, b.player).unique()));
+            }
+        })
+    });
+    group.finish();
+}
+
+fn bench_forward_search(c: &mut Criterion) {
+    let (searched, leafnode) = forward(FORWARD_DISCS);
+
+    let mut group = c.benchmark_group("forward_search");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(
+        (searched.len() + leafnode.len()) as u64,
+    ));
+    group.bench_function(format!("discs_{}", FORWARD_DISCS), |bench| {
+        bench.iter(|| black_box(forward(FORWARD_DISCS)))
+    });
+    group.finish();
+}
+
+fn bench_retrospective_search(c: &mut Criterion) {
+    let (_, leafnode) = forward(LEAF_DISCS);
+    // f5 d6 c3 d3 c4 f4 f6 f3 e6 e7（14 石）
+    let board = Board::play_sequence(&[37, 43, 18, 19, 26, 29, 45, 21, 44, 52]).unwrap();
+    let run = || {
+        let mut searched = Btable::new(1 << 16, 1 << 8);
+        let mut retroflips = vec![];
+        let mut node_count = 0;
+        let result = retrospective_search(
+            &board,
+            false,
+            LEAF_DISCS,
+            &leafnode,
+            &mut searched,
+            &mut retroflips,
+            &mut node_count,
+            usize::MAX,
+        )
+        .unwrap();
+        (result, node_count)
+    };
+    let (result, nodes) = run();
+    assert_eq!(result, SearchResult::Found);
+
+    let mut group = c.benchmark_group("retrospective_search");
+    group.throughput(Throughput::Elements(nodes as u64));
+    group.bench_function("tiger_14_discs", |bench| bench.iter(|| black_box(run())));
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_pass_orientation,
+    bench_forward_search,
+    bench_retrospective_search
+);
+criterion_main!(benches);
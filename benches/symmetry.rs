@@ -0,0 +1,45 @@
+//! `Board::unique()` の 8 通りの対称形をまとめて求める実装と、`board_symmetry` を 1 つずつ
+//! 呼ぶ素朴な実装の比較。`cargo bench --bench symmetry` で実行する。
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use othello_complexity_rs::othello::Board;
+
+mod common;
+
+/// まとめて求める前の `unique()`（対称変換ごとに `board_symmetry` を呼ぶ）
+fn unique_by_loop(board: &Board) -> [u64; 2] {
+    let mut answer = [board.player, board.opponent];
+    let mut sym = [0u64; 2];
+    for s in 1..8 {
+        board.board_symmetry(s, &mut sym);
+        if sym < answer {
+            answer = sym;
+        }
+    }
+    answer
+}
+
+fn bench_unique(c: &mut Criterion) {
+    let boards = common::playout_boards(100);
+    assert!(boards.iter().all(|b| b.unique() == unique_by_loop(b)));
+
+    let mut group = c.benchmark_group("unique");
+    group.throughput(Throughput::Elements(boards.len() as u64));
+    group.bench_function("batched", |bench| {
+        bench.iter(|| {
+            for b in &boards {
+                black_box(black_box(b).unique());
+            }
+        })
+    });
+    group.bench_function("board_symmetry_loop", |bench| {
+        bench.iter(|| {
+            for b in &boards {
+                black_box(unique_by_loop(black_box(b)));
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_unique);
+criterion_main!(benches);
@@ -0,0 +1,40 @@
+//! `retrospective_flip` が固定長のバッファ `[u64; 10_000]` をはみ出さず、
+//! 返すひっくり返り集合がどれも `opponent` の部分集合であることを確かめる。
+//!
+//! 入力は先頭 8 バイトが `opponent`（リトルエンディアン）、9 バイト目が直前の着手位置 `pos`。
+//! `pos` が中央 4 マスなら読み飛ばし、そうでなければ `opponent` に `pos` の石を足して呼ぶ。
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use othello_complexity_rs::othello::CENTER_MASK;
+use othello_complexity_rs::search::core::retrospective_flip;
+
+fuzz_target!(|data: &[u8]| {
+    let Some((bytes, rest)) = data.split_first_chunk::<8>() else {
+        return;
+    };
+    let Some(&pos) = rest.first() else {
+        return;
+    };
+    let pos = (pos % 64) as u32;
+    if (1u64 << pos) & CENTER_MASK != 0 {
+        return;
+    }
+    let opponent = u64::from_le_bytes(*bytes) | (1u64 << pos);
+
+    let mut result = [0u64; 10_000];
+    let num = retrospective_flip(pos, !opponent, opponent, &mut result);
+    assert!(num <= result.len());
+    if num > 0 {
+        assert_eq!(result[0], 0);
+    }
+    for &flipped in result.iter().take(num).skip(1) {
+        assert_ne!(flipped, 0);
+        assert_eq!(
+            flipped & !opponent,
+            0,
+            "flips a square not held by opponent"
+        );
+        assert_eq!(flipped & (1u64 << pos), 0, "flips the placed disc");
+    }
+});
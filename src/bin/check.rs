@@ -4,12 +4,20 @@ use std::path::{Path, PathBuf};
 
 use clap::{Args, Parser, Subcommand};
 
-use othello_complexity_rs::io::parse_file_to_boards;
+use othello_complexity_rs::io::{
+    count_unique_boards, create_output, disc_count_histogram, parse_file_to_boards,
+    write_canonical_index,
+};
 use othello_complexity_rs::othello::Board;
 use othello_complexity_rs::prunings::{
-    connectivity::is_connected, kissat::is_sat_ok, linear_programming::check_lp,
-    occupancy::check_occupancy_with_string, seg3::check_seg3_more,
+    connectivity::is_connected,
+    kissat::{is_sat_ok_with, SatBackend},
+    linear_programming::{check_lp, LP_AVAILABLE},
+    occupancy::check_occupancy_explained,
+    seg3::check_seg3_more,
 };
+use othello_complexity_rs::verbosity::{set_verbosity, INFO};
+use othello_complexity_rs::vprintln;
 
 #[derive(Parser, Debug)]
 #[command(
@@ -30,6 +38,18 @@ struct CommonOpts {
     /// Input file(s) containing board positions
     #[arg(value_name = "INPUT")]
     inputs: Vec<PathBuf>,
+
+    /// Prefix of the result files instead of the check name (PREFIX_OK.txt, PREFIX_NG.txt, ...)
+    #[arg(long = "output-prefix", value_name = "PREFIX")]
+    output_prefix: Option<String>,
+
+    /// Overwrite result files that already exist instead of failing
+    #[arg(long)]
+    force: bool,
+
+    /// Verbosity level (0 = results only, 1 = info, 2 = debug)
+    #[arg(short, long, default_value_t = 1)]
+    verbose: u8,
 }
 
 #[derive(Args, Debug, Clone)]
@@ -42,6 +62,16 @@ struct LpOpts {
     ip: bool,
 }
 
+#[derive(Args, Debug, Clone)]
+struct SatOpts {
+    #[command(flatten)]
+    common: CommonOpts,
+
+    /// SAT solver backend (cadical/minisat require the matching cargo feature)
+    #[arg(long, value_enum, default_value_t = SatBackend::Kissat)]
+    solver: SatBackend,
+}
+
 #[derive(Subcommand, Debug)]
 enum Command {
     /// Connectivity check
@@ -53,9 +83,15 @@ enum Command {
     /// Seg3-more pruning check
     Seg3More(CommonOpts),
     /// SAT pruning check
-    Sat(CommonOpts),
+    Sat(SatOpts),
     /// Symmetry check
     Sym(CommonOpts),
+    /// Count distinct boards up to symmetry in each input file
+    Unique(CommonOpts),
+    /// Print how many boards each input file has per disc count
+    Discs(CommonOpts),
+    /// Write board<TAB>canonical board<TAB>symmetry index for each board (canon_index.txt)
+    Canon(CommonOpts),
 }
 
 fn resolve_out_dir(dir: &Option<PathBuf>) -> PathBuf {
@@ -63,9 +99,25 @@ fn resolve_out_dir(dir: &Option<PathBuf>) -> PathBuf {
         .unwrap_or_else(|| PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("result"))
 }
 
+/// 結果ファイルの置き場所と名前。ファイル名は `{接頭辞}_{suffix}.txt` で、
+/// 接頭辞は `--output-prefix` があればそれ、無ければチェックの名前。
+struct OutputFiles<'a> {
+    dir: &'a Path,
+    prefix: Option<&'a str>,
+    force: bool,
+}
+
+impl OutputFiles<'_> {
+    fn create(&self, name: &str, suffix: &str) -> io::Result<File> {
+        fs::create_dir_all(self.dir)?;
+        let prefix = self.prefix.unwrap_or(name);
+        create_output(&self.dir.join(format!("{prefix}_{suffix}.txt")), self.force)
+    }
+}
+
 fn process_inputs(
     opts: &CommonOpts,
-    mut f: impl FnMut(&Path, &Path) -> io::Result<()>,
+    mut f: impl FnMut(&Path, &OutputFiles) -> io::Result<()>,
 ) -> io::Result<()> {
     if opts.inputs.is_empty() {
         return Err(io::Error::new(
@@ -73,9 +125,18 @@ fn process_inputs(
             "at least one input file is required",
         ));
     }
+    set_verbosity(opts.verbose);
+    for (i, arg) in std::env::args().enumerate() {
+        vprintln!(INFO, "argv[{}] : {}", i, arg);
+    }
     let out_dir = resolve_out_dir(&opts.out_dir);
+    let out = OutputFiles {
+        dir: &out_dir,
+        prefix: opts.output_prefix.as_deref(),
+        force: opts.force,
+    };
     for input in &opts.inputs {
-        if let Err(e) = f(input, &out_dir) {
+        if let Err(e) = f(input, &out) {
             eprintln!("Error processing {}: {}", input.display(), e);
         }
     }
@@ -86,11 +147,10 @@ fn to_path_string(path: &Path) -> String {
     path.to_string_lossy().into_owned()
 }
 
-fn process_con_file(path: &Path, out_dir: &Path) -> io::Result<()> {
+fn process_con_file(path: &Path, out: &OutputFiles) -> io::Result<()> {
     let boards = parse_file_to_boards(&to_path_string(path))?;
-    fs::create_dir_all(out_dir)?;
-    let mut okfile = File::create(out_dir.join("con_OK.txt"))?;
-    let mut ngfile = File::create(out_dir.join("con_NG.txt"))?;
+    let mut okfile = out.create("con", "OK")?;
+    let mut ngfile = out.create("con", "NG")?;
 
     for board in boards {
         let line = board.to_string();
@@ -102,12 +162,11 @@ fn process_con_file(path: &Path, out_dir: &Path) -> io::Result<()> {
     Ok(())
 }
 
-fn process_lp_file(path: &Path, out_dir: &Path, by_ip_solver: bool) -> io::Result<()> {
+fn process_lp_file(path: &Path, out: &OutputFiles, by_ip_solver: bool) -> io::Result<()> {
     let boards = parse_file_to_boards(&to_path_string(path))?;
-    fs::create_dir_all(out_dir)?;
-    let prefix = if by_ip_solver { "ip" } else { "lp" };
-    let mut okfile = File::create(out_dir.join(format!("{prefix}_OK.txt")))?;
-    let mut ngfile = File::create(out_dir.join(format!("{prefix}_NG.txt")))?;
+    let name = if by_ip_solver { "ip" } else { "lp" };
+    let mut okfile = out.create(name, "OK")?;
+    let mut ngfile = out.create(name, "NG")?;
 
     for board in boards {
         let line = board.to_string();
@@ -120,16 +179,15 @@ fn process_lp_file(path: &Path, out_dir: &Path, by_ip_solver: bool) -> io::Resul
     Ok(())
 }
 
-fn process_occupancy_file(path: &Path, out_dir: &Path) -> io::Result<()> {
+fn process_occupancy_file(path: &Path, out: &OutputFiles) -> io::Result<()> {
     let boards = parse_file_to_boards(&to_path_string(path))?;
-    fs::create_dir_all(out_dir)?;
-    let mut okfile = File::create(out_dir.join("occupancy_OK.txt"))?;
-    let mut ngfile = File::create(out_dir.join("occupancy_NG.txt"))?;
-    let mut okfile_ex = File::create(out_dir.join("occupancy_OK_explainable.txt"))?;
-    let mut ngfile_ex = File::create(out_dir.join("occupancy_NG_explainable.txt"))?;
+    let mut okfile = out.create("occupancy", "OK")?;
+    let mut ngfile = out.create("occupancy", "NG")?;
+    let mut okfile_ex = out.create("occupancy", "OK_explainable")?;
+    let mut ngfile_ex = out.create("occupancy", "NG_explainable")?;
 
     for board in boards {
-        let (ok, text) = check_occupancy_with_string(board.player | board.opponent);
+        let (ok, text) = check_occupancy_explained(board.player | board.opponent);
         let line = board.to_string();
         if ok {
             writeln!(okfile, "{}", line)?;
@@ -142,11 +200,10 @@ fn process_occupancy_file(path: &Path, out_dir: &Path) -> io::Result<()> {
     Ok(())
 }
 
-fn process_seg3more_file(path: &Path, out_dir: &Path) -> io::Result<()> {
+fn process_seg3more_file(path: &Path, out: &OutputFiles) -> io::Result<()> {
     let boards = parse_file_to_boards(&to_path_string(path))?;
-    fs::create_dir_all(out_dir)?;
-    let mut okfile = File::create(out_dir.join("seg3more_OK.txt"))?;
-    let mut ngfile = File::create(out_dir.join("seg3more_NG.txt"))?;
+    let mut okfile = out.create("seg3more", "OK")?;
+    let mut ngfile = out.create("seg3more", "NG")?;
 
     for board in boards {
         let line = board.to_string();
@@ -159,15 +216,14 @@ fn process_seg3more_file(path: &Path, out_dir: &Path) -> io::Result<()> {
     Ok(())
 }
 
-fn process_sat_file(path: &Path, out_dir: &Path) -> io::Result<()> {
+fn process_sat_file(path: &Path, out: &OutputFiles, backend: SatBackend) -> io::Result<()> {
     let boards = parse_file_to_boards(&to_path_string(path))?;
-    fs::create_dir_all(out_dir)?;
-    let mut okfile = File::create(out_dir.join("sat_OK.txt"))?;
-    let mut ngfile = File::create(out_dir.join("sat_NG.txt"))?;
+    let mut okfile = out.create("sat", "OK")?;
+    let mut ngfile = out.create("sat", "NG")?;
 
     for (index, board) in boards.iter().enumerate() {
         let line = board.to_string();
-        match is_sat_ok(index, &line) {
+        match is_sat_ok_with(index, &line, backend) {
             Ok(true) => {
                 println!("SAT: {}", line);
                 writeln!(okfile, "{}", line)?;
@@ -184,11 +240,10 @@ fn process_sat_file(path: &Path, out_dir: &Path) -> io::Result<()> {
     Ok(())
 }
 
-fn process_sym_file(path: &Path, out_dir: &Path) -> io::Result<()> {
+fn process_sym_file(path: &Path, out: &OutputFiles) -> io::Result<()> {
     let boards = parse_file_to_boards(&to_path_string(path))?;
-    fs::create_dir_all(out_dir)?;
-    let mut okfile = File::create(out_dir.join("sym_OK.txt"))?;
-    let mut ngfile = File::create(out_dir.join("sym_NG.txt"))?;
+    let mut okfile = out.create("sym", "OK")?;
+    let mut ngfile = out.create("sym", "NG")?;
 
     for board in boards {
         let line = board.to_string();
@@ -222,20 +277,48 @@ fn is_sym_ok(board: &Board) -> io::Result<bool> {
 }
 
 fn main() {
-    for (i, arg) in std::env::args().enumerate() {
-        println!("argv[{}] : {}", i, arg);
-    }
-
     let cli = Cli::parse();
     let result = match cli.command {
         Command::Con(opts) => process_inputs(&opts, process_con_file),
-        Command::Lp(opts) => process_inputs(&opts.common, |path, out_dir| {
-            process_lp_file(path, out_dir, opts.ip)
+        Command::Lp(_) if !LP_AVAILABLE => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "built without LP support (rebuild with the `lp` feature)",
+        )),
+        Command::Lp(opts) => process_inputs(&opts.common, |path, out| {
+            process_lp_file(path, out, opts.ip)
         }),
         Command::Occupancy(opts) => process_inputs(&opts, process_occupancy_file),
         Command::Seg3More(opts) => process_inputs(&opts, process_seg3more_file),
-        Command::Sat(opts) => process_inputs(&opts, process_sat_file),
+        Command::Sat(opts) => process_inputs(&opts.common, |path, out| {
+            process_sat_file(path, out, opts.solver)
+        }),
         Command::Sym(opts) => process_inputs(&opts, process_sym_file),
+        Command::Unique(opts) => process_inputs(&opts, |path, _| {
+            let count = count_unique_boards(path)?;
+            println!("{}: {} distinct board(s)", path.display(), count);
+            Ok(())
+        }),
+        Command::Canon(opts) => process_inputs(&opts, |path, out| {
+            let mut file = out.create("canon", "index")?;
+            let written = write_canonical_index(path, &mut file)?;
+            vprintln!(INFO, "{}: {} board(s) indexed", path.display(), written);
+            Ok(())
+        }),
+        Command::Discs(opts) => process_inputs(&opts, |path, _| {
+            let histogram = disc_count_histogram(path)?;
+            let total: usize = histogram.values().sum();
+            println!("{}: {} board(s)", path.display(), total);
+            println!("{:>5}  {:>10}  {:>6}", "discs", "boards", "%");
+            for (discs, count) in &histogram {
+                println!(
+                    "{:>5}  {:>10}  {:>6.2}",
+                    discs,
+                    count,
+                    100.0 * *count as f64 / total as f64
+                );
+            }
+            Ok(())
+        }),
     };
 
     if let Err(e) = result {
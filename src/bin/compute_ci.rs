@@ -1,9 +1,11 @@
 use bigdecimal::{BigDecimal, FromPrimitive};
 use clap::Parser;
-use statrs::distribution::{ContinuousCDF, Normal};
 use std::error::Error;
+use std::path::PathBuf;
 
-const POPULATION_SIZE: u128 = 3_u128.pow(60) * 2_u128.pow(4);
+use othello_complexity_rs::io::{OutputNames, ResultSummary};
+use othello_complexity_rs::sampling::{population_size, ALL_STATES as POPULATION_SIZE};
+use othello_complexity_rs::stats::reachability_interval;
 
 #[derive(Debug, Parser)]
 #[command(
@@ -12,92 +14,81 @@ const POPULATION_SIZE: u128 = 3_u128.pow(60) * 2_u128.pow(4);
 )]
 struct Args {
     /// Count of observed successes
-    #[arg(long)]
-    ok: u64,
+    #[arg(
+        long,
+        required_unless_present = "from_dir",
+        conflicts_with = "from_dir"
+    )]
+    ok: Option<u64>,
 
     /// Count of observed failures
-    #[arg(long)]
-    ng: u64,
+    #[arg(
+        long,
+        required_unless_present = "from_dir",
+        conflicts_with = "from_dir"
+    )]
+    ng: Option<u64>,
 
     /// Count of samples with unknown outcome
-    #[arg(long)]
-    unknown: u64,
+    #[arg(
+        long,
+        required_unless_present = "from_dir",
+        conflicts_with = "from_dir"
+    )]
+    unknown: Option<u64>,
+
+    /// Read the counts from PREFIX_summary.bin in DIR (written by reverse_to_initial --summary)
+    #[arg(long = "from-dir", value_name = "DIR")]
+    from_dir: Option<PathBuf>,
+
+    /// Prefix of the summary file read by --from-dir
+    #[arg(
+        long = "output-prefix",
+        value_name = "PREFIX",
+        requires = "from_dir",
+        default_value = OutputNames::DEFAULT_PREFIX
+    )]
+    output_prefix: String,
+
+    /// Use only the boards with N discs and the population of N-disc boards (for samples drawn with N - 4 stones)
+    #[arg(long, value_name = "N", requires = "from_dir", value_parser = clap::value_parser!(u32).range(5..=64))]
+    discs: Option<u32>,
 
     /// Significance level (two-sided alpha); e.g. 0.005 for 99.5% CI
     #[arg(long, default_value_t = 0.005)]
     alpha: f64,
 }
 
-#[derive(Debug)]
-struct WilsonCI {
-    ok: u64,
-    ng: u64,
-    unknown: u64,
-    alpha: f64,
-}
-
-impl WilsonCI {
-    fn compute(&self) -> Result<(f64, f64, f64), Box<dyn Error>> {
-        self.validate()?;
-        let n = (self.ok + self.ng + self.unknown) as f64;
-
-        let normal = Normal::new(0.0, 1.0).unwrap();
-        let z = normal.inverse_cdf(1.0 - self.alpha / 2.0);
-
-        let lower = wilson_lower(self.ok as f64, n, z);
-        let upper = wilson_upper((self.ok + self.unknown) as f64, n, z);
-        let conf_level = 100.0 * (1.0 - self.alpha);
-        Ok((lower, upper, conf_level))
-    }
-
-    fn validate(&self) -> Result<(), Box<dyn Error>> {
-        if self.ok == 0 && self.ng == 0 && self.unknown == 0 {
-            return Err("Sample size N = ok + ng + unknown must be > 0.".into());
-        }
-        if self.ok + self.unknown > self.ok + self.ng + self.unknown {
-            return Err("Counts inconsistent: require ok+unknown ≤ N.".into());
-        }
-        if self.alpha <= 0.0 || self.alpha >= 1.0 {
-            return Err("alpha must be in (0,1).".into());
-        }
-        println!("Sample size = {}", self.ok + self.ng + self.unknown);
-        Ok(())
-    }
-}
-
-/// source:
-/// - https://en.wikipedia.org/wiki/Binomial_proportion_confidence_interval#Wilson_score_interval
-/// - https://www.itl.nist.gov/div898/handbook/prc/section2/prc241.htm
-fn wilson_bounds(x: f64, n: f64, z: f64) -> (f64, f64) {
-    let p_hat = x / n;
-    let z2 = z * z;
-    let denom = 1.0 + z2 / n;
-    let center = p_hat + z2 / (2.0 * n);
-    let rad = z * ((p_hat * (1.0 - p_hat)) / n + z2 / (4.0 * n * n)).sqrt();
-    let lower = (center - rad) / denom;
-    let upper = (center + rad) / denom;
-    (lower, upper)
-}
-
-fn wilson_lower(x: f64, n: f64, z: f64) -> f64 {
-    wilson_bounds(x, n, z).0
-}
-
-fn wilson_upper(x: f64, n: f64, z: f64) -> f64 {
-    wilson_bounds(x, n, z).1
-}
-
 fn main() -> Result<(), Box<dyn Error>> {
     let args = Args::parse();
-    let (lower, upper, conf_level) = WilsonCI {
-        ok: args.ok,
-        ng: args.ng,
-        unknown: args.unknown,
-        alpha: args.alpha,
-    }
-    .compute()?;
-
-    let population = BigDecimal::from(POPULATION_SIZE);
+    let ([ok, ng, unknown], population) = match &args.from_dir {
+        Some(dir) => {
+            let path = OutputNames::new(args.output_prefix.clone(), false).summary_path(dir);
+            let summary = ResultSummary::load(&path)
+                .map_err(|e| format!("failed to read '{}': {}", path.display(), e))?;
+            let (counts, population) = match args.discs {
+                Some(discs) => (summary.level(discs), population_size(discs as usize - 4)),
+                None => (summary.totals(), POPULATION_SIZE),
+            };
+            println!(
+                "Counts from '{}': OK = {}, NG = {}, UNKNOWN = {}",
+                path.display(),
+                counts[0],
+                counts[1],
+                counts[2]
+            );
+            (counts, population)
+        }
+        None => (
+            [args.ok.unwrap(), args.ng.unwrap(), args.unknown.unwrap()],
+            POPULATION_SIZE,
+        ),
+    };
+    let interval = reachability_interval(ok, ng, unknown, args.alpha)?;
+    let (lower, upper) = (interval.lower, interval.upper);
+    println!("Sample size = {}", ok + ng + unknown);
+
+    let population = BigDecimal::from(population);
     let expected_lower = BigDecimal::from_f64(lower)
         .ok_or("failed to convert lower bound to BigDecimal")?
         * &population;
@@ -105,7 +96,10 @@ fn main() -> Result<(), Box<dyn Error>> {
         .ok_or("failed to convert upper bound to BigDecimal")?
         * &population;
 
-    println!("{}% Wilson CI: [{:.6}, {:.6}]", conf_level, lower, upper);
+    println!(
+        "{}% Wilson CI: [{:.6}, {:.6}]",
+        interval.conf_level, lower, upper
+    );
     println!(
         "Expected |R| interval: [{:.6e}, {:.6e}]",
         expected_lower, expected_upper
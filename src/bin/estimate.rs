@@ -0,0 +1,193 @@
+use std::error::Error;
+use std::path::PathBuf;
+
+use bigdecimal::{BigDecimal, FromPrimitive};
+use clap::Parser;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use othello_complexity_rs::io::{ensure_outputs, OutputNames};
+use othello_complexity_rs::othello::Ruleset;
+use othello_complexity_rs::sampling::{population_size, random_board};
+use othello_complexity_rs::search::core::{Btable, SearchResult};
+use othello_complexity_rs::search::parallel_dfs::init_rayon;
+use othello_complexity_rs::search::reverse_common::{
+    load_or_build_leaf_cache, search_with_strategy, Strategy,
+};
+use othello_complexity_rs::stats::reachability_interval;
+use othello_complexity_rs::verbosity::{set_verbosity, INFO};
+use othello_complexity_rs::vprintln;
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "estimate",
+    about = "Sample random boards, run the reverse search on each and print the Wilson CI for |R|"
+)]
+struct Args {
+    /// Number of squares outside the center to fill (0 = sample from all states)
+    #[arg(short = 'n', long, default_value_t = 0)]
+    stones: usize,
+
+    /// Number of boards to sample
+    #[arg(short = 'c', long, default_value_t = 1000)]
+    count: usize,
+
+    /// Seed for the board sampler (random if omitted; the seed used is printed)
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Search strategy
+    #[arg(long, value_enum, default_value_t = Strategy::Dfs)]
+    strategy: Strategy,
+
+    /// Number of discs at which to stop the forward search
+    #[arg(long, default_value_t = 10)]
+    discs: i32,
+
+    /// Maximum number of nodes to expand per board
+    #[arg(long = "max-nodes", value_name = "N", default_value_t = 1_000_000)]
+    max_nodes: usize,
+
+    /// Capacity of the visited-position table
+    #[arg(long = "table-size", value_name = "N", default_value_t = 0x1000_0000)]
+    table_size: usize,
+
+    /// Load the forward leaf table from FILE (built and saved there if missing)
+    #[arg(long = "leaf-cache", value_name = "FILE")]
+    leaf_cache: Option<PathBuf>,
+
+    /// Also write the sampled boards to reverse_{OK,NG,UNKNOWN}.txt under DIR
+    #[arg(short, long, value_name = "DIR")]
+    out_dir: Option<PathBuf>,
+
+    /// Prefix of the result files written under --out-dir
+    #[arg(
+        long = "output-prefix",
+        value_name = "PREFIX",
+        default_value = OutputNames::DEFAULT_PREFIX,
+        requires = "out_dir"
+    )]
+    output_prefix: String,
+
+    /// Overwrite result files that already exist instead of failing
+    #[arg(long, requires = "out_dir")]
+    force: bool,
+
+    /// Significance level (two-sided alpha); e.g. 0.005 for 99.5% CI
+    #[arg(long, default_value_t = 0.005)]
+    alpha: f64,
+
+    /// Verbosity level (0 = results only, 1 = info, 2 = debug)
+    #[arg(short, long, default_value_t = 1)]
+    verbose: u8,
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args = Args::parse();
+    set_verbosity(args.verbose);
+    if args.stones > 60 {
+        return Err("--stones must be at most 60.".into());
+    }
+    // しきい値より石の少ない盤面はリーフ表に無いので、探索すると必ず NG になる
+    if args.stones > 0 && args.discs > args.stones as i32 + 4 {
+        return Err(format!(
+            "--discs ({}) must not exceed the number of discs on the sampled boards ({}).",
+            args.discs,
+            args.stones + 4
+        )
+        .into());
+    }
+    if args.count == 0 {
+        return Err("--count must be > 0.".into());
+    }
+    if args.alpha <= 0.0 || args.alpha >= 1.0 {
+        return Err("alpha must be in (0,1).".into());
+    }
+
+    let seed = args.seed.unwrap_or_else(|| rand::rng().random());
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    let leaf_cache =
+        load_or_build_leaf_cache(args.leaf_cache.as_deref(), args.discs, &Ruleset::standard())?;
+    vprintln!(
+        INFO,
+        "info: discs = {}: internal = {}, leaf = {}",
+        args.discs,
+        leaf_cache.searched_count(),
+        leaf_cache.leaf_count()
+    );
+    if args.strategy == Strategy::Parallel {
+        init_rayon(None);
+    }
+    OutputNames::new(args.output_prefix.clone(), args.force).install();
+    let mut outputs = match &args.out_dir {
+        Some(dir) => Some(ensure_outputs(dir)?),
+        None => None,
+    };
+
+    let mut retrospective_searched = Btable::new(args.table_size, 0x10000);
+    let mut retroflips: Vec<[u64; 10_000]> = vec![];
+    let (mut ok, mut ng, mut unknown) = (0u64, 0u64, 0u64);
+    for i in 0..args.count {
+        let board = random_board(&mut rng, args.stones);
+        // 中央が空いている盤面は到達不能なので NG として数える
+        let result = search_with_strategy(
+            args.strategy,
+            &board,
+            args.discs,
+            leaf_cache.leaf(),
+            &mut retrospective_searched,
+            &mut retroflips,
+            args.max_nodes,
+            args.table_size,
+        )
+        .map_or(SearchResult::NotFound, |(result, _)| result);
+        match result {
+            SearchResult::Found => ok += 1,
+            SearchResult::NotFound => ng += 1,
+            SearchResult::Unknown => unknown += 1,
+        }
+        if let Some(outputs) = outputs.as_mut() {
+            outputs.write_result(result, &board, &board.to_string())?;
+        }
+        if (i + 1) % 100 == 0 {
+            vprintln!(
+                INFO,
+                "info: {}/{} board(s): OK = {}, NG = {}, UNKNOWN = {}",
+                i + 1,
+                args.count,
+                ok,
+                ng,
+                unknown
+            );
+        }
+    }
+    if let Some(outputs) = outputs.as_mut() {
+        outputs.flush()?;
+    }
+
+    // UNKNOWN は下限では NG、上限では OK として数える（compute_ci と同じ）
+    let interval = reachability_interval(ok, ng, unknown, args.alpha)?;
+    let (lower, upper, conf_level) = (interval.lower, interval.upper, interval.conf_level);
+
+    let population = BigDecimal::from(population_size(args.stones));
+    let expected_lower = BigDecimal::from_f64(lower)
+        .ok_or("failed to convert lower bound to BigDecimal")?
+        * &population;
+    let expected_upper = BigDecimal::from_f64(upper)
+        .ok_or("failed to convert upper bound to BigDecimal")?
+        * &population;
+
+    println!("Seed = {}", seed);
+    println!(
+        "Sample size = {} (OK = {}, NG = {}, UNKNOWN = {})",
+        args.count, ok, ng, unknown
+    );
+    println!("Population size = {}", population);
+    println!("{}% Wilson CI: [{:.6}, {:.6}]", conf_level, lower, upper);
+    println!(
+        "Expected |R| interval: [{:.6e}, {:.6e}]",
+        expected_lower, expected_upper
+    );
+    Ok(())
+}
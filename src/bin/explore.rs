@@ -0,0 +1,218 @@
+use std::io::{self, BufRead, Write};
+
+use othello_complexity_rs::io::boards_from_transcript;
+use othello_complexity_rs::othello::{get_moves, mobility, validate_board, Board};
+use othello_complexity_rs::prunings::connectivity::is_connected;
+use othello_complexity_rs::prunings::occupancy::check_occupancy;
+use othello_complexity_rs::prunings::seg3::check_seg3_more;
+use othello_complexity_rs::search::core::one_step_predecessors;
+
+const HELP: &str = "\
+Enter a board as
+  - a 64-character line of X/O/- (X = side to move), or
+  - 8 lines of 8 X/O/- characters (the grid printed by show()), or
+  - a move transcript such as f5d6c3 (the position after the last move).
+Commands: help, quit";
+
+/// マス番号を `a1`〜`h8` の座標に変換する
+fn square_name(pos: usize) -> String {
+    format!("{}{}", (b'a' + (pos % 8) as u8) as char, pos / 8 + 1)
+}
+
+/// ビットボードの立っているマスを座標の列にする
+fn squares(mut bits: u64) -> String {
+    let mut names = Vec::new();
+    while bits != 0 {
+        names.push(square_name(bits.trailing_zeros() as usize));
+        bits &= bits - 1;
+    }
+    if names.is_empty() {
+        "-".to_string()
+    } else {
+        names.join(" ")
+    }
+}
+
+/// 盤面 1 つについての要約を組み立てる
+fn describe(board: &Board) -> String {
+    let mut out = board.show();
+    if let Err(e) = validate_board(board) {
+        out.push_str(&format!("invalid board: {}\n", e));
+        return out;
+    }
+    let occupied = board.player | board.opponent;
+    out.push_str(&format!(
+        "discs: {} (X {}, O {})\n",
+        occupied.count_ones(),
+        board.player.count_ones(),
+        board.opponent.count_ones()
+    ));
+    out.push_str(&format!(
+        "mobility: X {} [{}], O {} [{}]\n",
+        mobility(board.player, board.opponent),
+        squares(get_moves(board.player, board.opponent)),
+        mobility(board.opponent, board.player),
+        squares(get_moves(board.opponent, board.player))
+    ));
+    let occupancy = check_occupancy(occupied);
+    // check_seg3_more は占有到達性を満たす盤面でしか判定できない
+    let seg3 = occupancy.then(|| check_seg3_more(board.player, board.opponent));
+    let connected = is_connected(occupied);
+    out.push_str(&format!("check_occupancy: {}\n", occupancy));
+    out.push_str(&format!(
+        "check_seg3_more: {}\n",
+        seg3.map_or("skipped".to_string(), |seg3| seg3.to_string())
+    ));
+    out.push_str(&format!("is_connected: {}\n", connected));
+    out.push_str(&format!(
+        "prunings: {}\n",
+        if occupancy && seg3 == Some(true) && connected {
+            "pass"
+        } else {
+            "pruned"
+        }
+    ));
+    let preds = one_step_predecessors(board);
+    let mut moves: Vec<usize> = preds.iter().map(|&(_, pos)| pos).collect();
+    moves.dedup();
+    out.push_str(&format!(
+        "predecessors: {} (last move at: {})\n",
+        preds.len(),
+        if moves.is_empty() {
+            "-".to_string()
+        } else {
+            moves
+                .iter()
+                .map(|&pos| square_name(pos))
+                .collect::<Vec<_>>()
+                .join(" ")
+        }
+    ));
+    out
+}
+
+/// 入力行を解釈して応答を返す REPL の本体。
+///
+/// 8 行のグリッドは 64 マス分たまるまで溜めておき、その間は `None` を返す。
+#[derive(Default)]
+struct Explorer {
+    pending: String,
+}
+
+impl Explorer {
+    fn handle_line(&mut self, line: &str) -> Option<String> {
+        let line = line.trim();
+        if line.is_empty() {
+            return None;
+        }
+        if line
+            .chars()
+            .all(|c| matches!(c, 'X' | 'O' | '-') || c.is_whitespace())
+        {
+            self.pending.push_str(line);
+            let cells = self.pending.chars().filter(|c| !c.is_whitespace()).count();
+            if cells < 64 {
+                return None;
+            }
+            let grid = std::mem::take(&mut self.pending);
+            return Some(match Board::parse(&grid) {
+                Some(board) => describe(&board),
+                None => format!("expected 64 cells, got {}\n", cells),
+            });
+        }
+        if !self.pending.is_empty() {
+            self.pending.clear();
+            return Some("incomplete grid discarded\n".to_string());
+        }
+        if matches!(line, "help" | "?") {
+            return Some(format!("{}\n", HELP));
+        }
+        Some(match boards_from_transcript(line) {
+            Ok(boards) => describe(boards.last().unwrap_or(&Board::initial())),
+            Err(e) => format!("error: {}\n", e),
+        })
+    }
+}
+
+fn main() -> io::Result<()> {
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+    let mut explorer = Explorer::default();
+    eprintln!("{}", HELP);
+    for line in stdin.lock().lines() {
+        let line = line?;
+        if matches!(line.trim(), "quit" | "exit") {
+            break;
+        }
+        if let Some(reply) = explorer.handle_line(&line) {
+            writeln!(stdout, "{}", reply)?;
+            stdout.flush()?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grid_lines_are_collected_until_64_cells() {
+        let board = Board::play_sequence(&[37, 43, 18]).unwrap();
+        let mut explorer = Explorer::default();
+        let rows: Vec<String> = board
+            .to_string()
+            .as_bytes()
+            .chunks(8)
+            .map(|row| String::from_utf8(row.to_vec()).unwrap())
+            .collect();
+        for row in &rows[..7] {
+            assert_eq!(explorer.handle_line(row), None);
+        }
+        assert_eq!(explorer.handle_line(&rows[7]), Some(describe(&board)));
+        // 1 行の 64 文字でも、棋譜でも同じ盤面になる
+        assert_eq!(
+            explorer.handle_line(&board.to_string()),
+            Some(describe(&board))
+        );
+        assert_eq!(explorer.handle_line("f5d6c3"), Some(describe(&board)));
+    }
+
+    #[test]
+    fn other_input_is_answered_without_a_board() {
+        let mut explorer = Explorer::default();
+        assert_eq!(explorer.handle_line("   "), None);
+        assert_eq!(explorer.handle_line("XO--"), None);
+        assert_eq!(
+            explorer.handle_line("help").as_deref(),
+            Some("incomplete grid discarded\n")
+        );
+        assert_eq!(explorer.handle_line("help"), Some(format!("{}\n", HELP)));
+        let reply = explorer.handle_line("f5z9").unwrap();
+        assert!(reply.starts_with("error: "), "{}", reply);
+    }
+
+    #[test]
+    fn description_reports_moves_prunings_and_predecessors() {
+        let reply = describe(&Board::play_sequence(&[37]).unwrap());
+        assert!(reply.contains("discs: 5 (X 1, O 4)\n"), "{}", reply);
+        assert!(
+            reply.contains("mobility: X 3 [f4 d6 f6], O 3 [c3 d3 c4]\n"),
+            "{}",
+            reply
+        );
+        assert!(reply.contains("prunings: pass\n"), "{}", reply);
+        assert!(reply.contains("(last move at: f5)\n"), "{}", reply);
+
+        // 中央から離れた隅の石は占有到達性で落ち、反転整合性は調べない
+        let initial = Board::initial();
+        let detached = Board::new(initial.player, initial.opponent | 1);
+        let reply = describe(&detached);
+        assert!(reply.contains("check_occupancy: false\n"), "{}", reply);
+        assert!(reply.contains("check_seg3_more: skipped\n"), "{}", reply);
+        assert!(reply.contains("prunings: pruned\n"), "{}", reply);
+
+        let overlap = Board::new(initial.player | 1, initial.opponent | 1);
+        assert!(describe(&overlap).contains("invalid board: "));
+    }
+}
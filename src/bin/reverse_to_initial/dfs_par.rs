@@ -7,6 +7,7 @@ use othello_complexity_rs::search::reverse_common::{
     default_input_path, default_out_dir, read_env_with_default, run_parallel_dfs,
 };
 
+use othello_complexity_rs::verbosity::set_verbosity;
 #[derive(Parser, Debug)]
 #[command(
     name = "reverse_to_initial_par",
@@ -36,9 +37,18 @@ struct Cli {
     /// Number of rayon worker threads (0 = default)
     #[arg(long, value_name = "N")]
     threads: Option<usize>,
+
+    /// Load the forward leaf table from FILE (built and saved there if missing)
+    #[arg(long = "leaf-cache", value_name = "FILE")]
+    leaf_cache: Option<PathBuf>,
+
+    /// Verbosity level (0 = results only, 1 = info, 2 = debug)
+    #[arg(short, long, default_value_t = 1)]
+    verbose: u8,
 }
 
 fn run(cli: Cli) -> io::Result<()> {
+    set_verbosity(cli.verbose);
     let input = cli.input.unwrap_or_else(default_input_path);
     let out_dir = cli.out_dir.unwrap_or_else(default_out_dir);
     let discs = cli
@@ -59,7 +69,15 @@ fn run(cli: Cli) -> io::Result<()> {
         Some(thread_setting)
     };
 
-    run_parallel_dfs(&input, &out_dir, discs, max_nodes, table_size, threads)
+    run_parallel_dfs(
+        std::slice::from_ref(&input),
+        &out_dir,
+        discs,
+        max_nodes,
+        table_size,
+        threads,
+        cli.leaf_cache.as_deref(),
+    )
 }
 
 fn main() {
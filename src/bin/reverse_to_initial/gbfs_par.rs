@@ -3,10 +3,13 @@ use std::path::PathBuf;
 
 use clap::Parser;
 
+use othello_complexity_rs::prunings::{Pruning, PruningConfig};
+use othello_complexity_rs::search::move_ordering::Heuristic;
 use othello_complexity_rs::search::reverse_common::{
     default_input_path, default_out_dir, read_env_with_default, run_parallel_gbfs,
 };
 
+use othello_complexity_rs::verbosity::set_verbosity;
 #[derive(Parser, Debug)]
 #[command(
     name = "reverse_to_initial_par",
@@ -29,16 +32,46 @@ struct Cli {
     #[arg(long = "max-nodes", value_name = "N")]
     max_nodes: Option<usize>,
 
-    /// Table size hint for the transposition table
+    /// Use LP-solver for pruning (same as adding lp to --prunings)
     #[arg(long)]
     use_lp: bool,
 
+    /// Prunings applied during the search (lp solves an LP for every expanded node)
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        default_values_t = [Pruning::Occupancy, Pruning::Seg3]
+    )]
+    prunings: Vec<Pruning>,
+
     /// Number of rayon worker threads (0 = default)
     #[arg(long, value_name = "N")]
     threads: Option<usize>,
+
+    /// Dump the GBFS frontier and visited set under DIR (resumes from them if present)
+    #[arg(long = "frontier-dir", value_name = "DIR")]
+    frontier_dir: Option<PathBuf>,
+
+    /// Expanded nodes between frontier dumps (0 = dump only when the node limit is hit)
+    #[arg(long = "dump-interval", value_name = "N", default_value_t = 0)]
+    dump_interval: usize,
+
+    /// Heuristic used to order the priority queue
+    #[arg(long, value_enum, default_value_t = Heuristic::Pow2)]
+    heuristic: Heuristic,
+
+    /// Stop queueing positions with a given disc count once N of them have been queued (undecided boards become UNKNOWN)
+    #[arg(long = "limit-per-disc", value_name = "N")]
+    limit_per_disc: Option<usize>,
+
+    /// Verbosity level (0 = results only, 1 = info, 2 = debug)
+    #[arg(short, long, default_value_t = 1)]
+    verbose: u8,
 }
 
 fn run(cli: Cli) -> io::Result<()> {
+    set_verbosity(cli.verbose);
     let input = cli.input.unwrap_or_else(default_input_path);
     let out_dir = cli.out_dir.unwrap_or_else(default_out_dir);
     let discs = cli
@@ -47,7 +80,8 @@ fn run(cli: Cli) -> io::Result<()> {
     let max_nodes = cli
         .max_nodes
         .unwrap_or_else(|| read_env_with_default("MAX_NODES", 1_000_000usize));
-    let use_lp = cli.use_lp;
+    let mut prunings = PruningConfig::from_list(&cli.prunings);
+    prunings.lp |= cli.use_lp;
     let thread_setting = cli
         .threads
         .unwrap_or_else(|| read_env_with_default("RAYON_THREADS", 60usize));
@@ -57,7 +91,18 @@ fn run(cli: Cli) -> io::Result<()> {
         Some(thread_setting)
     };
 
-    run_parallel_gbfs(&input, &out_dir, discs, max_nodes, use_lp, threads)
+    run_parallel_gbfs(
+        std::slice::from_ref(&input),
+        &out_dir,
+        discs,
+        max_nodes,
+        prunings,
+        threads,
+        cli.frontier_dir.as_deref(),
+        cli.dump_interval,
+        cli.heuristic,
+        cli.limit_per_disc,
+    )
 }
 
 fn main() {
@@ -1,13 +1,28 @@
+use std::collections::HashSet;
 use std::io;
 use std::path::PathBuf;
+use std::time::{Duration, Instant};
 
+use clap::builder::{PossibleValuesParser, TypedValueParser};
 use clap::{Args, Parser, Subcommand};
 
-use othello_complexity_rs::search::bfs::Cfg as BfsCfg;
+use othello_complexity_rs::io::{
+    canonicalize_outputs, check_expected, write_hard_corpus, OutputNames,
+};
+use othello_complexity_rs::othello::Ruleset;
+use othello_complexity_rs::prunings::{Pruning, PruningConfig};
+use othello_complexity_rs::search::bfs::{Cfg as BfsCfg, DEFAULT_BLOCK_SIZE, DEFAULT_DISCS};
+use othello_complexity_rs::search::core::SearchResult;
+use othello_complexity_rs::search::discs_auto::AUTO_DISCS_SAMPLES;
+use othello_complexity_rs::search::move_ordering::Heuristic;
 use othello_complexity_rs::search::reverse_common::{
-    default_input_path, default_out_dir, read_env_with_default, run_bfs, run_dfs,
-    run_dfs_move_ordering, run_parallel_bfs, run_parallel_dfs, run_parallel_gbfs,
+    default_input_path, default_out_dir, read_env_with_default, run_bfs, run_compare, run_dfs,
+    run_dfs_discs_auto, run_dfs_escalating, run_dfs_move_ordering, run_dfs_sized, run_parallel_bfs,
+    run_parallel_dfs, run_parallel_gbfs, RunMeta, SlowBoardLog, Strategy,
 };
+use othello_complexity_rs::search::threads::ThreadConfig;
+use othello_complexity_rs::verbosity::{set_verbosity, INFO};
+use othello_complexity_rs::vprintln;
 
 #[derive(Parser, Debug)]
 #[command(
@@ -19,12 +34,41 @@ use othello_complexity_rs::search::reverse_common::{
 pub struct Cli {
     #[command(subcommand)]
     command: Command,
+
+    /// Merge symmetric boards in the OK/NG/UNKNOWN files into one line per class with its multiplicity
+    #[arg(long, global = true)]
+    canonical: bool,
+
+    /// Compare verdicts with the EXPECTED labels of `BOARD<TAB>EXPECTED` input lines; exit nonzero on mismatch
+    #[arg(long = "check-expected", global = true)]
+    check_expected: bool,
+
+    /// Prefix of the result files (PREFIX_OK.txt, PREFIX_NG.txt, PREFIX_UNKNOWN.txt, PREFIX_OK_leaf.txt)
+    #[arg(
+        long = "output-prefix",
+        value_name = "PREFIX",
+        global = true,
+        default_value = OutputNames::DEFAULT_PREFIX
+    )]
+    output_prefix: String,
+
+    /// Overwrite result files that already exist instead of failing
+    #[arg(long, global = true)]
+    force: bool,
+
+    /// Also write PREFIX_summary.bin with the OK/NG/UNKNOWN counts per disc count (read by compute_ci --from-dir)
+    #[arg(long, global = true)]
+    summary: bool,
+
+    /// Report boards whose dfs / dfs-move-ordering / dfs-parallel search takes longer than SECS (with disc and node counts)
+    #[arg(long = "slow-board-secs", value_name = "SECS", global = true)]
+    slow_board_secs: Option<f64>,
 }
 
-#[derive(Subcommand, Debug)]
+#[derive(Subcommand, Debug, Clone)]
 pub enum Command {
     /// Sequential depth-first reverse search (default implementation)
-    Dfs(BasicOpts),
+    Dfs(DfsOpts),
     /// Sequential reverse search with move ordering heuristics
     #[command(name = "dfs-move-ordering")]
     MoveOrdering(BasicOpts),
@@ -39,18 +83,24 @@ pub enum Command {
     /// Parallel BFS search with resume support
     #[command(name = "bfs-parallel")]
     BfsPar(BfsArgs),
+    /// Run several strategies on each board and tabulate verdicts, nodes and time
+    Compare(CompareOpts),
 }
 
 #[derive(Args, Debug, Clone)]
 pub struct BasicOpts {
-    /// Input file containing board positions
+    /// Input files containing board positions (their boards are judged together into one result set)
     #[arg(value_name = "INPUT")]
-    input: Option<PathBuf>,
+    inputs: Vec<PathBuf>,
 
     /// Output directory for result files
     #[arg(short, long, value_name = "DIR")]
     out_dir: Option<PathBuf>,
 
+    /// Write the results of each INPUT to its own subdirectory OUT_DIR/<file stem> instead of one aggregated set
+    #[arg(long = "per-file")]
+    per_file: bool,
+
     /// Number of discs at which to stop the forward search
     #[arg(long, value_name = "N")]
     discs: Option<i32>,
@@ -58,11 +108,24 @@ pub struct BasicOpts {
     /// Maximum number of nodes to explore in reverse search
     #[arg(long = "max-nodes", value_name = "N")]
     max_nodes: Option<usize>,
+
+    /// Load the forward leaf table from FILE (built and saved there if missing)
+    #[arg(long = "leaf-cache", value_name = "FILE")]
+    leaf_cache: Option<PathBuf>,
+
+    /// Verbosity level (0 = results only, 1 = info, 2 = debug)
+    #[arg(short, long, default_value_t = 1)]
+    verbose: u8,
 }
 
 impl BasicOpts {
-    fn resolve(&self) -> (PathBuf, PathBuf, i32, usize) {
-        let input = self.input.clone().unwrap_or_else(default_input_path);
+    fn resolve(&self) -> (Vec<PathBuf>, PathBuf, i32, usize) {
+        set_verbosity(self.verbose);
+        let inputs = if self.inputs.is_empty() {
+            vec![default_input_path()]
+        } else {
+            self.inputs.clone()
+        };
         let out_dir = self.out_dir.clone().unwrap_or_else(default_out_dir);
         let discs = self
             .discs
@@ -70,10 +133,85 @@ impl BasicOpts {
         let max_nodes = self
             .max_nodes
             .unwrap_or_else(|| read_env_with_default("MAX_NODES", 1_000_000usize));
-        (input, out_dir, discs, max_nodes)
+        (inputs, out_dir, discs, max_nodes)
     }
 }
 
+#[derive(Args, Debug, Clone)]
+pub struct DfsOpts {
+    #[command(flatten)]
+    basic: BasicOpts,
+
+    /// Pick the forward-search threshold per board by sampling forward/reverse tree sizes
+    #[arg(long = "discs-auto", conflicts_with_all = ["discs", "leaf_cache"])]
+    discs_auto: bool,
+
+    /// Number of random paths sampled per board to estimate the costs for --discs-auto
+    #[arg(
+        long = "discs-auto-samples",
+        value_name = "N",
+        default_value_t = AUTO_DISCS_SAMPLES,
+        requires = "discs_auto"
+    )]
+    discs_auto_samples: usize,
+
+    /// Seed for the --discs-auto sampling (random if omitted; the seed used is logged at -v 1)
+    #[arg(long = "discs-auto-seed", value_name = "SEED", requires = "discs_auto")]
+    discs_auto_seed: Option<u64>,
+
+    /// Re-run only the Unknown boards with an escalated node limit, up to N
+    #[arg(long = "escalate-to", value_name = "N", conflicts_with = "discs_auto")]
+    escalate_to: Option<usize>,
+
+    /// Multiply the node limit by this factor on each escalation round
+    #[arg(
+        long = "escalate-factor",
+        value_name = "K",
+        default_value_t = 10,
+        requires = "escalate_to"
+    )]
+    escalate_factor: usize,
+
+    /// Keep the visited table across boards while they are proven unreachable
+    #[arg(long = "keep-table", conflicts_with_all = ["discs_auto", "escalate_to"])]
+    keep_table: bool,
+
+    /// Answer boards already decided in FILE without searching, and add new OK/NG results to it
+    #[arg(
+        long = "reachability-cache",
+        value_name = "FILE",
+        conflicts_with_all = ["discs_auto", "escalate_to"]
+    )]
+    reachability_cache: Option<PathBuf>,
+
+    /// Initial position of the forward search: standard, parallel, or a 64-cell board with discs only on the four center squares
+    #[arg(
+        long,
+        value_name = "RULESET",
+        default_value = "standard",
+        conflicts_with = "discs_auto"
+    )]
+    ruleset: Ruleset,
+
+    /// Report a position as UNKNOWN instead of expanding it when one square has more than N reverse flip sets
+    #[arg(long = "flip-cap", value_name = "N")]
+    flip_cap: Option<usize>,
+
+    /// After the run, write the boards left UNKNOWN (deduplicated by canonical form) to FILE
+    #[arg(long = "hard-corpus", value_name = "FILE", conflicts_with = "per_file")]
+    hard_corpus: Option<PathBuf>,
+
+    /// Board size; 6 searches 6x6 boards (36-cell lines) back to the initial position itself
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 8,
+        value_parser = PossibleValuesParser::new(["6", "8"])
+            .map(|s| s.parse::<u32>().unwrap())
+    )]
+    size: u32,
+}
+
 #[derive(Args, Debug, Clone)]
 pub struct ParallelOpts {
     #[command(flatten)]
@@ -89,8 +227,8 @@ pub struct ParallelOpts {
 }
 
 impl ParallelOpts {
-    fn resolve(&self) -> (PathBuf, PathBuf, i32, usize, usize, Option<usize>) {
-        let (input, out_dir, discs, max_nodes) = self.basic.resolve();
+    fn resolve(&self) -> (Vec<PathBuf>, PathBuf, i32, usize, usize, Option<usize>) {
+        let (inputs, out_dir, discs, max_nodes) = self.basic.resolve();
         let table_size = self
             .table_size
             .unwrap_or_else(|| read_env_with_default("TABLE_SIZE", 100_000usize));
@@ -102,7 +240,7 @@ impl ParallelOpts {
         } else {
             Some(thread_setting)
         };
-        (input, out_dir, discs, max_nodes, table_size, threads)
+        (inputs, out_dir, discs, max_nodes, table_size, threads)
     }
 }
 
@@ -111,18 +249,52 @@ pub struct GbfsOpts {
     #[command(flatten)]
     basic: BasicOpts,
 
-    /// Use LP-solver for pruning
+    /// Use LP-solver for pruning (same as adding lp to --prunings)
     #[arg(long)]
     use_lp: bool,
 
+    /// Prunings applied during the search (lp solves an LP for every expanded node)
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        default_values_t = [Pruning::Occupancy, Pruning::Seg3]
+    )]
+    prunings: Vec<Pruning>,
+
     /// Number of rayon worker threads (0 = library default)
     #[arg(long, value_name = "N")]
     threads: Option<usize>,
+
+    /// Dump the GBFS frontier and visited set under DIR (resumes from them if present)
+    #[arg(long = "frontier-dir", value_name = "DIR", conflicts_with = "per_file")]
+    frontier_dir: Option<PathBuf>,
+
+    /// Expanded nodes between frontier dumps (0 = dump only when the node limit is hit)
+    #[arg(long = "dump-interval", value_name = "N", default_value_t = 0)]
+    dump_interval: usize,
+
+    /// Heuristic used to order the priority queue
+    #[arg(long, value_enum, default_value_t = Heuristic::Pow2)]
+    heuristic: Heuristic,
+
+    /// Stop queueing positions with a given disc count once N of them have been queued (undecided boards become UNKNOWN)
+    #[arg(long = "limit-per-disc", value_name = "N")]
+    limit_per_disc: Option<usize>,
 }
 
 impl GbfsOpts {
-    fn resolve(&self) -> (PathBuf, PathBuf, i32, usize, bool, Option<usize>) {
-        let (input, out_dir, discs, max_nodes) = self.basic.resolve();
+    fn resolve(
+        &self,
+    ) -> (
+        Vec<PathBuf>,
+        PathBuf,
+        i32,
+        usize,
+        PruningConfig,
+        Option<usize>,
+    ) {
+        let (inputs, out_dir, discs, max_nodes) = self.basic.resolve();
         let thread_setting = self
             .threads
             .unwrap_or_else(|| read_env_with_default("RAYON_THREADS", 60usize));
@@ -131,10 +303,27 @@ impl GbfsOpts {
         } else {
             Some(thread_setting)
         };
-        (input, out_dir, discs, max_nodes, self.use_lp, threads)
+        let mut prunings = PruningConfig::from_list(&self.prunings);
+        prunings.lp |= self.use_lp;
+        (inputs, out_dir, discs, max_nodes, prunings, threads)
     }
 }
 
+#[derive(Args, Debug, Clone)]
+pub struct CompareOpts {
+    #[command(flatten)]
+    parallel: ParallelOpts,
+
+    /// Strategies to compare (comma separated)
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        default_values_t = [Strategy::Dfs, Strategy::MoveOrdering, Strategy::Parallel]
+    )]
+    strategies: Vec<Strategy>,
+}
+
 #[derive(Args, Debug, Clone)]
 pub struct BfsArgs {
     /// Input file containing board positions
@@ -149,16 +338,16 @@ pub struct BfsArgs {
     #[arg(short = 'j', long, default_value_t = 0)]
     jobs: usize,
 
-    /// Verbosity level
+    /// Verbosity level (0 = results only, 1 = info, 2 = debug)
     #[arg(short, long, default_value_t = 0)]
     verbose: u8,
 
-    /// Block size for BFS batching
-    #[arg(short = 'b', long, default_value_t = 1_000_000)]
+    /// Block size for BFS batching (an upper bound in parallel mode)
+    #[arg(short = 'b', long, default_value_t = DEFAULT_BLOCK_SIZE)]
     block_size: usize,
 
     /// Disc threshold for forward search
-    #[arg(short = 'd', long, default_value_t = 10)]
+    #[arg(short = 'd', long, default_value_t = DEFAULT_DISCS)]
     discs: usize,
 
     /// Temporary directory for intermediate files
@@ -168,56 +357,387 @@ pub struct BfsArgs {
     /// Resume from intermediate state
     #[arg(short = 'r', long)]
     resume: bool,
+
+    /// Print the disc-level schedule and size estimates without searching
+    #[arg(long, conflicts_with = "resume")]
+    plan: bool,
+
+    /// Write the r_*.bin / b_*.bin intermediate files zstd-compressed
+    #[arg(long)]
+    compress: bool,
+
+    /// Overlap each level's merge with the next level's block processing (bfs-parallel only)
+    #[arg(long)]
+    pipeline: bool,
+
+    /// Initial position of the forward search: standard, parallel, or a 64-cell board with discs only on the four center squares
+    #[arg(long, value_name = "RULESET", default_value = "standard")]
+    ruleset: Ruleset,
 }
 
-impl From<BfsArgs> for BfsCfg {
-    fn from(args: BfsArgs) -> Self {
-        BfsCfg {
-            input: args.input,
-            out_dir: args.out_dir,
-            jobs: args.jobs,
-            verbose: args.verbose,
-            block_size: args.block_size,
-            discs: args.discs,
-            tmp_dir: args.tmp_dir,
-            resume: args.resume,
+impl BfsArgs {
+    fn into_cfg(self) -> io::Result<BfsCfg> {
+        BfsCfg::builder(self.input)
+            .out_dir(self.out_dir)
+            .jobs(self.jobs)
+            .verbose(self.verbose)
+            .block_size(self.block_size)
+            .discs(self.discs)
+            .tmp_dir(self.tmp_dir)
+            .resume(self.resume)
+            .plan(self.plan)
+            .compress(self.compress)
+            .pipeline(self.pipeline)
+            .ruleset(self.ruleset)
+            .build()
+    }
+}
+
+impl Command {
+    /// BFS 以外のサブコマンドが共通に持つ入出力の指定
+    fn basic_mut(&mut self) -> Option<&mut BasicOpts> {
+        match self {
+            Command::Dfs(opts) => Some(&mut opts.basic),
+            Command::MoveOrdering(opts) => Some(opts),
+            Command::Parallel(opts) => Some(&mut opts.basic),
+            Command::GbfsPar(opts) => Some(&mut opts.basic),
+            Command::Compare(opts) => Some(&mut opts.parallel.basic),
+            Command::Bfs(_) | Command::BfsPar(_) => None,
         }
     }
 }
 
-fn dispatch(cli: Cli) -> io::Result<()> {
-    match cli.command {
+/// `--per-file`: 入力ファイルごとに `OUT_DIR/<ファイル名の stem>` を出力先にして順に実行する
+fn dispatch_per_file(mut command: Command) -> io::Result<Vec<(PathBuf, RunMeta, Duration)>> {
+    let basic = command.basic_mut().expect("--per-file needs INPUT files");
+    basic.per_file = false;
+    let (inputs, out_dir, _, _) = basic.resolve();
+    let mut stems = HashSet::new();
+    let mut jobs = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        let stem = input.file_stem().map(|s| s.to_os_string()).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{}: input has no file name", input.display()),
+            )
+        })?;
+        if !stems.insert(stem.clone()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "--per-file: two inputs share the file stem '{}'",
+                    stem.to_string_lossy()
+                ),
+            ));
+        }
+        jobs.push((input, out_dir.join(stem)));
+    }
+    let mut runs = Vec::new();
+    for (input, dir) in jobs {
+        vprintln!(
+            INFO,
+            "info: --per-file: '{}' -> '{}'",
+            input.display(),
+            dir.display()
+        );
+        let mut command = command.clone();
+        let basic = command.basic_mut().unwrap();
+        basic.inputs = vec![input];
+        basic.out_dir = Some(dir);
+        runs.extend(dispatch(command)?);
+    }
+    Ok(runs)
+}
+
+/// サブコマンドを実行し、結果ディレクトリと `run_meta.json` に書く実行条件・実行時間を返す
+/// （`--plan` のように結果を書かない実行では空、`--per-file` では入力ファイルごとに 1 つ）
+fn dispatch(mut command: Command) -> io::Result<Vec<(PathBuf, RunMeta, Duration)>> {
+    if command.basic_mut().is_some_and(|basic| basic.per_file) {
+        return dispatch_per_file(command);
+    }
+    let start = Instant::now();
+    let run = dispatch_one(command)?;
+    Ok(run
+        .into_iter()
+        .map(|(out_dir, meta)| (out_dir, meta, start.elapsed()))
+        .collect())
+}
+
+/// `dispatch` の本体（出力先 1 つ分）
+fn dispatch_one(command: Command) -> io::Result<Option<(PathBuf, RunMeta)>> {
+    match command {
         Command::Dfs(opts) => {
-            let (input, out_dir, discs, max_nodes) = opts.resolve();
-            run_dfs(&input, &out_dir, discs, max_nodes)
+            let (inputs, out_dir, discs, max_nodes) = opts.basic.resolve();
+            if opts.size != 8 {
+                if opts.discs_auto
+                    || opts.escalate_to.is_some()
+                    || opts.keep_table
+                    || opts.reachability_cache.is_some()
+                    || opts.hard_corpus.is_some()
+                    || opts.basic.leaf_cache.is_some()
+                    || opts.flip_cap.is_some()
+                    || opts.ruleset != Ruleset::standard()
+                {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "--size 6 cannot be combined with 8x8-only options",
+                    ));
+                }
+                run_dfs_sized(&inputs, &out_dir, opts.size, max_nodes)?;
+                let meta = RunMeta {
+                    strategy: format!("dfs --size {}", opts.size),
+                    inputs,
+                    discs: None,
+                    node_limit: Some(max_nodes),
+                    threads: 1,
+                };
+                return Ok(Some((out_dir, meta)));
+            }
+            let flip_cap = opts.flip_cap.unwrap_or(usize::MAX);
+            let (strategy, node_limit) = if opts.discs_auto {
+                run_dfs_discs_auto(
+                    &inputs,
+                    &out_dir,
+                    max_nodes,
+                    flip_cap,
+                    opts.discs_auto_samples,
+                    opts.discs_auto_seed,
+                )?;
+                ("dfs --discs-auto", max_nodes)
+            } else if let Some(max_node_limit) = opts.escalate_to {
+                run_dfs_escalating(
+                    &inputs,
+                    &out_dir,
+                    discs,
+                    max_nodes,
+                    max_node_limit,
+                    opts.escalate_factor,
+                    flip_cap,
+                    &opts.ruleset,
+                    opts.basic.leaf_cache.as_deref(),
+                )?;
+                ("dfs --escalate-to", max_node_limit)
+            } else {
+                run_dfs(
+                    &inputs,
+                    &out_dir,
+                    discs,
+                    max_nodes,
+                    flip_cap,
+                    &opts.ruleset,
+                    opts.basic.leaf_cache.as_deref(),
+                    opts.keep_table,
+                    opts.reachability_cache.as_deref(),
+                )?;
+                ("dfs", max_nodes)
+            };
+            if let Some(corpus) = &opts.hard_corpus {
+                let count = write_hard_corpus(&out_dir, corpus)?;
+                vprintln!(
+                    INFO,
+                    "info: wrote {} hard board(s) to '{}'",
+                    count,
+                    corpus.display()
+                );
+            }
+            let meta = RunMeta {
+                strategy: strategy.to_string(),
+                inputs,
+                discs: (!opts.discs_auto).then_some(discs),
+                node_limit: Some(node_limit),
+                threads: 1,
+            };
+            Ok(Some((out_dir, meta)))
         }
         Command::MoveOrdering(opts) => {
-            let (input, out_dir, discs, max_nodes) = opts.resolve();
-            run_dfs_move_ordering(&input, &out_dir, discs, max_nodes)
+            let (inputs, out_dir, discs, max_nodes) = opts.resolve();
+            run_dfs_move_ordering(
+                &inputs,
+                &out_dir,
+                discs,
+                max_nodes,
+                opts.leaf_cache.as_deref(),
+            )?;
+            let meta = RunMeta {
+                strategy: "dfs-move-ordering".to_string(),
+                inputs,
+                discs: Some(discs),
+                node_limit: Some(max_nodes),
+                threads: 1,
+            };
+            Ok(Some((out_dir, meta)))
         }
         Command::Parallel(opts) => {
-            let (input, out_dir, discs, max_nodes, table_size, threads) = opts.resolve();
-            run_parallel_dfs(&input, &out_dir, discs, max_nodes, table_size, threads)
+            let (inputs, out_dir, discs, max_nodes, table_size, threads) = opts.resolve();
+            run_parallel_dfs(
+                &inputs,
+                &out_dir,
+                discs,
+                max_nodes,
+                table_size,
+                threads,
+                opts.basic.leaf_cache.as_deref(),
+            )?;
+            let meta = RunMeta {
+                strategy: "dfs-parallel".to_string(),
+                inputs,
+                discs: Some(discs),
+                node_limit: Some(max_nodes),
+                threads: ThreadConfig::global().num_threads(),
+            };
+            Ok(Some((out_dir, meta)))
         }
         Command::GbfsPar(opts) => {
-            let (input, out_dir, discs, max_nodes, use_lp, threads) = opts.resolve();
-            run_parallel_gbfs(&input, &out_dir, discs, max_nodes, use_lp, threads)
+            let (inputs, out_dir, discs, max_nodes, prunings, threads) = opts.resolve();
+            run_parallel_gbfs(
+                &inputs,
+                &out_dir,
+                discs,
+                max_nodes,
+                prunings,
+                threads,
+                opts.frontier_dir.as_deref(),
+                opts.dump_interval,
+                opts.heuristic,
+                opts.limit_per_disc,
+            )?;
+            let meta = RunMeta {
+                strategy: "gbfs-parallel".to_string(),
+                inputs,
+                discs: Some(discs),
+                node_limit: Some(max_nodes),
+                threads: ThreadConfig::global().num_threads(),
+            };
+            Ok(Some((out_dir, meta)))
         }
         Command::Bfs(args) => {
-            let cfg: BfsCfg = args.into();
-            run_bfs(&cfg)
+            let cfg = args.into_cfg()?;
+            run_bfs(&cfg)?;
+            Ok(bfs_meta("bfs", cfg, 1))
         }
         Command::BfsPar(args) => {
-            let cfg: BfsCfg = args.into();
-            run_parallel_bfs(&cfg)
+            let cfg = args.into_cfg()?;
+            run_parallel_bfs(&cfg)?;
+            let threads = ThreadConfig::global().num_threads();
+            Ok(bfs_meta("bfs-parallel", cfg, threads))
+        }
+        Command::Compare(opts) => {
+            let (inputs, out_dir, discs, max_nodes, table_size, threads) = opts.parallel.resolve();
+            run_compare(
+                &inputs,
+                &out_dir,
+                discs,
+                max_nodes,
+                table_size,
+                threads,
+                &opts.strategies,
+                opts.parallel.basic.leaf_cache.as_deref(),
+            )?;
+            let names: Vec<&str> = opts.strategies.iter().map(|s| s.name()).collect();
+            let threads = if opts.strategies.contains(&Strategy::Parallel) {
+                ThreadConfig::global().num_threads()
+            } else {
+                1
+            };
+            let meta = RunMeta {
+                strategy: format!("compare --strategies {}", names.join(",")),
+                inputs,
+                discs: Some(discs),
+                node_limit: Some(max_nodes),
+                threads,
+            };
+            Ok(Some((out_dir, meta)))
         }
     }
 }
 
+fn bfs_meta(strategy: &str, cfg: BfsCfg, threads: usize) -> Option<(PathBuf, RunMeta)> {
+    if cfg.plan {
+        return None;
+    }
+    let meta = RunMeta {
+        strategy: strategy.to_string(),
+        inputs: vec![cfg.input],
+        discs: Some(cfg.discs as i32),
+        node_limit: None,
+        threads,
+    };
+    Some((cfg.out_dir, meta))
+}
+
 fn main() {
     let cli = Cli::parse();
-    if let Err(e) = dispatch(cli) {
+    let (canonical, check) = (cli.canonical, cli.check_expected);
+    if (canonical || check) && matches!(cli.command, Command::Compare(_)) {
+        eprintln!("error: --canonical and --check-expected cannot be used with compare");
+        std::process::exit(1);
+    }
+    if check && matches!(&cli.command, Command::Dfs(opts) if opts.size != 8) {
+        eprintln!("error: --check-expected supports 8x8 boards only");
+        std::process::exit(1);
+    }
+    if cli
+        .slow_board_secs
+        .is_some_and(|secs| !secs.is_finite() || secs < 0.0)
+    {
+        eprintln!("error: --slow-board-secs must be a non-negative number of seconds");
+        std::process::exit(1);
+    }
+    OutputNames::new(cli.output_prefix.clone(), cli.force)
+        .with_summary(cli.summary)
+        .install();
+    SlowBoardLog::new(cli.slow_board_secs.map(Duration::from_secs_f64)).install();
+    let mut mismatches = 0;
+    let result = dispatch(cli.command).and_then(|runs| {
+        for (out_dir, meta, elapsed) in runs {
+            if check {
+                let mut mismatched = Vec::new();
+                for input in &meta.inputs {
+                    mismatched.extend(check_expected(input, &out_dir)?);
+                }
+                for m in mismatched {
+                    eprintln!(
+                        "mismatch: {} expected {} got {}",
+                        m.board.to_string(),
+                        verdict_label(Some(m.expected)),
+                        verdict_label(m.got)
+                    );
+                    mismatches += 1;
+                }
+            }
+            if canonical {
+                let [ok, ng, unknown] = canonicalize_outputs(&out_dir)?;
+                vprintln!(
+                    INFO,
+                    "info: canonical classes: OK = {}, NG = {}, UNKNOWN = {}",
+                    ok,
+                    ng,
+                    unknown
+                );
+            }
+            meta.write(&out_dir, elapsed)?;
+        }
+        Ok(())
+    });
+    if let Err(e) = result {
         eprintln!("error: {}", e);
         std::process::exit(1);
     }
+    if mismatches > 0 {
+        eprintln!(
+            "error: {} board(s) did not match the expected verdict",
+            mismatches
+        );
+        std::process::exit(1);
+    }
+}
+
+/// 出力ファイル名と同じ判定の表記（`OK` / `NG` / `UNKNOWN`、出力に無ければ `missing`）
+fn verdict_label(result: Option<SearchResult>) -> &'static str {
+    match result {
+        Some(SearchResult::Found) => "OK",
+        Some(SearchResult::NotFound) => "NG",
+        Some(SearchResult::Unknown) => "UNKNOWN",
+        None => "missing",
+    }
 }
@@ -7,6 +7,7 @@ use othello_complexity_rs::search::reverse_common::{
     default_input_path, default_out_dir, read_env_with_default, run_dfs_move_ordering,
 };
 
+use othello_complexity_rs::verbosity::set_verbosity;
 #[derive(Parser, Debug)]
 #[command(
     name = "reverse_to_initial_mo",
@@ -28,9 +29,18 @@ struct Cli {
     /// Maximum number of reverse-search nodes
     #[arg(long = "max-nodes", value_name = "N")]
     max_nodes: Option<usize>,
+
+    /// Load the forward leaf table from FILE (built and saved there if missing)
+    #[arg(long = "leaf-cache", value_name = "FILE")]
+    leaf_cache: Option<PathBuf>,
+
+    /// Verbosity level (0 = results only, 1 = info, 2 = debug)
+    #[arg(short, long, default_value_t = 1)]
+    verbose: u8,
 }
 
 fn run(cli: Cli) -> io::Result<()> {
+    set_verbosity(cli.verbose);
     let input = cli.input.unwrap_or_else(default_input_path);
     let out_dir = cli.out_dir.unwrap_or_else(default_out_dir);
     let discs = cli
@@ -40,7 +50,13 @@ fn run(cli: Cli) -> io::Result<()> {
         .max_nodes
         .unwrap_or_else(|| read_env_with_default("MAX_NODES", 1_000_000usize));
 
-    run_dfs_move_ordering(&input, &out_dir, discs, max_nodes)
+    run_dfs_move_ordering(
+        std::slice::from_ref(&input),
+        &out_dir,
+        discs,
+        max_nodes,
+        cli.leaf_cache.as_deref(),
+    )
 }
 
 fn main() {
@@ -1,83 +1,10 @@
-use std::collections::VecDeque;
 use std::fs;
 use std::io::Write;
 use std::path::Path;
 
-use othello_complexity_rs::othello::{east, ne, north, nw, se, south, sw, west, CENTER_MASK};
-use othello_complexity_rs::prunings::occupancy::{occupied_to_string, reachable_occupancy};
-
-/// 中央4マスから到達可能なoccupied bitboardを計算し、各ステップの途中経過を返す
-///
-/// # 前提条件
-/// - 中央2x2 (D4, E4, D5, E5) は常に占有されている必要がある
-///
-/// # 戻り値
-/// - タプルの最初の要素: 中央4マスから到達可能なマス目を表すビットマスク（最終結果）
-/// - タプルの2番目の要素: 中央からBFS順に外側へ広がるよう更新された`explained`の履歴（初期値を含む）
-fn reachable_occupancy_with_steps(occupied: u64) -> (u64, Vec<u64>) {
-    let final_explained = reachable_occupancy(occupied);
-    let mut steps = Vec::new();
-    let mut visited = CENTER_MASK & final_explained;
-
-    // 初期状態（中央4マス）を記録
-    steps.push(visited);
-
-    if visited == final_explained {
-        return (final_explained, steps);
-    }
-
-    let mut queue = VecDeque::new();
-
-    // 中央4マスからBFSの初期フロンティアを構築
-    let mut seeds = visited;
-    while seeds != 0 {
-        let tz = seeds.trailing_zeros();
-        let bit = 1u64 << tz;
-        queue.push_back(bit);
-        seeds &= seeds - 1;
-    }
-
-    // 8方向の近傍に順次拡張し、盤面中央から外側へと波状に広げる
-    while let Some(bit) = queue.pop_front() {
-        for neighbor in neighbors(bit) {
-            if neighbor == 0 || (final_explained & neighbor) == 0 || (visited & neighbor) != 0 {
-                continue;
-            }
-            visited |= neighbor;
-            steps.push(visited);
-            queue.push_back(neighbor);
-        }
-    }
-
-    // 念のため、BFSで拾えなかったマスがあれば補完（理論上は空のはず）
-    if visited != final_explained {
-        eprint!("warning: some squares were not reached in BFS, completing remaining squares...\n");
-        let mut remaining = final_explained & !visited;
-        while remaining != 0 {
-            let tz = remaining.trailing_zeros();
-            let bit = 1u64 << tz;
-            visited |= bit;
-            steps.push(visited);
-            remaining &= remaining - 1;
-        }
-    }
-
-    (final_explained, steps)
-}
-
-/// 指定したマスの8近傍を返す（盤面外は0）
-fn neighbors(bit: u64) -> [u64; 8] {
-    [
-        north(bit),
-        ne(bit),
-        east(bit),
-        se(bit),
-        south(bit),
-        sw(bit),
-        west(bit),
-        nw(bit),
-    ]
-}
+use othello_complexity_rs::prunings::occupancy::{
+    occupied_to_string, reachable_occupancy_with_steps,
+};
 
 /// O/X/G/-形式の文字列をu64ビットボードに変換
 /// - O, X, または G: 占有マス (bit = 1)
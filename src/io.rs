@@ -1,47 +1,106 @@
-use crate::{othello::Board, search::core::SearchResult};
+use crate::{
+    othello::{flip, has_move, validate_board, Board, BoardKey},
+    search::core::{FoundLeaf, SearchResult},
+};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs::{self, File};
-use std::io::{self, BufRead, BufReader, Write};
-use std::path::Path;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
 
 /// 64セルの 'X', 'O', '-' 文字列を Board に変換。失敗したら None。
+/// `BoardLayout::RowMajorTopLeft` として読む（`parse_line_to_board_with_layout` を参照）。
 pub fn parse_line_to_board(line: &str) -> Option<Board> {
-    let mut player: u64 = 0;
-    let mut opponent: u64 = 0;
-    let mut idx = 0u32;
-    for c in line.chars() {
-        match c {
-            'X' => {
-                if idx >= 64 {
-                    return None;
-                }
-                player |= 1_u64 << idx;
-                idx += 1;
-            }
-            'O' => {
-                if idx >= 64 {
-                    return None;
-                }
-                opponent |= 1_u64 << idx;
-                idx += 1;
-            }
-            '-' => {
-                if idx >= 64 {
-                    return None;
-                }
-                idx += 1;
-            }
-            _ => (),
+    parse_line_to_board_with_layout(line, BoardLayout::default())
+}
+
+/// 64 文字の盤面文字列で、何文字目がどのマスかの並び順。
+///
+/// 内部のビット番号は `y * 8 + x`（x = 0..8 が a〜h 列、y = 0..8 が 1〜8 段）で、
+/// a1 が bit 0、h1 が bit 7、a8 が bit 56、h8 が bit 63。
+/// 以下 `i` は 'X' / 'O' / '-' だけを数えた 0 始まりの文字番号。
+///
+/// | 並び順 | `i` 文字目のマス | 先頭 8 文字 |
+/// |---|---|---|
+/// | `RowMajorTopLeft` | bit `i`（`x = i % 8`, `y = i / 8`） | a1 b1 … h1 |
+/// | `RowMajorBottomLeft` | `x = i % 8`, `y = 7 - i / 8` | a8 b8 … h8 |
+/// | `ColumnMajorTopLeft` | `x = i / 8`, `y = i % 8` | a1 a2 … a8 |
+/// | `ColumnMajorBottomLeft` | `x = i / 8`, `y = 7 - i % 8` | a8 a7 … a1 |
+///
+/// `RowMajorTopLeft` が既定で、`Board::to_string` / `Board::show` の出力と同じ並び
+/// （1 段目を上にして左上から行ごと）。どの並び順でも 64 文字あれば読めてしまうので、
+/// 外部のファイルを読むときは並び順を明示すること。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum BoardLayout {
+    /// 1 段目が上、a1 から行ごと（このクレートの形式）
+    #[default]
+    RowMajorTopLeft,
+    /// 8 段目が上、a8 から行ごと（1 段目を下にして描いた盤面を上から読む形式）
+    RowMajorBottomLeft,
+    /// a 列から列ごと、各列は 1 段目から
+    ColumnMajorTopLeft,
+    /// a 列から列ごと、各列は 8 段目から
+    ColumnMajorBottomLeft,
+}
+
+impl BoardLayout {
+    pub const ALL: [BoardLayout; 4] = [
+        BoardLayout::RowMajorTopLeft,
+        BoardLayout::RowMajorBottomLeft,
+        BoardLayout::ColumnMajorTopLeft,
+        BoardLayout::ColumnMajorBottomLeft,
+    ];
+
+    /// コマンドライン引数などで使う名前
+    pub fn name(self) -> &'static str {
+        match self {
+            BoardLayout::RowMajorTopLeft => "row-major-top-left",
+            BoardLayout::RowMajorBottomLeft => "row-major-bottom-left",
+            BoardLayout::ColumnMajorTopLeft => "column-major-top-left",
+            BoardLayout::ColumnMajorBottomLeft => "column-major-bottom-left",
+        }
+    }
+
+    /// `RowMajorTopLeft` として読んだ盤面を、この並び順で読んだ盤面に直す
+    fn reorient(self, board: Board) -> Board {
+        match self {
+            BoardLayout::RowMajorTopLeft => board,
+            BoardLayout::RowMajorBottomLeft => board.mirrored_v(),
+            BoardLayout::ColumnMajorTopLeft => board.transposed(),
+            BoardLayout::ColumnMajorBottomLeft => board.transposed().mirrored_v(),
         }
     }
+}
+
+impl std::str::FromStr for BoardLayout {
+    type Err = io::Error;
 
-    if idx == 64 {
-        Some(Board::new(player, opponent))
-    } else {
-        None
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        BoardLayout::ALL
+            .into_iter()
+            .find(|layout| layout.name() == s)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "unknown board layout: {} (expected one of {})",
+                        s,
+                        BoardLayout::ALL.map(BoardLayout::name).join(", ")
+                    ),
+                )
+            })
     }
 }
 
+/// 64セルの 'X', 'O', '-' 文字列を、並び順 `layout` で Board に変換。失敗したら None。
+/// それ以外の文字は `Board::parse` と同じく読み飛ばす。
+pub fn parse_line_to_board_with_layout(line: &str, layout: BoardLayout) -> Option<Board> {
+    Board::parse(line).map(|board| layout.reorient(board))
+}
+
 /// ファイルから 'X', 'O', '-' 文字列を読み込み、Board の Vec に変換。失敗したら Err。
+/// タブ以降（`BOARD<TAB>EXPECTED` 形式の期待する判定）は読み飛ばす。
 pub fn parse_file_to_boards(path: &str) -> io::Result<Vec<Board>> {
     let file = File::open(path)?;
     let reader = BufReader::new(file);
@@ -50,7 +109,7 @@ pub fn parse_file_to_boards(path: &str) -> io::Result<Vec<Board>> {
 
     for line in reader.lines() {
         let l = line?;
-        let filtered: String = l
+        let filtered: String = board_field(&l)
             .chars()
             .filter(|&c| c == 'X' || c == 'O' || c == '-')
             .collect();
@@ -71,28 +130,482 @@ pub fn parse_file_to_boards(path: &str) -> io::Result<Vec<Board>> {
     ))
 }
 
-/// 出力ディレクトリを作成し、ReverseOutputsを返す
+/// `BOARD<TAB>EXPECTED` 形式の行の盤面の部分。タブが無ければ行全体。
+fn board_field(line: &str) -> &str {
+    line.split('\t').next().unwrap_or(line)
+}
+
+/// 入力ファイルの `BOARD<TAB>EXPECTED`（EXPECTED は `OK` / `NG` / `UNKNOWN`）形式の行から、
+/// 盤面と期待する判定の組を読む。タブの無い行（期待する判定の無い盤面）は読み飛ばす。
+pub fn read_expected(path: &Path) -> io::Result<Vec<(Board, SearchResult)>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut labeled = Vec::new();
+    for (lineno, line) in reader.lines().enumerate() {
+        let line = line?;
+        let Some((board, expected)) = line.split_once('\t') else {
+            continue;
+        };
+        let expected = match expected.trim() {
+            "OK" => SearchResult::Found,
+            "NG" => SearchResult::NotFound,
+            "UNKNOWN" => SearchResult::Unknown,
+            other => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "line {}: expected verdict must be OK, NG or UNKNOWN, got '{}'",
+                        lineno + 1,
+                        other
+                    ),
+                ))
+            }
+        };
+        let board = Board::parse(board).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("line {}: failed to parse the board", lineno + 1),
+            )
+        })?;
+        labeled.push((board, expected));
+    }
+    Ok(labeled)
+}
+
+/// 期待する判定と実際の判定が食い違った盤面
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExpectedMismatch {
+    pub board: Board,
+    pub expected: SearchResult,
+    /// 実際の判定（出力のどのファイルにも無ければ `None`）
+    pub got: Option<SearchResult>,
+}
+
+/// `input` の期待する判定（`read_expected`）を、`out_dir` の `reverse_{OK,NG,UNKNOWN}.txt` に
+/// 書き出された実際の判定と突き合わせ、食い違った盤面を返す。盤面は入力の向きのまま比べるので、
+/// `canonicalize_outputs` で書き直す前に呼ぶこと。
+pub fn check_expected(input: &Path, out_dir: &Path) -> io::Result<Vec<ExpectedMismatch>> {
+    let names = OutputNames::global();
+    let mut actual = HashMap::new();
+    for result in VERDICTS {
+        let text = fs::read_to_string(names.result_path(out_dir, result))?;
+        for board in text.lines().filter_map(Board::parse) {
+            actual.insert(BoardKey::from(board), result);
+        }
+    }
+    Ok(read_expected(input)?
+        .into_iter()
+        .filter_map(|(board, expected)| {
+            let got = actual.get(&BoardKey::from(board)).copied();
+            (got != Some(expected)).then_some(ExpectedMismatch {
+                board,
+                expected,
+                got,
+            })
+        })
+        .collect())
+}
+
+/// 棋譜の着手列（`f5d6c3...` のように列 a-h・段 1-8 の 2 文字を連結したもの）を再生し、
+/// 各着手の直後の局面を順に返す。
+///
+/// 大文字・小文字は区別せず、空白は読み飛ばす。手番側に合法手が無く相手にある場合は
+/// 自動でパスする（`pa` / `ps` / `--` と明示されたパスは、パスが必要な局面でだけ受け付ける）。
+/// 不正な着手や終局後の着手は `ErrorKind::InvalidData`。
+pub fn boards_from_transcript(moves: &str) -> io::Result<Vec<Board>> {
+    let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+    let chars: Vec<char> = moves.chars().filter(|c| !c.is_whitespace()).collect();
+    if !chars.len().is_multiple_of(2) {
+        return Err(invalid(format!(
+            "transcript has an odd number of characters: {}",
+            moves.trim()
+        )));
+    }
+
+    let mut b = Board::initial();
+    let mut boards = Vec::with_capacity(chars.len() / 2);
+    for (ply, pair) in chars.chunks(2).enumerate() {
+        let token: String = pair.iter().collect::<String>().to_ascii_lowercase();
+        let needs_pass = !has_move(b.player, b.opponent);
+        if needs_pass && !has_move(b.opponent, b.player) {
+            return Err(invalid(format!("move {} ({}) after game over", ply, token)));
+        }
+        if matches!(token.as_str(), "pa" | "ps" | "--") {
+            if !needs_pass {
+                return Err(invalid(format!(
+                    "pass at move {} but a legal move exists",
+                    ply
+                )));
+            }
+            // 明示されたパスはその場で手番を渡す（続けて 2 回パスすると上で弾かれる）
+            b = Board::new(b.opponent, b.player);
+            continue;
+        }
+        let (file, rank) = (pair[0].to_ascii_lowercase(), pair[1]);
+        if !('a'..='h').contains(&file) || !('1'..='8').contains(&rank) {
+            return Err(invalid(format!("bad square '{}' at move {}", token, ply)));
+        }
+        let pos = (rank as usize - '1' as usize) * 8 + (file as usize - 'a' as usize);
+        if needs_pass {
+            b = Board::new(b.opponent, b.player);
+        }
+        let flipped = flip(pos, b.player, b.opponent);
+        if flipped == 0 {
+            return Err(invalid(format!("illegal move '{}' at move {}", token, ply)));
+        }
+        b = Board::new(b.opponent ^ flipped, b.player ^ (flipped | (1u64 << pos)));
+        boards.push(b);
+    }
+    Ok(boards)
+}
+
+/// ファイル中の盤面を対称変換で同一視したときの種類数（`unique()` の正規形の数）を返す。
+/// 不正な盤面は正規形を持たないので、盤面そのものを 1 種類として数える。
+pub fn count_unique_boards(path: &Path) -> io::Result<usize> {
+    let boards = parse_file_to_boards(&path.to_string_lossy())?;
+    let keys: HashSet<[u64; 2]> = boards
+        .iter()
+        .map(|board| {
+            if validate_board(board).is_ok() {
+                board.unique()
+            } else {
+                [board.player, board.opponent]
+            }
+        })
+        .collect();
+    Ok(keys.len())
+}
+
+/// ファイル中の盤面ごとに `元の盤面<TAB>正規形<TAB>対称変換の添字` を `out` に書き、書いた行数を返す。
+///
+/// 正規形と添字は `Board::unique_with_symmetry` のもので、`board_symmetry(添字, ..)` で元の盤面から
+/// 正規形が得られる（正規形から元の向きへ戻すには `Board::inverse_symmetry`）。正規形を持たない
+/// 不正な盤面は警告を出して飛ばす。
+pub fn write_canonical_index(path: &Path, out: &mut impl Write) -> io::Result<usize> {
+    let boards = parse_file_to_boards(&path.to_string_lossy())?;
+    let mut written = 0;
+    for board in &boards {
+        if let Err(e) = validate_board(board) {
+            eprintln!("warning: skipping {}: {}", board.to_string(), e);
+            continue;
+        }
+        let ([player, opponent], symmetry) = board.unique_with_symmetry();
+        writeln!(
+            out,
+            "{}\t{}\t{}",
+            board.to_string(),
+            Board::new(player, opponent).to_string(),
+            symmetry
+        )?;
+        written += 1;
+    }
+    Ok(written)
+}
+
+/// ファイル中の盤面を石数ごとに数え、`石数 → 盤面数` を石数の昇順で返す。
+/// 探索の前に `discs` やノード数の上限を決める目安にする。
+pub fn disc_count_histogram(path: &Path) -> io::Result<BTreeMap<u32, usize>> {
+    let boards = parse_file_to_boards(&path.to_string_lossy())?;
+    let mut histogram = BTreeMap::new();
+    for board in &boards {
+        *histogram.entry(board.popcount()).or_insert(0) += 1;
+    }
+    Ok(histogram)
+}
+
+/// `out_dir` の `reverse_UNKNOWN.txt`（ノード数の上限内で判定できなかった盤面）を、
+/// 正規形が重複しないように `corpus` へ書き出す。書き出した盤面数を返す。
+///
+/// `dfs --escalate-to` と組み合わせると、上限を上げても決まらなかった盤面だけが残るので、
+/// LP/SAT による枝刈りを試す対象の一覧として使える。
+pub fn write_hard_corpus(out_dir: &Path, corpus: &Path) -> io::Result<usize> {
+    let unknown_path = OutputNames::global().result_path(out_dir, SearchResult::Unknown);
+    let unknown = BufReader::new(File::open(unknown_path)?);
+    let mut seen = HashSet::new();
+    let mut w = io::BufWriter::new(File::create(corpus)?);
+    for line in unknown.lines() {
+        let Some(board) = Board::parse(&line?) else {
+            continue;
+        };
+        if seen.insert(board.unique()) {
+            writeln!(w, "{}", board.to_string())?;
+        }
+    }
+    w.flush()?;
+    Ok(seen.len())
+}
+
+/// `out_dir` の `reverse_{OK,NG,UNKNOWN}.txt` を、対称な盤面を 1 つの同値類（`unique()` の正規形）に
+/// まとめて書き直す。各行は「正規形の盤面 入力に現れた個数」で、同値類の数を `[OK, NG, UNKNOWN]` の順に返す。
+///
+/// 同じ同値類の盤面が `Unknown` と他の判定に分かれた場合（ノード数の上限による）は
+/// `Found`、`NotFound`、`Unknown` の順に優先する。到達可能性は対称変換で変わらないので、
+/// `Found` と `NotFound` に分かれていれば探索の誤りとして `ErrorKind::InvalidData` を返し、
+/// ファイルは書き直さない。不正な盤面は正規形を持たないのでそのまま 1 つの類とする。
+///
+/// 個数の列は参考のための記録で、`compute_ci --from-dir` が読む `{prefix}_summary.bin` は
+/// 書き直さない（入力の盤面ごとの集計のまま）。
+pub fn canonicalize_outputs(out_dir: &Path) -> io::Result<[usize; 3]> {
+    let names = OutputNames::global();
+    let files = VERDICTS.map(|result| names.result_path(out_dir, result));
+    // 正規形 -> (同値類の盤面があった結果ファイルのビット集合, 個数)。出力は最初に現れた順
+    let mut classes: HashMap<[u64; 2], (u8, usize)> = HashMap::new();
+    let mut order: Vec<[u64; 2]> = Vec::new();
+    for (rank, path) in files.iter().enumerate() {
+        let text = fs::read_to_string(path)?;
+        for line in text.lines() {
+            let Some(board) = Board::parse(line) else {
+                continue;
+            };
+            let key = if validate_board(&board).is_ok() {
+                board.unique()
+            } else {
+                [board.player, board.opponent]
+            };
+            let entry = classes.entry(key).or_insert_with(|| {
+                order.push(key);
+                (0, 0)
+            });
+            entry.0 |= 1 << rank;
+            entry.1 += 1;
+        }
+    }
+    if let Some(key) = order.iter().find(|key| classes[*key].0 & 0b011 == 0b011) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "symmetric forms of {} are both OK and NG",
+                Board::new(key[0], key[1]).to_string()
+            ),
+        ));
+    }
+
+    let mut counts = [0usize; 3];
+    // この実行で書いたファイルの書き直しなので、`--force` によらず上書きする
+    for (rank, path) in files.iter().enumerate() {
+        let mut w = io::BufWriter::new(File::create(path)?);
+        // 優先するのは、類の盤面があったうち `VERDICTS` で最も前の判定
+        for key in order
+            .iter()
+            .filter(|key| classes[*key].0.trailing_zeros() as usize == rank)
+        {
+            let board = Board::new(key[0], key[1]);
+            writeln!(w, "{} {}", board.to_string(), classes[key].1)?;
+            counts[rank] += 1;
+        }
+        w.flush()?;
+    }
+    Ok(counts)
+}
+
+/// 結果ファイルの優先順（`canonicalize_outputs` で判定が分かれたときもこの順に優先する）
+const VERDICTS: [SearchResult; 3] = [
+    SearchResult::Found,
+    SearchResult::NotFound,
+    SearchResult::Unknown,
+];
+
+/// プロセス全体で使う結果ファイル名の設定（`OutputNames::install` で設定する）
+static OUTPUT_NAMES: OnceLock<OutputNames> = OnceLock::new();
+
+/// 結果ファイルの名前と、既存のファイルを上書きするかどうか。
+///
+/// 結果ファイルは `{prefix}_OK.txt` / `{prefix}_NG.txt` / `{prefix}_UNKNOWN.txt` /
+/// `{prefix}_OK_leaf.txt` で、`ensure_outputs` や `canonicalize_outputs` など結果ファイルを扱う関数は
+/// `OutputNames::global()` の設定を使う。`install` していなければ接頭辞は `reverse` で、
+/// 既存のファイルは上書きする（ライブラリとしての従来の動作）。
+/// `with_summary(true)` なら石数ごとの判定数の集計 `{prefix}_summary.bin`（`ResultSummary`）も書く。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputNames {
+    prefix: String,
+    force: bool,
+    summary: bool,
+}
+
+impl Default for OutputNames {
+    fn default() -> Self {
+        OutputNames::new(OutputNames::DEFAULT_PREFIX, true)
+    }
+}
+
+impl OutputNames {
+    pub const DEFAULT_PREFIX: &'static str = "reverse";
+
+    /// `force` が false なら、既にある結果ファイルには書かずに `ErrorKind::AlreadyExists` を返す
+    pub fn new(prefix: impl Into<String>, force: bool) -> Self {
+        OutputNames {
+            prefix: prefix.into(),
+            force,
+            summary: false,
+        }
+    }
+
+    /// 結果ファイルと一緒に `summary_path` の集計を書くかどうか
+    pub fn with_summary(mut self, summary: bool) -> Self {
+        self.summary = summary;
+        self
+    }
+
+    pub fn summary(&self) -> bool {
+        self.summary
+    }
+
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    pub fn force(&self) -> bool {
+        self.force
+    }
+
+    /// この設定をプロセス全体の設定にする。設定できるのは最初の 1 回だけで、
+    /// 以降に違う値を渡すと警告を出して無視する。
+    pub fn install(self) {
+        if let Err(rejected) = OUTPUT_NAMES.set(self) {
+            let current = OUTPUT_NAMES.get().unwrap();
+            if *current != rejected {
+                eprintln!(
+                    "warning: output names are already set to prefix '{}'; ignoring '{}'",
+                    current.prefix, rejected.prefix
+                );
+            }
+        }
+    }
+
+    /// `install` 済みならその設定、まだなら `OutputNames::default()`
+    pub fn global() -> Self {
+        OUTPUT_NAMES.get().cloned().unwrap_or_default()
+    }
+
+    /// `{prefix}_{suffix}.txt`
+    pub fn file_name(&self, suffix: &str) -> String {
+        format!("{}_{}.txt", self.prefix, suffix)
+    }
+
+    /// 判定 `result` の盤面を書く `out_dir` 内のファイル
+    pub fn result_path(&self, out_dir: &Path, result: SearchResult) -> PathBuf {
+        let suffix = match result {
+            SearchResult::Found => "OK",
+            SearchResult::NotFound => "NG",
+            SearchResult::Unknown => "UNKNOWN",
+        };
+        out_dir.join(self.file_name(suffix))
+    }
+
+    /// 石数ごとの判定数の集計を書く `out_dir` 内のファイル `{prefix}_summary.bin`
+    pub fn summary_path(&self, out_dir: &Path) -> PathBuf {
+        out_dir.join(format!("{}_summary.bin", self.prefix))
+    }
+
+    /// `create_output(path, self.force())`
+    pub fn create(&self, path: &Path) -> io::Result<File> {
+        create_output(path, self.force)
+    }
+}
+
+/// 結果ファイルを作る。`force` が false で `path` が既にあれば、上書きせずに
+/// `ErrorKind::AlreadyExists` を返す。
+pub fn create_output(path: &Path, force: bool) -> io::Result<File> {
+    if force {
+        return File::create(path);
+    }
+    File::create_new(path).map_err(|e| {
+        if e.kind() == io::ErrorKind::AlreadyExists {
+            already_exists(path)
+        } else {
+            e
+        }
+    })
+}
+
+fn already_exists(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        format!(
+            "'{}' already exists; use --force to overwrite it or choose another output prefix",
+            path.display()
+        ),
+    )
+}
+
+/// 出力ディレクトリを作成し、ReverseOutputsを返す。
+/// ファイル名と上書きの扱いは `OutputNames::global()` に従う。
 pub fn ensure_outputs(out_dir: &Path) -> io::Result<ReverseOutputs> {
     fs::create_dir_all(out_dir)?;
     ReverseOutputs::create(out_dir)
 }
 
 /// reverse探索の結果を3つのファイル（OK/NG/UNKNOWN）に書き出すための構造体
+///
+/// 到達したリーフを報告できる探索では、`reverse_OK_leaf.txt`（接頭辞は `OutputNames` で変えられる）に
+/// 「入力盤面 リーフ(入力の向き) リーフ(正規形) 対称変換の添字」を 1 行ずつ書く。
 pub struct ReverseOutputs {
     pub ok: io::BufWriter<File>,
     pub ng: io::BufWriter<File>,
     pub unknown: io::BufWriter<File>,
+    pub leaf: io::BufWriter<File>,
+    summary: Option<SummaryFile>,
 }
 
 impl ReverseOutputs {
     fn create(out_dir: &Path) -> io::Result<Self> {
-        let ok = io::BufWriter::new(File::create(out_dir.join("reverse_OK.txt"))?);
-        let ng = io::BufWriter::new(File::create(out_dir.join("reverse_NG.txt"))?);
-        let unknown = io::BufWriter::new(File::create(out_dir.join("reverse_UNKNOWN.txt"))?);
-        Ok(ReverseOutputs { ok, ng, unknown })
+        Self::create_with(out_dir, &OutputNames::global())
+    }
+
+    /// `create` の、ファイル名と上書きの扱いを `names` で与える版
+    fn create_with(out_dir: &Path, names: &OutputNames) -> io::Result<Self> {
+        let paths = [
+            names.result_path(out_dir, SearchResult::Found),
+            names.result_path(out_dir, SearchResult::NotFound),
+            names.result_path(out_dir, SearchResult::Unknown),
+            out_dir.join(names.file_name("OK_leaf")),
+        ];
+        let summary_path = names.summary().then(|| names.summary_path(out_dir));
+        // 1 つでも既にあれば、どのファイルも作らないうちに失敗させる
+        if !names.force() {
+            if let Some(path) = paths.iter().chain(&summary_path).find(|path| path.exists()) {
+                return Err(already_exists(path));
+            }
+        }
+        let [ok, ng, unknown, leaf] = paths.map(|path| names.create(&path).map(io::BufWriter::new));
+        let (ok, ng, unknown, leaf) = (ok?, ng?, unknown?, leaf?);
+        let summary = match summary_path {
+            Some(path) => {
+                let file = SummaryFile::new(path);
+                file.counts.save(&file.path)?;
+                Some(file)
+            }
+            None => None,
+        };
+        Ok(ReverseOutputs {
+            ok,
+            ng,
+            unknown,
+            leaf,
+            summary,
+        })
     }
 
-    pub fn write_result(&mut self, result: SearchResult, line: &str) -> io::Result<()> {
+    /// これまでに書いた盤面の石数ごとの判定数（`OutputNames::with_summary` のときだけ）
+    pub fn summary(&self) -> Option<&ResultSummary> {
+        self.summary.as_ref().map(|file| &file.counts)
+    }
+
+    fn count(&mut self, result: SearchResult, discs: u32) {
+        if let Some(file) = self.summary.as_mut() {
+            file.counts.add(discs, result);
+            file.dirty = true;
+        }
+    }
+
+    /// 判定 `result` の結果ファイルに盤面 `board` の行 `line` を書く
+    /// （`line` は普通は `board.to_string()`。集計には `board` の石数を使う）
+    pub fn write_result(
+        &mut self,
+        result: SearchResult,
+        board: &Board,
+        line: &str,
+    ) -> io::Result<()> {
+        self.count(result, board.popcount());
         match result {
             SearchResult::Found => writeln!(self.ok, "{}", line),
             SearchResult::NotFound => writeln!(self.ng, "{}", line),
@@ -100,14 +613,732 @@ impl ReverseOutputs {
         }
     }
 
-    pub fn write_invalid(&mut self, line: &str) -> io::Result<()> {
+    pub fn write_found_leaf(&mut self, line: &str, found: &FoundLeaf) -> io::Result<()> {
+        let canonical = Board::new(found.canonical[0], found.canonical[1]);
+        writeln!(
+            self.leaf,
+            "{} {} {} {}",
+            line,
+            found.board.to_string(),
+            canonical.to_string(),
+            found.symmetry
+        )
+    }
+
+    pub fn write_invalid(&mut self, board: &Board, line: &str) -> io::Result<()> {
+        self.count(SearchResult::NotFound, board.popcount());
         writeln!(self.ng, "{}", line)
     }
 
+    /// 結果ファイルを flush する。集計は前回の書き出しから `SUMMARY_SAVE_INTERVAL` 以上経っていれば書き出す
+    /// （盤面ごとに呼ばれても集計ファイルを毎回書き直さないため。最後の集計は drop 時に書く）。
     pub fn flush(&mut self) -> io::Result<()> {
         self.ok.flush()?;
         self.ng.flush()?;
         self.unknown.flush()?;
+        self.leaf.flush()?;
+        if let Some(file) = self.summary.as_mut() {
+            if file.dirty && file.saved.elapsed() >= SUMMARY_SAVE_INTERVAL {
+                file.save()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for ReverseOutputs {
+    fn drop(&mut self) {
+        if let Some(file) = self.summary.as_mut() {
+            if file.dirty {
+                if let Err(e) = file.save() {
+                    eprintln!("warning: failed to write '{}': {}", file.path.display(), e);
+                }
+            }
+        }
+    }
+}
+
+/// 実行中に集計ファイルを書き直す間隔
+const SUMMARY_SAVE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// `ReverseOutputs` が書いている集計ファイル
+struct SummaryFile {
+    path: PathBuf,
+    counts: ResultSummary,
+    saved: Instant,
+    dirty: bool,
+}
+
+impl SummaryFile {
+    fn new(path: PathBuf) -> Self {
+        SummaryFile {
+            path,
+            counts: ResultSummary::default(),
+            saved: Instant::now(),
+            dirty: false,
+        }
+    }
+
+    fn save(&mut self) -> io::Result<()> {
+        self.counts.save(&self.path)?;
+        self.saved = Instant::now();
+        self.dirty = false;
         Ok(())
     }
 }
+
+/// 集計ファイル先頭のマジックナンバー
+const RESULT_SUMMARY_MAGIC: &[u8; 8] = b"OTHSUM01";
+
+/// 石数ごとの OK / NG / UNKNOWN の盤面数。
+///
+/// 結果ファイルの行数と同じ数え方で（不正な盤面は NG）、何十億行のファイルを `wc -l` しなくても
+/// `compute_ci --from-dir` に正確な数を渡せる。
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResultSummary {
+    /// 石数 -> [OK, NG, UNKNOWN]
+    levels: BTreeMap<u32, [u64; 3]>,
+}
+
+impl ResultSummary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 石数 `discs` の盤面 1 つの判定 `result` を数える
+    pub fn add(&mut self, discs: u32, result: SearchResult) {
+        let index = VERDICTS.iter().position(|&v| v == result).unwrap();
+        self.levels.entry(discs).or_default()[index] += 1;
+    }
+
+    /// 石数 `discs` の `[OK, NG, UNKNOWN]`
+    pub fn level(&self, discs: u32) -> [u64; 3] {
+        self.levels.get(&discs).copied().unwrap_or_default()
+    }
+
+    /// 盤面のあった石数ごとの `(石数, [OK, NG, UNKNOWN])`（石数の昇順）
+    pub fn levels(&self) -> impl Iterator<Item = (u32, [u64; 3])> + '_ {
+        self.levels.iter().map(|(&discs, &counts)| (discs, counts))
+    }
+
+    /// 全ての石数を合わせた `[OK, NG, UNKNOWN]`
+    pub fn totals(&self) -> [u64; 3] {
+        self.levels.values().fold([0; 3], |mut acc, counts| {
+            for (a, c) in acc.iter_mut().zip(counts) {
+                *a += c;
+            }
+            acc
+        })
+    }
+
+    /// 数えた盤面の総数
+    pub fn total(&self) -> u64 {
+        self.totals().iter().sum()
+    }
+
+    /// `path` に書き出す（一時ファイルに書いてから置き換えるので、読む側が書きかけを見ることはない）。
+    ///
+    /// 形式: マジックナンバー(8) / 石数の種類数(u64) の後に、石数の昇順で
+    /// 石数・OK・NG・UNKNOWN（各 u64）を並べる。整数は全てリトルエンディアン。
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let tmp = Path::new(&tmp);
+        let mut w = io::BufWriter::new(File::create(tmp)?);
+        w.write_all(RESULT_SUMMARY_MAGIC)?;
+        w.write_all(&(self.levels.len() as u64).to_le_bytes())?;
+        for (&discs, counts) in &self.levels {
+            w.write_all(&u64::from(discs).to_le_bytes())?;
+            for count in counts {
+                w.write_all(&count.to_le_bytes())?;
+            }
+        }
+        w.flush()?;
+        drop(w);
+        fs::rename(tmp, path)
+    }
+
+    /// `save` で書いたファイルを読む
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let mut r = BufReader::new(File::open(path)?);
+        let mut magic = [0u8; 8];
+        r.read_exact(&mut magic)?;
+        if &magic != RESULT_SUMMARY_MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} is not a result summary file", path.display()),
+            ));
+        }
+        let mut read_u64 = || -> io::Result<u64> {
+            let mut buf = [0u8; 8];
+            r.read_exact(&mut buf)?;
+            Ok(u64::from_le_bytes(buf))
+        };
+        let len = read_u64()?;
+        let mut levels = BTreeMap::new();
+        for _ in 0..len {
+            let discs = u32::try_from(read_u64()?).map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{}: disc count out of range", path.display()),
+                )
+            })?;
+            let counts = [read_u64()?, read_u64()?, read_u64()?];
+            levels.insert(discs, counts);
+        }
+        Ok(ResultSummary { levels })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::core::{retrospective_search, Btable};
+    use crate::search::leaf_cache::LeafCache;
+    use crate::search::reachable::is_reachable;
+    use crate::test_util::scratch_dir;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    /// マス番号を棋譜の 2 文字（`f5` など）に
+    fn square_name(pos: usize) -> String {
+        format!("{}{}", (b'a' + (pos % 8) as u8) as char, pos / 8 + 1)
+    }
+
+    #[test]
+    fn transcript_boards_are_all_reachable() {
+        let moves = [37, 43, 18, 19, 26, 29, 45, 21, 44, 52, 51, 34];
+        let transcript: String = moves.iter().map(|&m| square_name(m)).collect();
+        assert_eq!(&transcript[..10], "f5d6c3d3c4");
+        let boards = boards_from_transcript(&transcript.to_uppercase()).unwrap();
+        assert_eq!(boards.len(), moves.len());
+        for (ply, b) in boards.iter().enumerate() {
+            assert_eq!(*b, Board::play_sequence(&moves[..=ply]).unwrap());
+            assert_eq!(is_reachable(b), SearchResult::Found, "ply {}", ply);
+        }
+    }
+
+    #[test]
+    fn transcript_replays_a_random_game_with_passes() {
+        let mut rng = StdRng::seed_from_u64(627);
+        // パスを含む対局が出るまで乱択で打つ
+        let (transcript, expected) = loop {
+            let mut b = Board::initial();
+            let (mut transcript, mut boards, mut passed) = (String::new(), vec![], false);
+            loop {
+                let mut moves = crate::othello::get_moves(b.player, b.opponent);
+                if moves == 0 {
+                    if !has_move(b.opponent, b.player) {
+                        break;
+                    }
+                    b = Board::new(b.opponent, b.player);
+                    moves = crate::othello::get_moves(b.player, b.opponent);
+                    // 最初のパスだけ `pa` と明示し、以降は省略する
+                    if !passed {
+                        transcript.push_str("pa");
+                    }
+                    passed = true;
+                }
+                for _ in 0..rng.random_range(0..moves.count_ones()) {
+                    moves &= moves - 1;
+                }
+                let pos = moves.trailing_zeros() as usize;
+                let flipped = flip(pos, b.player, b.opponent);
+                b = Board::new(b.opponent ^ flipped, b.player ^ (flipped | (1u64 << pos)));
+                transcript.push_str(&square_name(pos));
+                transcript.push(' ');
+                boards.push(b);
+            }
+            if passed {
+                break (transcript, boards);
+            }
+        };
+        assert_eq!(boards_from_transcript(&transcript).unwrap(), expected);
+        // 同じパスを 2 回続けて書くと、2 回目は合法手がある局面でのパスになる
+        let doubled = transcript.replacen("pa", "papa", 1);
+        let err = boards_from_transcript(&doubled).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("pass at move"), "{}", err);
+    }
+
+    #[test]
+    fn malformed_transcripts_are_rejected() {
+        for bad in ["f5d", "f5z9", "f5f5", "f5pa", "a1"] {
+            let err = boards_from_transcript(bad).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{}", bad);
+        }
+        assert!(boards_from_transcript("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn board_left_unknown_at_a_small_budget_is_in_the_hard_corpus() {
+        let dir = scratch_dir("hard_corpus");
+        let corpus = dir.join("hard.txt");
+        let leaf_cache = LeafCache::new(8);
+        let search = |board: &Board, node_limit: usize| {
+            retrospective_search(
+                board,
+                false,
+                8,
+                leaf_cache.leaf(),
+                &mut Btable::new(1 << 16, 1 << 8),
+                &mut vec![],
+                &mut 0,
+                node_limit,
+            )
+            .unwrap()
+        };
+        // f5 d6 c3 d3 c4 f4 f6 f3 e6 e7（14 石）は 10 ノードでは決まらないが、f5 d6 c3 d3 c4 は決まる
+        let hard = Board::play_sequence(&[37, 43, 18, 19, 26, 29, 45, 21, 44, 52]).unwrap();
+        let easy = Board::play_sequence(&[37, 43, 18, 19, 26]).unwrap();
+        assert_eq!(search(&hard, 1_000_000), SearchResult::Found);
+
+        let mut outputs = ensure_outputs(&dir).unwrap();
+        for board in [hard, easy] {
+            outputs
+                .write_result(search(&board, 10), &board, &board.to_string())
+                .unwrap();
+        }
+        drop(outputs);
+        let written = write_hard_corpus(&dir, &corpus).unwrap();
+        let lines = fs::read_to_string(&corpus).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(written, 1);
+        assert_eq!(lines.lines().collect::<Vec<_>>(), [hard.to_string()]);
+    }
+
+    #[test]
+    fn canonical_outputs_merge_symmetric_boards_by_verdict_precedence() {
+        let dir = scratch_dir("canonical");
+        let names = OutputNames::global();
+        let b = Board::play_sequence(&[37, 43, 18, 19, 26]).unwrap();
+        let c = Board::play_sequence(&[37, 45]).unwrap();
+        let d = Board::play_sequence(&[37, 43, 18]).unwrap();
+        let invalid = Board::new(1, 0);
+        assert_ne!(b.symmetry_orbit()[3], b);
+        assert_ne!(c.symmetry_orbit()[3], c);
+        let write = |result, boards: &[Board]| {
+            let text: String = boards.iter().map(|b| b.to_string() + "\n").collect();
+            fs::write(names.result_path(&dir, result), text).unwrap();
+        };
+        // b とその鏡像は OK と UNKNOWN、c とその鏡像は NG と UNKNOWN に分かれている
+        write(SearchResult::Found, &[b, invalid]);
+        write(SearchResult::NotFound, &[c]);
+        write(
+            SearchResult::Unknown,
+            &[b.symmetry_orbit()[3], c.symmetry_orbit()[3], d, invalid],
+        );
+
+        let counts = canonicalize_outputs(&dir).unwrap();
+        let read = |result| fs::read_to_string(names.result_path(&dir, result)).unwrap();
+        let (ok, ng, unknown) = (
+            read(SearchResult::Found),
+            read(SearchResult::NotFound),
+            read(SearchResult::Unknown),
+        );
+        fs::remove_dir_all(&dir).unwrap();
+        let canonical = |b: Board| {
+            let [player, opponent] = b.unique();
+            Board::new(player, opponent).to_string()
+        };
+        assert_eq!(counts, [2, 1, 1]);
+        assert_eq!(
+            ok.lines().collect::<Vec<_>>(),
+            [
+                format!("{} 2", canonical(b)),
+                // 不正な盤面は正規化せずにそのまま数える
+                format!("{} 2", invalid.to_string()),
+            ]
+        );
+        assert_eq!(
+            ng.lines().collect::<Vec<_>>(),
+            [format!("{} 2", canonical(c))]
+        );
+        assert_eq!(
+            unknown.lines().collect::<Vec<_>>(),
+            [format!("{} 1", canonical(d))]
+        );
+    }
+
+    #[test]
+    fn symmetric_boards_judged_both_ok_and_ng_are_reported_without_rewriting() {
+        let dir = scratch_dir("canonical_conflict");
+        let names = OutputNames::global();
+        let b = Board::play_sequence(&[37, 43, 18, 19, 26]).unwrap();
+        let mirrored = b.symmetry_orbit()[3];
+        assert_ne!(mirrored, b);
+        let ok = format!("{}\n", b.to_string());
+        let ng = format!("{}\n", mirrored.to_string());
+        fs::write(names.result_path(&dir, SearchResult::Found), &ok).unwrap();
+        fs::write(names.result_path(&dir, SearchResult::NotFound), &ng).unwrap();
+        fs::write(names.result_path(&dir, SearchResult::Unknown), "").unwrap();
+
+        let err = canonicalize_outputs(&dir).unwrap_err();
+        let read = |result| fs::read_to_string(names.result_path(&dir, result)).unwrap();
+        let (ok_after, ng_after) = (read(SearchResult::Found), read(SearchResult::NotFound));
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let [player, opponent] = b.unique();
+        assert!(err
+            .to_string()
+            .contains(&Board::new(player, opponent).to_string()));
+        assert_eq!((ok_after, ng_after), (ok, ng));
+    }
+
+    #[test]
+    fn unique_count_identifies_symmetric_boards() {
+        let dir = scratch_dir("unique");
+        let path = dir.join("boards.txt");
+        let b = Board::play_sequence(&[37, 43, 18, 19, 26]).unwrap();
+        let c = Board::play_sequence(&[37, 45]).unwrap();
+        let invalid = Board::new(1, 0);
+        let mut boards = b.symmetry_orbit().to_vec();
+        boards.extend([c, invalid, invalid]);
+        let text: String = boards.iter().map(|b| b.to_string() + "\n").collect();
+        fs::write(&path, text).unwrap();
+        let count = count_unique_boards(&path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        // b の 8 通りの向きで 1 種類、c で 1 種類、同じ不正な盤面 2 つで 1 種類
+        assert_eq!(count, 3);
+    }
+
+    #[test]
+    fn expected_verdicts_are_read_and_checked_against_the_outputs() {
+        let dir = scratch_dir("expected");
+        let input = dir.join("input.txt");
+        let [b, c, d, e] = [&[37][..], &[37, 45], &[37, 43, 18], &[37, 43, 18, 19]]
+            .map(|moves| Board::play_sequence(moves).unwrap());
+        fs::write(
+            &input,
+            format!(
+                "{}\tOK\n{}\n{}\t NG \n{}\tUNKNOWN\n",
+                b.to_string(),
+                c.to_string(),
+                d.to_string(),
+                e.to_string()
+            ),
+        )
+        .unwrap();
+        let expected = read_expected(&input).unwrap();
+        // タブの無い c は期待する判定が無いので読み飛ばすが、探索の入力には含まれる
+        assert_eq!(
+            expected,
+            [
+                (b, SearchResult::Found),
+                (d, SearchResult::NotFound),
+                (e, SearchResult::Unknown)
+            ]
+        );
+        assert_eq!(
+            parse_file_to_boards(&input.to_string_lossy()).unwrap(),
+            [b, c, d, e]
+        );
+
+        let names = OutputNames::global();
+        fs::write(
+            names.result_path(&dir, SearchResult::Found),
+            format!("{}\n{}\n{}\n", b.to_string(), c.to_string(), d.to_string()),
+        )
+        .unwrap();
+        fs::write(names.result_path(&dir, SearchResult::NotFound), "").unwrap();
+        fs::write(names.result_path(&dir, SearchResult::Unknown), "").unwrap();
+        let mismatches = check_expected(&input, &dir).unwrap();
+
+        fs::write(
+            &input,
+            format!("{}\tOK\n\n{}\tMAYBE\n", b.to_string(), c.to_string()),
+        )
+        .unwrap();
+        let err = read_expected(&input).unwrap_err();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            mismatches,
+            [
+                ExpectedMismatch {
+                    board: d,
+                    expected: SearchResult::NotFound,
+                    got: Some(SearchResult::Found),
+                },
+                // どの結果ファイルにも書かれていない盤面
+                ExpectedMismatch {
+                    board: e,
+                    expected: SearchResult::Unknown,
+                    got: None,
+                },
+            ]
+        );
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().starts_with("line 3:"), "{}", err);
+        assert!(err.to_string().contains("'MAYBE'"), "{}", err);
+    }
+
+    #[test]
+    fn without_force_existing_outputs_are_refused_before_anything_is_created() {
+        let dir = scratch_dir("no_force");
+        let names = OutputNames::new("run", false).with_summary(true);
+        let existing = names.result_path(&dir, SearchResult::Unknown);
+        fs::write(&existing, "kept\n").unwrap();
+
+        let err = create_output(&existing, false).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        let err = names.create(&existing).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        let err = ReverseOutputs::create_with(&dir, &names).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert!(err.to_string().contains("run_UNKNOWN.txt"), "{}", err);
+        // どのファイルも作られず、既にあったファイルもそのまま
+        let mut files: Vec<_> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        files.sort();
+        assert_eq!(files, ["run_UNKNOWN.txt"]);
+        assert_eq!(fs::read_to_string(&existing).unwrap(), "kept\n");
+
+        // 新しいファイルなら作れ、force なら上書きする
+        let fresh = dir.join("fresh.txt");
+        names.create(&fresh).unwrap();
+        assert!(fresh.exists());
+        create_output(&existing, true).unwrap();
+        let emptied = fs::read_to_string(&existing).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(emptied, "");
+    }
+
+    #[test]
+    fn symmetric_unknown_boards_enter_the_hard_corpus_once() {
+        let dir = scratch_dir("hard_corpus_symmetric");
+        let corpus = dir.join("hard.txt");
+        let b = Board::play_sequence(&[37, 43, 18, 19, 26]).unwrap();
+        let c = Board::play_sequence(&[37, 45]).unwrap();
+        let mut unknown: Vec<Board> = b.symmetry_orbit().to_vec();
+        unknown.push(c);
+        unknown.push(b);
+        let text: String = unknown.iter().map(|b| b.to_string() + "\n").collect();
+        fs::write(
+            OutputNames::global().result_path(&dir, SearchResult::Unknown),
+            text,
+        )
+        .unwrap();
+        let written = write_hard_corpus(&dir, &corpus).unwrap();
+        let lines = fs::read_to_string(&corpus).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        // 同値類ごとに最初に現れた向きで 1 行ずつ
+        assert_eq!(written, 2);
+        assert_eq!(
+            lines.lines().collect::<Vec<_>>(),
+            [b.symmetry_orbit()[0].to_string(), c.to_string()]
+        );
+    }
+
+    #[test]
+    fn histogram_counts_boards_per_disc_count_in_ascending_order() {
+        let dir = scratch_dir("histogram");
+        let path = dir.join("boards.txt");
+        let boards = [
+            Board::play_sequence(&[37, 43, 18, 19, 26]).unwrap(),
+            Board::play_sequence(&[37]).unwrap(),
+            Board::play_sequence(&[37, 43, 18, 19, 26, 29, 45]).unwrap(),
+            Board::initial(),
+            Board::play_sequence(&[37, 43]).unwrap(),
+            Board::play_sequence(&[19]).unwrap(),
+        ];
+        let text: String = boards.iter().map(|b| b.to_string() + "\n").collect();
+        fs::write(&path, text).unwrap();
+        let histogram = disc_count_histogram(&path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            histogram.into_iter().collect::<Vec<_>>(),
+            [(4, 1), (5, 2), (6, 1), (9, 1), (11, 1)]
+        );
+    }
+
+    #[test]
+    fn canonical_index_maps_each_board_to_its_canonical_form_and_back() {
+        let dir = scratch_dir("canonical_index");
+        let path = dir.join("boards.txt");
+        let b = Board::play_sequence(&[37, 43, 18, 19, 26]).unwrap();
+        let mut boards = b.symmetry_orbit().to_vec();
+        boards.push(Board::play_sequence(&[37, 45]).unwrap());
+        let invalid = Board::new(1, 0);
+        let mut text: String = boards.iter().map(|b| b.to_string() + "\n").collect();
+        text.push_str(&(invalid.to_string() + "\n"));
+        fs::write(&path, text).unwrap();
+
+        let mut out = Vec::new();
+        let written = write_canonical_index(&path, &mut out).unwrap();
+        // 不正な盤面は飛ばす
+        assert_eq!(written, boards.len());
+        let out = String::from_utf8(out).unwrap();
+        let mut canonical_column = String::new();
+        for (line, board) in out.lines().zip(&boards) {
+            let fields: Vec<&str> = line.split('\t').collect();
+            assert_eq!(fields.len(), 3, "{}", line);
+            assert_eq!(fields[0], board.to_string());
+            let canonical = Board::parse(fields[1]).unwrap();
+            let symmetry: i32 = fields[2].parse().unwrap();
+            assert_eq!([canonical.player, canonical.opponent], board.unique());
+            let mut sym = [0u64; 2];
+            board.board_symmetry(symmetry, &mut sym);
+            assert_eq!(sym, board.unique());
+            canonical.board_symmetry(Board::inverse_symmetry(symmetry), &mut sym);
+            assert_eq!(Board::new(sym[0], sym[1]), *board);
+            canonical_column.push_str(fields[1]);
+            canonical_column.push('\n');
+        }
+
+        // 正規形を入力し直すと、どれも添字 0 で同じ正規形になる
+        fs::write(&path, canonical_column).unwrap();
+        let mut again = Vec::new();
+        write_canonical_index(&path, &mut again).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        for line in String::from_utf8(again).unwrap().lines() {
+            let fields: Vec<&str> = line.split('\t').collect();
+            assert_eq!(fields[0], fields[1]);
+            assert_eq!(fields[2], "0");
+        }
+    }
+
+    #[test]
+    fn each_layout_puts_characters_on_its_documented_squares() {
+        let expected = [
+            (BoardLayout::RowMajorTopLeft, "a1 b1 c1 d1 e1 f1 g1 h1"),
+            (BoardLayout::RowMajorBottomLeft, "a8 b8 c8 d8 e8 f8 g8 h8"),
+            (BoardLayout::ColumnMajorTopLeft, "a1 a2 a3 a4 a5 a6 a7 a8"),
+            (
+                BoardLayout::ColumnMajorBottomLeft,
+                "a8 a7 a6 a5 a4 a3 a2 a1",
+            ),
+        ];
+        for (layout, first_row) in expected {
+            let squares: Vec<String> = (0..64)
+                .map(|i| {
+                    let mut line = vec![b'-'; 64];
+                    line[i] = b'X';
+                    let board = parse_line_to_board_with_layout(
+                        std::str::from_utf8(&line).unwrap(),
+                        layout,
+                    )
+                    .unwrap();
+                    assert_eq!(board.player.count_ones(), 1);
+                    assert_eq!(board.opponent, 0);
+                    square_name(board.player.trailing_zeros() as usize)
+                })
+                .collect();
+            assert_eq!(squares[..8].join(" "), first_row, "{}", layout.name());
+            // 64 文字で全てのマスをちょうど 1 回ずつ指す
+            let distinct: HashSet<&String> = squares.iter().collect();
+            assert_eq!(distinct.len(), 64, "{}", layout.name());
+        }
+        let line = Board::play_sequence(&[37, 43, 18]).unwrap().to_string();
+        assert_eq!(parse_line_to_board(&line), Board::parse(&line));
+    }
+
+    #[test]
+    fn layouts_parse_from_their_names() {
+        for layout in BoardLayout::ALL {
+            assert_eq!(layout.name().parse::<BoardLayout>().unwrap(), layout);
+        }
+        let err = "row-major".parse::<BoardLayout>().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(
+            err.to_string().contains("column-major-bottom-left"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn summary_counts_every_board_written_by_disc_count() {
+        let dir = scratch_dir("summary");
+        let names = OutputNames::new("run", true).with_summary(true);
+        let boards = [
+            (
+                Board::play_sequence(&[37, 43, 18]).unwrap(),
+                SearchResult::Found,
+            ),
+            (
+                Board::play_sequence(&[37, 45]).unwrap(),
+                SearchResult::Found,
+            ),
+            (
+                Board::play_sequence(&[37, 43]).unwrap(),
+                SearchResult::NotFound,
+            ),
+            (
+                Board::play_sequence(&[37, 43, 18]).unwrap(),
+                SearchResult::Unknown,
+            ),
+        ];
+        let mut outputs = ReverseOutputs::create_with(&dir, &names).unwrap();
+        for (board, result) in &boards {
+            outputs
+                .write_result(*result, board, &board.to_string())
+                .unwrap();
+        }
+        // 不正な盤面は NG として数える
+        let invalid = Board::new(1, 0);
+        outputs
+            .write_invalid(&invalid, &invalid.to_string())
+            .unwrap();
+        let counted = outputs.summary().unwrap().clone();
+        drop(outputs);
+        let loaded = ResultSummary::load(&names.summary_path(&dir)).unwrap();
+        let lines = [
+            SearchResult::Found,
+            SearchResult::NotFound,
+            SearchResult::Unknown,
+        ]
+        .map(|result| {
+            fs::read_to_string(names.result_path(&dir, result))
+                .unwrap()
+                .lines()
+                .count() as u64
+        });
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(loaded, counted);
+        assert_eq!(loaded.totals(), lines);
+        assert_eq!(loaded.total(), 5);
+        assert_eq!(
+            loaded.levels().collect::<Vec<_>>(),
+            [(1, [0, 1, 0]), (6, [1, 1, 0]), (7, [1, 0, 1])]
+        );
+        assert_eq!(loaded.level(8), [0, 0, 0]);
+    }
+
+    #[test]
+    fn result_summary_round_trips_and_rejects_bad_files() {
+        let dir = scratch_dir("summary_file");
+        let path = dir.join("summary.bin");
+        let mut summary = ResultSummary::new();
+        for (discs, result, times) in [
+            (20, SearchResult::Found, 3),
+            (20, SearchResult::Unknown, 1),
+            (64, SearchResult::NotFound, 2),
+        ] {
+            for _ in 0..times {
+                summary.add(discs, result);
+            }
+        }
+        summary.save(&path).unwrap();
+        let bytes = fs::read(&path).unwrap();
+        assert_eq!(bytes.len(), 8 + 8 + 2 * 4 * 8);
+        assert_eq!(ResultSummary::load(&path).unwrap(), summary);
+        // 一時ファイルは残らない
+        assert!(!dir.join("summary.bin.tmp").exists());
+
+        let mut bad_magic = bytes.clone();
+        bad_magic[0] ^= 1;
+        fs::write(&path, &bad_magic).unwrap();
+        let err = ResultSummary::load(&path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        for len in [4, 12, bytes.len() - 1] {
+            fs::write(&path, &bytes[..len]).unwrap();
+            let err = ResultSummary::load(&path).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof, "len = {}", len);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
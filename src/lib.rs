@@ -1,4 +1,20 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "std")]
 pub mod io;
 pub mod othello;
+pub mod prelude;
 pub mod prunings;
+#[cfg(feature = "std")]
+pub mod sampling;
+#[cfg(feature = "std")]
 pub mod search;
+#[cfg(feature = "std")]
+pub mod stats;
+#[cfg(test)]
+mod test_util;
+pub mod verbosity;
+#[cfg(feature = "wasm")]
+pub mod wasm;
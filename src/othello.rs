@@ -1,4 +1,8 @@
-use std::cmp::Ordering;
+#[cfg(not(feature = "std"))]
+use alloc::{string::String, vec, vec::Vec};
+use core::cmp::Ordering;
+#[cfg(feature = "std")]
+use std::sync::OnceLock;
 
 /**
  * edax-reversi
@@ -31,6 +35,108 @@ use std::cmp::Ordering;
 
 pub const CENTER_MASK: u64 = 0x0000_0018_1800_0000u64; // 4 center squares
 
+/// 探索の前提となる初期配置（変則ルールの開始局面を表す）
+///
+/// `initial()[0]` が手番側、`initial()[1]` が相手側の石。
+/// 石の置き方は変えられるが、占有するマスは標準と同じ中央4マス（`CENTER_MASK`）でなければならない。
+/// 逆方向探索と枝刈り（`check_occupancy` / `is_connected`）は中央4マスの占有だけを前提にしているので
+/// 配置によらず共通で、初期配置の違いはリーフ表（`LeafCache::with_ruleset`）にだけ現れる。
+/// この前提を崩さないよう、任意の配置は `Ruleset::new` で検査してから作る。
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Ruleset {
+    initial: [u64; 2],
+}
+
+impl Ruleset {
+    /// 標準ルール（中央4マスに斜めに交差した配置）
+    pub const fn standard() -> Self {
+        Self {
+            initial: [0x0000_0008_1000_0000, 0x0000_0010_0800_0000],
+        }
+    }
+
+    /// 平行配置（同じ色の石が横に並ぶ配置）
+    pub const fn parallel() -> Self {
+        Self {
+            initial: [0x0000_0000_1800_0000, 0x0000_0018_0000_0000],
+        }
+    }
+
+    /// 手番側 `player`、相手側 `opponent` の初期配置。石がちょうど中央4マスを埋めていなければ `Err`。
+    pub fn new(player: u64, opponent: u64) -> Result<Self, RulesetError> {
+        if player & opponent != 0 {
+            return Err(RulesetError::Overlap);
+        }
+        if player | opponent != CENTER_MASK {
+            return Err(RulesetError::NotCenter);
+        }
+        Ok(Self {
+            initial: [player, opponent],
+        })
+    }
+
+    /// `[手番側, 相手側]` の石
+    pub fn initial(&self) -> [u64; 2] {
+        self.initial
+    }
+
+    pub fn initial_board(&self) -> Board {
+        Board::new(self.initial[0], self.initial[1])
+    }
+}
+
+/// `standard` / `parallel`、または中央4マスだけに石がある 64 セルの盤面文字列（`Board::parse` の形式）
+impl core::str::FromStr for Ruleset {
+    type Err = RulesetError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "standard" => Ok(Self::standard()),
+            "parallel" => Ok(Self::parallel()),
+            _ => {
+                let board = Board::parse(s).ok_or(RulesetError::Unparsable)?;
+                Self::new(board.player, board.opponent)
+            }
+        }
+    }
+}
+
+/// `Ruleset::new` / `Ruleset::from_str` のエラー型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RulesetError {
+    /// 手番側と相手側の石が重なっている
+    Overlap,
+    /// 石がちょうど中央4マスを埋めていない
+    NotCenter,
+    /// `standard` / `parallel` でも 64 セルの盤面でもない
+    Unparsable,
+}
+
+impl core::fmt::Display for RulesetError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            RulesetError::Overlap => write!(f, "two discs on the same square"),
+            RulesetError::NotCenter => {
+                write!(
+                    f,
+                    "the initial discs must fill exactly the four center squares"
+                )
+            }
+            RulesetError::Unparsable => {
+                write!(f, "expected standard, parallel or a 64-cell board of X/O/-")
+            }
+        }
+    }
+}
+
+impl core::error::Error for RulesetError {}
+
+impl Default for Ruleset {
+    fn default() -> Self {
+        Self::standard()
+    }
+}
+
 /// 8方向を表すEnum
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub enum Direction {
@@ -74,6 +180,21 @@ impl Direction {
     }
 }
 
+/// 方向dに1マス分ビットシフトする
+#[inline]
+pub fn shift(d: Direction, b: u64) -> u64 {
+    match d {
+        Direction::N => north(b),
+        Direction::S => south(b),
+        Direction::E => east(b),
+        Direction::W => west(b),
+        Direction::NE => ne(b),
+        Direction::NW => nw(b),
+        Direction::SE => se(b),
+        Direction::SW => sw(b),
+    }
+}
+
 /// 方向dと逆方向に1マス分ビットシフトする
 #[inline]
 pub fn backshift(d: Direction, b: u64) -> u64 {
@@ -89,6 +210,39 @@ pub fn backshift(d: Direction, b: u64) -> u64 {
     }
 }
 
+/// 一辺 `width` マスの盤（行ストライドも `width`、u64 の下位 `width * width` ビット）の全マス
+pub const fn square_board_mask(width: u32) -> u64 {
+    if width >= 8 {
+        u64::MAX
+    } else {
+        (1u64 << (width * width)) - 1
+    }
+}
+
+/// 一辺 `width` マスの正方盤（行ストライドも `width`）の中央 4 マス
+pub const fn square_center_mask(width: u32) -> u64 {
+    let a = (width / 2 - 1) * width + width / 2 - 1;
+    (0b11u64 << a) | (0b11u64 << (a + width))
+}
+
+// 8x8 の中央 4 マスは `CENTER_MASK` と一致していなければならない
+const _: () = assert!(square_center_mask(8) == CENTER_MASK);
+
+/// 一辺 `width` マスの盤で、全ての石を `(dx, dy)` 方向に 1 マス動かす。盤外に出た石は消える。
+#[inline]
+pub fn shift_sized(b: u64, dx: i32, dy: i32, width: u32) -> u64 {
+    let delta = dy * width as i32 + dx;
+    let shifted = if delta >= 0 { b << delta } else { b >> -delta };
+    // 横方向に動かすときは、反対側の端の列に回り込んだ石を落とす
+    let column = (0..width).fold(0u64, |m, y| m | (1u64 << (y * width)));
+    let wrapped = match dx {
+        1 => column,
+        -1 => column << (width - 1),
+        _ => 0,
+    };
+    shifted & !wrapped & square_board_mask(width)
+}
+
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub struct Board {
     pub player: u64,
@@ -100,6 +254,13 @@ impl Board {
         Self { player, opponent }
     }
 
+    /// `validate_board` を通る場合だけ盤面を作る
+    pub fn try_new(player: u64, opponent: u64) -> Result<Self, BoardValidation> {
+        let board = Self { player, opponent };
+        validate_board(&board)?;
+        Ok(board)
+    }
+
     pub fn empty() -> Self {
         Self {
             player: 0,
@@ -107,6 +268,24 @@ impl Board {
         }
     }
 
+    /// 手番側・相手側の順にそれぞれ 8 バイトのリトルエンディアンで並べた 16 バイト。
+    /// ホストのエンディアンによらないので、機械の間で持ち運ぶファイルに使う。
+    pub fn to_le_bytes(&self) -> [u8; 16] {
+        let mut bytes = [0u8; 16];
+        bytes[..8].copy_from_slice(&self.player.to_le_bytes());
+        bytes[8..].copy_from_slice(&self.opponent.to_le_bytes());
+        bytes
+    }
+
+    /// `to_le_bytes` の逆
+    pub fn from_le_bytes(bytes: [u8; 16]) -> Self {
+        let (player, opponent) = bytes.split_at(8);
+        Self {
+            player: u64::from_le_bytes(player.try_into().unwrap()),
+            opponent: u64::from_le_bytes(opponent.try_into().unwrap()),
+        }
+    }
+
     fn transpose(b: u64) -> u64 {
         let mut b = b;
         let mut t;
@@ -133,12 +312,35 @@ impl Board {
         ((b >> 4) & 0x0F0F0F0F0F0F0F0F) | ((b << 4) & 0xF0F0F0F0F0F0F0F0)
     }
 
+    /// 左右反転（a 列 ↔ h 列）した盤面
+    pub fn mirrored_h(&self) -> Board {
+        Board::new(
+            Self::horizontal_mirror(self.player),
+            Self::horizontal_mirror(self.opponent),
+        )
+    }
+
+    /// 上下反転（1 段目 ↔ 8 段目）した盤面
+    pub fn mirrored_v(&self) -> Board {
+        Board::new(
+            Self::vertical_mirror(self.player),
+            Self::vertical_mirror(self.opponent),
+        )
+    }
+
+    /// a1-h8 の対角線で転置した盤面
+    pub fn transposed(&self) -> Board {
+        Board::new(Self::transpose(self.player), Self::transpose(self.opponent))
+    }
+
+    /// 1 段目を下にして描いたときに時計回りに 90 度回した盤面（a1 → a8, h1 → a1）
+    pub fn rotated_90(&self) -> Board {
+        self.transposed().mirrored_v()
+    }
+
     fn board_check(board: [u64; 2]) {
-        if board[0] & board[1] != 0 {
-            panic!("Two discs on the same square?");
-        }
-        if (board[0] | board[1]) & 0x0000001818000000 != 0x0000001818000000 {
-            panic!("Empty center?");
+        if let Err(e) = validate_board(&Board::new(board[0], board[1])) {
+            panic!("{}", e);
         }
     }
 
@@ -166,12 +368,62 @@ impl Board {
         self.player.count_ones() + self.opponent.count_ones()
     }
 
+    /// 両者とも合法手が無い（終局している）か
+    pub fn is_game_over(&self) -> bool {
+        !has_move(self.player, self.opponent) && !has_move(self.opponent, self.player)
+    }
+
+    /// 終局していれば石数で決めた勝敗、していなければ `None`。
+    /// `Board` は石の色を持たないので、勝敗は `player` 側から見たものになる。
+    pub fn result(&self) -> Option<Outcome> {
+        if !self.is_game_over() {
+            return None;
+        }
+        let (p, o) = (self.player.count_ones(), self.opponent.count_ones());
+        Some(match p.cmp(&o) {
+            core::cmp::Ordering::Greater => Outcome::PlayerWin,
+            core::cmp::Ordering::Less => Outcome::OpponentWin,
+            core::cmp::Ordering::Equal => Outcome::Draw,
+        })
+    }
+
+    /// 1 枚の bitboard の 8 通りの対称形（添字 s は `board_symmetry` と同じ意味）。
+    /// 鏡映の途中結果を共有して計算量を減らす。
+    fn symmetries_of(b: u64) -> [u64; 8] {
+        let h = Self::horizontal_mirror(b);
+        let v = Self::vertical_mirror(b);
+        let hv = Self::vertical_mirror(h);
+        [
+            b,
+            h,
+            v,
+            hv,
+            Self::transpose(b),
+            Self::transpose(h),
+            Self::transpose(v),
+            Self::transpose(hv),
+        ]
+    }
+
+    /// 8 通りの対称形をまとめて返す（`sym[s]` は `board_symmetry(s, ..)` の結果と一致）
+    pub fn all_symmetries(&self) -> [[u64; 2]; 8] {
+        let p = Self::symmetries_of(self.player);
+        let o = Self::symmetries_of(self.opponent);
+        core::array::from_fn(|s| [p[s], o[s]])
+    }
+
+    /// 8 通りの対称形を `Board` で返す（`orbit[s]` は `board_symmetry(s, ..)` の結果と一致）。
+    /// 重複は除かないので、対称な盤面では同じ盤面が複数回現れる。最小のものが `unique()`。
+    pub fn symmetry_orbit(&self) -> [Board; 8] {
+        self.all_symmetries()
+            .map(|[player, opponent]| Board::new(player, opponent))
+    }
+
     pub fn unique(&self) -> [u64; 2] {
-        let mut tmp = [0u64, 0u64];
-        let mut answer = [self.player, self.opponent];
+        let syms = self.all_symmetries();
+        let mut answer = syms[0];
 
-        for i in 1..8 {
-            self.board_symmetry(i, &mut tmp);
+        for &tmp in &syms[1..] {
             if tmp < answer {
                 answer = tmp;
             }
@@ -181,9 +433,121 @@ impl Board {
         answer
     }
 
+    /// `unique()` と、その正規形を与える対称変換の添字 `s`
+    /// （`board_symmetry(s, ..)` の結果が正規形に一致する最小の `s`）を返す。
+    pub fn unique_with_symmetry(&self) -> ([u64; 2], i32) {
+        let syms = self.all_symmetries();
+        let mut answer = syms[0];
+        let mut index = 0;
+
+        for (s, &tmp) in syms.iter().enumerate().skip(1) {
+            if tmp < answer {
+                answer = tmp;
+                index = s as i32;
+            }
+        }
+
+        Self::board_check(answer);
+        (answer, index)
+    }
+
+    /// 対称変換 `s` の逆変換の添字。
+    /// 鏡映は自身が逆変換で、転置と鏡映の合成（s = 5, 6）は互いに逆になる。
+    pub const fn inverse_symmetry(s: i32) -> i32 {
+        const INVERSE: [i32; 8] = [0, 1, 2, 3, 4, 6, 5, 7];
+        INVERSE[s as usize]
+    }
+
+    /// `(self.unique(), 手番を入れ替えた盤面の unique())` を 1 回の対称変換でまとめて求める。
+    /// パス後の向きも登録する箇所で `unique()` を 2 回呼ぶ代わりに使う。
+    pub fn unique_with_swapped(&self) -> ([u64; 2], [u64; 2]) {
+        let syms = self.all_symmetries();
+        let mut answer = syms[0];
+        let mut swapped = [syms[0][1], syms[0][0]];
+
+        for &tmp in &syms[1..] {
+            if tmp < answer {
+                answer = tmp;
+            }
+            let tmp = [tmp[1], tmp[0]];
+            if tmp < swapped {
+                swapped = tmp;
+            }
+        }
+
+        Self::board_check(answer);
+        (answer, swapped)
+    }
+
     pub fn initial() -> Self {
-        Self::new(0x0000000810000000, 0x0000001008000000)
+        Ruleset::standard().initial_board()
+    }
+
+    /// 初期配置から着手列 `moves`（各要素は 0..64 のマス番号）を順に打った局面を返す。
+    /// 手番側に合法手が無く相手にある場合は自動でパスする。
+    pub fn play_sequence(moves: &[usize]) -> Result<Board, MoveError> {
+        let mut b = Self::initial();
+        for (index, &pos) in moves.iter().enumerate() {
+            if !has_move(b.player, b.opponent) {
+                if !has_move(b.opponent, b.player) {
+                    return Err(MoveError::GameOver { index });
+                }
+                b = Board::new(b.opponent, b.player);
+            }
+            if pos >= 64 {
+                return Err(MoveError::IllegalMove { index, pos });
+            }
+            let flipped = flip(pos, b.player, b.opponent);
+            if flipped == 0 {
+                return Err(MoveError::IllegalMove { index, pos });
+            }
+            b = Board {
+                player: b.opponent ^ flipped,
+                opponent: b.player ^ (flipped | (1u64 << pos)),
+            };
+        }
+        Ok(b)
+    }
+
+    /// 64セルの 'X', 'O', '-' 文字列を Board に変換。失敗したら None。
+    /// `to_string` の逆で、それ以外の文字は読み飛ばす。
+    pub fn parse(line: &str) -> Option<Board> {
+        let mut player: u64 = 0;
+        let mut opponent: u64 = 0;
+        let mut idx = 0u32;
+        for c in line.chars() {
+            match c {
+                'X' => {
+                    if idx >= 64 {
+                        return None;
+                    }
+                    player |= 1_u64 << idx;
+                    idx += 1;
+                }
+                'O' => {
+                    if idx >= 64 {
+                        return None;
+                    }
+                    opponent |= 1_u64 << idx;
+                    idx += 1;
+                }
+                '-' => {
+                    if idx >= 64 {
+                        return None;
+                    }
+                    idx += 1;
+                }
+                _ => (),
+            }
+        }
+
+        if idx == 64 {
+            Some(Board::new(player, opponent))
+        } else {
+            None
+        }
     }
+
     pub fn to_string(&self) -> String {
         let mut ans: Vec<char> = vec![];
         for y in 0..8 {
@@ -235,6 +599,91 @@ impl Ord for Board {
     }
 }
 
+/// 対称な盤面を同一視する `Board` のラッパ。
+/// `Hash`/`Eq` は `unique()` の結果で定義されるので、`HashSet<CanonicalBoard>` で
+/// 対称形の重複をそのまま除ける。
+#[derive(Debug, Clone, Copy)]
+pub struct CanonicalBoard {
+    board: Board,
+    key: [u64; 2],
+}
+
+impl CanonicalBoard {
+    pub fn new(board: Board) -> Self {
+        Self {
+            board,
+            key: board.unique(),
+        }
+    }
+
+    /// 包んでいる元の盤面（正規化前）
+    pub fn board(&self) -> Board {
+        self.board
+    }
+
+    /// 正規形（`unique()` の結果）
+    pub fn key(&self) -> [u64; 2] {
+        self.key
+    }
+}
+
+impl From<Board> for CanonicalBoard {
+    fn from(board: Board) -> Self {
+        Self::new(board)
+    }
+}
+
+impl PartialEq for CanonicalBoard {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl Eq for CanonicalBoard {}
+
+impl core::hash::Hash for CanonicalBoard {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        self.key.hash(state);
+    }
+}
+
+/// 盤面の `[player, opponent]` を表すキー。既訪問表・BFS の一時ファイル・GBFS の優先度キューで
+/// 共通に使う。順序は `[u64; 2]`（および `(u64, u64)`）の辞書式順序と同じで、
+/// `.bin` ファイルのレコード（ネイティブエンディアンの u64 が 2 つ）とバイト列として一致する。
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BoardKey(pub [u64; 2]);
+
+// `[u64; 2]` の透過的なラッパなので、`.bin` の読み書きでバイト列として扱ってよい
+#[cfg(feature = "std")]
+unsafe impl bytemuck::Zeroable for BoardKey {}
+#[cfg(feature = "std")]
+unsafe impl bytemuck::Pod for BoardKey {}
+
+impl From<[u64; 2]> for BoardKey {
+    fn from(key: [u64; 2]) -> Self {
+        BoardKey(key)
+    }
+}
+
+impl From<BoardKey> for [u64; 2] {
+    fn from(key: BoardKey) -> Self {
+        key.0
+    }
+}
+
+impl From<Board> for BoardKey {
+    fn from(board: Board) -> Self {
+        BoardKey([board.player, board.opponent])
+    }
+}
+
+impl From<BoardKey> for Board {
+    fn from(key: BoardKey) -> Self {
+        Board::new(key.0[0], key.0[1])
+    }
+}
+
 /// 1方向に対する「はさみ取り」判定。はさめるならその方向の反転集合を返す。
 #[inline(always)]
 fn ray_flips<F>(move_bb: u64, player: u64, opponent: u64, step: F) -> u64
@@ -276,6 +725,23 @@ const fn not_rank_8() -> u64 {
     0x00FF_FFFF_FFFF_FFFF
 }
 
+/// bitboard を 8 行の格子として表示する（立っているビットは `#`、それ以外は `.`）。
+/// 並びは `Board::show` と同じで、1 行目が A1..H1。
+pub fn format_mask(m: u64) -> String {
+    let mut s = String::with_capacity(72);
+    for y in 0..8 {
+        for x in 0..8 {
+            s.push(if m & (1u64 << (y * 8 + x)) != 0 {
+                '#'
+            } else {
+                '.'
+            });
+        }
+        s.push('\n');
+    }
+    s
+}
+
 #[inline(always)]
 pub fn east(x: u64) -> u64 {
     (x << 1) & not_a_file()
@@ -329,20 +795,294 @@ pub fn flip(pos: usize, player: u64, opponent: u64) -> u64 {
         | ray_flips(move_bb, player, opponent, sw)
 }
 
+/// `flip` を方向ごとに分けたもの。`i` 番目は `Direction::all()[i]` 方向に返る石の集合で、
+/// すべての OR は `flip(pos, player, opponent)` と一致する。
+pub fn flip_by_direction(pos: usize, player: u64, opponent: u64) -> [u64; 8] {
+    debug_assert!(pos < 64);
+    let move_bb = 1u64 << pos;
+    if (move_bb & (player | opponent)) != 0 {
+        return [0; 8];
+    }
+    Direction::all().map(|d| ray_flips(move_bb, player, opponent, |x| shift(d, x)))
+}
+
+/// `flip_tabled` が使う表。
+///
+/// 着手マスを通る 4 本のライン (横・縦・2 本の斜め) それぞれを 8 ビットに詰め、
+/// ライン上の位置と内側 6 マスの相手石の配置から「挟む側の自石が来るべきマス」を、
+/// さらにそのマスと着手位置から反転するマスを引く (edax の kindergarten 方式と同じ考え方)。
+///
+/// メモリ使用量は `outflank` 512 B + `flipped` 2 KiB + `column` 2 KiB + 斜めのマスク 1 KiB で、
+/// 合計 5.5 KiB 程度。std では最初の `FlipTables::get` で一度だけ構築される
+/// (no_std では `FlipTables::new` で構築したものを使い回す)。
+pub struct FlipTables {
+    /// `[ライン上の位置][内側 6 マスの相手石]` -> 挟む自石が置かれうるマス (8 ビット)
+    outflank: [[u8; 64]; 8],
+    /// `[ライン上の位置][挟んでいる自石]` -> 反転するマス (8 ビット)
+    flipped: [[u8; 256]; 8],
+    /// ランク順の 8 ビットを A 列へ展開したもの
+    column: [u64; 256],
+    /// 各マスを通る a1-h8 方向の斜めライン
+    diag: [u64; 64],
+    /// 各マスを通る h1-a8 方向の斜めライン
+    anti: [u64; 64],
+}
+
+const FILE_A: u64 = 0x0101_0101_0101_0101;
+/// A 列の 8 マスを最上位バイトへランク順に集めるための乗数
+const FILE_A_TO_BYTE: u64 = 0x0102_0408_1020_4080;
+
+impl FlipTables {
+    pub fn new() -> Self {
+        let mut outflank = [[0u8; 64]; 8];
+        for (p, row) in outflank.iter_mut().enumerate() {
+            for (o6, entry) in row.iter_mut().enumerate() {
+                let opp = (o6 as u32) << 1;
+                let mut mask = 0u8;
+                let mut i = p + 1;
+                while i < 8 && (opp >> i) & 1 != 0 {
+                    i += 1;
+                }
+                if i > p + 1 && i < 8 {
+                    mask |= 1 << i;
+                }
+                let mut i = p as i32 - 1;
+                while i >= 0 && (opp >> i) & 1 != 0 {
+                    i -= 1;
+                }
+                if i < p as i32 - 1 && i >= 0 {
+                    mask |= 1 << i;
+                }
+                *entry = mask;
+            }
+        }
+
+        let mut flipped = [[0u8; 256]; 8];
+        for (p, row) in flipped.iter_mut().enumerate() {
+            for (of, entry) in row.iter_mut().enumerate() {
+                let mut mask = 0u8;
+                for q in 0..8 {
+                    if (of >> q) & 1 == 0 {
+                        continue;
+                    }
+                    for i in (p.min(q) + 1)..p.max(q) {
+                        mask |= 1 << i;
+                    }
+                }
+                *entry = mask;
+            }
+        }
+
+        let mut column = [0u64; 256];
+        for (b, entry) in column.iter_mut().enumerate() {
+            for r in 0..8 {
+                if (b >> r) & 1 != 0 {
+                    *entry |= 1u64 << (r * 8);
+                }
+            }
+        }
+
+        let mut diag = [0u64; 64];
+        let mut anti = [0u64; 64];
+        for pos in 0..64 {
+            let (r0, f0) = ((pos / 8) as i32, (pos % 8) as i32);
+            for sq in 0..64 {
+                let (r, f) = (sq / 8, sq % 8);
+                if r - f == r0 - f0 {
+                    diag[pos] |= 1u64 << sq;
+                }
+                if r + f == r0 + f0 {
+                    anti[pos] |= 1u64 << sq;
+                }
+            }
+        }
+
+        FlipTables {
+            outflank,
+            flipped,
+            column,
+            diag,
+            anti,
+        }
+    }
+
+    /// 共有の表を返す（初回呼び出し時に構築する）
+    #[cfg(feature = "std")]
+    pub fn get() -> &'static FlipTables {
+        static TABLES: OnceLock<FlipTables> = OnceLock::new();
+        TABLES.get_or_init(FlipTables::new)
+    }
+
+    /// 8 ビットに詰めたライン上で、位置 `p` に打ったときに反転するマスを返す
+    #[inline(always)]
+    fn line_flip(&self, p: usize, player: u64, opponent: u64) -> u64 {
+        let o6 = ((opponent >> 1) & 0x3f) as usize;
+        let of = self.outflank[p][o6] as u64 & player;
+        self.flipped[p][of as usize] as u64
+    }
+
+    /// `flip` と同じ結果を表引きで求める
+    #[inline]
+    pub fn flip(&self, pos: usize, player: u64, opponent: u64) -> u64 {
+        debug_assert!(pos < 64);
+        if (1u64 << pos) & (player | opponent) != 0 {
+            return 0;
+        }
+        let (rank, file) = (pos / 8, pos % 8);
+
+        let shift = rank * 8;
+        let mut flips =
+            self.line_flip(file, (player >> shift) & 0xff, (opponent >> shift) & 0xff) << shift;
+
+        let col = |x: u64| ((x >> file) & FILE_A).wrapping_mul(FILE_A_TO_BYTE) >> 56;
+        flips |= self.column[self.line_flip(rank, col(player), col(opponent)) as usize] << file;
+
+        for mask in [self.diag[pos], self.anti[pos]] {
+            let line = |x: u64| (x & mask).wrapping_mul(FILE_A) >> 56;
+            let f = self.line_flip(file, line(player), line(opponent));
+            flips |= f.wrapping_mul(FILE_A) & mask;
+        }
+        flips
+    }
+}
+
+impl Default for FlipTables {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// `flip` の表引き版。結果は `flip` と同じ。
+#[cfg(feature = "std")]
+pub fn flip_tabled(pos: usize, player: u64, opponent: u64) -> u64 {
+    FlipTables::get().flip(pos, player, opponent)
+}
+
 pub fn get_moves(player: u64, opponent: u64) -> u64 {
     let mut moves = 0u64;
     for pos in 0..64 {
         let bit = 1u64 << pos;
-        if bit & (player | opponent) == 0 {
-            if flip(pos, player, opponent) != 0 {
-                moves |= bit;
-            }
+        if bit & (player | opponent) == 0 && flip(pos, player, opponent) != 0 {
+            moves |= bit;
         }
     }
     moves
 }
 
-/// ボード検証のエラー型
+/// 合法手があるかどうか。最初の合法手が見つかった時点で打ち切るので、
+/// `get_moves(player, opponent) != 0` より速い。
+pub fn has_move(player: u64, opponent: u64) -> bool {
+    let mut empty = !(player | opponent);
+    while empty != 0 {
+        let pos = empty.trailing_zeros() as usize;
+        empty &= empty - 1;
+        if flip(pos, player, opponent) != 0 {
+            return true;
+        }
+    }
+    false
+}
+
+/// 合法手の数
+pub fn mobility(player: u64, opponent: u64) -> u32 {
+    get_moves(player, opponent).count_ones()
+}
+
+/// 初期局面から `path.last()` までの局面の列が正しい棋譜になっているか確かめる。
+///
+/// 先頭が `Board::initial()` であり、隣り合う局面がちょうど 1 つの合法手か、
+/// 合法手が無いときのパス（相手には合法手がある）で結ばれていれば `Ok`。
+/// そうでなければ最初に見つかった問題を返す。
+pub fn verify_path(path: &[Board]) -> Result<(), PathError> {
+    let Some(first) = path.first() else {
+        return Err(PathError::Empty);
+    };
+    if *first != Board::initial() {
+        return Err(PathError::WrongStart);
+    }
+    for (index, pair) in path.windows(2).enumerate() {
+        let (prev, next) = (pair[0], pair[1]);
+        if !is_successor(&prev, &next) {
+            return Err(PathError::BrokenLink { index: index + 1 });
+        }
+    }
+    Ok(())
+}
+
+/// `verify_path` に加えて、最後の局面が `target` と一致するか確かめる。
+/// 逆方向探索が返した経路を、探索した盤面に対して検証するときに使う。
+pub fn verify_path_to(path: &[Board], target: &Board) -> Result<(), PathError> {
+    verify_path(path)?;
+    if path.last() != Some(target) {
+        return Err(PathError::WrongEnd);
+    }
+    Ok(())
+}
+
+/// `next` が `prev` から 1 手（またはパス）で進んだ局面か
+fn is_successor(prev: &Board, next: &Board) -> bool {
+    if !has_move(prev.player, prev.opponent) {
+        return has_move(prev.opponent, prev.player)
+            && *next == Board::new(prev.opponent, prev.player);
+    }
+    let placed = (next.player | next.opponent) & !(prev.player | prev.opponent);
+    if placed.count_ones() != 1 {
+        return false;
+    }
+    let pos = placed.trailing_zeros() as usize;
+    let flipped = flip(pos, prev.player, prev.opponent);
+    flipped != 0 && *next == Board::new(prev.opponent ^ flipped, prev.player ^ (flipped | placed))
+}
+
+/// `verify_path` のエラー型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathError {
+    /// 局面が 1 つも無い
+    Empty,
+    /// 先頭が初期局面ではない
+    WrongStart,
+    /// 最後の局面が `verify_path_to` の `target` ではない
+    WrongEnd,
+    /// `path[index - 1]` から `path[index]` へ 1 手でもパスでも進めない
+    BrokenLink { index: usize },
+}
+
+impl core::fmt::Display for PathError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            PathError::Empty => write!(f, "the path is empty"),
+            PathError::WrongStart => write!(f, "the path does not start at the initial position"),
+            PathError::WrongEnd => write!(f, "the path does not end at the target position"),
+            PathError::BrokenLink { index } => write!(
+                f,
+                "position {} is not reachable from position {} by one move or pass",
+                index,
+                index - 1
+            ),
+        }
+    }
+}
+
+impl core::error::Error for PathError {}
+
+/// 終局した盤面の勝敗（`Board::result`）。`player` / `opponent` は `Board` の同名の側を指す。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    PlayerWin,
+    OpponentWin,
+    Draw,
+}
+
+/// 着手列の再生エラー型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveError {
+    /// `index` 番目の着手 `pos` が合法手ではない
+    IllegalMove { index: usize, pos: usize },
+    /// `index` 番目の着手の時点で両者とも合法手が無い（終局済み）
+    GameOver { index: usize },
+}
+
+/// ボード検証のエラー型（クレート全体でこの型だけを使う）
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BoardValidation {
     /// プレイヤーと相手の石が重なっている
@@ -351,6 +1091,25 @@ pub enum BoardValidation {
     MissingCenter,
 }
 
+impl core::fmt::Display for BoardValidation {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            BoardValidation::Overlap => write!(f, "two discs on the same square"),
+            BoardValidation::MissingCenter => write!(f, "a center square is empty"),
+        }
+    }
+}
+
+impl core::error::Error for BoardValidation {}
+
+/// `io::Result` を返す関数の中で `?` を使えるように `InvalidInput` へ変換する
+#[cfg(feature = "std")]
+impl From<BoardValidation> for std::io::Error {
+    fn from(e: BoardValidation) -> Self {
+        std::io::Error::new(std::io::ErrorKind::InvalidInput, e)
+    }
+}
+
 /// ボードが有効かどうかを検証する
 pub fn validate_board(board: &Board) -> Result<(), BoardValidation> {
     if (board.player & board.opponent) != 0 {
@@ -362,3 +1121,517 @@ pub fn validate_board(board: &Board) -> Result<(), BoardValidation> {
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use crate::test_util::{play, playout_boards, XorShift};
+    use std::collections::HashSet;
+
+    /// 初期局面からの再現可能な乱択プレイアウトで現れる局面（パスも含む）
+    fn sample_boards() -> Vec<Board> {
+        playout_boards(&mut XorShift::new(0x9E37_79B9_7F4A_7C15), 20)
+    }
+
+    /// 再現可能な乱択で 1 局打ち切り、着手列（パスは含まない）と途中でパスがあったかを返す
+    fn random_game(rng: &mut XorShift) -> (Vec<usize>, Board, bool) {
+        let mut b = Board::initial();
+        let mut moves_played = vec![];
+        let mut passed = false;
+        loop {
+            let moves = get_moves(b.player, b.opponent);
+            if moves == 0 {
+                if !has_move(b.opponent, b.player) {
+                    return (moves_played, b, passed);
+                }
+                passed = true;
+                b = Board::new(b.opponent, b.player);
+                continue;
+            }
+            let pos = rng.pick(moves);
+            b = play(&b, pos);
+            moves_played.push(pos);
+        }
+    }
+
+    #[test]
+    fn play_sequence_replays_a_known_opening() {
+        // f5: 黒が e5 を返し、白番
+        let f5 = Board::play_sequence(&[37]).unwrap();
+        assert_eq!(
+            f5,
+            Board::new(1 << 27, (1 << 28) | (1 << 35) | (1 << 36) | (1 << 37))
+        );
+        // f5 d6: 白が d5 を返し、黒番
+        let f5d6 = Board::play_sequence(&[37, 43]).unwrap();
+        assert_eq!(
+            f5d6,
+            Board::new(
+                (1 << 28) | (1 << 36) | (1 << 37),
+                (1 << 27) | (1 << 35) | (1 << 43)
+            )
+        );
+        assert_eq!(Board::play_sequence(&[]).unwrap(), Board::initial());
+    }
+
+    #[test]
+    fn play_sequence_rejects_illegal_moves() {
+        // 既に石がある / 何も返せない / 盤外
+        assert_eq!(
+            Board::play_sequence(&[37, 37]),
+            Err(MoveError::IllegalMove { index: 1, pos: 37 })
+        );
+        assert_eq!(
+            Board::play_sequence(&[0]),
+            Err(MoveError::IllegalMove { index: 0, pos: 0 })
+        );
+        assert_eq!(
+            Board::play_sequence(&[37, 64]),
+            Err(MoveError::IllegalMove { index: 1, pos: 64 })
+        );
+    }
+
+    #[test]
+    fn play_sequence_passes_automatically() {
+        let mut rng = XorShift::new(0x2545_F491_4F6C_DD1D);
+        let (moves, last, _) = loop {
+            let game = random_game(&mut rng);
+            if game.2 {
+                break game;
+            }
+        };
+        assert_eq!(Board::play_sequence(&moves), Ok(last));
+        assert!(last.is_game_over());
+        // 終局後の着手は GameOver
+        let mut extra = moves.clone();
+        extra.push(0);
+        assert_eq!(
+            Board::play_sequence(&extra),
+            Err(MoveError::GameOver { index: moves.len() })
+        );
+    }
+
+    fn apply(board: &Board, s: i32) -> Board {
+        let mut sym = [0u64; 2];
+        board.board_symmetry(s, &mut sym);
+        Board::new(sym[0], sym[1])
+    }
+
+    #[test]
+    fn symmetry_helpers_agree_with_board_symmetry() {
+        for b in sample_boards() {
+            let all = b.all_symmetries();
+            let orbit = b.symmetry_orbit();
+            for s in 0..8 {
+                let expected = apply(&b, s);
+                assert_eq!(all[s as usize], [expected.player, expected.opponent]);
+                assert_eq!(orbit[s as usize], expected);
+            }
+            let unique = *all.iter().min().unwrap();
+            assert_eq!(b.unique(), unique);
+            let (canonical, s) = b.unique_with_symmetry();
+            assert_eq!(canonical, unique);
+            assert_eq!(apply(&b, s), Board::new(unique[0], unique[1]));
+            assert!((0..s).all(|t| all[t as usize] != unique));
+            let swapped = Board::new(b.opponent, b.player).unique();
+            assert_eq!(b.unique_with_swapped(), (unique, swapped));
+        }
+    }
+
+    #[test]
+    fn public_symmetry_methods_generate_all_symmetries() {
+        let key = |b: Board| [b.player, b.opponent];
+        for b in sample_boards() {
+            let compositions: HashSet<[u64; 2]> = [
+                b,
+                b.mirrored_h(),
+                b.mirrored_v(),
+                b.mirrored_h().mirrored_v(),
+                b.transposed(),
+                b.mirrored_h().transposed(),
+                b.mirrored_v().transposed(),
+                b.mirrored_h().mirrored_v().transposed(),
+            ]
+            .into_iter()
+            .map(key)
+            .collect();
+            let expected: HashSet<[u64; 2]> = b.all_symmetries().into_iter().collect();
+            assert_eq!(compositions, expected);
+            assert_eq!(compositions.iter().min(), Some(&b.unique()));
+
+            // 回転 4 回で元に戻り、途中の 3 つも対称形の 1 つ
+            let mut rotated = b;
+            for _ in 0..4 {
+                rotated = rotated.rotated_90();
+                assert!(expected.contains(&key(rotated)));
+            }
+            assert_eq!(rotated, b);
+            assert_eq!(b.mirrored_h().mirrored_h(), b);
+            assert_eq!(b.mirrored_v().mirrored_v(), b);
+            assert_eq!(b.transposed().transposed(), b);
+        }
+        // a1 → a8, h1 → a1
+        let corners = Board::new(1, 1 << 7).rotated_90();
+        assert_eq!((corners.player, corners.opponent), (1 << 56, 1));
+    }
+
+    #[test]
+    fn inverse_symmetry_round_trips() {
+        for b in sample_boards() {
+            for s in 0..8 {
+                let inverse = Board::inverse_symmetry(s);
+                assert_eq!(Board::inverse_symmetry(inverse), s);
+                assert_eq!(apply(&apply(&b, s), inverse), b);
+                assert_eq!(apply(&b, inverse).unique(), b.unique());
+            }
+            // 正規形から元の盤面へ戻せる
+            let (canonical, s) = b.unique_with_symmetry();
+            let canonical = Board::new(canonical[0], canonical[1]);
+            assert_eq!(apply(&canonical, Board::inverse_symmetry(s)), b);
+        }
+    }
+
+    #[test]
+    fn canonical_board_merges_symmetric_boards() {
+        let boards = sample_boards();
+        for b in &boards {
+            let set: HashSet<CanonicalBoard> = b
+                .symmetry_orbit()
+                .into_iter()
+                .map(CanonicalBoard::from)
+                .collect();
+            assert_eq!(set.len(), 1);
+            assert_eq!(set.iter().next().unwrap().key(), b.unique());
+        }
+        let keys: HashSet<[u64; 2]> = boards.iter().map(Board::unique).collect();
+        let canonical: HashSet<CanonicalBoard> =
+            boards.iter().copied().map(CanonicalBoard::from).collect();
+        assert_eq!(canonical.len(), keys.len());
+    }
+
+    #[test]
+    fn format_mask_renders_center_mask() {
+        assert_eq!(
+            format_mask(CENTER_MASK),
+            "........\n\
+             ........\n\
+             ........\n\
+             ...##...\n\
+             ...##...\n\
+             ........\n\
+             ........\n\
+             ........\n"
+        );
+        // A1 は 1 行目の左端、H8 は 8 行目の右端
+        let corners = format_mask(1 | (1u64 << 63));
+        assert!(corners.starts_with("#......."));
+        assert!(corners.ends_with(".......#\n"));
+    }
+
+    #[test]
+    fn flip_tables_match_flip_on_random_boards() {
+        let tables = FlipTables::new();
+        let mut rng = XorShift::new(0x0123_4567_89AB_CDEF);
+        let mut next = || rng.next_u64();
+        for _ in 0..2000 {
+            // 空きマスの割合が偏らないよう、占有マスと色を別々の乱数で決める
+            let occupied = (next() & next()) | (next() & next() & next());
+            let player = occupied & next();
+            let opponent = occupied & !player;
+            for pos in 0..64 {
+                let expected = flip(pos, player, opponent);
+                assert_eq!(tables.flip(pos, player, opponent), expected);
+                #[cfg(feature = "std")]
+                assert_eq!(flip_tabled(pos, player, opponent), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn flip_by_direction_separates_two_directions() {
+        // A1 に打つと B1 が東向き、A2 が北向きに返る（B2 は空きなので北東には返らない）
+        let player = (1u64 << 2) | (1u64 << 16);
+        let opponent = (1u64 << 1) | (1u64 << 8);
+        let flips = flip_by_direction(0, player, opponent);
+        for (d, &f) in Direction::all().iter().zip(flips.iter()) {
+            let expected = match d {
+                Direction::E => 1u64 << 1,
+                Direction::N => 1u64 << 8,
+                _ => 0,
+            };
+            assert_eq!(f, expected, "{:?}", d);
+        }
+        for b in sample_boards() {
+            for pos in 0..64 {
+                let by_dir = flip_by_direction(pos, b.player, b.opponent);
+                assert_eq!(
+                    by_dir.iter().fold(0, |acc, &f| acc | f),
+                    flip(pos, b.player, b.opponent)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn has_move_and_mobility_agree_with_get_moves() {
+        let mut rng = XorShift::new(0x0F1E_2D3C_4B5A_6978);
+        let mut next = || rng.next_u64();
+        let random = (0..2000).map(|_| {
+            let occupied = next() | next();
+            let player = occupied & next();
+            Board::new(player, occupied & !player)
+        });
+        for b in sample_boards().into_iter().chain(random) {
+            for (p, o) in [(b.player, b.opponent), (b.opponent, b.player)] {
+                let moves = get_moves(p, o);
+                assert_eq!(has_move(p, o), moves != 0);
+                assert_eq!(mobility(p, o), moves.count_ones());
+            }
+        }
+    }
+
+    #[test]
+    fn result_counts_discs_on_full_boards() {
+        // 上 5 行（40 石）と下 3 行（24 石）、上 4 行と下 4 行（32 石ずつ）
+        let five_rows = 0x0000_00FF_FFFF_FFFFu64;
+        let four_rows = 0x0000_0000_FFFF_FFFFu64;
+        for (b, outcome) in [
+            (Board::new(five_rows, !five_rows), Outcome::PlayerWin),
+            (Board::new(!five_rows, five_rows), Outcome::OpponentWin),
+            (Board::new(four_rows, !four_rows), Outcome::Draw),
+        ] {
+            assert!(b.is_game_over());
+            assert_eq!(b.result(), Some(outcome));
+        }
+    }
+
+    #[test]
+    fn result_on_terminal_boards_with_empty_squares() {
+        // 片方の石が無くなった盤面は空きマスがあっても双方打てない
+        let wiped_out = Board::new(CENTER_MASK | (1u64 << 20), 0);
+        assert!(wiped_out.is_game_over());
+        assert_eq!(wiped_out.result(), Some(Outcome::PlayerWin));
+        let swapped = Board::new(wiped_out.opponent, wiped_out.player);
+        assert_eq!(swapped.result(), Some(Outcome::OpponentWin));
+
+        // 終局していない盤面（手番側がパスするだけの盤面を含む）は `None`
+        assert_eq!(Board::initial().result(), None);
+        for b in sample_boards() {
+            let over = get_moves(b.player, b.opponent) == 0 && get_moves(b.opponent, b.player) == 0;
+            assert_eq!(b.is_game_over(), over);
+            assert_eq!(b.result().is_some(), over);
+        }
+    }
+
+    #[test]
+    fn board_validation_reports_overlap_and_missing_center() {
+        let initial = Board::initial();
+        assert_eq!(
+            Board::try_new(initial.player, initial.opponent),
+            Ok(initial)
+        );
+        let overlap = Board::new(initial.player | 1, initial.opponent | 1);
+        assert_eq!(validate_board(&overlap), Err(BoardValidation::Overlap));
+        assert_eq!(
+            Board::try_new(overlap.player, overlap.opponent),
+            Err(BoardValidation::Overlap)
+        );
+        // 重なりは中央の欠けより先に報告する
+        assert_eq!(
+            validate_board(&Board::new(1, 1)),
+            Err(BoardValidation::Overlap)
+        );
+        let missing = Board::new(
+            initial.player & !(1u64 << 27),
+            initial.opponent & !(1u64 << 27),
+        );
+        assert_eq!(
+            validate_board(&missing),
+            Err(BoardValidation::MissingCenter)
+        );
+        assert_eq!(
+            std::format!("{}", BoardValidation::MissingCenter),
+            "a center square is empty"
+        );
+        #[cfg(feature = "std")]
+        {
+            let err: std::io::Error = BoardValidation::Overlap.into();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+            assert_eq!(err.to_string(), "two discs on the same square");
+        }
+    }
+
+    #[test]
+    fn ruleset_accepts_only_the_four_center_squares() {
+        for ruleset in [Ruleset::standard(), Ruleset::parallel()] {
+            let [player, opponent] = ruleset.initial();
+            assert_eq!(Ruleset::new(player, opponent), Ok(ruleset));
+            assert_eq!(ruleset.initial_board(), Board::new(player, opponent));
+            assert_eq!(
+                ruleset.initial_board().to_string().parse::<Ruleset>(),
+                Ok(ruleset)
+            );
+        }
+        assert_eq!("standard".parse::<Ruleset>(), Ok(Ruleset::standard()));
+        assert_eq!("parallel".parse::<Ruleset>(), Ok(Ruleset::parallel()));
+        assert_eq!(Ruleset::standard().initial_board(), Board::initial());
+
+        let [player, opponent] = Ruleset::standard().initial();
+        assert_eq!(
+            Ruleset::new(player | (1u64 << 27), opponent),
+            Err(RulesetError::Overlap)
+        );
+        assert_eq!(
+            Ruleset::new(player | 1, opponent),
+            Err(RulesetError::NotCenter)
+        );
+        assert_eq!(
+            Ruleset::new(player & !(1u64 << 35), opponent),
+            Err(RulesetError::NotCenter)
+        );
+        assert_eq!("diagonal".parse::<Ruleset>(), Err(RulesetError::Unparsable));
+    }
+
+    /// 着手列を初期局面から再生した局面の列（パスした局面も 1 つの局面として含む）
+    fn replay_path(moves: &[usize]) -> Vec<Board> {
+        let mut b = Board::initial();
+        let mut path = vec![b];
+        for &pos in moves {
+            if !has_move(b.player, b.opponent) {
+                b = Board::new(b.opponent, b.player);
+                path.push(b);
+            }
+            b = play(&b, pos);
+            path.push(b);
+        }
+        path
+    }
+
+    #[test]
+    fn verify_path_accepts_replayed_games_with_passes() {
+        let mut rng = XorShift::new(0x5851_F42D_4C95_7F2D);
+        let (moves, last, _) = loop {
+            let game = random_game(&mut rng);
+            if game.2 {
+                break game;
+            }
+        };
+        let path = replay_path(&moves);
+        assert!(path.len() > moves.len() + 1);
+        assert_eq!(*path.last().unwrap(), last);
+        assert_eq!(verify_path(&path), Ok(()));
+        assert_eq!(verify_path_to(&path, &last), Ok(()));
+        assert_eq!(verify_path(&path[..1]), Ok(()));
+    }
+
+    #[test]
+    fn verify_path_reports_the_first_broken_link() {
+        // f5 d6 c3 d3 c4
+        let mut path = replay_path(&[37, 43, 18, 19, 26]);
+        // 3 手目を飛ばす
+        path.remove(3);
+        assert_eq!(verify_path(&path), Err(PathError::BrokenLink { index: 3 }));
+        // 手番を入れ替えただけの局面は、合法手があるならパスではない
+        let path = replay_path(&[37]);
+        let swapped = Board::new(path[1].opponent, path[1].player);
+        assert_eq!(
+            verify_path(&[path[0], path[1], swapped]),
+            Err(PathError::BrokenLink { index: 2 })
+        );
+    }
+
+    #[test]
+    fn verify_path_rejects_a_wrong_start_or_end() {
+        let path = replay_path(&[37, 43, 18]);
+        assert_eq!(verify_path(&path[1..]), Err(PathError::WrongStart));
+        assert_eq!(verify_path(&[]), Err(PathError::Empty));
+        assert_eq!(verify_path_to(&path, &path[3]), Ok(()));
+        assert_eq!(verify_path_to(&path, &path[2]), Err(PathError::WrongEnd));
+        // 先頭の誤りは終端より先に報告する
+        assert_eq!(
+            verify_path_to(&path[1..], &path[3]),
+            Err(PathError::WrongStart)
+        );
+    }
+
+    #[test]
+    fn board_key_orders_and_converts_like_the_pair_it_wraps() {
+        let mut rng = XorShift::new(0x9E37_79B9_7F4A_7C15);
+        // 上位語が等しい組も混ぜる
+        let mut next = || rng.next_u64() & 0xF000_0000_0000_000F;
+        let pairs: Vec<[u64; 2]> = (0..200).map(|_| [next(), next()]).collect();
+        for a in &pairs {
+            let key = BoardKey::from(*a);
+            assert_eq!(<[u64; 2]>::from(key), *a);
+            assert_eq!(BoardKey::from(Board::from(key)), key);
+            for b in &pairs {
+                assert_eq!(key.cmp(&BoardKey::from(*b)), a.cmp(b));
+            }
+        }
+        let mut keys: Vec<BoardKey> = pairs.iter().map(|&p| BoardKey::from(p)).collect();
+        let mut sorted = pairs.clone();
+        keys.sort();
+        sorted.sort();
+        assert!(keys.iter().map(|k| k.0).eq(sorted));
+    }
+
+    /// `.bin` のレコードとしてのバイト列はネイティブエンディアンの `player`, `opponent`
+    #[cfg(feature = "std")]
+    #[test]
+    fn board_key_bytes_are_the_native_endian_pair() {
+        let key = BoardKey([0x0102_0304_0506_0708, 0x1112_1314_1516_1718]);
+        let bytes = bytemuck::bytes_of(&key);
+        assert_eq!(bytes[..8], key.0[0].to_ne_bytes());
+        assert_eq!(bytes[8..], key.0[1].to_ne_bytes());
+        assert_eq!(*bytemuck::from_bytes::<BoardKey>(bytes), key);
+    }
+
+    #[test]
+    fn le_bytes_put_the_player_first_in_little_endian() {
+        let board = Board::new(0x0102_0304_0506_0708, 0x1112_1314_1516_1718);
+        let bytes = board.to_le_bytes();
+        assert_eq!(
+            bytes,
+            [
+                0x08, 0x07, 0x06, 0x05, 0x04, 0x03, 0x02, 0x01, //
+                0x18, 0x17, 0x16, 0x15, 0x14, 0x13, 0x12, 0x11,
+            ]
+        );
+        assert_eq!(Board::from_le_bytes(bytes), board);
+        for b in sample_boards() {
+            assert_eq!(Board::from_le_bytes(b.to_le_bytes()), b);
+        }
+    }
+
+    /// 対称形の種類数（重複を除いた数）
+    fn distinct_forms(orbit: &[Board; 8]) -> usize {
+        let mut forms: Vec<Board> = orbit.to_vec();
+        forms.sort_by_key(|b| (b.player, b.opponent));
+        forms.dedup();
+        forms.len()
+    }
+
+    #[test]
+    fn orbit_is_closed_under_symmetry() {
+        for b in sample_boards() {
+            let orbit = b.symmetry_orbit();
+            assert_eq!(orbit[0], b);
+            let mut sorted = orbit;
+            sorted.sort_by_key(|b| (b.player, b.opponent));
+            for member in orbit {
+                // どの対称形から作っても同じ 8 通り（順序は違う）になり、正規形も同じ
+                let mut other = member.symmetry_orbit();
+                other.sort_by_key(|b| (b.player, b.opponent));
+                assert_eq!(other, sorted);
+                assert_eq!(member.unique(), b.unique());
+            }
+            // 種類数は 8 の約数
+            assert_eq!(8 % distinct_forms(&orbit), 0);
+        }
+        // 初期局面は左右・上下の反転で対角線の色が入れ替わるので 2 通り
+        assert_eq!(distinct_forms(&Board::initial().symmetry_orbit()), 2);
+        assert_eq!(distinct_forms(&Board::empty().symmetry_orbit()), 1);
+    }
+}
@@ -0,0 +1,82 @@
+//! よく使う公開 API をまとめて再エクスポートする。
+//!
+//! モジュール構成が変わってもここからの名前は変えないので、利用側は
+//! `use othello_complexity_rs::prelude::*;` だけで盤面・探索・枝刈り・入出力を使える。
+//!
+//! ```no_run
+//! use othello_complexity_rs::prelude::*;
+//!
+//! let board = Board::initial();
+//! assert!(validate_board(&board).is_ok());
+//! assert!(passes_search_prunings(board.player, board.opponent));
+//! assert_eq!(is_reachable(&board), SearchResult::Found);
+//! ```
+
+pub use crate::othello::{
+    flip, get_moves, has_move, mobility, validate_board, verify_path, verify_path_to, Board,
+    BoardValidation, Outcome, PathError, CENTER_MASK,
+};
+pub use crate::prunings::connectivity::is_connected;
+pub use crate::prunings::occupancy::{check_occupancy, reachable_occupancy};
+pub use crate::prunings::seg3::{check_seg3, check_seg3_cond1, check_seg3_more};
+pub use crate::prunings::{passes_search_prunings, PruningConfig};
+
+#[cfg(feature = "std")]
+pub use crate::io::{
+    boards_from_transcript, ensure_outputs, parse_file_to_boards, OutputNames, ReverseOutputs,
+};
+#[cfg(feature = "std")]
+pub use crate::search::core::{
+    retrospective_search, retrospective_search_with_store, Btable, SearchResult,
+};
+#[cfg(feature = "std")]
+pub use crate::search::leaf_cache::LeafCache;
+#[cfg(feature = "std")]
+pub use crate::search::reachability_cache::{ReachabilityKey, ReachabilityStore};
+#[cfg(feature = "std")]
+pub use crate::search::reachable::{is_reachable, DEFAULT_DISCS, DEFAULT_NODE_LIMIT};
+
+/// `std` を外したビルドで、盤面の中核と軽い枝刈りが `core` と `alloc` だけで使えることを確かめる
+/// （`cargo test --lib --no-default-features` で実行される）。
+#[cfg(all(test, not(feature = "std")))]
+mod no_std_tests {
+    use super::*;
+    use crate::othello::{format_mask, FlipTables};
+    use alloc::vec::Vec;
+
+    #[test]
+    fn core_api_works_without_std() {
+        // f5 d6 c3 d3 c4 の虎定石
+        let board = Board::play_sequence(&[37, 43, 18, 19, 26]).unwrap();
+        assert_eq!(board.popcount(), 9);
+        assert!(validate_board(&board).is_ok());
+        assert!(passes_search_prunings(board.player, board.opponent));
+        assert!(is_connected(board.player | board.opponent));
+
+        let tables = FlipTables::new();
+        let moves = get_moves(board.player, board.opponent);
+        assert_eq!(moves.count_ones(), mobility(board.player, board.opponent));
+        let children: Vec<Board> = (0..64)
+            .filter(|&pos| moves & (1u64 << pos) != 0)
+            .map(|pos| {
+                let flipped = flip(pos, board.player, board.opponent);
+                assert_eq!(tables.flip(pos, board.player, board.opponent), flipped);
+                Board::new(
+                    board.opponent ^ flipped,
+                    board.player ^ flipped ^ (1u64 << pos),
+                )
+            })
+            .collect();
+        assert!(children
+            .iter()
+            .all(|c| passes_search_prunings(c.player, c.opponent)));
+
+        let mut sym = [0u64; 2];
+        board.board_symmetry(3, &mut sym);
+        assert_eq!(Board::new(sym[0], sym[1]).unique(), board.unique());
+        assert_eq!(board.to_string().len(), 64);
+        assert_eq!(format_mask(CENTER_MASK).matches('#').count(), 4);
+        // 診断出力は no_std では何もしない
+        crate::vprintln!(crate::verbosity::QUIET, "{}", board.to_string());
+    }
+}
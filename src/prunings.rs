@@ -1,5 +1,145 @@
 pub mod connectivity;
+#[cfg(feature = "std")]
 pub mod kissat;
+#[cfg(feature = "lp")]
+pub mod linear_programming;
+#[cfg(all(feature = "std", not(feature = "lp")))]
+#[path = "prunings/linear_programming_stub.rs"]
 pub mod linear_programming;
 pub mod occupancy;
 pub mod seg3;
+
+/// 逆方向探索の各ノードで共通に使う枝刈り (占有到達性 + 反転整合性)。
+/// 逐次・並列のどの探索もこの関数を通すことで、同じ盤面に対して同じ判定になる。
+/// `false` なら初期局面から到達不能。
+#[inline]
+pub fn passes_search_prunings(player: u64, opponent: u64) -> bool {
+    occupancy::check_occupancy(player | opponent) && seg3::check_seg3_more(player, opponent)
+}
+
+/// 逆方向探索で使える枝刈りの種類（`PruningConfig` の各項目に対応）
+#[cfg_attr(feature = "std", derive(clap::ValueEnum))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pruning {
+    /// 占有到達性（`occupancy::check_occupancy`）
+    Occupancy,
+    /// 反転整合性（`seg3::check_seg3_more`）。占有到達性を満たす盤面でしか判定できないので、
+    /// 有効にすると占有到達性も調べる
+    Seg3,
+    /// 石の連結性（`connectivity::is_connected`）
+    Connectivity,
+    /// LP 緩和の実行可能性（`linear_programming::check_lp`、`lp` feature が必要）
+    Lp,
+}
+
+/// 逆方向探索で使う枝刈りの組み合わせ。既定は `passes_search_prunings` と同じ
+/// （占有到達性 + 反転整合性）。どれを外しても到達不能な盤面を到達可能と判定することはなく、
+/// 探索が広がる（ノード数の上限に当たって `Unknown` になりやすくなる）だけ。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PruningConfig {
+    pub occupancy: bool,
+    pub seg3: bool,
+    pub connectivity: bool,
+    /// LP は 1 回の判定が重いので、探索側で展開する局面にだけ使う
+    pub lp: bool,
+}
+
+impl PruningConfig {
+    /// `passes_search_prunings` と同じ組み合わせ
+    pub const SEARCH: Self = PruningConfig {
+        occupancy: true,
+        seg3: true,
+        connectivity: false,
+        lp: false,
+    };
+
+    /// `prunings` に挙げた枝刈りだけを有効にした設定
+    pub fn from_list(prunings: &[Pruning]) -> Self {
+        PruningConfig {
+            occupancy: prunings.contains(&Pruning::Occupancy),
+            seg3: prunings.contains(&Pruning::Seg3),
+            connectivity: prunings.contains(&Pruning::Connectivity),
+            lp: prunings.contains(&Pruning::Lp),
+        }
+    }
+
+    /// LP 以外の有効な枝刈りを全て通るか。`false` なら初期局面から到達不能。
+    #[inline]
+    pub fn passes_cheap(&self, player: u64, opponent: u64) -> bool {
+        let occupied = player | opponent;
+        // check_seg3_more は占有到達性を満たさない盤面では panic する
+        let needs_occupancy = self.occupancy || self.seg3;
+        (!needs_occupancy || occupancy::check_occupancy(occupied))
+            && (!self.seg3 || seg3::check_seg3_more(player, opponent))
+            && (!self.connectivity || connectivity::is_connected(occupied))
+    }
+}
+
+impl Default for PruningConfig {
+    fn default() -> Self {
+        Self::SEARCH
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::othello::{get_moves, has_move, Board, CENTER_MASK};
+    use crate::test_util::{play, XorShift};
+
+    /// 初期局面からの再現可能な乱択プレイアウトで現れる局面（終局したら初期局面からやり直す）。
+    /// 各枝刈りのモジュールのテストでも使う。
+    pub(super) fn playout_boards() -> [Board; 600] {
+        let mut boards = [Board::initial(); 600];
+        let mut rng = XorShift::new(0x2545_F491_4F6C_DD1D);
+        let mut b = Board::initial();
+        for slot in boards.iter_mut() {
+            let moves = get_moves(b.player, b.opponent);
+            if moves == 0 {
+                b = if has_move(b.opponent, b.player) {
+                    Board::new(b.opponent, b.player)
+                } else {
+                    Board::initial()
+                };
+            } else {
+                b = play(&b, rng.pick(moves));
+            }
+            *slot = b;
+        }
+        boards
+    }
+
+    /// LP 以外の枝刈りは `lp` feature の有無（`--no-default-features` を含む）に関係なく使える
+    #[test]
+    fn cheap_prunings_accept_reachable_boards() {
+        let all =
+            PruningConfig::from_list(&[Pruning::Occupancy, Pruning::Seg3, Pruning::Connectivity]);
+        for b in playout_boards() {
+            assert!(passes_search_prunings(b.player, b.opponent));
+            assert!(all.passes_cheap(b.player, b.opponent));
+        }
+    }
+
+    #[test]
+    fn cheap_prunings_reject_a_detached_corner() {
+        let b = Board::new(
+            CENTER_MASK & 0x0000_0010_0800_0000,
+            (CENTER_MASK & 0x0000_0008_1000_0000) | 1,
+        );
+        assert!(!passes_search_prunings(b.player, b.opponent));
+        assert!(!PruningConfig::from_list(&[Pruning::Occupancy]).passes_cheap(b.player, b.opponent));
+        assert!(
+            !PruningConfig::from_list(&[Pruning::Connectivity]).passes_cheap(b.player, b.opponent)
+        );
+        assert!(PruningConfig::from_list(&[]).passes_cheap(b.player, b.opponent));
+    }
+
+    #[test]
+    fn seg3_alone_rejects_an_unoccupiable_board_without_panicking() {
+        // 占有到達性では落ちるが check_seg3 は通り、check_seg3_more に渡すと panic する盤面
+        let (player, opponent) = (0x0100_020d_0900_0000, 0x0001_0110_1010_0000);
+        assert!(!occupancy::check_occupancy(player | opponent));
+        assert!(seg3::check_seg3(player | opponent));
+        assert!(!PruningConfig::from_list(&[Pruning::Seg3]).passes_cheap(player, opponent));
+    }
+}
@@ -1,3 +1,5 @@
+use crate::othello::CENTER_MASK;
+
 // translated with ChatGPT 4o
 /**
  * retrospective-dfs-reversi
@@ -11,7 +13,7 @@
 /// 盤面 `b` が 8 近傍で連結しているかを判定する関数。
 /// 中央4マス(初期配置)が必ず含まれる前提です。
 pub fn is_connected(b: u64) -> bool {
-    let mut mark: u64 = 0x0000_0018_1800_0000u64;
+    let mut mark: u64 = CENTER_MASK;
     let mut old_mark: u64 = 0;
 
     // 中央 4 マスが存在しているか確認
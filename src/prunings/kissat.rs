@@ -1,8 +1,11 @@
-use crate::othello::Direction;
+use crate::othello::{Board, Direction};
+use crate::verbosity::INFO;
+use crate::vprintln;
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
-use std::io::{Error, ErrorKind, Write};
+use std::io::{self, BufWriter, Error, ErrorKind, Write};
+use std::path::Path;
 
 use rustsat::{
     instances::Cnf,
@@ -27,98 +30,180 @@ impl VarMaker {
 }
 
 #[inline]
-fn xy2sq(x: i32, y: i32) -> usize {
-    (y * 8 + x) as usize
+fn xy2sq(x: i32, y: i32, width: i32) -> usize {
+    (y * width + x) as usize
 }
 
-fn solve_by_kissat(
-    _index: usize,
-    vs: &Vec<Vec<i32>>,
-    _num_var: usize,
-    _comment: &HashMap<usize, String>,
-) -> bool {
-    let mut solver = rustsat_kissat::Kissat::default();
+/// SAT ソルバの種類（kissat 以外は cargo feature で有効化する）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum SatBackend {
+    #[default]
+    Kissat,
+    #[cfg(feature = "cadical")]
+    Cadical,
+    #[cfg(feature = "minisat")]
+    Minisat,
+}
+
+fn solve_with<S: Solve>(mut solver: S, cnf: Cnf) -> bool {
+    if solver.add_cnf(cnf).is_err() {
+        return false;
+    }
+    let result = match solver.solve() {
+        Ok(res) => res,
+        Err(_) => return false,
+    };
+    result == rustsat::solvers::SolverResult::Sat
+}
+
+/// 節集合 `vs` を `backend` のソルバで解き、充足可能なら true を返す
+pub fn solve_cnf(vs: &[Vec<i32>], backend: SatBackend) -> bool {
     let mut cnf = Cnf::new();
     for line in vs {
         let mut clause = Clause::new();
-        for i in 0..line.len() {
-            if line[i] > 0 {
-                clause.add(Lit::positive(line[i] as u32));
+        for &l in line {
+            if l > 0 {
+                clause.add(Lit::positive(l as u32));
             } else {
-                clause.add(Lit::negative((-line[i]) as u32));
+                clause.add(Lit::negative((-l) as u32));
             }
         }
         cnf.add_clause(clause);
     }
-    if let Err(_) = solver.add_cnf(cnf) {
-        return false;
+    match backend {
+        SatBackend::Kissat => solve_with(rustsat_kissat::Kissat::default(), cnf),
+        #[cfg(feature = "cadical")]
+        SatBackend::Cadical => solve_with(rustsat_cadical::CaDiCaL::default(), cnf),
+        #[cfg(feature = "minisat")]
+        SatBackend::Minisat => solve_with(rustsat_minisat::core::Minisat::default(), cnf),
     }
-    let result = match solver.solve() {
-        Ok(res) => res,
-        Err(_) => return false,
-    };
-    result == rustsat::solvers::SolverResult::Sat
 }
 
-#[allow(dead_code)]
-fn output_cnf(
-    index: usize,
-    vs: &Vec<Vec<i32>>,
+/// 節集合を DIMACS 形式で書き出す。
+/// `comment` が与えられた場合は変数名を `c` 行として変数番号順に併記し、各節の前にも名前付きの `c` 行を出力する。
+fn write_dimacs<W: Write>(
+    w: &mut W,
+    vs: &[Vec<i32>],
     num_var: usize,
-    comment: &HashMap<usize, String>,
-) -> Result<(), Error> {
-    let filename = format!("{}.cnf", index);
-    let mut file = File::create(&filename)?;
-    for (i, line) in comment.iter() {
-        writeln!(file, "c Var_{}, {}", i, line)?;
+    comment: Option<&HashMap<usize, String>>,
+) -> io::Result<()> {
+    if let Some(comment) = comment {
+        let mut names: Vec<(&usize, &String)> = comment.iter().collect();
+        names.sort();
+        for (i, name) in names {
+            writeln!(w, "c Var_{}, {}", i, name)?;
+        }
     }
-    writeln!(file, "p cnf {} {}", num_var, vs.len())?;
+    writeln!(w, "p cnf {} {}", num_var, vs.len())?;
     for line in vs {
-        write!(file, "c ")?;
-        for i in 0..line.len() {
-            if i > 0 {
-                write!(file, " ")?;
-            }
-            if line[i] > 0 {
-                let v = line[i] as usize;
-                write!(file, "{}", comment.get(&v).unwrap())?;
-            } else {
-                let v = (-line[i]) as usize;
-                write!(file, "-{}", comment.get(&v).unwrap())?;
-            }
+        if let Some(comment) = comment {
+            let names: Vec<String> = line
+                .iter()
+                .map(|&l| {
+                    let name = comment
+                        .get(&(l.unsigned_abs() as usize))
+                        .map(|s| s.as_str())
+                        .unwrap_or("?");
+                    if l > 0 {
+                        name.to_string()
+                    } else {
+                        format!("-{}", name)
+                    }
+                })
+                .collect();
+            writeln!(w, "c {}", names.join(" "))?;
         }
-        writeln!(file, "")?;
-        for i in 0..line.len() {
-            if i > 0 {
-                write!(file, " ")?;
-            }
-            write!(file, "{}", line[i])?;
+        for l in line {
+            write!(w, "{} ", l)?;
         }
-        writeln!(file, " 0")?;
+        writeln!(w, "0")?;
     }
-    writeln!(file, "")?;
-    Err(Error::new(ErrorKind::Other, "one cnf file only"))
+    Ok(())
+}
+
+/// 盤面の SAT インスタンスを DIMACS 形式で `path` に書き出す。
+/// `with_comments` が真なら各変数の意味をコメント行として併記する。
+pub fn write_cnf(board: &Board, path: &Path, with_comments: bool) -> io::Result<()> {
+    let inst = build_instance(&board.to_string(), 8)?;
+    let mut w = BufWriter::new(File::create(path)?);
+    let comment = if with_comments {
+        Some(&inst.comment)
+    } else {
+        None
+    };
+    write_dimacs(&mut w, &inst.clauses, inst.num_var, comment)?;
+    w.flush()
 }
 
 pub fn is_sat_ok(index: usize, line: &String) -> Result<bool, Error> {
+    is_sat_ok_sized(index, line, 8, SatBackend::Kissat)
+}
+
+/// `is_sat_ok` のソルバを `backend` に置き換えた版
+pub fn is_sat_ok_with(index: usize, line: &str, backend: SatBackend) -> Result<bool, Error> {
+    is_sat_ok_sized(index, line, 8, backend)
+}
+
+/// 6x6 盤（36 文字の 'X', 'O', '-' 文字列）に対する `is_sat_ok`
+pub fn is_sat_ok6(index: usize, line: &str) -> Result<bool, Error> {
+    is_sat_ok_sized(index, line, 6, SatBackend::Kissat)
+}
+
+/// 一辺 `width` マスの正方盤に対する SAT 判定。
+fn is_sat_ok_sized(
+    index: usize,
+    line: &str,
+    width: i32,
+    backend: SatBackend,
+) -> Result<bool, Error> {
+    let inst = build_instance(line, width)?;
+    let ans = solve_cnf(&inst.clauses, backend);
+    vprintln!(
+        INFO,
+        "index={}, ans={}, vars={}, clauses={}",
+        index,
+        ans,
+        inst.num_var,
+        inst.clauses.len()
+    );
+    Ok(ans)
+}
+
+/// 盤面から生成した SAT インスタンス
+struct SatInstance {
+    clauses: Vec<Vec<i32>>,
+    num_var: usize,
+    comment: HashMap<usize, String>,
+}
+
+/// 1 回の flip を表す `(sq', col, d, len)`
+type FlipKey = (usize, usize, usize, usize);
+
+/// 一辺 `width` マスの正方盤（'X', 'O', '-' 文字列）の SAT インスタンスを生成する。
+/// 8x8 と 6x6 で同じ節生成を共有するため、盤サイズに依存する箇所はすべて `width` から求める。
+fn build_instance(line: &str, width: i32) -> Result<SatInstance, Error> {
+    let num_sq = (width * width) as usize;
     let cs: Vec<char> = line.chars().collect();
-    if cs.len() != 64 {
+    if cs.len() != num_sq {
         return Err(Error::new(
             ErrorKind::Other,
-            "length is not 64 format error",
+            format!("length is not {} format error", num_sq),
         ));
     }
+    // 中央 2x2 の座標範囲
+    let c0 = width / 2 - 1;
+    let c1 = width / 2;
     let mut sqi: Vec<usize> = vec![];
     let mut sqo: Vec<usize> = vec![];
     let mut sqall: Vec<usize> = vec![];
     let mut vm = VarMaker::new();
-    let mut in_sqo: Vec<bool> = vec![false; 64];
-    for y in 0..8 {
-        for x in 0..8 {
-            let sq = xy2sq(x, y);
+    let mut in_sqo: Vec<bool> = vec![false; num_sq];
+    for y in 0..width {
+        for x in 0..width {
+            let sq = xy2sq(x, y, width);
             if cs[sq] != '-' {
                 sqall.push(sq);
-                if 3 <= x && x <= 4 && 3 <= y && y <= 4 {
+                if c0 <= x && x <= c1 && c0 <= y && y <= c1 {
                     sqi.push(sq);
                 } else {
                     sqo.push(sq);
@@ -133,19 +218,19 @@ pub fn is_sat_ok(index: usize, line: &String) -> Result<bool, Error> {
     // let sq33 = xy2sq(3, 3);
 
     // First[sq][col] : sqに最初に置かれる石がcolかどうかを表す論理変数
-    let mut first: Vec<Vec<i32>> = vec![vec![0; 2]; 64];
+    let mut first: Vec<Vec<i32>> = vec![vec![0; 2]; num_sq];
 
     // Flip[sq][col] : [(sq', col, d, len)], sqをcolにflipするflip全体
-    let mut flip: Vec<Vec<Vec<(usize, usize, usize, usize)>>> = vec![vec![vec![]; 2]; 64];
+    let mut flip: Vec<Vec<Vec<FlipKey>>> = vec![vec![vec![]; 2]; num_sq];
 
     // Set[sq][col] : [(sq', col, d, len)], flipに加えて First[sq][col] に対応する(sq, col, 0, 0) も含む
-    let mut set: Vec<Vec<Vec<(usize, usize, usize, usize)>>> = vec![vec![vec![]; 2]; 64];
+    let mut set: Vec<Vec<Vec<FlipKey>>> = vec![vec![vec![]; 2]; num_sq];
 
     // Base[sq][col] : [(sq', col, d, len)], sqがcolであることを利用してcolにflipするflip
-    let mut base: Vec<Vec<Vec<(usize, usize, usize, usize)>>> = vec![vec![vec![]; 2]; 64];
+    let mut base: Vec<Vec<Vec<FlipKey>>> = vec![vec![vec![]; 2]; num_sq];
 
     // F[(sq, col, d, len)] : flip (sq, col, d, len) から論理変数への変換
-    let mut f: HashMap<(usize, usize, usize, usize), i32> = HashMap::new();
+    let mut f: HashMap<FlipKey, i32> = HashMap::new();
 
     let v_sq33 = vm.mk_var();
     let mut comment: HashMap<usize, String> = HashMap::new();
@@ -155,7 +240,12 @@ pub fn is_sat_ok(index: usize, line: &String) -> Result<bool, Error> {
             comment.insert(vm.count() + 1, format!("Square_{}", sq).to_string());
             vm.mk_var()
         } else {
-            v_sq33 * if sq / 8 == sq % 8 { 1 } else { -1 }
+            v_sq33
+                * if sq / width as usize == sq % width as usize {
+                    1
+                } else {
+                    -1
+                }
         };
         for col in 0..2 {
             let t = (sq, col, 0, 0);
@@ -165,7 +255,7 @@ pub fn is_sat_ok(index: usize, line: &String) -> Result<bool, Error> {
             set[sq][col].push(t);
         }
     }
-    let mut cmp: Vec<Vec<i32>> = vec![vec![0; 64]; 64];
+    let mut cmp: Vec<Vec<i32>> = vec![vec![0; num_sq]; num_sq];
     let mut s: Vec<Vec<i32>> = vec![];
     // eprintln!("sqo.len() = {}", sqo.len());
     for &sq in &sqo {
@@ -194,8 +284,8 @@ pub fn is_sat_ok(index: usize, line: &String) -> Result<bool, Error> {
     }
     //eprintln!("end of Cmp, s.len()={}", s.len());
     for &sq in &sqo {
-        let x = (sq % 8) as i32;
-        let y = (sq / 8) as i32;
+        let x = sq as i32 % width;
+        let y = sq as i32 / width;
         for col in 0..2 {
             let mut ps: Vec<i32> = vec![]; // sqにcolの石を置くすべてのflip
             for (d, direction) in Direction::all().iter().enumerate() {
@@ -205,9 +295,14 @@ pub fn is_sat_ok(index: usize, line: &String) -> Result<bool, Error> {
                 let mut x1 = x + dx;
                 let mut y1 = y + dy;
                 let mut samedir: Vec<i32> = vec![];
-                while 0 <= x1 && x1 < 8 && 0 <= y1 && y1 < 8 && cs[xy2sq(x1, y1)] != '-' {
+                while 0 <= x1
+                    && x1 < width
+                    && 0 <= y1
+                    && y1 < width
+                    && cs[xy2sq(x1, y1, width)] != '-'
+                {
                     rl += 1;
-                    let sq1 = xy2sq(x1, y1);
+                    let sq1 = xy2sq(x1, y1, width);
                     if rl >= 3 {
                         let t = (sq, col, d, rl);
                         let v = vm.mk_var();
@@ -280,14 +375,8 @@ pub fn is_sat_ok(index: usize, line: &String) -> Result<bool, Error> {
     //eprintln!("end of Last, s.len()={}", s.len());
 
     // Before
-    let mut before: HashMap<
-        (
-            usize,
-            (usize, usize, usize, usize),
-            (usize, usize, usize, usize),
-        ),
-        i32,
-    > = HashMap::new();
+    // 節の出力順が実行ごとに変わらないよう、キー順に走査できる BTreeMap にする
+    let mut before: BTreeMap<(usize, FlipKey, FlipKey), i32> = BTreeMap::new();
     for &sq in &sqo {
         for col in 0..2 {
             for &t in &set[sq][col] {
@@ -344,15 +433,211 @@ pub fn is_sat_ok(index: usize, line: &String) -> Result<bool, Error> {
             }
         }
     }
-    // output_cnf(index, &s, vm.count(), &comment);
-    let ans = solve_by_kissat(index, &s, vm.count(), &comment);
-    println!(
-        "index={}, ans={}, vars={}, clauses={}",
-        index,
-        ans,
-        vm.count(),
-        s.len()
-    );
+    Ok(SatInstance {
+        clauses: s,
+        num_var: vm.count(),
+        comment,
+    })
+}
 
-    Ok(ans)
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::core::SearchResult;
+    use crate::search::sized::{
+        board_to_string_sized, flip_sized, get_moves_sized, initial_board_sized,
+        retrospective_search_sized,
+    };
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use std::collections::HashSet;
+
+    /// 6x6 の初期局面から `plies` 手までに現れる局面（パスは含めない）
+    fn forward_boards6(plies: usize) -> Vec<Board> {
+        let mut boards = vec![initial_board_sized(6)];
+        let mut level = vec![initial_board_sized(6)];
+        let mut seen = HashSet::new();
+        for _ in 0..plies {
+            let mut next = vec![];
+            for b in &level {
+                let mut moves = get_moves_sized(b.player, b.opponent, 6);
+                while moves != 0 {
+                    let pos = moves.trailing_zeros();
+                    moves &= moves - 1;
+                    let flipped = flip_sized(pos, b.player, b.opponent, 6);
+                    let child =
+                        Board::new(b.opponent ^ flipped, b.player ^ flipped ^ (1u64 << pos));
+                    if seen.insert([child.player, child.opponent]) {
+                        next.push(child);
+                    }
+                }
+            }
+            boards.extend_from_slice(&next);
+            level = next;
+        }
+        boards
+    }
+
+    /// 6x6 の全探索による到達可能性（どちらの手番でも初期局面に戻れれば到達可能）
+    fn reachable6(board: &Board) -> bool {
+        [*board, Board::new(board.opponent, board.player)]
+            .iter()
+            .any(|b| {
+                let mut retroflips = vec![];
+                let mut node_count = 0;
+                retrospective_search_sized(
+                    b,
+                    6,
+                    false,
+                    &mut HashSet::new(),
+                    &mut retroflips,
+                    &mut node_count,
+                    usize::MAX,
+                ) == SearchResult::Found
+            })
+    }
+
+    #[test]
+    fn is_sat_ok6_agrees_with_exhaustive_search() {
+        let reachable = forward_boards6(5);
+        for (i, b) in reachable.iter().enumerate() {
+            assert!(is_sat_ok6(i, &board_to_string_sized(b, 6)).unwrap());
+        }
+
+        // 到達可能な局面の石を 1 つ塗り替えた局面は到達不能なことが多い。
+        // SAT が偽と判定した局面は全探索でも到達不能でなければならない。
+        let mut rng = StdRng::seed_from_u64(602);
+        let center = crate::search::core::square_center_mask(6);
+        let mut rejected = 0;
+        for (i, b) in reachable.iter().enumerate() {
+            let outer = (b.player | b.opponent) & !center;
+            if outer == 0 {
+                continue;
+            }
+            let mut m = outer;
+            for _ in 0..rng.random_range(0..outer.count_ones()) {
+                m &= m - 1;
+            }
+            let bit = m & m.wrapping_neg();
+            let recolored = Board::new(b.player ^ bit, b.opponent ^ bit);
+            let sat = is_sat_ok6(i, &board_to_string_sized(&recolored, 6)).unwrap();
+            if !sat {
+                rejected += 1;
+            }
+            assert!(sat || !reachable6(&recolored));
+        }
+        assert!(rejected > 0);
+    }
+
+    /// DIMACS ファイルの `p cnf` 行の (変数数, 節数) と、節の行、コメント行の数
+    fn read_dimacs(path: &Path) -> ((usize, usize), Vec<Vec<i32>>, usize) {
+        let text = std::fs::read_to_string(path).unwrap();
+        let mut header = None;
+        let mut clauses = vec![];
+        let mut comments = 0;
+        for line in text.lines() {
+            if line.starts_with('c') {
+                comments += 1;
+            } else if let Some(rest) = line.strip_prefix("p cnf ") {
+                let nums: Vec<usize> = rest
+                    .split_whitespace()
+                    .map(|t| t.parse().unwrap())
+                    .collect();
+                assert!(header.is_none() && clauses.is_empty());
+                header = Some((nums[0], nums[1]));
+            } else {
+                let lits: Vec<i32> = line
+                    .split_whitespace()
+                    .map(|t| t.parse().unwrap())
+                    .collect();
+                assert_eq!(lits.last(), Some(&0), "{}", line);
+                clauses.push(lits[..lits.len() - 1].to_vec());
+            }
+        }
+        (header.unwrap(), clauses, comments)
+    }
+
+    #[test]
+    fn write_cnf_header_matches_clause_lines() {
+        // f5 d6 c3 d3 c4
+        let board = Board::play_sequence(&[37, 43, 18, 19, 26]).unwrap();
+        let dir = std::env::temp_dir();
+        let plain = dir.join(format!("write_cnf_plain_{}.cnf", std::process::id()));
+        let commented = dir.join(format!("write_cnf_commented_{}.cnf", std::process::id()));
+        write_cnf(&board, &plain, false).unwrap();
+        write_cnf(&board, &commented, true).unwrap();
+        let ((num_var, num_clause), clauses, comments) = read_dimacs(&plain);
+        let ((num_var2, num_clause2), clauses2, comments2) = read_dimacs(&commented);
+        std::fs::remove_file(&commented).unwrap();
+
+        assert_eq!(num_clause, clauses.len());
+        assert!(clauses
+            .iter()
+            .flatten()
+            .all(|&l| l != 0 && l.unsigned_abs() as usize <= num_var));
+        assert_eq!(comments, 0);
+        // 変数名のコメントは節を変えない
+        assert_eq!((num_var2, num_clause2), (num_var, num_clause));
+        assert_eq!(clauses2, clauses);
+        assert!(comments2 > clauses.len());
+        // 同じ盤面からは毎回同じファイルになる
+        write_cnf(&board, &plain, false).unwrap();
+        assert_eq!(read_dimacs(&plain).1, clauses);
+        std::fs::remove_file(&plain).unwrap();
+        // 書き出した節は `is_sat_ok` が解くものと同じ
+        assert_eq!(
+            solve_cnf(&clauses, SatBackend::Kissat),
+            is_sat_ok(0, &board.to_string()).unwrap()
+        );
+    }
+
+    /// 有効な全てのソルバが同じ判定を返す。既定のビルドでは kissat だけで、
+    /// `--features cadical,minisat` を付けると各ソルバを突き合わせる。
+    #[test]
+    fn backends_agree_on_small_instances() {
+        use clap::ValueEnum;
+
+        // f5 d6 c3 d3 c4 は到達可能、その石を 1 つ塗り替えた局面は到達不能（SAT でも UNSAT）
+        let board = Board::play_sequence(&[37, 43, 18, 19, 26]).unwrap();
+        let mut cases = vec![
+            (vec![vec![1, 2], vec![-1], vec![-2, 3]], true),
+            (
+                vec![vec![1, 2], vec![-1, 2], vec![1, -2], vec![-1, -2]],
+                false,
+            ),
+            (build_instance(&board.to_string(), 8).unwrap().clauses, true),
+        ];
+        let unreachable = (0..64)
+            .map(|i| 1u64 << i)
+            .filter(|&bit| {
+                (board.player | board.opponent) & bit & !crate::othello::CENTER_MASK != 0
+            })
+            .map(|bit| Board::new(board.player ^ bit, board.opponent ^ bit))
+            .find(|b| !is_sat_ok(0, &b.to_string()).unwrap())
+            .unwrap();
+        cases.push((
+            build_instance(&unreachable.to_string(), 8).unwrap().clauses,
+            false,
+        ));
+
+        for backend in SatBackend::value_variants() {
+            for (i, (clauses, expected)) in cases.iter().enumerate() {
+                assert_eq!(
+                    solve_cnf(clauses, *backend),
+                    *expected,
+                    "{:?} case {}",
+                    backend,
+                    i
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn is_sat_ok6_rejects_malformed_boards() {
+        assert!(is_sat_ok6(0, "------").is_err());
+        let mut empty_center = board_to_string_sized(&initial_board_sized(6), 6);
+        empty_center.replace_range(14..15, "-");
+        assert!(is_sat_ok6(0, &empty_center).is_err());
+    }
 }
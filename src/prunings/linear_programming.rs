@@ -1,7 +1,7 @@
 #[allow(unused_imports)]
 use crate::othello::{Board, Direction, CENTER_MASK};
 use crate::prunings::occupancy::occupancy_order;
-use highs::{HighsModelStatus, RowProblem, Sense};
+use highs::{HighsModelStatus, Model, RowProblem, Sense};
 use std::ffi::CString;
 use std::fs::File;
 use std::io::{self, BufWriter, Write};
@@ -104,6 +104,34 @@ pub fn dump_solution_columns<P: AsRef<Path>>(
     Ok(())
 }
 
+/// 列（変数）に `vm` の名前を付け、名前を付けられた列の数を返す。名前はデバッグ用で解には影響しないので、
+/// NUL を含んで `CString` にできない名前は付けずに飛ばし、HiGHS がエラーを返しても警告だけ出して続ける。
+fn name_columns(model: &mut Model, n_vars: usize, vm: &VarMaker) -> usize {
+    let mptr = model.as_mut_ptr();
+    let mut named = 0;
+    for i in 0..n_vars {
+        let col_name = vm.get_symbol(i);
+        let Ok(name) = CString::new(col_name.as_str()) else {
+            eprintln!(
+                "warning: LP column {} name {:?} contains NUL; left unnamed",
+                i, col_name
+            );
+            continue;
+        };
+        // SAFETY: mptr は model が持つ有効な Highs インスタンスで、name は呼び出しの間生きている
+        let status = unsafe { highs_sys::Highs_passColName(mptr, i as i32, name.as_ptr()) };
+        if status != highs_sys::STATUS_OK {
+            eprintln!(
+                "warning: Highs_passColName({}, {:?}) returned status {}",
+                i, col_name, status
+            );
+            continue;
+        }
+        named += 1;
+    }
+    named
+}
+
 /// 連続緩和(0<=x<=1)で可否のみ判定 (HiGHS 1.12.0 API)
 fn check_feasibility(
     n_vars: usize,
@@ -150,15 +178,7 @@ fn check_feasibility(
 
     // モデル化 → オプション設定 → 解く
     let mut model = pb.optimise(Sense::Minimise);
-    unsafe {
-        // モデル名
-        let mptr = model.as_mut_ptr();
-        // 列（変数）名：生成順の index を使う
-        for i in 0..n_vars {
-            let col_name = vm.get_symbol(i);
-            highs_sys::Highs_passColName(mptr, i as i32, CString::new(col_name).unwrap().as_ptr());
-        }
-    }
+    name_columns(&mut model, n_vars, vm);
     //
     //model.set_option("output_flag", true);          // ログ表示
     //model.set_option("log_dev_level", 1);
@@ -194,6 +214,9 @@ fn check_feasibility(
     }
 }
 
+/// LP ソルバを使えるかどうか（`lp` feature 無しでビルドすると false）
+pub const LP_AVAILABLE: bool = true;
+
 pub fn check_lp(player: u64, opponent: u64, by_ip_solver: bool) -> bool {
     //let b = Board::new(player, opponent);
     //println!("b={}", b.to_string());
@@ -384,3 +407,48 @@ pub fn check_lp(player: u64, opponent: u64, by_ip_solver: bool) -> bool {
         true
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 目的係数 0、範囲 [0, 1] の列を `n` 個持つ制約なしのモデル
+    fn model_with_columns(n: usize) -> Model {
+        let mut pb = RowProblem::default();
+        for _ in 0..n {
+            let _ = pb.add_column(0.0, 0.0..=1.0);
+        }
+        pb.optimise(Sense::Minimise)
+    }
+
+    #[test]
+    fn names_with_nul_are_left_out() {
+        let mut vm = VarMaker::new();
+        for name in ["first_27_0", "bad\0name", "first_28_0"] {
+            vm.mk_var(name.to_string());
+        }
+        let mut model = model_with_columns(3);
+        assert_eq!(name_columns(&mut model, 3, &vm), 2);
+    }
+
+    #[test]
+    fn rejected_names_do_not_stop_naming_or_solving() {
+        // 列より多い名前を渡すと、範囲外の列には HiGHS がエラーを返す
+        let mut vm = VarMaker::new();
+        for i in 0..3 {
+            vm.mk_var(format!("x_{}", i));
+        }
+        let mut model = model_with_columns(2);
+        assert_eq!(name_columns(&mut model, 3, &vm), 2);
+        assert_eq!(model.solve().status(), HighsModelStatus::Optimal);
+    }
+
+    #[test]
+    fn reachable_boards_pass_the_lp() {
+        assert!(LP_AVAILABLE);
+        for moves in [&[37][..], &[37, 43, 18, 19, 26]] {
+            let b = Board::play_sequence(moves).unwrap();
+            assert!(check_lp(b.player, b.opponent, false));
+        }
+    }
+}
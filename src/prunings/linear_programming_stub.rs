@@ -0,0 +1,27 @@
+//! `lp` feature を無効にしてビルドしたときの `linear_programming` の代わり。
+//! HiGHS を使わないので、LP による枝刈りは常に「実行可能」（枝刈りしない）とみなす。
+
+/// LP ソルバを使えるかどうか
+pub const LP_AVAILABLE: bool = false;
+
+/// LP ソルバ無しでは判定できないので、常に true（枝刈りしない）を返す
+pub fn check_lp(_player: u64, _opponent: u64, _by_ip_solver: bool) -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const _: () = assert!(!LP_AVAILABLE);
+
+    #[test]
+    fn stub_never_prunes() {
+        assert!(check_lp(
+            0x0000_0010_0800_0000,
+            0x0000_0008_1000_0001,
+            false
+        ));
+        assert!(check_lp(0x0000_0010_0800_0000, 0x0000_0008_1000_0001, true));
+    }
+}
@@ -1,4 +1,11 @@
-use crate::othello::{backshift, Direction, CENTER_MASK};
+use alloc::collections::VecDeque;
+#[cfg(not(feature = "std"))]
+use alloc::{format, string::String, vec::Vec};
+
+use crate::othello::{
+    backshift, east, ne, north, nw, se, shift_sized, south, square_center_mask, sw, west,
+    Direction, CENTER_MASK,
+};
 // 前提：A1 が LSB(bit 0)、H1 が bit 7、A8 が bit 56、H8 が bit 63。
 //       方向は N=+8, S=-8, E=+1, W=-1, NE=+9, NW=+7, SE=-7, SW=-9。
 
@@ -25,12 +32,37 @@ pub fn occupied_to_string(o: u64) -> String {
 /// # 戻り値
 /// 中央4マスから到達可能なマス目を表すビットマスク
 pub fn reachable_occupancy(occupied: u64) -> u64 {
+    reachable_occupancy_with(occupied, CENTER_MASK, backshift)
+}
+
+/// `reachable_occupancy` の盤サイズ可変版。
+/// 一辺 `width` マス（`4 <= width <= 8`、偶数）の正方盤を行ストライド `width` で u64 の下位ビットに詰めた表現
+/// （`search::sized` と同じ）を扱い、中央 2x2 から到達可能なマスを返す。`width == 8` では `reachable_occupancy` と一致する。
+pub fn reachable_occupancy_sized(occupied: u64, width: u32) -> u64 {
+    debug_assert!((4..=8).contains(&width) && width.is_multiple_of(2));
+    reachable_occupancy_with(occupied, square_center_mask(width), |d, b| {
+        let (dx, dy) = d.to_offset();
+        shift_sized(b, -dx, -dy, width)
+    })
+}
+
+/// `reachable_occupancy` の本体。盤の大きさは中央マス `center` と 1 マスずらす関数 `backshift` で与える
+#[inline(always)]
+fn reachable_occupancy_with(
+    occupied: u64,
+    center: u64,
+    backshift: impl Fn(Direction, u64) -> u64,
+) -> u64 {
     let dirs = Direction::all();
 
     // 中央4マスから到達可能であることが確認済みのマスの集合（初期値は中央4マス）
-    let mut explained: u64 = CENTER_MASK;
+    let mut explained: u64 = center;
+
+    // 1 回の反復で少なくとも 1 マス増えなければ収束するので、
+    // 反復回数は中央以外の占有マス数で抑えられる（盤の大きさに依存しない上限）
+    let max_iter = (occupied & !center).count_ones();
 
-    for _ in 0..60 {
+    for _ in 0..max_iter {
         let mut add_all: u64 = 0;
         for &d in &dirs {
             // 方向dにおいて、既に到達可能な2マスが隣接しているペアを検出
@@ -73,6 +105,150 @@ pub fn check_occupancy(occupied: u64) -> bool {
     return result == occupied;
 }
 
+/// 中央4マスから到達可能なoccupied bitboardを計算し、各ステップの途中経過を返す
+///
+/// # 前提条件
+/// - 中央2x2 (D4, E4, D5, E5) は常に占有されている必要がある
+///
+/// # 戻り値
+/// - タプルの最初の要素: 中央4マスから到達可能なマス目を表すビットマスク（最終結果）
+/// - タプルの2番目の要素: 中央からBFS順に外側へ広がるよう更新された`explained`の履歴（初期値を含む）。
+///   1 ステップごとに 1 マスずつ増え、最後の要素は最初の要素と同じ値になる
+pub fn reachable_occupancy_with_steps(occupied: u64) -> (u64, Vec<u64>) {
+    let final_explained = reachable_occupancy(occupied);
+    let mut steps = Vec::new();
+    let mut visited = CENTER_MASK & final_explained;
+
+    // 初期状態（中央4マス）を記録
+    steps.push(visited);
+
+    if visited == final_explained {
+        return (final_explained, steps);
+    }
+
+    let mut queue = VecDeque::new();
+
+    // 中央4マスからBFSの初期フロンティアを構築
+    let mut seeds = visited;
+    while seeds != 0 {
+        let tz = seeds.trailing_zeros();
+        let bit = 1u64 << tz;
+        queue.push_back(bit);
+        seeds &= seeds - 1;
+    }
+
+    // 8方向の近傍に順次拡張し、盤面中央から外側へと波状に広げる
+    while let Some(bit) = queue.pop_front() {
+        for neighbor in neighbors(bit) {
+            if neighbor == 0 || (final_explained & neighbor) == 0 || (visited & neighbor) != 0 {
+                continue;
+            }
+            visited |= neighbor;
+            steps.push(visited);
+            queue.push_back(neighbor);
+        }
+    }
+
+    // 念のため、BFSで拾えなかったマスがあれば補完（到達可能なマスは中央から 8 連結なので、
+    // 理論上は空のはず）
+    debug_assert_eq!(visited, final_explained);
+    if visited != final_explained {
+        let mut remaining = final_explained & !visited;
+        while remaining != 0 {
+            let tz = remaining.trailing_zeros();
+            let bit = 1u64 << tz;
+            visited |= bit;
+            steps.push(visited);
+            remaining &= remaining - 1;
+        }
+    }
+
+    (final_explained, steps)
+}
+
+/// 指定したマスの8近傍を返す（盤面外は0）
+fn neighbors(bit: u64) -> [u64; 8] {
+    [
+        north(bit),
+        ne(bit),
+        east(bit),
+        se(bit),
+        south(bit),
+        sw(bit),
+        west(bit),
+        nw(bit),
+    ]
+}
+
+/// `check_occupancy_batch` で 1 度にまとめて不動点計算をする盤面数
+const OCCUPANCY_LANES: usize = 4;
+
+/// `occupied` の各要素に `check_occupancy` を適用した結果を `out` に書き込む。
+///
+/// シフトのマスクは盤面によらないので、`OCCUPANCY_LANES` 個の盤面の不動点計算を
+/// 同じ方向ごとに並べて回す（呼び出しの手間が減り、コンパイラが SIMD 化しやすい形になる）。
+/// 大量の盤面を並列に処理するときは、両方のスライスを同じ大きさのチャンクに分けて呼べばよい。
+///
+/// # Panics
+/// `occupied` と `out` の長さが異なる場合
+pub fn check_occupancy_batch(occupied: &[u64], out: &mut [bool]) {
+    assert_eq!(
+        occupied.len(),
+        out.len(),
+        "check_occupancy_batch: input and output lengths differ"
+    );
+    let mut chunks = occupied.chunks_exact(OCCUPANCY_LANES);
+    let mut results = out.chunks_exact_mut(OCCUPANCY_LANES);
+    for (occ, res) in (&mut chunks).zip(&mut results) {
+        let occ: [u64; OCCUPANCY_LANES] = occ.try_into().unwrap();
+        let reachable = reachable_occupancy_lanes(occ);
+        for ((r, &o), &reach) in res.iter_mut().zip(&occ).zip(&reachable) {
+            *r = (o & CENTER_MASK) == CENTER_MASK && reach == o;
+        }
+    }
+    for (r, &o) in results.into_remainder().iter_mut().zip(chunks.remainder()) {
+        *r = check_occupancy(o);
+    }
+}
+
+/// `reachable_occupancy` を `OCCUPANCY_LANES` 盤面ぶん同時に計算する
+fn reachable_occupancy_lanes(occupied: [u64; OCCUPANCY_LANES]) -> [u64; OCCUPANCY_LANES] {
+    let mut explained = [CENTER_MASK; OCCUPANCY_LANES];
+    loop {
+        let mut add_all = [0u64; OCCUPANCY_LANES];
+        for d in Direction::all() {
+            let mut r_d = [0u64; OCCUPANCY_LANES];
+            for ((r, &e), &occ) in r_d.iter_mut().zip(&explained).zip(&occupied) {
+                *r = backshift(d, backshift(d, e) & e) & occ;
+            }
+            // 1 列は 8 マスで、うち 2 マスは到達済みのペアなので、その先の占有マスの鎖は
+            // 高々 6 マス（1 マス目は上で求めた）。残り 5 マスぶん分岐せず伸ばす。
+            // 伸ばし切れなかったとしても、外側のループが不動点まで回すので結果は変わらない
+            for _ in 0..5 {
+                for (r, &occ) in r_d.iter_mut().zip(&occupied) {
+                    *r |= backshift(d, *r) & occ;
+                }
+            }
+            for (add, r) in add_all.iter_mut().zip(r_d) {
+                *add |= r;
+            }
+        }
+        let mut changed = false;
+        for (e, add) in explained.iter_mut().zip(add_all) {
+            changed |= add & !*e != 0;
+            *e |= add;
+        }
+        if !changed {
+            return explained;
+        }
+    }
+}
+
+/// 占有マスのうち中央4マスから到達できないマスの集合
+pub fn unreachable_occupied(occupied: u64) -> u64 {
+    occupied & !reachable_occupancy(occupied)
+}
+
 pub fn check_occupancy_with_string(occupied: u64) -> (bool, String) {
     if (occupied & CENTER_MASK) != CENTER_MASK {
         return (false, occupied_to_string(occupied));
@@ -82,13 +258,57 @@ pub fn check_occupancy_with_string(occupied: u64) -> (bool, String) {
     return (result == occupied, line);
 }
 
+/// 占有マスと到達可能なマスの差分を 64 文字で表す。
+/// 'G' は到達可能な占有マス、'X' は中央から到達できない占有マス、'-' は空きマス。
+pub fn occupancy_diff_to_string(occupied: u64, reachable: u64) -> String {
+    let mut s = String::with_capacity(64);
+    for i in 0..64 {
+        let bit = 1u64 << i;
+        s.push(if occupied & bit == 0 {
+            '-'
+        } else if reachable & bit != 0 {
+            'G'
+        } else {
+            'X'
+        });
+    }
+    s
+}
+
+/// `check_occupancy_with_string` の説明付き版。
+/// 文字列は「入力の占有マス 到達可能なマス 差分 (`occupancy_diff_to_string`)」を空白区切りで並べたもの。
+/// 中央4マスが埋まっていない場合は到達可能なマスを空として扱う。
+pub fn check_occupancy_explained(occupied: u64) -> (bool, String) {
+    let reachable = if (occupied & CENTER_MASK) == CENTER_MASK {
+        reachable_occupancy(occupied)
+    } else {
+        0
+    };
+    let line = format!(
+        "{} {} {}",
+        occupied_to_string(occupied),
+        occupied_to_string(reachable),
+        occupancy_diff_to_string(occupied, reachable)
+    );
+    (reachable == occupied, line)
+}
+
 /// 下記の考え方に基づいて、各石の置かれた順序を計算
 /// 1. マスAの石を取り除いたら、マスBが説明不可能になった
-/// → マスBは、マスAを経由して初めて中心と接続できた
-/// → つまり、マスBはマスAの後に置かれた石
+///    → マスBは、マスAを経由して初めて中心と接続できた
+///    → つまり、マスBはマスAの後に置かれた石
 /// 2. マスAを取り除いても、マスCが依然として説明可能
-/// → マスCは、マスAに依存せずに中心と接続できている
-/// → つまり、マスCはマスAと同時またはそれ以前に置かれた石
+///    → マスCは、マスAに依存せずに中心と接続できている
+///    → つまり、マスCはマスAと同時またはそれ以前に置かれた石
+///
+/// # 前提条件
+/// - 中央2x2 (D4, E4, D5, E5) は常に占有されている必要がある
+///
+/// # 戻り値
+/// 占有マス `sq` について、`ans[sq]` はマス `sq` と同時またはそれ以前に置かれた石の集合
+/// （`sq` の石を取り除いた盤面で中央4マスから到達可能なマスに `sq` 自身を加えたもの）で、
+/// 常に `CENTER_MASK | (1 << sq)` を含む。空きマスの要素は 0 で意味を持たないので、
+/// 占有マスだけを引くこと。引く側で保証できない場合は `occupancy_order_checked` を使う。
 pub fn occupancy_order(occupied: u64) -> [u64; 64] {
     let mut ans = [0; 64];
     let mut b = occupied;
@@ -99,7 +319,214 @@ pub fn occupancy_order(occupied: u64) -> [u64; 64] {
         let b_one = b ^ newb;
         // マスsqと同時またはそれ以前に置かれた石の集合
         ans[sq] = reachable_occupancy(occupied ^ b_one) | b_one;
+        debug_assert_eq!(ans[sq] & (CENTER_MASK | b_one), CENTER_MASK | b_one);
         b = newb;
     }
     ans
 }
+
+/// `occupancy_order` の、空きマスの要素を `None` にした版。
+/// 占有マスかどうかを確かめずに引く試行錯誤用のコード向け。
+pub fn occupancy_order_checked(occupied: u64) -> Vec<Option<u64>> {
+    let order = occupancy_order(occupied);
+    (0..64)
+        .map(|sq| (occupied & (1u64 << sq) != 0).then_some(order[sq]))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{playout_boards, XorShift};
+    use alloc::vec;
+
+    /// 座標で直接書いた不動点計算: 方向 d に 2 マス続けて到達可能なマスがある占有マスを、増えなくなるまで加える
+    fn naive_reachable(occupied: u64, width: i32) -> u64 {
+        let a = (width / 2 - 1) * width + width / 2 - 1;
+        let mut explained = (0b11u64 << a) | (0b11u64 << (a + width));
+        let at = |x: i32, y: i32| {
+            (0 <= x && x < width && 0 <= y && y < width).then(|| 1u64 << (y * width + x))
+        };
+        loop {
+            let mut next = explained;
+            for y in 0..width {
+                for x in 0..width {
+                    let bit = at(x, y).unwrap();
+                    if occupied & bit == 0 {
+                        continue;
+                    }
+                    for d in Direction::all() {
+                        let (dx, dy) = d.to_offset();
+                        if let (Some(p), Some(q)) = (at(x + dx, y + dy), at(x + 2 * dx, y + 2 * dy))
+                        {
+                            if explained & p != 0 && explained & q != 0 {
+                                next |= bit;
+                            }
+                        }
+                    }
+                }
+            }
+            if next == explained {
+                return explained;
+            }
+            explained = next;
+        }
+    }
+
+    #[test]
+    fn sized_matches_8x8_version() {
+        let mut rng = XorShift::new(0x9E37_79B9_7F4A_7C15);
+        for _ in 0..1000 {
+            let occupied = (rng.next_u64() & rng.next_u64()) | CENTER_MASK;
+            assert_eq!(
+                reachable_occupancy_sized(occupied, 8),
+                reachable_occupancy(occupied)
+            );
+            assert_eq!(reachable_occupancy(occupied), naive_reachable(occupied, 8));
+        }
+    }
+
+    #[test]
+    fn converges_on_6x6() {
+        let mut rng = XorShift::new(0x2545_F491_4F6C_DD1D);
+        let board = (1u64 << 36) - 1;
+        for _ in 0..1000 {
+            let occupied = rng.next_u64() & board;
+            let reachable = reachable_occupancy_sized(occupied, 6);
+            assert_eq!(reachable, naive_reachable(occupied, 6), "{:036b}", occupied);
+            assert_eq!(reachable & !board, 0);
+        }
+        // 全マス占有なら盤全体に広がり、盤外（36 ビット目以降）にははみ出さない
+        assert_eq!(reachable_occupancy_sized(board, 6), board);
+        // 中央から離れた角のマスだけは到達できない
+        let corner = (0b11u64 << 14) | (0b11u64 << 20) | 1;
+        assert_eq!(reachable_occupancy_sized(corner, 6), corner & !1);
+    }
+
+    #[test]
+    fn unreachable_occupied_is_the_disconnected_island() {
+        // c4 は中央の d4 e4 から伸ばせるが、隅の a1 b1 a2 は中央とつながっていない
+        let c4 = 1u64 << 26;
+        let island = (1u64 << 0) | (1u64 << 1) | (1u64 << 8);
+        let occupied = CENTER_MASK | c4 | island;
+        assert_eq!(unreachable_occupied(occupied), island);
+        assert!(!check_occupancy(occupied));
+        assert_eq!(unreachable_occupied(CENTER_MASK | c4), 0);
+        assert!(check_occupancy(CENTER_MASK | c4));
+    }
+
+    #[test]
+    fn explained_output_marks_the_unreachable_squares() {
+        let c4 = 1u64 << 26;
+        let island = (1u64 << 0) | (1u64 << 1) | (1u64 << 8);
+        let occupied = CENTER_MASK | c4 | island;
+        let (ok, line) = check_occupancy_explained(occupied);
+        assert!(!ok);
+        let parts: Vec<&str> = line.split(' ').collect();
+        assert_eq!(parts.len(), 3);
+        assert_eq!(parts[0], occupied_to_string(occupied));
+        assert_eq!(parts[1], occupied_to_string(CENTER_MASK | c4));
+        // 差分では a1 b1 a2 だけが 'X'
+        let marked: Vec<usize> = parts[2].match_indices('X').map(|(i, _)| i).collect();
+        assert_eq!(marked, [0, 1, 8]);
+        assert_eq!(parts[2].matches('G').count(), 5);
+
+        let (ok, line) = check_occupancy_explained(CENTER_MASK | c4);
+        assert!(ok);
+        assert!(!line.contains('X'));
+        // 中央が欠けていれば到達可能なマスは無く、占有マスはすべて 'X'
+        let (ok, line) = check_occupancy_explained(c4 | island);
+        assert!(!ok);
+        assert_eq!(line.split(' ').nth(2).unwrap().matches('X').count(), 4);
+    }
+
+    /// 初期局面からの再現可能な乱択プレイアウトで現れる局面の占有マス
+    fn playout_occupancies(rng: &mut XorShift) -> Vec<u64> {
+        playout_boards(rng, 20)
+            .iter()
+            .map(|b| b.player | b.opponent)
+            .collect()
+    }
+
+    #[test]
+    fn batch_matches_check_occupancy_lane_by_lane() {
+        let mut rng = XorShift::new(0x0123_4567_89AB_CDEF);
+        let mut occupied = playout_occupancies(&mut rng);
+        for i in 0..2000 {
+            let random = rng.next_u64() & rng.next_u64();
+            // 半分は中央を埋め、残りは中央が欠けうる
+            occupied.push(if i % 2 == 0 {
+                random | CENTER_MASK
+            } else {
+                random
+            });
+        }
+        // 端から端まで占有された行と列（1 方向に最も長い鎖ができる）
+        occupied.push(CENTER_MASK | 0x00FF_0000_0000_0000 | 0x0010_1010_1010_1010);
+        occupied.push(u64::MAX);
+        assert!(occupied.iter().any(|&o| check_occupancy(o)));
+        assert!(occupied.iter().any(|&o| !check_occupancy(o)));
+        // 端数が出る長さ（レーン幅の倍数でない）も試す
+        for len in [occupied.len(), occupied.len() - 1, 3, 1, 0] {
+            let input = &occupied[..len];
+            let mut out = vec![false; len];
+            check_occupancy_batch(input, &mut out);
+            for (i, (&o, &r)) in input.iter().zip(&out).enumerate() {
+                assert_eq!(
+                    r,
+                    check_occupancy(o),
+                    "len = {}, lane {}: {:#018x}",
+                    len,
+                    i,
+                    o
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn steps_end_at_the_reachable_occupancy_one_square_at_a_time() {
+        let mut rng = XorShift::new(0x6A09_E667_F3BC_C908);
+        let mut occupied = playout_occupancies(&mut rng);
+        for _ in 0..500 {
+            occupied.push((rng.next_u64() & rng.next_u64()) | CENTER_MASK);
+        }
+        for o in occupied {
+            let (last, steps) = reachable_occupancy_with_steps(o);
+            assert_eq!(last, reachable_occupancy_sized(o, 8));
+            assert_eq!(steps[0], CENTER_MASK);
+            assert_eq!(*steps.last().unwrap(), last);
+            for pair in steps.windows(2) {
+                // 直前の集合を真に含み、増えるのはちょうど 1 マス
+                assert_eq!(pair[1] & pair[0], pair[0]);
+                assert_eq!((pair[1] ^ pair[0]).count_ones(), 1);
+            }
+        }
+    }
+
+    #[test]
+    fn order_of_each_stone_contains_the_center_and_itself() {
+        let mut rng = XorShift::new(0xBB67_AE85_84CA_A73B);
+        let mut occupied = playout_occupancies(&mut rng);
+        for _ in 0..500 {
+            occupied.push((rng.next_u64() & rng.next_u64()) | CENTER_MASK);
+        }
+        for o in occupied {
+            let order = occupancy_order(o);
+            let checked = occupancy_order_checked(o);
+            assert_eq!(checked.len(), 64);
+            for sq in 0..64 {
+                let bit = 1u64 << sq;
+                if o & bit == 0 {
+                    assert_eq!(checked[sq], None);
+                    continue;
+                }
+                assert_eq!(checked[sq], Some(order[sq]));
+                assert_eq!(order[sq] & (CENTER_MASK | bit), CENTER_MASK | bit);
+                // 盤上の石だけからなり、sq を除いた盤面で説明できるマスに sq を加えたもの
+                assert_eq!(order[sq] & !o, 0);
+                assert_eq!(order[sq], reachable_occupancy(o ^ bit) | bit);
+            }
+        }
+    }
+}
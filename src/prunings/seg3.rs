@@ -1,6 +1,11 @@
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
+
 use crate::{
-    othello::{Board, Direction},
+    othello::{backshift, format_mask, Board, Direction, CENTER_MASK},
     prunings::occupancy::occupancy_order,
+    veprintln,
+    verbosity::DEBUG,
 };
 
 pub fn no_cycle(g: Vec<Vec<usize>>) -> bool {
@@ -33,6 +38,51 @@ pub fn no_cycle(g: Vec<Vec<usize>>) -> bool {
     true
 }
 
+/// 各マスから 8 方向（`Direction::all()` の順）に続く石の数（自身を含む）。空きマスは全方向 0。
+///
+/// `check_seg3` と `check_seg3_more` が共通に使う方向ごとの走査を 1 回にまとめたもの。
+/// 両方の枝刈りを使うときは `check_seg3_both` で 1 回だけ作る。
+pub struct Seg3Runs {
+    len: [[u8; 8]; 64],
+}
+
+impl Seg3Runs {
+    pub fn new(occupied: u64) -> Self {
+        let mut len = [[0u8; 8]; 64];
+        for y in 0..8 {
+            for x in 0..8 {
+                let i = y * 8 + x;
+                if occupied & (1 << i) == 0 {
+                    continue;
+                }
+                for (d, dir) in Direction::all().iter().enumerate() {
+                    let (dx, dy) = dir.to_offset();
+                    let mut l = 1;
+                    let mut x1 = x + dx;
+                    let mut y1 = y + dy;
+                    while 0 <= x1
+                        && x1 < 8
+                        && 0 <= y1
+                        && y1 < 8
+                        && occupied & (1 << (y1 * 8 + x1)) != 0
+                    {
+                        l += 1;
+                        x1 += dx;
+                        y1 += dy;
+                    }
+                    len[i as usize][d] = l;
+                }
+            }
+        }
+        Seg3Runs { len }
+    }
+
+    /// マス `i` から方向 `d` に続く石の数（自身を含む）
+    pub fn len(&self, i: usize, d: usize) -> u8 {
+        self.len[i][d]
+    }
+}
+
 /// 下記の2条件によって到達不能な局面を検出する
 /// 矛盾が生じる（到達不能）ならfalse, 無矛盾ならtrue
 ///
@@ -45,6 +95,24 @@ pub fn no_cycle(g: Vec<Vec<usize>>) -> bool {
 /// $G_s$ に閉路が存在するならば、$G_s$に対応する局面$s$は初期局面から到達不能である。
 /// 閉路が存在することは「着手の依存関係に循環がある」ことを意味し、矛盾する。
 pub fn check_seg3(b: u64) -> bool {
+    check_seg3_cond1(b) && check_seg3_with(b, &Seg3Runs::new(b))
+}
+
+/// `check_seg3` の cond 1 だけを調べる。中央 4 マス以外の全ての石について、
+/// 8方向のいずれかに自身を含めて3つ以上の石が連続していれば true。
+///
+/// ビット演算だけで判定できるので、`Seg3Runs` を作る前の安価な前段として使える。
+pub fn check_seg3_cond1(b: u64) -> bool {
+    let mut ok = 0u64;
+    for d in Direction::all() {
+        let next = backshift(d, b);
+        ok |= b & next & backshift(d, next);
+    }
+    b & !CENTER_MASK & !ok == 0
+}
+
+/// `check_seg3` の、走査済みの `runs`（`Seg3Runs::new(b)`）を使う版
+pub fn check_seg3_with(b: u64, runs: &Seg3Runs) -> bool {
     let mut g: Vec<Vec<usize>> = vec![vec![]; 64];
     for y in 0..8 {
         for x in 0..8 {
@@ -56,19 +124,9 @@ pub fn check_seg3(b: u64) -> bool {
                 continue;
             }
             let mut oks: Vec<Vec<usize>> = vec![];
-            for dir in Direction::all().iter() {
-                let (dx, dy) = dir.to_offset();
-                let mut l = 1;
-                let mut x1 = x + dx;
-                let mut y1 = y + dy;
-                let mut i1 = y1 * 8 + x1;
-                while 0 <= x1 && x1 < 8 && 0 <= y1 && y1 < 8 && b & (1 << i1) != 0 {
-                    l += 1;
-                    x1 += dx;
-                    y1 += dy;
-                    i1 = y1 * 8 + x1;
-                }
-                if l >= 3 {
+            for (d, dir) in Direction::all().iter().enumerate() {
+                if runs.len(i as usize, d) >= 3 {
+                    let (dx, dy) = dir.to_offset();
                     let di = dy * 8 + dx;
                     oks.push(vec![(i + di) as usize, (i + di * 2) as usize]);
                 }
@@ -85,7 +143,7 @@ pub fn check_seg3(b: u64) -> bool {
     return no_cycle(g);
 }
 
-fn can_put_flip(occupied: u64, order: &[u64; 64]) -> ([u8; 64], [u8; 64]) {
+fn can_put_flip(occupied: u64, order: &[u64; 64], runs: &Seg3Runs) -> ([u8; 64], [u8; 64]) {
     let mut canput: [u8; 64] = [0; 64];
     let mut canflip: [u8; 64] = [0; 64];
     for y in 0..8 {
@@ -94,39 +152,25 @@ fn can_put_flip(occupied: u64, order: &[u64; 64]) -> ([u8; 64], [u8; 64]) {
             if occupied & (1 << i) == 0 {
                 continue;
             }
-            let mut ls: [u8; 8] = [0; 8];
-            let mut ls1: [u8; 8] = [0; 8];
             let o1 = order[i as usize];
+            // occupancy_order は占有マスの要素だけが意味を持つ
+            debug_assert!(o1 & (1 << i) != 0);
             for (d, dir) in Direction::all().iter().enumerate() {
+                // 石が続く範囲のうち、先頭から order[i] に含まれる部分の長さ
+                let l = runs.len(i as usize, d);
                 let (dx, dy) = dir.to_offset();
-                let mut l = 1;
                 let mut l1 = 1;
-                let mut x1 = x + dx;
-                let mut y1 = y + dy;
-                let mut i1 = y1 * 8 + x1;
-                let mut in_o1 = true;
-                while 0 <= x1 && x1 < 8 && 0 <= y1 && y1 < 8 && occupied & (1 << i1) != 0 {
-                    if in_o1 && o1 & (1 << i1) == 0 {
-                        in_o1 = false;
-                    }
-                    if in_o1 {
-                        l1 += 1;
-                    }
-                    l += 1;
-                    x1 += dx;
-                    y1 += dy;
-                    i1 = y1 * 8 + x1;
+                let mut i1 = i + dy * 8 + dx;
+                while l1 < l && o1 & (1 << i1) != 0 {
+                    l1 += 1;
+                    i1 += dy * 8 + dx;
                 }
-                ls[d] = l;
-                ls1[d] = l1;
-            }
-            for d in 0..8 {
-                if ls1[d] >= 3 {
+                if l1 >= 3 {
                     if !(3 <= x && x <= 4 && 3 <= y && y <= 4) {
                         canput[i as usize] |= 1u8 << d;
                     }
                 }
-                if d < 4 && ls[d] >= 2 && ls[d + 4] >= 2 {
+                if d < 4 && l >= 2 && runs.len(i as usize, d + 4) >= 2 {
                     canflip[i as usize] |= 1u8 << d;
                 }
             }
@@ -135,15 +179,30 @@ fn can_put_flip(occupied: u64, order: &[u64; 64]) -> ([u8; 64], [u8; 64]) {
     (canput, canflip)
 }
 
+/// `check_seg3` と `check_seg3_more` を、方向ごとの走査を共有して両方行う。両方通れば true。
+pub fn check_seg3_both(player: u64, opponent: u64) -> bool {
+    let occupied = player | opponent;
+    if !check_seg3_cond1(occupied) {
+        return false;
+    }
+    let runs = Seg3Runs::new(occupied);
+    check_seg3_with(occupied, &runs) && check_seg3_more_with(player, opponent, &runs)
+}
+
 /// 盤面が初期配置に到達不能かどうかの粗めのチェック．
 pub fn check_seg3_more(player: u64, opponent: u64) -> bool {
+    check_seg3_more_with(player, opponent, &Seg3Runs::new(player | opponent))
+}
+
+/// `check_seg3_more` の、走査済みの `runs`（`Seg3Runs::new(player | opponent)`）を使う版
+pub fn check_seg3_more_with(player: u64, opponent: u64, runs: &Seg3Runs) -> bool {
     //if !check_seg3_more(player, opponent) {
     //    return false;
     //}
 
     let occupied = player | opponent;
     let order = occupancy_order(occupied);
-    let (canput, canflip) = can_put_flip(occupied, &order);
+    let (canput, canflip) = can_put_flip(occupied, &order, runs);
     let ps = [player, opponent];
     for i in 0..2 {
         let p0 = ps[i];
@@ -157,8 +216,9 @@ pub fn check_seg3_more(player: u64, opponent: u64) -> bool {
                     continue;
                 }
                 if canput[i as usize] == 0 {
-                    eprintln!("canput = 0, i={}, x={}, y={}", i, x, y);
-                    eprintln!("{}", Board::new(player, opponent).show());
+                    veprintln!(DEBUG, "canput = 0, i={}, x={}, y={}", i, x, y);
+                    veprintln!(DEBUG, "{}", Board::new(player, opponent).show());
+                    veprintln!(DEBUG, "order[{}] =\n{}", i, format_mask(order[i as usize]));
                     panic!("inconsistent");
                 }
                 // putの方向が1方向で後でflipされた可能性がない．
@@ -209,3 +269,76 @@ pub fn check_seg3_more(player: u64, opponent: u64) -> bool {
     }
     true
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prunings::tests::playout_boards;
+    use crate::test_util::XorShift;
+
+    #[test]
+    fn both_matches_running_the_two_checks_separately() {
+        let mut rejected = 0;
+        for b in playout_boards() {
+            // 石の色を 1 つ入れ替えた盤面（占有は変わらないので check_seg3_more も使える）
+            let bit = 1u64 << (b.opponent | b.player).trailing_zeros();
+            let recolored = Board::new(b.player ^ bit, b.opponent ^ bit);
+            for c in [b, recolored] {
+                let separate =
+                    check_seg3(c.player | c.opponent) && check_seg3_more(c.player, c.opponent);
+                assert_eq!(
+                    check_seg3_both(c.player, c.opponent),
+                    separate,
+                    "{}",
+                    c.show()
+                );
+                rejected += !separate as usize;
+            }
+        }
+        assert!(rejected > 0);
+    }
+
+    /// `check_seg3_with` のループ内の cond 1（中央以外の各石で `Seg3Runs` の長さ 3 以上の方向があるか）
+    fn cond1_by_runs(b: u64) -> bool {
+        let runs = Seg3Runs::new(b);
+        (0..64)
+            .filter(|&i| b & !CENTER_MASK & (1u64 << i) != 0)
+            .all(|i| (0..8).any(|d| runs.len(i, d) >= 3))
+    }
+
+    #[test]
+    fn cond1_matches_the_run_length_scan() {
+        let mut rng = XorShift::new(0x1405_7B7E_F767_814F);
+        let mut rejected = 0;
+        for i in 0..2000 {
+            let state = rng.next_u64();
+            // 密度の違う乱択の占有（3 回に 1 回は疎に、残りは 2 つの乱数の論理和で密に）
+            let r = state.rotate_left(17) ^ state.wrapping_mul(0x2545_F491_4F6C_DD1D);
+            let occupied = CENTER_MASK
+                | match i % 3 {
+                    0 => state & r,
+                    1 => state,
+                    _ => state | r,
+                };
+            assert_eq!(check_seg3_cond1(occupied), cond1_by_runs(occupied));
+            rejected += !cond1_by_runs(occupied) as usize;
+        }
+        assert!(rejected > 0);
+        for b in playout_boards() {
+            let occupied = b.player | b.opponent;
+            assert!(check_seg3_cond1(occupied));
+            assert!(cond1_by_runs(occupied));
+        }
+    }
+
+    #[test]
+    fn cond1_rejects_a_stone_one_square_apart_from_the_center() {
+        // d3 は d3-d4-d5 と 3 つ続くが、d2 は d3 が空いているのでどの方向にも続かない
+        let d3 = 1u64 << 19;
+        let d2 = 1u64 << 11;
+        assert!(check_seg3_cond1(CENTER_MASK | d3));
+        assert!(!check_seg3_cond1(CENTER_MASK | d2));
+        assert!(!check_seg3(CENTER_MASK | d2));
+        assert!(check_seg3_cond1(CENTER_MASK));
+    }
+}
@@ -0,0 +1,449 @@
+//! 盤面の一様抽出と、抽出結果から到達可能な局面数 |R| を見積もるための区間推定
+use std::cmp::min;
+use std::collections::HashSet;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::othello::Board;
+
+/// 中央 4 マスは黒か白、残り 60 マスは空・黒・白のいずれかである盤面の総数
+pub const ALL_STATES: u128 = 3_u128.pow(60) * 2_u128.pow(4);
+
+/// `random_board(rng, n)` が抽出する母集団の大きさ。
+/// `n == 0` なら `ALL_STATES`、それ以外は中央以外の `n` マスに石がある盤面の総数
+/// `2^4 * C(60, n) * 2^n`。
+pub fn population_size(n: usize) -> u128 {
+    if n == 0 {
+        return ALL_STATES;
+    }
+    combination_u128(60, n).unwrap() * 2_u128.pow(4 + n as u32)
+}
+
+/// nCk を u128 で返す。u128 を超える場合は None。
+pub fn combination_u128(n: usize, k: usize) -> Option<u128> {
+    if k > n {
+        return Some(0); // 慣習的に n < k なら 0
+    }
+    let k = min(k, n - k);
+    if k == 0 {
+        return Some(1);
+    }
+
+    let mut res: u128 = 1;
+
+    for i in 1..=k {
+        // 分子 (n - k + i), 分母 i
+        let mut a = (n - k + i) as u128;
+        let mut b = i as u128;
+
+        // 分子と分母でまず約分
+        let g1 = gcd_u128(a, b);
+        a /= g1;
+        b /= g1;
+
+        // さらに現在の res と分母 b を約分（分母をできるだけ 1 に近づける）
+        let g2 = gcd_u128(res, b);
+        res /= g2;
+        b /= g2;
+
+        // ここまでで b は通常 1 になる（ならなくても整数結果は保たれる）
+        // まず掛け算でオーバーフロー検出
+        res = res.checked_mul(a)?;
+        if b != 1 {
+            // 念のため（整数性は保たれているはず）
+            debug_assert!(res.is_multiple_of(b));
+            res /= b;
+        }
+    }
+    Some(res)
+}
+
+#[inline]
+fn gcd_u128(mut a: u128, mut b: u128) -> u128 {
+    while b != 0 {
+        let r = a % b;
+        a = b;
+        b = r;
+    }
+    a
+}
+
+/// 区間 0..lim から乱数を生成
+fn mk_rand<R: Rng + ?Sized>(rng: &mut R, lim: u128) -> u128 {
+    let maxv: u128 = (u128::MAX / lim) * lim; // u128::MAX以下で最大のlimの倍数
+
+    // 乱数の範囲を [0, maxv) に制限し, [maxv, u128::MAX] の値を棄却する
+    loop {
+        let x: u128 = rng.random();
+        if x < maxv {
+            return x % lim;
+        }
+    }
+}
+
+/// n+4マス埋まりのランダムなビットボードを生成（到達可能とは限らない）
+/// - rng: 疑似乱数生成器
+/// - n: 中心4マス以外に石を置くマス数 (n==0ならばマス数を限定しない全状態から抽出)
+///
+/// 抽出は `population_size(n)` 個の盤面からの一様抽出になる。
+pub fn random_board<R: Rng + ?Sized>(rng: &mut R, n: usize) -> Board {
+    let mut player: u64 = 0;
+    let mut opponent: u64 = 0;
+
+    if n == 0 {
+        return board_from_index(mk_rand(rng, ALL_STATES), 0);
+    }
+
+    let mut rest_stone = n; //置くべき石が残りいくつあるか
+    let mut rest_sq = 60; //まだ石を置いていないマスの数
+
+    for y in 0..8 {
+        for x in 0..8 {
+            let i = y * 8 + x;
+            if (3..=4).contains(&x) && (3..=4).contains(&y) {
+                let v = mk_rand(rng, 2);
+                if v == 0 {
+                    player |= 1u64 << i;
+                } else {
+                    opponent |= 1u64 << i;
+                }
+            } else {
+                rest_sq -= 1;
+                let mut set_count: u128 = 0;
+                let mut blank_count: u128 = 0;
+                if rest_sq < rest_stone {
+                    // always set
+                    set_count = 1;
+                } else if rest_stone == 0 {
+                    blank_count = 1;
+                } else {
+                    set_count = combination_u128(rest_sq, rest_stone - 1).unwrap();
+                    blank_count = combination_u128(rest_sq, rest_stone).unwrap();
+                }
+                let v = mk_rand(rng, set_count + blank_count);
+                if v < set_count {
+                    rest_stone -= 1;
+                    let v = mk_rand(rng, 2);
+                    if v == 0 {
+                        player |= 1u64 << i;
+                    } else {
+                        opponent |= 1u64 << i;
+                    }
+                }
+            }
+        }
+    }
+    Board::new(player, opponent)
+}
+
+/// `0..population_size(n)` の添字から盤面への全単射（`random_board` と同じ母集団）。
+///
+/// `n == 0` では添字を中央は 2 進、それ以外は 3 進の桁としてマス順に読む（`random_board` と同じ）。
+/// `n > 0` では添字を `(中央の色 × C(60, n) + 石を置くマスの組の順位) × 2^n + 石の色` と分解する。
+/// 添字が範囲外なら panic する。
+pub fn board_from_index(index: u128, n: usize) -> Board {
+    assert!(
+        index < population_size(n),
+        "index {} out of range for n = {}",
+        index,
+        n
+    );
+    let mut player: u64 = 0;
+    let mut opponent: u64 = 0;
+    let is_center = |x: usize, y: usize| (3..=4).contains(&x) && (3..=4).contains(&y);
+
+    if n == 0 {
+        let mut v = index;
+        for y in 0..8 {
+            for x in 0..8 {
+                let i = y * 8 + x;
+                let sq = if is_center(x, y) {
+                    let ans = (v % 2) + 1;
+                    v /= 2;
+                    ans
+                } else {
+                    let ans = v % 3;
+                    v /= 3;
+                    ans
+                };
+                if sq == 1 {
+                    player |= 1u64 << i;
+                } else if sq == 2 {
+                    opponent |= 1u64 << i;
+                }
+            }
+        }
+        return Board::new(player, opponent);
+    }
+
+    let mut colors = index % (1u128 << n);
+    let rest = index >> n;
+    let combinations = combination_u128(60, n).unwrap();
+    let mut rank = rest % combinations;
+    let mut center = rest / combinations;
+    let mut rest_stone = n;
+    let mut rest_sq = 60;
+    for y in 0..8 {
+        for x in 0..8 {
+            let bit = 1u64 << (y * 8 + x);
+            let stone = if is_center(x, y) {
+                let c = center % 2;
+                center /= 2;
+                Some(c)
+            } else {
+                rest_sq -= 1;
+                // このマスに置く組の数（残りのマスから残りの石 - 1 個を選ぶ）
+                let set_count = if rest_stone == 0 {
+                    0
+                } else {
+                    combination_u128(rest_sq, rest_stone - 1).unwrap()
+                };
+                if rank < set_count {
+                    rest_stone -= 1;
+                    let c = colors % 2;
+                    colors /= 2;
+                    Some(c)
+                } else {
+                    rank -= set_count;
+                    None
+                }
+            };
+            match stone {
+                Some(0) => player |= bit,
+                Some(_) => opponent |= bit,
+                None => (),
+            }
+        }
+    }
+    Board::new(player, opponent)
+}
+
+/// `DistinctSampler` の非復元抽出の方法
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DistinctMethod {
+    /// `random_board` で抽出し、既に出た盤面なら引き直す。出した盤面を全て覚えておくので、
+    /// 母集団が小さい（または抽出数が少ない）場合向け。母集団の大半を引くと引き直しが増える。
+    Rejection,
+    /// 添字 `0..population_size(n)` を seed から決まる Feistel 置換で並べ替え、その順に
+    /// `board_from_index` で盤面にする。重複は起きず、メモリも使わないので大きな母集団向け。
+    Feistel,
+}
+
+/// Feistel 置換の段数
+const FEISTEL_ROUNDS: usize = 6;
+
+/// splitmix64 の出力関数（Feistel のラウンド関数と鍵の生成に使う）
+fn mix64(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// `0..size` の上の、seed で決まる置換（Feistel ネットワーク + cycle walking）
+#[derive(Debug, Clone)]
+struct FeistelPermutation {
+    size: u128,
+    half_bits: u32,
+    keys: [u64; FEISTEL_ROUNDS],
+}
+
+impl FeistelPermutation {
+    fn new(size: u128, seed: u64) -> Self {
+        // size - 1 を表せる偶数ビットの領域を 2 つに分けて混ぜる（半分は高々 64 ビット）
+        let bits = (128 - (size.max(2) - 1).leading_zeros()).max(2);
+        let half_bits = bits.div_ceil(2);
+        let mut state = seed;
+        let keys = core::array::from_fn(|_| {
+            state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            mix64(state)
+        });
+        FeistelPermutation {
+            size,
+            half_bits,
+            keys,
+        }
+    }
+
+    fn round(&self, x: u128) -> u128 {
+        let mask = (1u128 << self.half_bits) - 1;
+        let (mut left, mut right) = (x >> self.half_bits, x & mask);
+        for &key in &self.keys {
+            let f = mix64(right as u64 ^ key) as u128 & mask;
+            (left, right) = (right, left ^ f);
+        }
+        (left << self.half_bits) | right
+    }
+
+    /// `i` の行き先。領域の外に出たら中に戻るまで置換を繰り返す（cycle walking）ので、
+    /// `0..size` の上の全単射になる。
+    fn apply(&self, i: u128) -> u128 {
+        let mut x = self.round(i);
+        while x >= self.size {
+            x = self.round(x);
+        }
+        x
+    }
+}
+
+/// `random_board(rng, n)` と同じ母集団からの非復元抽出。seed が同じなら同じ列を返す。
+pub struct DistinctSampler {
+    n: usize,
+    population: u128,
+    draws: u64,
+    collisions: u64,
+    emitted: u128,
+    state: DistinctState,
+}
+
+enum DistinctState {
+    Rejection {
+        rng: Box<StdRng>,
+        seen: HashSet<[u64; 2]>,
+    },
+    Feistel(FeistelPermutation),
+}
+
+impl DistinctSampler {
+    pub fn new(n: usize, method: DistinctMethod, seed: u64) -> Self {
+        let population = population_size(n);
+        let state = match method {
+            DistinctMethod::Rejection => DistinctState::Rejection {
+                rng: Box::new(StdRng::seed_from_u64(seed)),
+                seen: HashSet::new(),
+            },
+            DistinctMethod::Feistel => {
+                DistinctState::Feistel(FeistelPermutation::new(population, seed))
+            }
+        };
+        DistinctSampler {
+            n,
+            population,
+            draws: 0,
+            collisions: 0,
+            emitted: 0,
+            state,
+        }
+    }
+
+    /// まだ出していない盤面を 1 つ返す。母集団を出し尽くしたら `None`。
+    pub fn next_board(&mut self) -> Option<Board> {
+        if self.emitted >= self.population {
+            return None;
+        }
+        let board = match &mut self.state {
+            DistinctState::Rejection { rng, seen } => loop {
+                let board = random_board(rng.as_mut(), self.n);
+                self.draws += 1;
+                if seen.insert([board.player, board.opponent]) {
+                    break board;
+                }
+                self.collisions += 1;
+            },
+            DistinctState::Feistel(perm) => {
+                self.draws += 1;
+                board_from_index(perm.apply(self.emitted), self.n)
+            }
+        };
+        self.emitted += 1;
+        Some(board)
+    }
+
+    /// 乱数で盤面を引いた回数（`Feistel` では出した盤面数と同じ）
+    pub fn draws(&self) -> u64 {
+        self.draws
+    }
+
+    /// 既に出た盤面を引いて引き直した回数（`Feistel` では常に 0）
+    pub fn collisions(&self) -> u64 {
+        self.collisions
+    }
+
+    /// `collisions / draws`。復元抽出なら重複していた割合に相当する。
+    pub fn collision_rate(&self) -> f64 {
+        if self.draws == 0 {
+            0.0
+        } else {
+            self.collisions as f64 / self.draws as f64
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 母集団を出し尽くすまで抽出し、出した盤面の集合を返す
+    fn drain(sampler: &mut DistinctSampler) -> HashSet<[u64; 2]> {
+        let mut seen = HashSet::new();
+        while let Some(board) = sampler.next_board() {
+            assert!(
+                seen.insert([board.player, board.opponent]),
+                "{:?} emitted twice",
+                board
+            );
+        }
+        assert_eq!(sampler.next_board(), None);
+        seen
+    }
+
+    #[test]
+    fn board_from_index_is_a_bijection_onto_the_population() {
+        let n = 1;
+        assert_eq!(population_size(n), 1920);
+        let boards: HashSet<[u64; 2]> = (0..population_size(n))
+            .map(|i| {
+                let board = board_from_index(i, n);
+                let occupied = board.player | board.opponent;
+                assert_eq!(board.player & board.opponent, 0);
+                assert_eq!(occupied & 0x0000_0018_1800_0000, 0x0000_0018_1800_0000);
+                assert_eq!(occupied.count_ones(), 5);
+                [board.player, board.opponent]
+            })
+            .collect();
+        assert_eq!(boards.len(), 1920);
+    }
+
+    #[test]
+    fn distinct_samplers_emit_each_board_exactly_once() {
+        let population: HashSet<[u64; 2]> = (0..population_size(1))
+            .map(|i| {
+                let board = board_from_index(i, 1);
+                [board.player, board.opponent]
+            })
+            .collect();
+        for method in [DistinctMethod::Rejection, DistinctMethod::Feistel] {
+            let mut sampler = DistinctSampler::new(1, method, 42);
+            assert_eq!(drain(&mut sampler), population, "{:?}", method);
+        }
+    }
+
+    #[test]
+    fn collision_counts_match_the_redrawn_boards() {
+        let mut rejection = DistinctSampler::new(1, DistinctMethod::Rejection, 7);
+        assert_eq!(rejection.collision_rate(), 0.0);
+        drain(&mut rejection);
+        // 出した盤面以外の抽出は全て引き直し
+        assert_eq!(rejection.draws() - rejection.collisions(), 1920);
+        assert!(rejection.collisions() > 0);
+        let rate = rejection.collision_rate();
+        assert!(rate > 0.0 && rate < 1.0, "{}", rate);
+
+        let mut feistel = DistinctSampler::new(1, DistinctMethod::Feistel, 7);
+        drain(&mut feistel);
+        assert_eq!(feistel.draws(), 1920);
+        assert_eq!(feistel.collisions(), 0);
+        assert_eq!(feistel.collision_rate(), 0.0);
+    }
+
+    #[test]
+    fn same_seed_gives_the_same_sequence() {
+        for method in [DistinctMethod::Rejection, DistinctMethod::Feistel] {
+            let mut a = DistinctSampler::new(2, method, 3);
+            let mut b = DistinctSampler::new(2, method, 3);
+            for _ in 0..100 {
+                assert_eq!(a.next_board(), b.next_board());
+            }
+        }
+    }
+}
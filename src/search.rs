@@ -1,8 +1,13 @@
 pub mod bfs;
 pub mod core;
+pub mod discs_auto;
 pub mod leaf_cache;
 pub mod move_ordering;
 pub mod parallel_dfs;
 pub mod parallel_gbfs;
+pub mod reachability_cache;
+pub mod reachable;
 pub mod reverse_common;
 pub mod search_fwd_par;
+pub mod sized;
+pub mod threads;
@@ -2,20 +2,31 @@ use std::cmp::Reverse;
 use std::collections::{BinaryHeap, HashSet};
 use std::fs::{self, File};
 use std::io::{self, BufReader, BufWriter, Error, ErrorKind, Read, Result, Seek, SeekFrom, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::{
     atomic::{AtomicBool, AtomicUsize, Ordering},
-    Arc,
+    mpsc::{sync_channel, Receiver, SyncSender},
+    Arc, Mutex,
 };
 use std::thread;
 
-use bytemuck;
 use clap::Parser;
 
-use crate::othello::{get_moves, Board};
-use crate::prunings::{occupancy::check_occupancy, seg3::check_seg3_more};
-use crate::search::core::{retrospective_flip, SearchResult};
+use crate::othello::{
+    flip, get_moves, has_move, validate_board, Board, BoardKey, Ruleset, CENTER_MASK,
+};
+use crate::prunings::passes_search_prunings;
+use crate::search::core::{leaf_discs, retrospective_flip, reverse_candidates, SearchResult};
+use crate::search::threads::ThreadConfig;
+use crate::verbosity::{DEBUG, INFO};
+use crate::{veprintln, vprintln};
+
+/// `Cfg::block_size` の既定値
+pub const DEFAULT_BLOCK_SIZE: usize = 1_000_000;
+/// `Cfg::discs` の既定値
+pub const DEFAULT_DISCS: usize = 10;
 
+/// BFS の設定。ライブラリから使う場合は `Cfg::builder` で組み立てる。
 #[derive(Debug, Clone, Parser)]
 #[command(name = "reverse_to_initial_bfs", version)]
 pub struct Cfg {
@@ -30,16 +41,17 @@ pub struct Cfg {
     #[arg(short = 'j', long, default_value_t = 0)]
     pub jobs: usize,
 
-    /// ログ詳細度
+    /// ログ詳細度（0: 結果のみ, 1: 情報, 2: デバッグ）
     #[arg(short, long, default_value_t = 0)]
     pub verbose: u8,
 
-    /// ブロックサイズ
-    #[arg(short = 'b', long, default_value_t = 1000000)]
+    /// ブロックサイズ（並列版では 1 ブロックの局面数の上限。実際の値は
+    /// 段ごとの局面数をスレッド数で割った量から決める）
+    #[arg(short = 'b', long, default_value_t = DEFAULT_BLOCK_SIZE)]
     pub block_size: usize,
 
     /// forwardとreverseで合流する石数
-    #[arg(short = 'd', long, default_value_t = 10)]
+    #[arg(short = 'd', long, default_value_t = DEFAULT_DISCS)]
     pub discs: usize,
 
     /// tmp_dir
@@ -49,15 +61,134 @@ pub struct Cfg {
     /// resume
     #[arg(short = 'r', long)]
     pub resume: bool,
+
+    /// 探索せずに段ごとの予定とファイルサイズの見積もりを表示して終わる
+    #[arg(long, conflicts_with = "resume")]
+    pub plan: bool,
+
+    /// 一時ファイル（`r_*.bin` / `b_*.bin`）を zstd で圧縮して書く
+    #[arg(long)]
+    pub compress: bool,
+
+    /// 並列版で、ある段のマージと次の段のブロック処理を重ねて実行する
+    #[arg(long)]
+    pub pipeline: bool,
+
+    /// 順方向探索の初期配置（standard / parallel / 中央4マスだけに石がある 64 セルの盤面）
+    #[arg(long, default_value = "standard")]
+    pub ruleset: Ruleset,
+}
+
+impl Cfg {
+    /// 入力ファイル `input` 以外をコマンドラインの既定値にした `CfgBuilder`
+    pub fn builder(input: impl Into<PathBuf>) -> CfgBuilder {
+        CfgBuilder {
+            cfg: Cfg {
+                input: input.into(),
+                out_dir: PathBuf::from("result"),
+                jobs: 0,
+                verbose: 0,
+                block_size: DEFAULT_BLOCK_SIZE,
+                discs: DEFAULT_DISCS,
+                tmp_dir: PathBuf::from("tmp"),
+                resume: false,
+                plan: false,
+                compress: false,
+                pipeline: false,
+                ruleset: Ruleset::standard(),
+            },
+        }
+    }
+}
+
+/// `Cfg` を組み立てる。`build` で値を検証する。
+#[derive(Debug, Clone)]
+pub struct CfgBuilder {
+    cfg: Cfg,
+}
+
+impl CfgBuilder {
+    pub fn out_dir(mut self, out_dir: impl Into<PathBuf>) -> Self {
+        self.cfg.out_dir = out_dir.into();
+        self
+    }
+
+    /// スレッド数（0 で自動）
+    pub fn jobs(mut self, jobs: usize) -> Self {
+        self.cfg.jobs = jobs;
+        self
+    }
+
+    pub fn verbose(mut self, verbose: u8) -> Self {
+        self.cfg.verbose = verbose;
+        self
+    }
+
+    pub fn block_size(mut self, block_size: usize) -> Self {
+        self.cfg.block_size = block_size;
+        self
+    }
+
+    pub fn discs(mut self, discs: usize) -> Self {
+        self.cfg.discs = discs;
+        self
+    }
+
+    pub fn tmp_dir(mut self, tmp_dir: impl Into<PathBuf>) -> Self {
+        self.cfg.tmp_dir = tmp_dir.into();
+        self
+    }
+
+    pub fn resume(mut self, resume: bool) -> Self {
+        self.cfg.resume = resume;
+        self
+    }
+
+    pub fn plan(mut self, plan: bool) -> Self {
+        self.cfg.plan = plan;
+        self
+    }
+
+    pub fn compress(mut self, compress: bool) -> Self {
+        self.cfg.compress = compress;
+        self
+    }
+
+    pub fn pipeline(mut self, pipeline: bool) -> Self {
+        self.cfg.pipeline = pipeline;
+        self
+    }
+
+    pub fn ruleset(mut self, ruleset: Ruleset) -> Self {
+        self.cfg.ruleset = ruleset;
+        self
+    }
+
+    /// `block_size` が 0、`discs` が 4 未満、または `resume` と `plan` の両方が指定されていれば
+    /// `ErrorKind::InvalidInput`
+    pub fn build(self) -> io::Result<Cfg> {
+        let cfg = self.cfg;
+        let invalid = |msg: String| Err(Error::new(ErrorKind::InvalidInput, msg));
+        if cfg.block_size == 0 {
+            return invalid("block_size must be > 0".to_string());
+        }
+        if cfg.discs < 4 {
+            return invalid(format!("discs must be at least 4, got {}", cfg.discs));
+        }
+        if cfg.resume && cfg.plan {
+            return invalid("resume and plan cannot be used together".to_string());
+        }
+        Ok(cfg)
+    }
 }
 
 fn process_board(
-    board: [u64; 2],
-    prev_boards: &mut HashSet<[u64; 2]>,
+    board: BoardKey,
+    prev_boards: &mut HashSet<BoardKey>,
     retroflips: &mut [u64; 10_000],
 ) {
-    let board: Board = Board::new(board[0], board[1]);
-    let mut b = board.opponent & !0x0000_0018_1800_0000u64;
+    let board = Board::from(board);
+    let mut b = reverse_candidates(board.opponent, CENTER_MASK);
     if b == 0 {
         return;
     }
@@ -76,24 +207,18 @@ fn process_board(
                 player: board.opponent ^ (flipped | (1u64 << index)),
                 opponent: board.player ^ flipped,
             };
-            let occupied = prev.player | prev.opponent;
-            //if !is_connected(occupied) {
-            //    continue;
-            //}
-            //if !check_seg3(occupied) {
-            //    continue;
-            //}
-            if !check_occupancy(occupied) {
+            if flip(index as usize, prev.player, prev.opponent) != flipped {
                 continue;
             }
-            if !check_seg3_more(prev.player, prev.opponent) {
+            if !passes_search_prunings(prev.player, prev.opponent) {
                 continue;
             }
-            let uni = prev.unique();
-            prev_boards.insert(uni);
-            if get_moves(prev.opponent, prev.player) == 0 {
-                let uni = Board::new(prev.opponent, prev.player).unique();
-                prev_boards.insert(uni);
+            if !has_move(prev.opponent, prev.player) {
+                let (uni, swapped) = prev.unique_with_swapped();
+                prev_boards.insert(BoardKey(uni));
+                prev_boards.insert(BoardKey(swapped));
+            } else {
+                prev_boards.insert(BoardKey(prev.unique()));
             }
         }
     }
@@ -101,187 +226,538 @@ fn process_board(
 
 fn process_bfs_block(
     num_disc: i32,
-    tmp_dir: &PathBuf,
+    tmp_dir: &Path,
     block_size: usize,
     block_number: usize,
+    compress: bool,
 ) -> Result<bool> {
-    let rfilename = format!("r_{}.bin", num_disc + 1);
-    let mut file = File::open(&tmp_dir.join(rfilename))?;
-    let meta = file.metadata()?;
-    let len = meta.len() as usize;
-
-    if len % 16 != 0 {
-        return Err(io::Error::new(
-            ErrorKind::InvalidData,
-            format!("file size {} is not a multiple of 16 bytes", len),
-        ));
-    }
-    let offset = block_size * block_number * 16;
+    let offset = block_size * block_number;
+    let (mut r, len) = open_records(&r_file_path(tmp_dir, num_disc + 1), offset)?;
     if offset >= len {
         return Err(io::Error::new(
             ErrorKind::InvalidData,
             format!(
-                "block_size {} x block_number {} is greater than file size {}",
+                "block_size {} x block_number {} is greater than record count {}",
                 block_size, block_number, len
             ),
         ));
     }
-    file.seek(SeekFrom::Start(offset as u64))?;
-    let mut r = BufReader::new(file);
-    let mut buf = [0u8; 16];
-    let nrecs = std::cmp::min(block_size, (len - offset) / 16);
-    let mut prev_boards: HashSet<[u64; 2]> = HashSet::new();
+    let nrecs = std::cmp::min(block_size, len - offset);
+    let mut prev_boards: HashSet<BoardKey> = HashSet::new();
     let mut retroflips: [u64; 10_000] = [0u64; 10_000];
     for _ in 0..nrecs {
-        r.read_exact(&mut buf)?;
-        let a = u64::from_ne_bytes(buf[0..8].try_into().unwrap());
-        let b = u64::from_ne_bytes(buf[8..16].try_into().unwrap());
-        process_board([a, b], &mut prev_boards, &mut retroflips);
+        let key = read_key(&mut r)?
+            .ok_or_else(|| Error::new(ErrorKind::UnexpectedEof, "record file ended early"))?;
+        process_board(key, &mut prev_boards, &mut retroflips);
     }
-    if prev_boards.len() == 0 {
+    write_bfs_block(num_disc, tmp_dir, block_number, prev_boards, compress)
+}
+
+/// 1 ブロック分の前駆局面をソートして `b_{num_disc}_{block_number}.bin` に書く。
+/// 空なら書かずに `false` を返す。
+fn write_bfs_block(
+    num_disc: i32,
+    tmp_dir: &Path,
+    block_number: usize,
+    prev_boards: HashSet<BoardKey>,
+    compress: bool,
+) -> Result<bool> {
+    if prev_boards.is_empty() {
         return Ok(false);
     }
-    let mut bvec: Vec<[u64; 2]> = prev_boards.into_iter().collect();
+    let mut bvec: Vec<BoardKey> = prev_boards.into_iter().collect();
     bvec.sort();
     //eprintln!("num_disc={}, count={}", num_disc, bvec.len());
-    let ofilename = format!("b_{}_{}.bin", num_disc, block_number);
-    let ofile = File::create(&tmp_dir.join(ofilename))?;
-    let mut w = BufWriter::new(ofile);
-    w.write_all(bytemuck::cast_slice(&bvec))?;
-    w.flush()?;
+    let mut w = RecordWriter::create(&b_file_path(tmp_dir, num_disc, block_number), compress)?;
+    w.write_records(&bvec)?;
+    w.finish()?;
     Ok(true)
 }
 
-/// 1レコード (=16バイト) をネイティブエンディアンのまま読み取る
-fn read_pair(reader: &mut BufReader<File>) -> io::Result<Option<(u64, u64)>> {
-    let mut buf = [0u8; 16];
+/// 圧縮した一時ファイルの 1 フレームに入れるレコード数
+const FRAME_RECORDS: usize = 1 << 16;
+/// 一時ファイルの zstd 圧縮レベル
+const ZSTD_LEVEL: i32 = 3;
+
+/// 圧縮した一時ファイル `path` の索引（`{path}.idx`）。
+/// 中身は「レコード数」と「各フレームの先頭のバイト位置」をネイティブエンディアンの u64 で並べたもの。
+fn index_path(path: &Path) -> PathBuf {
+    let mut s = path.as_os_str().to_owned();
+    s.push(".idx");
+    PathBuf::from(s)
+}
+
+/// 一時ファイルへのレコードの書き出し。
+///
+/// 圧縮しない場合は 16 バイトのレコードをそのまま並べる。圧縮する場合は `FRAME_RECORDS` 件ごとに
+/// 独立した zstd フレームにして、フレームの先頭位置を索引に書く。zstd のストリームは途中から
+/// 伸長できないので、ブロック単位の読み出し（`open_records`）は索引で目的のレコードを含む
+/// フレームまで移動し、そこから先頭の余りを読み捨てる。フレームを小さくするほど読み捨ては減るが
+/// 圧縮率は落ちる。
+struct RecordWriter {
+    out: BufWriter<File>,
+    /// 圧縮する場合の `(索引ファイル, 各フレームの先頭位置, 未圧縮のレコード)`
+    frames: Option<(PathBuf, Vec<u64>, Vec<u8>)>,
+    written: u64,
+    count: usize,
+    hash: u64,
+}
+
+impl RecordWriter {
+    fn create(path: &Path, compress: bool) -> io::Result<Self> {
+        let index = index_path(path);
+        match fs::remove_file(&index) {
+            Err(e) if e.kind() != ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+        Ok(RecordWriter {
+            out: BufWriter::new(File::create(path)?),
+            frames: compress.then(|| (index, Vec::new(), Vec::with_capacity(FRAME_RECORDS * 16))),
+            written: 0,
+            count: 0,
+            hash: FNV_OFFSET,
+        })
+    }
+
+    fn write_key(&mut self, key: BoardKey) -> io::Result<()> {
+        let record = key_to_record(key);
+        let bytes = &record[..];
+        self.hash = fnv1a(self.hash, bytes);
+        self.count += 1;
+        match &mut self.frames {
+            None => {
+                self.out.write_all(bytes)?;
+            }
+            Some((_, _, pending)) => {
+                pending.extend_from_slice(bytes);
+                if pending.len() == FRAME_RECORDS * 16 {
+                    self.flush_frame()?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn write_records(&mut self, boards: &[BoardKey]) -> io::Result<()> {
+        for &key in boards {
+            self.write_key(key)?;
+        }
+        Ok(())
+    }
+
+    fn flush_frame(&mut self) -> io::Result<()> {
+        let Some((_, offsets, pending)) = &mut self.frames else {
+            return Ok(());
+        };
+        if pending.is_empty() {
+            return Ok(());
+        }
+        let frame = zstd::bulk::compress(pending, ZSTD_LEVEL)?;
+        offsets.push(self.written);
+        self.out.write_all(&frame)?;
+        self.written += frame.len() as u64;
+        pending.clear();
+        Ok(())
+    }
+
+    /// 書き終えて `(レコード数, 未圧縮のレコード列のチェックサム)` を返す
+    fn finish(mut self) -> io::Result<(usize, u64)> {
+        self.flush_frame()?;
+        self.out.flush()?;
+        if let Some((index, offsets, _)) = &self.frames {
+            let words: Vec<u64> = std::iter::once(self.count as u64)
+                .chain(offsets.iter().copied())
+                .collect();
+            let bytes: Vec<u8> = words.iter().flat_map(|&w| word_to_bytes(w)).collect();
+            fs::write(index, bytes)?;
+        }
+        Ok((self.count, self.hash))
+    }
+}
+
+/// 一時ファイル `path` を `start` 件目のレコードから読むリーダと、ファイル全体のレコード数を返す。
+/// 索引（`{path}.idx`）があれば `RecordWriter` で圧縮したファイルとして読む。
+fn open_records(path: &Path, start: usize) -> io::Result<(Box<dyn Read + Send>, usize)> {
+    let mut file = File::open(path)?;
+    let index = match fs::read(index_path(path)) {
+        Ok(index) => index,
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            let len = file.metadata()?.len() as usize;
+            if !len.is_multiple_of(16) {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!("file size {} is not a multiple of 16 bytes", len),
+                ));
+            }
+            file.seek(SeekFrom::Start((start.min(len / 16) * 16) as u64))?;
+            return Ok((Box::new(BufReader::new(file)), len / 16));
+        }
+        Err(e) => return Err(e),
+    };
+    let words: Vec<u64> = index
+        .chunks_exact(8)
+        .map(|w| word_from_bytes(w.try_into().unwrap()))
+        .collect();
+    let count = words.first().copied().unwrap_or(0) as usize;
+    let offsets = words.get(1..).unwrap_or(&[]);
+    if index.len() % 8 != 0 || words.is_empty() || offsets.len() != count.div_ceil(FRAME_RECORDS) {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            format!("{}: malformed index", index_path(path).display()),
+        ));
+    }
+    if start >= count {
+        return Ok((Box::new(io::empty()), count));
+    }
+    file.seek(SeekFrom::Start(offsets[start / FRAME_RECORDS]))?;
+    let mut r = zstd::stream::read::Decoder::new(file)?;
+    let skip = ((start % FRAME_RECORDS) * 16) as u64;
+    if io::copy(&mut (&mut r).take(skip), &mut io::sink())? != skip {
+        return Err(Error::new(
+            ErrorKind::UnexpectedEof,
+            "record file ended early",
+        ));
+    }
+    Ok((Box::new(r), count))
+}
+
+/// レコードのバイト列。既定ではネイティブエンディアンのまま、`portable-records` feature では
+/// `Board::to_le_bytes` と同じリトルエンディアン。
+#[cfg(not(feature = "portable-records"))]
+fn key_to_record(key: BoardKey) -> [u8; 16] {
+    bytemuck::cast(key)
+}
+
+#[cfg(feature = "portable-records")]
+fn key_to_record(key: BoardKey) -> [u8; 16] {
+    Board::from(key).to_le_bytes()
+}
+
+/// `key_to_record` の逆
+#[cfg(not(feature = "portable-records"))]
+fn key_from_record(record: [u8; 16]) -> BoardKey {
+    bytemuck::cast(record)
+}
+
+#[cfg(feature = "portable-records")]
+fn key_from_record(record: [u8; 16]) -> BoardKey {
+    BoardKey::from(Board::from_le_bytes(record))
+}
+
+/// 圧縮ファイルの索引の 1 語（エンディアンはレコードと同じ）
+#[cfg(not(feature = "portable-records"))]
+fn word_to_bytes(word: u64) -> [u8; 8] {
+    word.to_ne_bytes()
+}
+
+#[cfg(feature = "portable-records")]
+fn word_to_bytes(word: u64) -> [u8; 8] {
+    word.to_le_bytes()
+}
+
+/// `word_to_bytes` の逆
+#[cfg(not(feature = "portable-records"))]
+fn word_from_bytes(bytes: [u8; 8]) -> u64 {
+    u64::from_ne_bytes(bytes)
+}
+
+#[cfg(feature = "portable-records")]
+fn word_from_bytes(bytes: [u8; 8]) -> u64 {
+    u64::from_le_bytes(bytes)
+}
+
+/// 1レコード (=16バイト) を読み取る（バイト列の解釈は `key_from_record`）
+fn read_key(reader: &mut impl Read) -> io::Result<Option<BoardKey>> {
+    let mut record = [0u8; 16];
     // まず 1 バイト読んで EOF 判定を分ける（partial read 対策）
-    match reader.read(&mut buf[..1])? {
+    match reader.read(&mut record[..1])? {
         0 => return Ok(None), // EOF
         1 => {
             // すでに 1 バイト読んだので残り 15 バイト読む
-            reader.read_exact(&mut buf[1..])?;
+            reader.read_exact(&mut record[1..])?;
         }
         _ => unreachable!(),
     }
-    let p = u64::from_ne_bytes(buf[0..8].try_into().unwrap());
-    let o = u64::from_ne_bytes(buf[8..16].try_into().unwrap());
-    Ok(Some((p, o)))
+    Ok(Some(key_from_record(record)))
+}
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// FNV-1a（64bit）で `bytes` を `hash` に畳み込む
+fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for &b in bytes {
+        hash ^= b as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
+fn r_file_path(tmp_dir: &Path, num_disc: i32) -> PathBuf {
+    tmp_dir.join(format!("r_{}.bin", num_disc))
+}
+
+fn b_file_path(tmp_dir: &Path, num_disc: i32, block_number: usize) -> PathBuf {
+    tmp_dir.join(format!("b_{}_{}.bin", num_disc, block_number))
+}
+
+/// `r_{num_disc}.bin` のチェックサムを置くファイル（`r_{num_disc}.bin.sum`）
+fn r_sum_path(tmp_dir: &Path, num_disc: i32) -> PathBuf {
+    tmp_dir.join(format!("r_{}.bin.sum", num_disc))
+}
+
+/// `r_*.bin` を書き始める前にチェックサムのファイルへ置く印
+const INCOMPLETE_MARK: &str = "incomplete\n";
+
+/// `r_{num_disc}.bin` を書き始める前に、チェックサムのファイルを「書き込み中」の印で置き換える
+fn mark_incomplete(tmp_dir: &Path, num_disc: i32) -> io::Result<()> {
+    fs::write(r_sum_path(tmp_dir, num_disc), INCOMPLETE_MARK)
+}
+
+/// 「レコード数 チェックサム」を書き出す。`r_*.bin` を書き終えてから呼ぶこと
+/// （途中で止まった場合は印が残るので再開時に検出できる）。
+fn write_checksum(tmp_dir: &Path, num_disc: i32, count: usize, hash: u64) -> io::Result<()> {
+    fs::write(
+        r_sum_path(tmp_dir, num_disc),
+        format!("{} {:016x}\n", count, hash),
+    )
 }
 
-/// 1レコードを書き出し（ネイティブエンディアンのまま）
-fn write_pair(writer: &mut BufWriter<File>, p: u64, o: u64) -> io::Result<()> {
-    writer.write_all(&p.to_ne_bytes())?;
-    writer.write_all(&o.to_ne_bytes())?;
+/// `r_{num_disc}.bin` を `boards` で上書きし、チェックサムも書き出す
+fn write_r_file(
+    tmp_dir: &Path,
+    num_disc: i32,
+    boards: &[BoardKey],
+    compress: bool,
+) -> io::Result<()> {
+    mark_incomplete(tmp_dir, num_disc)?;
+    let mut w = RecordWriter::create(&r_file_path(tmp_dir, num_disc), compress)?;
+    w.write_records(boards)?;
+    let (count, hash) = w.finish()?;
+    write_checksum(tmp_dir, num_disc, count, hash)
+}
+
+/// `r_{num_disc}.bin` がチェックサムと一致するか確かめる。
+/// 前回の実行が書き込み途中で止まった（書き込み中の印が残っている・大きさや内容が合わない）場合は
+/// `InvalidData` を返す。圧縮したファイルは伸長した内容で確かめる。
+/// チェックサムのファイルそのものが無いのはチェックサムを記録する前の版が書いた `r_*.bin` なので、
+/// 警告して確かめずに使う。
+pub fn verify_r_file(tmp_dir: &Path, num_disc: i32) -> io::Result<()> {
+    let path = r_file_path(tmp_dir, num_disc);
+    let corrupted = |reason: String| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "{}: {} (the file may be incomplete)",
+                path.display(),
+                reason
+            ),
+        )
+    };
+    let sum = match fs::read_to_string(r_sum_path(tmp_dir, num_disc)) {
+        Ok(sum) => sum,
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            eprintln!(
+                "warning: {}: no checksum file; using it unverified (written by an older version?)",
+                path.display()
+            );
+            return Ok(());
+        }
+        Err(e) => return Err(e),
+    };
+    if sum == INCOMPLETE_MARK {
+        return Err(corrupted(
+            "the previous run stopped while writing it".to_string(),
+        ));
+    }
+    let mut it = sum.split_whitespace();
+    let (Some(count), Some(hash)) = (it.next(), it.next()) else {
+        return Err(corrupted("malformed checksum file".to_string()));
+    };
+    let count: usize = count
+        .parse()
+        .map_err(|_| corrupted("malformed checksum file".to_string()))?;
+    let hash = u64::from_str_radix(hash, 16)
+        .map_err(|_| corrupted("malformed checksum file".to_string()))?;
+
+    let (mut r, len) = match open_records(&path, 0) {
+        Ok(opened) => opened,
+        Err(e) if e.kind() == ErrorKind::InvalidData => return Err(corrupted(e.to_string())),
+        Err(e) => return Err(e),
+    };
+    if len != count {
+        return Err(corrupted(format!(
+            "{} record(s) on disk do not match {} record(s)",
+            len, count
+        )));
+    }
+    let mut actual = FNV_OFFSET;
+    let mut bytes = 0usize;
+    let mut buf = vec![0u8; 1 << 16];
+    loop {
+        let n = match r.read(&mut buf) {
+            Ok(n) => n,
+            // 圧縮したファイルが途中で切れている
+            Err(e) => return Err(corrupted(e.to_string())),
+        };
+        if n == 0 {
+            break;
+        }
+        actual = fnv1a(actual, &buf[..n]);
+        bytes += n;
+    }
+    if bytes != count * 16 || actual != hash {
+        return Err(corrupted("checksum mismatch".to_string()));
+    }
     Ok(())
 }
 
 /// ソート済みの bin ファイル群（ネイティブエンディアンの [u64;2] 連続）を、
 /// 重複を除去しながらマージして output に書き出す。
-/// 返り値は「書き出したユニーク件数」。
-pub fn merge_sorted_bins(inputs: &[PathBuf], output: &PathBuf) -> io::Result<usize> {
+/// 返り値は「書き出したユニーク件数」。入力は圧縮されていてもよく、出力は圧縮しない。
+pub fn merge_sorted_bins(inputs: &[PathBuf], output: &Path) -> io::Result<usize> {
+    merge_sorted_bins_hashed(inputs, output, false).map(|(written, _)| written)
+}
+
+/// `merge_sorted_bins` と同じだが、書き出した内容のチェックサムも返す
+fn merge_sorted_bins_hashed(
+    inputs: &[PathBuf],
+    output: &Path,
+    compress: bool,
+) -> io::Result<(usize, u64)> {
+    merge_sorted_bins_with(inputs, output, compress, |_| Ok(()))
+}
+
+/// `merge_sorted_bins_hashed` と同じだが、書き出した局面を 1 つずつ `on_key` にも渡す
+fn merge_sorted_bins_with(
+    inputs: &[PathBuf],
+    output: &Path,
+    compress: bool,
+    mut on_key: impl FnMut(BoardKey) -> io::Result<()>,
+) -> io::Result<(usize, u64)> {
     if inputs.is_empty() {
         return Err(Error::new(ErrorKind::InvalidInput, "no input files"));
     }
 
     // 各入力ファイルのリーダを用意
-    let mut readers: Vec<BufReader<File>> = Vec::with_capacity(inputs.len());
+    let mut readers: Vec<Box<dyn Read + Send>> = Vec::with_capacity(inputs.len());
     for p in inputs {
-        readers.push(BufReader::new(File::open(p)?));
+        readers.push(open_records(p, 0)?.0);
     }
 
-    // min-heap: (key=(p,o), file_idx)
-    let mut heap: BinaryHeap<Reverse<((u64, u64), usize)>> = BinaryHeap::new();
+    // min-heap: (key, file_idx)
+    let mut heap: BinaryHeap<Reverse<(BoardKey, usize)>> = BinaryHeap::new();
 
     // 各ファイルの先頭をヒープに積む
     for (i, r) in readers.iter_mut().enumerate() {
-        if let Some((p, o)) = read_pair(r)? {
-            heap.push(Reverse(((p, o), i)));
+        if let Some(key) = read_key(r)? {
+            heap.push(Reverse((key, i)));
         }
     }
 
-    let outfile = File::create(output)?;
-    let mut writer = BufWriter::new(outfile);
-
-    let mut written: usize = 0;
-    let mut last: Option<(u64, u64)> = None;
+    let mut writer = RecordWriter::create(output, compress)?;
+    let mut last: Option<BoardKey> = None;
 
-    while let Some(Reverse(((p, o), idx))) = heap.pop() {
+    while let Some(Reverse((key, idx))) = heap.pop() {
         // 重複排除
-        if last.map_or(true, |x| x != (p, o)) {
-            write_pair(&mut writer, p, o)?;
-            last = Some((p, o));
-            written += 1;
+        if last != Some(key) {
+            writer.write_key(key)?;
+            on_key(key)?;
+            last = Some(key);
         }
 
         // 取り出したファイルから次レコードを補充
-        if let Some((np, no)) = read_pair(&mut readers[idx])? {
-            heap.push(Reverse(((np, no), idx)));
+        if let Some(next) = read_key(&mut readers[idx])? {
+            heap.push(Reverse((next, idx)));
         }
     }
 
-    writer.flush()?;
-    Ok(written)
+    writer.finish()
 }
 
-fn merge_files(num_disc: i32, tmp_dir: &PathBuf, block_count: usize) -> Result<usize> {
-    let mut inputs: Vec<PathBuf> = vec![];
-    for i in 0..block_count {
-        inputs.push(tmp_dir.join(format!("b_{}_{}.bin", num_disc, i)));
+fn merge_files(num_disc: i32, tmp_dir: &Path, block_count: usize, compress: bool) -> Result<usize> {
+    // 前駆局面が 1 つも無かったブロックはファイルを書かない（`write_bfs_block`）
+    let blocks: Vec<usize> = (0..block_count)
+        .filter(|&i| b_file_path(tmp_dir, num_disc, i).exists())
+        .collect();
+    merge_blocks(num_disc, tmp_dir, &blocks, compress, |_| Ok(()))
+}
+
+/// `merge_files` と同じだが、マージするブロック番号を `blocks` で与え、書き出した局面を
+/// 1 つずつ `on_key` にも渡す。`blocks` が空なら空の `r_{num_disc}.bin` を書く。
+fn merge_blocks(
+    num_disc: i32,
+    tmp_dir: &Path,
+    blocks: &[usize],
+    compress: bool,
+    on_key: impl FnMut(BoardKey) -> io::Result<()>,
+) -> Result<usize> {
+    if blocks.is_empty() {
+        write_r_file(tmp_dir, num_disc, &[], compress)?;
+        veprintln!(INFO, "{} : {}", num_disc, 0);
+        return Ok(0);
     }
-    let outfile = tmp_dir.join(format!("r_{}.bin", num_disc));
-    let count = merge_sorted_bins(&inputs, &outfile)?;
-    for i in 0..inputs.len() {
-        fs::remove_file(&inputs[i])?;
+    let inputs: Vec<PathBuf> = blocks
+        .iter()
+        .map(|&i| b_file_path(tmp_dir, num_disc, i))
+        .collect();
+    let outfile = r_file_path(tmp_dir, num_disc);
+    mark_incomplete(tmp_dir, num_disc)?;
+    let (count, hash) = merge_sorted_bins_with(&inputs, &outfile, compress, on_key)?;
+    write_checksum(tmp_dir, num_disc, count, hash)?;
+    for input in &inputs {
+        fs::remove_file(input)?;
+        if compress {
+            fs::remove_file(index_path(input))?;
+        }
     }
-    eprintln!("{} : {}", num_disc, count);
+    veprintln!(INFO, "{} : {}", num_disc, count);
     Ok(count)
 }
 
-fn process_bfs_seq(num_disc: i32, tmp_dir: &PathBuf, block_size: usize) -> Result<bool> {
-    let rfilename = format!("r_{}.bin", num_disc + 1);
-    let file = File::open(&tmp_dir.join(rfilename))?;
-    let meta = file.metadata()?;
-    let len = meta.len() as usize;
-
-    if len % 16 != 0 {
-        return Err(io::Error::new(
-            ErrorKind::InvalidData,
-            format!("file size {} is not a multiple of 16 bytes", len),
-        ));
-    }
-    let all_count = len / 16;
+fn process_bfs_seq(
+    num_disc: i32,
+    tmp_dir: &Path,
+    block_size: usize,
+    compress: bool,
+) -> Result<usize> {
+    verify_r_file(tmp_dir, num_disc + 1)?;
+    let all_count = open_records(&r_file_path(tmp_dir, num_disc + 1), 0)?.1;
     let block_count = (all_count + block_size - 1) / block_size;
     for i in 0..block_count {
-        process_bfs_block(num_disc, tmp_dir, block_size, i)?;
+        process_bfs_block(num_disc, tmp_dir, block_size, i, compress)?;
     }
-    let len = merge_files(num_disc, tmp_dir, block_count)?;
-    if len == 0 {
-        return Ok(false);
-    }
-    Ok(true)
+    merge_files(num_disc, tmp_dir, block_count, compress)
 }
 
-pub fn process_bfs_par(num_disc: i32, tmp_dir: &PathBuf, num_threads: usize) -> io::Result<bool> {
-    let rfilename = format!("r_{}.bin", num_disc + 1);
-    let file = File::open(&tmp_dir.join(rfilename))?;
-    let len = file.metadata()?.len() as usize;
-
-    if len % 16 != 0 {
-        return Err(io::Error::new(
-            ErrorKind::InvalidData,
-            format!("file size {} is not a multiple of 16 bytes", len),
-        ));
-    }
-
-    let all_count = len / 16;
-    let block_size = std::cmp::min(5000000, std::cmp::max(1024, all_count / num_threads / 10));
+/// `r_{num_disc+1}.bin` から1手戻した局面を並列に列挙して `r_{num_disc}.bin` に書き出し、その局面数を返す。
+///
+/// ブロックの大きさはその段の局面数の 1/(10 × スレッド数) を目安に (最低 1024 局面)、
+/// `max_block_size` を上限として決める。`compress` なら書き出すファイルを zstd で圧縮する
+/// （読む側は圧縮の有無を索引ファイルの有無で判断する）。
+pub fn process_bfs_par(
+    num_disc: i32,
+    tmp_dir: &Path,
+    num_threads: usize,
+    max_block_size: usize,
+    compress: bool,
+) -> io::Result<usize> {
+    verify_r_file(tmp_dir, num_disc + 1)?;
+    let all_count = open_records(&r_file_path(tmp_dir, num_disc + 1), 0)?.1;
+    let block_size = par_block_size(all_count, num_threads, max_block_size);
+    vprintln!(
+        DEBUG,
+        "num_disc={}, block_size={}, block_count={}",
+        num_disc,
+        block_size,
+        all_count.div_ceil(block_size)
+    );
     let block_count = (all_count + block_size - 1) / block_size;
 
     // --- 並列実行（動的スケジューリング） ---
     let next = Arc::new(AtomicUsize::new(0)); // 次に配る block index
     let cancel = Arc::new(AtomicBool::new(false)); // エラー検知で新規受付を止める
-    let tdir = Arc::new(tmp_dir.clone());
+    let tdir = Arc::new(tmp_dir.to_path_buf());
 
     let mut handles = Vec::with_capacity(num_threads);
     for _ in 0..num_threads {
@@ -298,7 +774,7 @@ pub fn process_bfs_par(num_disc: i32, tmp_dir: &PathBuf, num_threads: usize) ->
                 if i >= block_count {
                     break;
                 }
-                if let Err(e) = process_bfs_block(num_disc, &tdir, block_size, i) {
+                if let Err(e) = process_bfs_block(num_disc, &tdir, block_size, i, compress) {
                     // 以降の配布を止める
                     cancel.store(true, Ordering::Relaxed);
                     return Err(e);
@@ -331,18 +807,274 @@ pub fn process_bfs_par(num_disc: i32, tmp_dir: &PathBuf, num_threads: usize) ->
     }
 
     // --- マージ ---
-    let out_len = merge_files(num_disc, &tdir, block_count)?;
-    if out_len == 0 {
-        return Ok(false);
+    merge_files(num_disc, &tdir, block_count, compress)
+}
+
+/// 並列版の 1 ブロックの局面数。段の局面数 `count` の 1/(10 × スレッド数) を目安に
+/// (最低 1024 局面)、`max_block_size` を上限とする。
+fn par_block_size(count: usize, num_threads: usize, max_block_size: usize) -> usize {
+    std::cmp::min(
+        max_block_size.max(1),
+        std::cmp::max(1024, count / num_threads / 10),
+    )
+}
+
+/// パイプライン版でワーカーに渡す 1 ブロック（ブロック番号と、そのブロックの局面）
+type Block = (usize, Vec<BoardKey>);
+
+/// 局面を `block_size` 個ずつまとめ、ブロック番号を振って有界チャネルに送る
+struct BlockSender {
+    tx: SyncSender<Block>,
+    block_size: usize,
+    next: usize,
+    buf: Vec<BoardKey>,
+}
+
+impl BlockSender {
+    fn new(tx: SyncSender<Block>, block_size: usize) -> Self {
+        BlockSender {
+            tx,
+            block_size,
+            next: 0,
+            buf: Vec::with_capacity(block_size),
+        }
+    }
+
+    fn push(&mut self, key: BoardKey) -> io::Result<()> {
+        self.buf.push(key);
+        if self.buf.len() >= self.block_size {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// 溜まっている局面を 1 ブロックとして送る。ワーカーが全て終了していれば `Other`。
+    fn flush(&mut self) -> io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        let block = std::mem::replace(&mut self.buf, Vec::with_capacity(self.block_size));
+        self.tx
+            .send((self.next, block))
+            .map_err(|_| Error::other("all BFS workers exited"))?;
+        self.next += 1;
+        Ok(())
     }
-    Ok(true)
 }
-use std::num::NonZeroUsize;
 
-fn available_threads() -> usize {
-    std::thread::available_parallelism()
-        .map(NonZeroUsize::get)
-        .unwrap_or(1) // 取得失敗時のフォールバック
+/// `num_disc` 段のブロックを処理するワーカーを `num_threads` 本起動する。各ワーカーは `rx` から
+/// 受け取ったブロックの前駆局面を `b_{num_disc}_{i}.bin` に書き、書いたブロック番号を返す。
+fn spawn_block_workers(
+    num_disc: i32,
+    tmp_dir: &Path,
+    num_threads: usize,
+    compress: bool,
+    rx: Receiver<Block>,
+) -> Vec<thread::JoinHandle<io::Result<Vec<usize>>>> {
+    let rx = Arc::new(Mutex::new(rx));
+    (0..num_threads)
+        .map(|_| {
+            let rx = Arc::clone(&rx);
+            let tdir = tmp_dir.to_path_buf();
+            thread::spawn(move || -> io::Result<Vec<usize>> {
+                let mut written = Vec::new();
+                let mut retroflips: [u64; 10_000] = [0u64; 10_000];
+                loop {
+                    // 受信している間だけロックを持つ
+                    let msg = rx.lock().unwrap().recv();
+                    let Ok((i, keys)) = msg else {
+                        break;
+                    };
+                    let mut prev_boards: HashSet<BoardKey> = HashSet::new();
+                    for key in keys {
+                        process_board(key, &mut prev_boards, &mut retroflips);
+                    }
+                    if write_bfs_block(num_disc, &tdir, i, prev_boards, compress)? {
+                        written.push(i);
+                    }
+                }
+                Ok(written)
+            })
+        })
+        .collect()
+}
+
+/// ワーカーを待ち、書かれたブロック番号を昇順で返す。最初のエラー（panic を含む）を返す。
+fn join_block_workers(
+    handles: Vec<thread::JoinHandle<io::Result<Vec<usize>>>>,
+) -> io::Result<Vec<usize>> {
+    let mut blocks = Vec::new();
+    let mut first_err: Option<io::Error> = None;
+    for h in handles {
+        match h.join() {
+            Ok(Ok(written)) => blocks.extend(written),
+            Ok(Err(e)) => {
+                first_err.get_or_insert(e);
+            }
+            Err(_) => {
+                first_err.get_or_insert(io::Error::other("worker thread panicked"));
+            }
+        }
+    }
+    if let Some(e) = first_err {
+        return Err(e);
+    }
+    blocks.sort_unstable();
+    Ok(blocks)
+}
+
+/// `r_{num_disc}.bin` から `r_{discs}.bin` までを、ある段のマージと次の段のブロック処理を
+/// 重ねて作る（`Cfg::pipeline`）。段ごとの局面数を `stats` に積み、途中の段で 0 になったら
+/// `false` を返す。
+///
+/// `r_{s+1}.bin` をマージで書き出しながら、確定した局面を `block_size` 個ずつ有界チャネルで
+/// `s` 段のワーカーに渡す。マージは整列済みで重複の無い順に局面を出すので、各ブロックは
+/// `r_{s+1}.bin` の連続した区間になり、書き出す `r_*.bin` は段ごとに `process_bfs_par` を
+/// 呼んだ場合と同じになる。`s` 段のマージは `s` 段の全ブロックを書き終えてから始める。
+/// マージ中の段は局面数がまだ分からないので、ブロックの大きさは 1 つ前の段の局面数から決める。
+fn process_bfs_pipeline(
+    num_disc: i32,
+    discs: i32,
+    tmp_dir: &Path,
+    num_threads: usize,
+    max_block_size: usize,
+    compress: bool,
+    stats: &mut BfsStats,
+) -> io::Result<bool> {
+    if discs >= num_disc {
+        return Ok(true);
+    }
+    verify_r_file(tmp_dir, num_disc)?;
+    let mut input_count = open_records(&r_file_path(tmp_dir, num_disc), 0)?.1;
+    let mut prev_blocks: Vec<usize> = Vec::new();
+    for s in (discs..num_disc).rev() {
+        let block_size = par_block_size(input_count, num_threads, max_block_size);
+        vprintln!(DEBUG, "num_disc={}, block_size={}", s, block_size);
+        let (tx, rx) = sync_channel(num_threads * 2);
+        let handles = spawn_block_workers(s, tmp_dir, num_threads, compress, rx);
+        let mut sender = BlockSender::new(tx, block_size);
+        let fed = if s == num_disc - 1 {
+            // 最初の段は入力局面の r ファイルから読む
+            (|| {
+                let mut r = open_records(&r_file_path(tmp_dir, num_disc), 0)?.0;
+                while let Some(key) = read_key(&mut r)? {
+                    sender.push(key)?;
+                }
+                sender.flush()?;
+                Ok(input_count)
+            })()
+        } else {
+            merge_blocks(s + 1, tmp_dir, &prev_blocks, compress, |key| {
+                sender.push(key)
+            })
+            .and_then(|count| sender.flush().map(|()| count))
+        };
+        // 送信側を閉じてワーカーを終わらせる（ワーカー側のエラーを優先して返す）
+        drop(sender);
+        let blocks = join_block_workers(handles)?;
+        let count = fed?;
+        if s != num_disc - 1 {
+            stats.push(s + 1, count);
+            if count == 0 {
+                return Ok(false);
+            }
+            input_count = count;
+        }
+        prev_blocks = blocks;
+    }
+    let count = merge_blocks(discs, tmp_dir, &prev_blocks, compress, |_| Ok(()))?;
+    stats.push(discs, count);
+    Ok(count != 0)
+}
+
+/// BFS の探索結果と、石数ごとの逆方向局面数。
+#[derive(Debug, Clone)]
+pub struct BfsStats {
+    /// `(石数, その石数で列挙された局面数)` を石数の降順に並べたもの。先頭は入力局面の段。
+    pub per_level: Vec<(i32, usize)>,
+    /// `per_level` の局面数の合計
+    pub total: usize,
+    pub result: SearchResult,
+    /// `Found` のとき、リーフテーブルと一致した局面（正規形）。`bfs_join_chain` で
+    /// 入力局面までの連鎖を復元できる。
+    pub leaf: Option<[u64; 2]>,
+}
+
+impl BfsStats {
+    fn new(num_disc: i32, count: usize) -> Self {
+        BfsStats {
+            per_level: vec![(num_disc, count)],
+            total: count,
+            result: SearchResult::Unknown,
+            leaf: None,
+        }
+    }
+
+    fn push(&mut self, num_disc: i32, count: usize) {
+        self.per_level.push((num_disc, count));
+        self.total += count;
+    }
+
+    fn finish(mut self, result: SearchResult) -> Self {
+        self.result = result;
+        self
+    }
+
+    fn found(mut self, leaf: [u64; 2]) -> Self {
+        self.leaf = Some(leaf);
+        self.finish(SearchResult::Found)
+    }
+}
+
+/// `Found` になった探索の一時ファイル（`r_{discs}.bin` 〜 `r_{num_disc}.bin`）から、
+/// 一致したリーフ `leaf` を起点に 1 手ずつ進めて入力局面の段まで辿った局面の列を返す。
+///
+/// 各段では、直前の局面（またはそのパス後の局面）から 1 手打った局面と正規形が一致する
+/// レコードを `r_{s+1}.bin` から探し、ファイルに書かれている向きのまま並べる。
+/// 先頭は `leaf`、末尾は `r_{num_disc}.bin` のレコード。どの段でも見つからなければ `InvalidData`。
+pub fn bfs_join_chain(
+    tmp_dir: &Path,
+    leaf: [u64; 2],
+    discs: i32,
+    num_disc: i32,
+) -> Result<Vec<[u64; 2]>> {
+    let mut chain = vec![leaf];
+    let mut current = leaf;
+    for s in discs..num_disc {
+        let mut children = HashSet::new();
+        for [player, opponent] in [current, [current[1], current[0]]] {
+            let mut moves = get_moves(player, opponent);
+            while moves != 0 {
+                let pos = moves.trailing_zeros() as usize;
+                moves &= moves - 1;
+                let flipped = flip(pos, player, opponent);
+                children.insert(
+                    Board::new(opponent ^ flipped, player ^ (flipped | (1u64 << pos))).unique(),
+                );
+            }
+        }
+        verify_r_file(tmp_dir, s + 1)?;
+        let mut r = open_records(&r_file_path(tmp_dir, s + 1), 0)?.0;
+        let mut next = None;
+        while let Some(key) = read_key(&mut r)? {
+            if children.contains(&Board::from(key).unique()) {
+                next = Some(key.0);
+                break;
+            }
+        }
+        current = next.ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "no position in r_{}.bin is one move after {}",
+                    s + 1,
+                    Board::new(current[0], current[1]).to_string()
+                ),
+            )
+        })?;
+        chain.push(current);
+    }
+    Ok(chain)
 }
 
 pub fn retrospective_search_bfs_par_resume(
@@ -350,45 +1082,47 @@ pub fn retrospective_search_bfs_par_resume(
     num_disc: i32,
     discs: i32,
     leafnode: &std::collections::HashSet<[u64; 2]>,
-) -> Result<SearchResult> {
+) -> Result<BfsStats> {
     let tmp_dir: &PathBuf = &cfg.tmp_dir;
-    let mut jobs = cfg.jobs;
-    if jobs == 0 {
-        jobs = available_threads();
+    let jobs = match cfg.jobs {
+        0 => ThreadConfig::global(),
+        n => ThreadConfig::new(Some(n)),
     }
-    println!("parallelism = {}", jobs);
-    for s in (discs..(num_disc as i32)).rev() {
-        let v = process_bfs_par(s, tmp_dir, jobs)?;
-        if !v {
-            return Ok(SearchResult::NotFound);
+    .num_threads();
+    vprintln!(INFO, "parallelism = {}", jobs);
+    verify_r_file(tmp_dir, num_disc)?;
+    let start = open_records(&r_file_path(tmp_dir, num_disc), 0)?.1;
+    let mut stats = BfsStats::new(num_disc, start);
+    if cfg.pipeline {
+        let tmp_dir = tmp_dir.as_path();
+        if !process_bfs_pipeline(
+            num_disc,
+            discs,
+            tmp_dir,
+            jobs,
+            cfg.block_size,
+            cfg.compress,
+            &mut stats,
+        )? {
+            return Ok(stats.finish(SearchResult::NotFound));
+        }
+    } else {
+        for s in (discs..num_disc).rev() {
+            let count = process_bfs_par(s, tmp_dir, jobs, cfg.block_size, cfg.compress)?;
+            stats.push(s, count);
+            if count == 0 {
+                return Ok(stats.finish(SearchResult::NotFound));
+            }
         }
     }
-    let rfilename = format!("r_{}.bin", discs);
-    let file = File::open(&tmp_dir.join(rfilename))?;
-    let meta = file.metadata()?;
-    let len = meta.len();
-
-    if len % 16 != 0 {
-        return Err(io::Error::new(
-            ErrorKind::InvalidData,
-            format!("file size {} is not a multiple of 16 bytes", len),
-        ));
-    }
-    let mut r = BufReader::new(file);
-    let mut buf = [0u8; 16];
-    let nrecs = len / 16;
-    //let mut prev_boards: HashSet<[u64; 2]> = HashSet::new();
-    //let mut retroflips: [u64; 10_000] = [0u64; 10_000];
-    for _ in 0..nrecs {
-        r.read_exact(&mut buf)?;
-        let a = u64::from_ne_bytes(buf[0..8].try_into().unwrap());
-        let b = u64::from_ne_bytes(buf[8..16].try_into().unwrap());
-        let uni = [a, b];
+    verify_r_file(tmp_dir, discs)?;
+    let mut r = open_records(&r_file_path(tmp_dir, discs), 0)?.0;
+    while let Some(BoardKey(uni)) = read_key(&mut r)? {
         if leafnode.contains(&uni) {
-            return Ok(SearchResult::Found);
+            return Ok(stats.found(uni));
         }
     }
-    Ok(SearchResult::NotFound)
+    Ok(stats.finish(SearchResult::NotFound))
 }
 
 //--------------------------------------
@@ -398,33 +1132,34 @@ pub fn retrospective_search_bfs_par(
     board: &Board,
     discs: i32,
     leafnode: &std::collections::HashSet<[u64; 2]>,
-) -> Result<SearchResult> {
+) -> Result<BfsStats> {
+    // 不正な盤面は ErrorKind::InvalidInput
+    validate_board(board)?;
     let uni = board.unique();
     let num_disc = board.popcount() as usize;
     let tmp_dir: &PathBuf = &cfg.tmp_dir;
-    // let block_size = cfg.block_size;
-
-    if (num_disc as i32) <= discs {
+    let discs = leaf_discs(discs);
+    if (num_disc as i32) < discs {
+        return Ok(BfsStats::new(num_disc as i32, 1).finish(SearchResult::Unknown));
+    }
+    if num_disc as i32 == discs {
+        let stats = BfsStats::new(num_disc as i32, 1);
         return if leafnode.contains(&uni) {
-            println!("info: found unique board in leafnodes:");
-            println!("unique player = {}", uni[0]);
-            println!("unique opponent = {}", uni[1]);
-            println!("board player = {}", board.player);
-            println!("board opponent = {}", board.opponent);
-            Ok(SearchResult::Found)
+            vprintln!(DEBUG, "info: found unique board in leafnodes:");
+            vprintln!(DEBUG, "unique player = {}", uni[0]);
+            vprintln!(DEBUG, "unique opponent = {}", uni[1]);
+            vprintln!(DEBUG, "board player = {}", board.player);
+            vprintln!(DEBUG, "board opponent = {}", board.opponent);
+            Ok(stats.found(uni))
         } else {
-            Ok(SearchResult::NotFound)
+            Ok(stats.finish(SearchResult::NotFound))
         };
     }
-    let mut boards: Vec<[u64; 2]> = vec![[board.player, board.opponent]];
-    if get_moves(board.opponent, board.player) == 0 {
-        boards.push([board.opponent, board.player]);
+    let mut boards = vec![BoardKey::from(*board)];
+    if !has_move(board.opponent, board.player) {
+        boards.push(BoardKey([board.opponent, board.player]));
     }
-    let rfilename = format!("r_{}.bin", num_disc);
-    let rfile = File::create(&tmp_dir.join(rfilename))?;
-    let mut w = BufWriter::new(rfile);
-    w.write_all(bytemuck::cast_slice(&boards))?;
-    w.flush()?;
+    write_r_file(tmp_dir, num_disc as i32, &boards, cfg.compress)?;
     retrospective_search_bfs_par_resume(cfg, num_disc as i32, discs, leafnode)
 }
 
@@ -435,103 +1170,70 @@ pub fn retrospective_search_bfs_seq(
     board: &Board,
     discs: i32,
     leafnode: &std::collections::HashSet<[u64; 2]>,
-) -> Result<SearchResult> {
+) -> Result<BfsStats> {
+    validate_board(board)?;
     let uni = board.unique();
     let num_disc = board.popcount() as usize;
     let tmp_dir: &PathBuf = &cfg.tmp_dir;
     let block_size = cfg.block_size;
 
-    if (num_disc as i32) <= discs {
+    let discs = leaf_discs(discs);
+    if (num_disc as i32) < discs {
+        return Ok(BfsStats::new(num_disc as i32, 1).finish(SearchResult::Unknown));
+    }
+    if num_disc as i32 == discs {
+        let stats = BfsStats::new(num_disc as i32, 1);
         return if leafnode.contains(&uni) {
-            println!("info: found unique board in leafnodes:");
-            println!("unique player = {}", uni[0]);
-            println!("unique opponent = {}", uni[1]);
-            println!("board player = {}", board.player);
-            println!("board opponent = {}", board.opponent);
-            Ok(SearchResult::Found)
+            vprintln!(DEBUG, "info: found unique board in leafnodes:");
+            vprintln!(DEBUG, "unique player = {}", uni[0]);
+            vprintln!(DEBUG, "unique opponent = {}", uni[1]);
+            vprintln!(DEBUG, "board player = {}", board.player);
+            vprintln!(DEBUG, "board opponent = {}", board.opponent);
+            Ok(stats.found(uni))
         } else {
-            Ok(SearchResult::NotFound)
+            Ok(stats.finish(SearchResult::NotFound))
         };
     }
-    let mut boards: Vec<[u64; 2]> = vec![[board.player, board.opponent]];
-    if get_moves(board.opponent, board.player) == 0 {
-        boards.push([board.opponent, board.player]);
+    let mut boards = vec![BoardKey::from(*board)];
+    if !has_move(board.opponent, board.player) {
+        boards.push(BoardKey([board.opponent, board.player]));
     }
-    let rfilename = format!("r_{}.bin", num_disc);
-    let rfile = File::create(&tmp_dir.join(rfilename))?;
-    let mut w = BufWriter::new(rfile);
-    w.write_all(bytemuck::cast_slice(&boards))?;
-    w.flush()?;
+    write_r_file(tmp_dir, num_disc as i32, &boards, cfg.compress)?;
+    let mut stats = BfsStats::new(num_disc as i32, boards.len());
     for s in (discs..(num_disc as i32)).rev() {
-        let v = process_bfs_seq(s, tmp_dir, block_size)?;
-        if !v {
-            return Ok(SearchResult::NotFound);
+        let count = process_bfs_seq(s, tmp_dir, block_size, cfg.compress)?;
+        stats.push(s, count);
+        if count == 0 {
+            return Ok(stats.finish(SearchResult::NotFound));
         }
     }
-    let rfilename = format!("r_{}.bin", discs);
-    let file = File::open(&tmp_dir.join(rfilename))?;
-    let meta = file.metadata()?;
-    let len = meta.len();
-
-    if len % 16 != 0 {
-        return Err(io::Error::new(
-            ErrorKind::InvalidData,
-            format!("file size {} is not a multiple of 16 bytes", len),
-        ));
-    }
-    let mut r = BufReader::new(file);
-    let mut buf = [0u8; 16];
-    let nrecs = len / 16;
-    //let mut prev_boards: HashSet<[u64; 2]> = HashSet::new();
-    //let mut retroflips: [u64; 10_000] = [0u64; 10_000];
-    for _ in 0..nrecs {
-        r.read_exact(&mut buf)?;
-        let a = u64::from_ne_bytes(buf[0..8].try_into().unwrap());
-        let b = u64::from_ne_bytes(buf[8..16].try_into().unwrap());
-        let uni = [a, b];
+    verify_r_file(tmp_dir, discs)?;
+    let mut r = open_records(&r_file_path(tmp_dir, discs), 0)?.0;
+    while let Some(BoardKey(uni)) = read_key(&mut r)? {
         if leafnode.contains(&uni) {
-            return Ok(SearchResult::Found);
+            return Ok(stats.found(uni));
         }
     }
-    Ok(SearchResult::NotFound)
+    Ok(stats.finish(SearchResult::NotFound))
 }
 
-fn process_bfs(num_disc: i32, tmp_dir: &PathBuf) -> Result<bool> {
-    let rfilename = format!("r_{}.bin", num_disc + 1);
-    let file = File::open(&tmp_dir.join(rfilename))?;
-    let meta = file.metadata()?;
-    let len = meta.len();
-
-    if len % 16 != 0 {
-        return Err(io::Error::new(
-            ErrorKind::InvalidData,
-            format!("file size {} is not a multiple of 16 bytes", len),
-        ));
-    }
-    let mut r = BufReader::new(file);
-    let mut buf = [0u8; 16];
-    let nrecs = len / 16;
-    println!("nrecs={}", nrecs);
-    let mut prev_boards: HashSet<[u64; 2]> = HashSet::new();
+fn process_bfs(num_disc: i32, tmp_dir: &Path, compress: bool) -> Result<usize> {
+    verify_r_file(tmp_dir, num_disc + 1)?;
+    let (mut r, nrecs) = open_records(&r_file_path(tmp_dir, num_disc + 1), 0)?;
+    vprintln!(DEBUG, "nrecs={}", nrecs);
+    let mut prev_boards: HashSet<BoardKey> = HashSet::new();
     let mut retroflips: [u64; 10_000] = [0u64; 10_000];
-    for _ in 0..nrecs {
-        r.read_exact(&mut buf)?;
-        let a = u64::from_ne_bytes(buf[0..8].try_into().unwrap());
-        let b = u64::from_ne_bytes(buf[8..16].try_into().unwrap());
-        process_board([a, b], &mut prev_boards, &mut retroflips);
+    while let Some(key) = read_key(&mut r)? {
+        process_board(key, &mut prev_boards, &mut retroflips);
     }
     if prev_boards.len() == 0 {
-        return Ok(false);
+        return Ok(0);
     }
-    let mut bvec: Vec<[u64; 2]> = prev_boards.into_iter().collect();
+    let mut bvec: Vec<BoardKey> = prev_boards.into_iter().collect();
     bvec.sort();
     // eprintln!("num_disc={}, count={}", num_disc, bvec.len());
-    let ofilename = format!("r_{}.bin", num_disc);
-    let ofile = File::create(&tmp_dir.join(ofilename))?;
-    let mut w = BufWriter::new(ofile);
-    w.write_all(bytemuck::cast_slice(&bvec))?;
-    w.flush()?;
-    Ok(true)
+    write_r_file(tmp_dir, num_disc, &bvec, compress)?;
+    Ok(bvec.len())
 }
 
 //--------------------------------------
@@ -541,62 +1243,484 @@ pub fn retrospective_search_bfs(
     board: &Board,
     discs: i32,
     leafnode: &std::collections::HashSet<[u64; 2]>,
-) -> Result<SearchResult> {
+) -> Result<BfsStats> {
+    // 不正な盤面は ErrorKind::InvalidInput
+    validate_board(board)?;
     let uni = board.unique();
     let num_disc = board.popcount() as usize;
     let tmp_dir: &PathBuf = &cfg.tmp_dir;
 
-    if (num_disc as i32) <= discs {
+    let discs = leaf_discs(discs);
+    if (num_disc as i32) < discs {
+        return Ok(BfsStats::new(num_disc as i32, 1).finish(SearchResult::Unknown));
+    }
+    if num_disc as i32 == discs {
+        let stats = BfsStats::new(num_disc as i32, 1);
         return if leafnode.contains(&uni) {
-            println!("info: found unique board in leafnodes:");
-            println!("unique player = {}", uni[0]);
-            println!("unique opponent = {}", uni[1]);
-            println!("board player = {}", board.player);
-            println!("board opponent = {}", board.opponent);
-            Ok(SearchResult::Found)
+            vprintln!(DEBUG, "info: found unique board in leafnodes:");
+            vprintln!(DEBUG, "unique player = {}", uni[0]);
+            vprintln!(DEBUG, "unique opponent = {}", uni[1]);
+            vprintln!(DEBUG, "board player = {}", board.player);
+            vprintln!(DEBUG, "board opponent = {}", board.opponent);
+            Ok(stats.found(uni))
         } else {
-            Ok(SearchResult::NotFound)
+            Ok(stats.finish(SearchResult::NotFound))
         };
     }
-    let mut boards: Vec<[u64; 2]> = vec![[board.player, board.opponent]];
-    if get_moves(board.opponent, board.player) == 0 {
-        boards.push([board.opponent, board.player]);
+    let mut boards = vec![BoardKey::from(*board)];
+    if !has_move(board.opponent, board.player) {
+        boards.push(BoardKey([board.opponent, board.player]));
     }
-    let rfilename = format!("r_{}.bin", num_disc);
-    let rfile = File::create(&tmp_dir.join(rfilename))?;
-    let mut w = BufWriter::new(rfile);
-    w.write_all(bytemuck::cast_slice(&boards))?;
-    w.flush()?;
+    write_r_file(tmp_dir, num_disc as i32, &boards, cfg.compress)?;
+    let mut stats = BfsStats::new(num_disc as i32, boards.len());
     for s in (discs..(num_disc as i32)).rev() {
-        let v = process_bfs(s, tmp_dir)?;
-        if !v {
-            return Ok(SearchResult::NotFound);
+        let count = process_bfs(s, tmp_dir, cfg.compress)?;
+        stats.push(s, count);
+        if count == 0 {
+            return Ok(stats.finish(SearchResult::NotFound));
         }
     }
-    let rfilename = format!("r_{}.bin", discs);
-    let file = File::open(&tmp_dir.join(rfilename))?;
-    let meta = file.metadata()?;
-    let len = meta.len();
+    verify_r_file(tmp_dir, discs)?;
+    let mut r = open_records(&r_file_path(tmp_dir, discs), 0)?.0;
+    while let Some(BoardKey(uni)) = read_key(&mut r)? {
+        if leafnode.contains(&uni) {
+            return Ok(stats.found(uni));
+        }
+    }
+    Ok(stats.finish(SearchResult::NotFound))
+}
 
-    if len % 16 != 0 {
-        return Err(io::Error::new(
-            ErrorKind::InvalidData,
-            format!("file size {} is not a multiple of 16 bytes", len),
-        ));
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::othello::BoardValidation;
+    use crate::search::core::search;
+    use crate::test_util::scratch_dir;
+
+    fn leaf_table(discs: i32) -> std::collections::HashSet<[u64; 2]> {
+        let mut searched = std::collections::HashSet::new();
+        let mut leafnode = std::collections::HashSet::new();
+        search(&Board::initial(), &mut searched, &mut leafnode, discs);
+        leafnode
     }
-    let mut r = BufReader::new(file);
-    let mut buf = [0u8; 16];
-    let nrecs = len / 16;
-    //let mut prev_boards: HashSet<[u64; 2]> = HashSet::new();
-    //let mut retroflips: [u64; 10_000] = [0u64; 10_000];
-    for _ in 0..nrecs {
-        r.read_exact(&mut buf)?;
-        let a = u64::from_ne_bytes(buf[0..8].try_into().unwrap());
-        let b = u64::from_ne_bytes(buf[8..16].try_into().unwrap());
-        let uni = [a, b];
-        if leafnode.contains(&uni) {
-            return Ok(SearchResult::Found);
+
+    #[test]
+    fn truncated_level_file_is_rejected_on_resume() {
+        // f5 d6 c3 d3 c4 f4 f6（11 石）
+        let board = Board::play_sequence(&[37, 43, 18, 19, 26, 29, 45]).unwrap();
+        let num_disc = board.popcount() as i32;
+        let leafnode = leaf_table(8);
+        for compress in [false, true] {
+            let tmp = scratch_dir(if compress {
+                "truncated_zst"
+            } else {
+                "truncated"
+            });
+            let cfg = Cfg::builder("unused")
+                .tmp_dir(&tmp)
+                .jobs(1)
+                .compress(compress)
+                .build()
+                .unwrap();
+            let path = r_file_path(&tmp, num_disc);
+
+            // 書き終えたファイルからは再開できる
+            write_r_file(&tmp, num_disc, &[BoardKey::from(board)], compress).unwrap();
+            let stats = retrospective_search_bfs_par_resume(&cfg, num_disc, 8, &leafnode).unwrap();
+            assert_eq!(stats.result, SearchResult::Found);
+
+            // 書き込み途中で止まったファイル
+            write_r_file(&tmp, num_disc, &[BoardKey::from(board)], compress).unwrap();
+            let len = fs::metadata(&path).unwrap().len();
+            File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_len(len - 3)
+                .unwrap();
+            let err =
+                retrospective_search_bfs_par_resume(&cfg, num_disc, 8, &leafnode).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidData, "{}", err);
+
+            // チェックサムを書く前に止まった
+            write_r_file(&tmp, num_disc, &[BoardKey::from(board)], compress).unwrap();
+            mark_incomplete(&tmp, num_disc).unwrap();
+            let err =
+                retrospective_search_bfs_par_resume(&cfg, num_disc, 8, &leafnode).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidData, "{}", err);
+
+            // チェックサムを記録する前の版が書いた（チェックサムのファイルが無い）ものは確かめずに使う
+            fs::remove_file(r_sum_path(&tmp, num_disc)).unwrap();
+            let stats = retrospective_search_bfs_par_resume(&cfg, num_disc, 8, &leafnode).unwrap();
+            assert_eq!(stats.result, SearchResult::Found);
+
+            fs::remove_dir_all(&tmp).unwrap();
+        }
+    }
+
+    #[test]
+    fn modified_record_fails_the_checksum() {
+        let tmp = scratch_dir("modified");
+        let boards = [BoardKey([1, 2]), BoardKey([3, 4])];
+        write_r_file(&tmp, 6, &boards, false).unwrap();
+        verify_r_file(&tmp, 6).unwrap();
+        let path = r_file_path(&tmp, 6);
+        let mut bytes = fs::read(&path).unwrap();
+        bytes[20] ^= 1;
+        fs::write(&path, bytes).unwrap();
+        let err = verify_r_file(&tmp, 6).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert!(err.to_string().contains("checksum mismatch"), "{}", err);
+        fs::remove_dir_all(&tmp).unwrap();
+    }
+
+    #[test]
+    fn every_bfs_variant_rejects_malformed_boards() {
+        let initial = Board::initial();
+        let overlap = Board::new(
+            initial.player | (1u64 << 19),
+            initial.opponent | (1u64 << 19),
+        );
+        let missing_center = Board::new(initial.player, initial.opponent & !(1u64 << 27));
+        let leafnode = leaf_table(6);
+        let tmp = scratch_dir("malformed");
+        let cfg = Cfg::builder("unused")
+            .tmp_dir(&tmp)
+            .jobs(1)
+            .build()
+            .unwrap();
+        type Variant = fn(&Cfg, &Board, i32, &HashSet<[u64; 2]>) -> Result<BfsStats>;
+        let variants: [(&str, Variant); 3] = [
+            ("bfs", retrospective_search_bfs),
+            ("bfs_seq", retrospective_search_bfs_seq),
+            ("bfs_par", retrospective_search_bfs_par),
+        ];
+        for (name, search) in variants {
+            for (board, expected) in [
+                (overlap, BoardValidation::Overlap),
+                (missing_center, BoardValidation::MissingCenter),
+            ] {
+                let err = search(&cfg, &board, 6, &leafnode).unwrap_err();
+                assert_eq!(err.kind(), ErrorKind::InvalidInput, "{}", name);
+                assert_eq!(err.to_string(), expected.to_string(), "{}", name);
+            }
+        }
+        // 中間ファイルは書かれない
+        let written = fs::read_dir(&tmp).unwrap().count();
+        fs::remove_dir_all(&tmp).unwrap();
+        assert_eq!(written, 0);
+    }
+
+    #[test]
+    fn per_level_counts_cover_every_level_down_to_the_threshold() {
+        // f5 d6 c3 d3 c4 f4 f6（11 石）
+        let board = Board::play_sequence(&[37, 43, 18, 19, 26, 29, 45]).unwrap();
+        let leafnode = leaf_table(8);
+        let tmp = scratch_dir("per_level");
+        let cfg = Cfg::builder("unused")
+            .tmp_dir(&tmp)
+            .jobs(1)
+            .block_size(16)
+            .build()
+            .unwrap();
+        let pipelined = Cfg {
+            pipeline: true,
+            ..cfg.clone()
+        };
+        let runs = [
+            retrospective_search_bfs(&cfg, &board, 8, &leafnode).unwrap(),
+            retrospective_search_bfs_seq(&cfg, &board, 8, &leafnode).unwrap(),
+            retrospective_search_bfs_par(&cfg, &board, 8, &leafnode).unwrap(),
+            retrospective_search_bfs_par(&pipelined, &board, 8, &leafnode).unwrap(),
+        ];
+        fs::remove_dir_all(&tmp).unwrap();
+
+        let levels: Vec<i32> = runs[0].per_level.iter().map(|&(d, _)| d).collect();
+        assert_eq!(levels, [11, 10, 9, 8]);
+        for stats in &runs {
+            assert_eq!(stats.result, SearchResult::Found);
+            assert_eq!(stats.per_level, runs[0].per_level);
+            assert!(stats.per_level.iter().all(|&(_, count)| count > 0));
+            assert_eq!(
+                stats.total,
+                stats
+                    .per_level
+                    .iter()
+                    .map(|&(_, count)| count)
+                    .sum::<usize>()
+            );
+            assert!(leafnode.contains(&stats.leaf.unwrap()));
+        }
+    }
+
+    #[test]
+    fn found_leaf_is_in_the_frontier_file_and_the_leaf_table() {
+        // f5 d6 c3 d3 c4 f4 f6（11 石）
+        let board = Board::play_sequence(&[37, 43, 18, 19, 26, 29, 45]).unwrap();
+        let leafnode = leaf_table(8);
+        let tmp = scratch_dir("found_leaf");
+        let cfg = Cfg::builder("unused")
+            .tmp_dir(&tmp)
+            .jobs(1)
+            .build()
+            .unwrap();
+        let stats = retrospective_search_bfs_par(&cfg, &board, 8, &leafnode).unwrap();
+        assert_eq!(stats.result, SearchResult::Found);
+        let leaf = stats.leaf.unwrap();
+        assert!(leafnode.contains(&leaf));
+
+        let mut frontier = vec![];
+        let mut r = open_records(&r_file_path(&tmp, 8), 0).unwrap().0;
+        while let Some(BoardKey(key)) = read_key(&mut r).unwrap() {
+            frontier.push(key);
+        }
+        assert!(frontier.contains(&leaf));
+
+        // リーフから入力局面の段まで 1 手ずつ辿れる
+        let chain = bfs_join_chain(&tmp, leaf, 8, 11).unwrap();
+        fs::remove_dir_all(&tmp).unwrap();
+        assert_eq!(chain.len(), 4);
+        assert_eq!(chain[0], leaf);
+        assert_eq!(Board::from(BoardKey(chain[3])).unique(), board.unique());
+        for (i, key) in chain.iter().enumerate() {
+            assert_eq!(
+                Board::from(BoardKey(*key)).popcount() as usize,
+                8 + i,
+                "chain[{}]",
+                i
+            );
+        }
+    }
+
+    #[test]
+    fn small_block_size_splits_a_level_into_more_blocks() {
+        assert_eq!(par_block_size(10_000_000, 4, DEFAULT_BLOCK_SIZE), 250_000);
+        assert_eq!(par_block_size(10_000_000, 4, 1000), 1000);
+        assert_eq!(par_block_size(5000, 4, DEFAULT_BLOCK_SIZE), 1024);
+        assert_eq!(par_block_size(5000, 4, 0), 1);
+
+        // 10 石の全局面（正規形）を 1 段として、1 手戻した 9 石の段を作る
+        let mut level: Vec<BoardKey> = leaf_table(10).into_iter().map(BoardKey).collect();
+        level.sort();
+        let mut merged = vec![];
+        let mut block_files = vec![];
+        for max_block_size in [DEFAULT_BLOCK_SIZE, 100] {
+            let tmp = scratch_dir(&format!("blocks_{}", max_block_size));
+            write_r_file(&tmp, 10, &level, false).unwrap();
+            let block_size = par_block_size(level.len(), 1, max_block_size);
+            let block_count = level.len().div_ceil(block_size);
+            for i in 0..block_count {
+                process_bfs_block(9, &tmp, block_size, i, false).unwrap();
+            }
+            block_files.push(
+                fs::read_dir(&tmp)
+                    .unwrap()
+                    .filter(|e| {
+                        let name = e.as_ref().unwrap().file_name();
+                        name.to_string_lossy().starts_with("b_9_")
+                    })
+                    .count(),
+            );
+            merge_files(9, &tmp, block_count, false).unwrap();
+            merged.push(fs::read(r_file_path(&tmp, 9)).unwrap());
+            // 並列版の入口も同じ段を作る
+            write_r_file(&tmp, 10, &level, false).unwrap();
+            let count = process_bfs_par(9, &tmp, 1, max_block_size, false).unwrap();
+            assert_eq!(count * 16, merged.last().unwrap().len());
+            assert_eq!(
+                &fs::read(r_file_path(&tmp, 9)).unwrap(),
+                merged.last().unwrap()
+            );
+            fs::remove_dir_all(&tmp).unwrap();
+        }
+        assert_eq!(block_files[0], level.len().div_ceil(1024));
+        assert_eq!(block_files[1], level.len().div_ceil(100));
+        assert!(block_files[1] > block_files[0]);
+        assert_eq!(merged[0], merged[1]);
+    }
+
+    /// `path` の `start` 件目以降のレコード
+    fn read_keys_from(path: &Path, start: usize) -> Vec<BoardKey> {
+        let mut r = open_records(path, start).unwrap().0;
+        let mut keys = vec![];
+        while let Some(key) = read_key(&mut r).unwrap() {
+            keys.push(key);
+        }
+        keys
+    }
+
+    #[test]
+    fn compressed_level_matches_the_uncompressed_one() {
+        // f5 d6 c3 d3 c4 f4 f6（11 石）から 2 段戻す。ブロックを小さくして複数ブロックを併合させる
+        let board = Board::play_sequence(&[37, 43, 18, 19, 26, 29, 45]).unwrap();
+        let mut levels = vec![];
+        for compress in [false, true] {
+            let tmp = scratch_dir(if compress { "level_zst" } else { "level" });
+            write_r_file(&tmp, 11, &[BoardKey::from(board)], compress).unwrap();
+            let count10 = process_bfs_seq(10, &tmp, 4, compress).unwrap();
+            let count9 = process_bfs_seq(9, &tmp, 4, compress).unwrap();
+            assert_eq!(index_path(&r_file_path(&tmp, 9)).exists(), compress);
+            verify_r_file(&tmp, 9).unwrap();
+            levels.push((
+                count10,
+                count9,
+                read_keys_from(&r_file_path(&tmp, 9), 0),
+                fs::read(r_sum_path(&tmp, 9)).unwrap(),
+            ));
+            fs::remove_dir_all(&tmp).unwrap();
+        }
+        assert!(levels[0].0 > 4);
+        assert_eq!(levels[0].2.len(), levels[0].1);
+        assert_eq!(levels[1], levels[0]);
+    }
+
+    #[test]
+    fn compressed_records_can_be_read_from_any_offset_across_frames() {
+        let tmp = scratch_dir("frames");
+        let keys: Vec<BoardKey> = (0..2 * FRAME_RECORDS as u64 + 5)
+            .map(|i| BoardKey([i, i.wrapping_mul(0x9E37_79B9_7F4A_7C15)]))
+            .collect();
+        let raw = tmp.join("raw.bin");
+        let zst = tmp.join("zst.bin");
+        for (path, compress) in [(&raw, false), (&zst, true)] {
+            let mut w = RecordWriter::create(path, compress).unwrap();
+            w.write_records(&keys).unwrap();
+            assert_eq!(w.finish().unwrap().0, keys.len());
+        }
+        assert!(fs::metadata(&zst).unwrap().len() < fs::metadata(&raw).unwrap().len());
+        for start in [
+            0,
+            1,
+            FRAME_RECORDS - 1,
+            FRAME_RECORDS,
+            FRAME_RECORDS + 7,
+            keys.len() - 1,
+            keys.len(),
+        ] {
+            assert_eq!(open_records(&zst, start).unwrap().1, keys.len());
+            let from_zst = read_keys_from(&zst, start);
+            assert_eq!(from_zst, read_keys_from(&raw, start), "start = {}", start);
+            assert_eq!(from_zst, keys[start..], "start = {}", start);
+        }
+        fs::remove_dir_all(&tmp).unwrap();
+    }
+
+    #[test]
+    fn builder_defaults_match_the_command_line_defaults() {
+        let parsed = Cfg::try_parse_from(["reverse_to_initial_bfs", "input.txt"]).unwrap();
+        let built = Cfg::builder("input.txt").build().unwrap();
+        assert_eq!(format!("{:?}", built), format!("{:?}", parsed));
+
+        let parsed = Cfg::try_parse_from([
+            "reverse_to_initial_bfs",
+            "input.txt",
+            "-o",
+            "out",
+            "-j",
+            "3",
+            "-b",
+            "16",
+            "-d",
+            "8",
+            "-t",
+            "scratch",
+            "--compress",
+            "--pipeline",
+        ])
+        .unwrap();
+        let built = Cfg::builder("input.txt")
+            .out_dir("out")
+            .jobs(3)
+            .block_size(16)
+            .discs(8)
+            .tmp_dir("scratch")
+            .compress(true)
+            .pipeline(true)
+            .build()
+            .unwrap();
+        assert_eq!(format!("{:?}", built), format!("{:?}", parsed));
+    }
+
+    #[test]
+    fn builder_rejects_invalid_settings() {
+        for builder in [
+            Cfg::builder("input.txt").block_size(0),
+            Cfg::builder("input.txt").discs(3),
+            Cfg::builder("input.txt").resume(true).plan(true),
+        ] {
+            let err = builder.build().unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidInput, "{}", err);
+        }
+        assert!(Cfg::builder("input.txt").discs(4).build().is_ok());
+    }
+
+    #[test]
+    fn records_round_trip_through_their_byte_layout() {
+        let key = BoardKey([0x0102_0304_0506_0708, 0x1112_1314_1516_1718]);
+        let record = key_to_record(key);
+        assert_eq!(key_from_record(record), key);
+        // portable-records では機械によらず Board::to_le_bytes と同じ並び
+        if cfg!(feature = "portable-records") || cfg!(target_endian = "little") {
+            assert_eq!(record, Board::from(key).to_le_bytes());
+        }
+        for word in [0, 1, u64::MAX, 0x0102_0304_0506_0708] {
+            assert_eq!(word_from_bytes(word_to_bytes(word)), word);
+        }
+    }
+
+    #[test]
+    fn pipelined_levels_are_identical_to_the_unpipelined_ones() {
+        // f5 d6 c3 d3 c4 f4 f6（11 石）
+        let board = Board::play_sequence(&[37, 43, 18, 19, 26, 29, 45]).unwrap();
+        let leafnode = leaf_table(8);
+        for compress in [false, true] {
+            let run = |name: &str, pipeline| {
+                let tmp = scratch_dir(name);
+                // ブロックを小さくして、1 段が複数のブロックに分かれるようにする（前駆局面の無いブロックも出る）
+                let cfg = Cfg::builder("unused")
+                    .tmp_dir(&tmp)
+                    .jobs(2)
+                    .block_size(4)
+                    .compress(compress)
+                    .pipeline(pipeline)
+                    .build()
+                    .unwrap();
+                let stats = retrospective_search_bfs_par(&cfg, &board, 8, &leafnode).unwrap();
+                let mut files: Vec<(String, Vec<u8>)> = fs::read_dir(&tmp)
+                    .unwrap()
+                    .map(|e| e.unwrap().path())
+                    .filter(|path| {
+                        path.file_name()
+                            .unwrap()
+                            .to_string_lossy()
+                            .starts_with("r_")
+                    })
+                    .map(|path| {
+                        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+                        (name, fs::read(&path).unwrap())
+                    })
+                    .collect();
+                files.sort();
+                fs::remove_dir_all(&tmp).unwrap();
+                (stats, files)
+            };
+            let (plain, plain_files) = run("unpipelined", false);
+            let (pipelined, pipelined_files) = run("pipelined", true);
+            assert_eq!(plain.result, SearchResult::Found);
+            assert!(plain.per_level.iter().any(|&(_, count)| count > 4));
+            assert_eq!(pipelined.result, plain.result);
+            assert_eq!(pipelined.per_level, plain.per_level);
+            assert!(!plain_files.is_empty());
+            assert_eq!(
+                pipelined_files
+                    .iter()
+                    .map(|(name, _)| name)
+                    .collect::<Vec<_>>(),
+                plain_files.iter().map(|(name, _)| name).collect::<Vec<_>>(),
+                "compress = {}",
+                compress
+            );
+            assert!(pipelined_files == plain_files, "compress = {}", compress);
         }
     }
-    Ok(SearchResult::NotFound)
 }
@@ -1,9 +1,19 @@
-use crate::othello::{flip, get_moves, Board};
+use crate::othello::{
+    flip, get_moves, has_move, validate_board, Board, BoardKey, BoardValidation, Direction,
+    CENTER_MASK,
+};
+use crate::prunings::connectivity::is_connected;
 use crate::prunings::occupancy::check_occupancy;
+use crate::prunings::passes_search_prunings;
 use crate::prunings::seg3::check_seg3_more;
+use crate::search::leaf_cache::LeafCache;
+use crate::search::reachability_cache::{ReachabilityKey, ReachabilityStore};
+use crate::verbosity::{DEBUG, INFO};
+use crate::vprintln;
 
 use std::cmp::min;
 use std::collections::HashSet;
+use std::time::Instant;
 
 /// Tri-state result for limited search.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -13,10 +23,41 @@ pub enum SearchResult {
     Unknown, // node limit exceeded or resource constraint
 }
 
+/// Why the root board was judged `NotFound`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotFoundReason {
+    /// occupied squares are not 8-connected to the center
+    Connectivity,
+    /// rejected by `check_occupancy`
+    Occupancy,
+    /// rejected by `check_seg3_more`
+    Seg3,
+    /// passed the root filters, but the reverse tree was exhausted
+    Exhausted,
+}
+
+/// ルート局面が `NotFound` になった理由を分類する。
+/// 非連結な盤面は占有チェックでも落ちるが、より具体的な `Connectivity` として返す。
+pub fn root_not_found_reason(board: &Board, discs: i32) -> NotFoundReason {
+    if board.popcount() as i32 <= discs {
+        return NotFoundReason::Exhausted;
+    }
+    let occupied = board.player | board.opponent;
+    if !is_connected(occupied) {
+        NotFoundReason::Connectivity
+    } else if !check_occupancy(occupied) {
+        NotFoundReason::Occupancy
+    } else if !check_seg3_more(board.player, board.opponent) {
+        NotFoundReason::Seg3
+    } else {
+        NotFoundReason::Exhausted
+    }
+}
+
 pub struct Btable {
     cache_size: usize,
-    table: Vec<[u64; 2]>,
-    cache: HashSet<[u64; 2]>,
+    table: Vec<BoardKey>,
+    cache: HashSet<BoardKey>,
 }
 
 impl Btable {
@@ -36,6 +77,7 @@ impl Btable {
         ans
     }
     pub fn insert(&mut self, uni: [u64; 2]) -> bool {
+        let uni = BoardKey(uni);
         if self.cache.contains(&uni) {
             return false;
         }
@@ -48,12 +90,12 @@ impl Btable {
                 self.cache.clear();
                 return true;
             }
-            let mut c2v: Vec<[u64; 2]> = self.cache.iter().map(|x| *x).collect();
+            let mut c2v: Vec<BoardKey> = self.cache.iter().copied().collect();
             self.cache.clear();
             c2v.sort();
             let mut i = self.table.len();
             let mut j = c2v.len();
-            self.table.resize(i + j, [0u64; 2]);
+            self.table.resize(i + j, BoardKey::default());
             for k in (0..(i + j)).rev() {
                 if j == 0 || (i > 0 && self.table[i - 1] >= c2v[j - 1]) {
                     self.table[k] = self.table[i - 1];
@@ -66,8 +108,74 @@ impl Btable {
         }
         return true;
     }
+
+    /// `mem_budget` バイトに収まる `(table_size, cache_size)` を、`sample` の逆方向探索を
+    /// ノード数 `TUNE_NODE_LIMIT` で打ち切って時間を測り、最も速かったものに決める。
+    ///
+    /// `table_size` は表に溜められる局面数で、これを超えるとキャッシュを捨てることになるので
+    /// 予算からキャッシュの分を引いた残りを全て表に回す。速さを左右するのはキャッシュから表への
+    /// マージの頻度なので、候補は `cache_size` だけを変える（計測時の表は打ち切りまでの局面数分だけ確保する）。
+    /// `leafnode` は `discs` で作った順方向探索のリーフ表。
+    pub fn tune(
+        sample: &Board,
+        discs: i32,
+        leafnode: &HashSet<[u64; 2]>,
+        mem_budget: usize,
+    ) -> (usize, usize) {
+        let table_size = |cache_size: usize| {
+            mem_budget.saturating_sub(cache_size * BTABLE_CACHE_ENTRY_BYTES) / BTABLE_ENTRY_BYTES
+        };
+        // キャッシュには予算の半分までを使う
+        let mut candidates: Vec<usize> = (10..=24)
+            .step_by(2)
+            .map(|k| 1usize << k)
+            .filter(|&c| c * BTABLE_CACHE_ENTRY_BYTES <= mem_budget / 2)
+            .collect();
+        if candidates.is_empty() {
+            candidates.push((mem_budget / 2 / BTABLE_CACHE_ENTRY_BYTES).max(1));
+        }
+
+        let mut best = (table_size(candidates[0]), candidates[0]);
+        let mut best_time = None;
+        let mut retroflips: Vec<[u64; 10_000]> = vec![];
+        for cache_size in candidates {
+            let mut table = Btable::new(table_size(cache_size).min(TUNE_NODE_LIMIT), cache_size);
+            let mut node_count = 0;
+            let start = Instant::now();
+            let _ = retrospective_search(
+                sample,
+                false,
+                discs,
+                leafnode,
+                &mut table,
+                &mut retroflips,
+                &mut node_count,
+                TUNE_NODE_LIMIT,
+            );
+            let elapsed = start.elapsed();
+            vprintln!(
+                DEBUG,
+                "info: Btable::tune: cache_size = {}, nodes = {}, elapsed = {:?}",
+                cache_size,
+                node_count,
+                elapsed
+            );
+            if best_time.is_none_or(|t| elapsed < t) {
+                best = (table_size(cache_size), cache_size);
+                best_time = Some(elapsed);
+            }
+        }
+        best
+    }
 }
 
+/// `Btable` の表 1 要素のバイト数
+pub const BTABLE_ENTRY_BYTES: usize = core::mem::size_of::<BoardKey>();
+/// `Btable` のキャッシュ（`HashSet`）1 要素あたりの見積もりバイト数（空きスロットと制御バイトを含む）
+pub const BTABLE_CACHE_ENTRY_BYTES: usize = 2 * BTABLE_ENTRY_BYTES + 1;
+/// `Btable::tune` の計測で探索を打ち切るノード数
+pub const TUNE_NODE_LIMIT: usize = 200_000;
+
 #[allow(dead_code)]
 fn mask_to_moves(m: u64) -> String {
     let mut ans: Vec<String> = vec!["[".to_string()];
@@ -102,36 +210,67 @@ pub fn search(
     leafnode: &mut HashSet<[u64; 2]>,
     discs: i32,
 ) {
+    search_bounded(board, searched, leafnode, discs, usize::MAX);
+}
+
+/// 順方向探索の進捗を表示する間隔（登録局面数）
+const FORWARD_REPORT_INTERVAL: usize = 1 << 20;
+
+/// `search` に登録局面数の上限を付けた版。
+/// `searched` と `leafnode` の合計が `max_nodes` を超えた時点で打ち切り、`false` を返す。
+pub fn search_bounded(
+    board: &Board,
+    searched: &mut HashSet<[u64; 2]>,
+    leafnode: &mut HashSet<[u64; 2]>,
+    discs: i32,
+    max_nodes: usize,
+) -> bool {
+    if searched.len() + leafnode.len() > max_nodes {
+        return false;
+    }
     let uni = board.unique();
 
+    // しきい値に達した局面は合法手の有無によらずリーフとする。
+    // 手番側がパスする局面はパス後の向きも登録する（逆方向探索はどちらの向きでも到達しうる）。
+    // 両者とも合法手が無い終局局面もそのまま登録する。
     if board.popcount() >= discs as u32 {
-        if get_moves(board.player, board.opponent) != 0 {
-            leafnode.insert(uni);
-            return;
-        } else if get_moves(board.opponent, board.player) != 0 {
-            let next = Board {
-                player: board.opponent,
-                opponent: board.player,
-            };
-            search(&next, searched, leafnode, discs);
+        if leafnode.insert(uni) && leafnode.len().is_multiple_of(FORWARD_REPORT_INTERVAL) {
+            vprintln!(
+                INFO,
+                "info: forward search: internal = {}, leaf = {}",
+                searched.len(),
+                leafnode.len()
+            );
+        }
+        if !has_move(board.player, board.opponent) && has_move(board.opponent, board.player) {
+            let (_, passed) = board.unique_with_swapped();
+            leafnode.insert(passed);
         }
-        return;
+        return true;
     }
 
     if !searched.insert(uni) {
-        return;
+        return true;
+    }
+    if searched.len().is_multiple_of(FORWARD_REPORT_INTERVAL) {
+        vprintln!(
+            INFO,
+            "info: forward search: internal = {}, leaf = {}",
+            searched.len(),
+            leafnode.len()
+        );
     }
 
     let mut moves = get_moves(board.player, board.opponent);
     if moves == 0 {
-        if get_moves(board.opponent, board.player) != 0 {
+        if has_move(board.opponent, board.player) {
             let next = Board {
                 player: board.opponent,
                 opponent: board.player,
             };
-            search(&next, searched, leafnode, discs);
+            return search_bounded(&next, searched, leafnode, discs, max_nodes);
         }
-        return;
+        return true;
     }
     // println!("{}", board.show());
     // println!("moves={}", mask_to_moves(moves));
@@ -147,7 +286,88 @@ pub fn search(
             player: board.opponent ^ flipped,
             opponent: board.player ^ (flipped | (1u64 << idx)),
         };
-        search(&next, searched, leafnode, discs);
+        if !search_bounded(&next, searched, leafnode, discs, max_nodes) {
+            return false;
+        }
+    }
+    true
+}
+
+/// 初期局面から順方向に到達可能な局面数を石数ごとに数える。
+///
+/// 戻り値の `counts[n]` は石数 `n` の局面 (対称性で正規化した [手番, 相手]) の個数で、
+/// `n < 4` の要素は 0。手番側がパスする局面は、パス後の向きも別の局面として数える
+/// (`search_bounded` のリーフ登録と同じ扱い)。石数ごとに 1 段ずつ幅優先で展開するので、
+/// メモリ使用量は最大の段の局面数に比例する。
+pub fn count_reachable_per_level(discs: i32) -> Vec<usize> {
+    let discs = discs.clamp(4, 64) as usize;
+    let mut counts = vec![0usize; discs + 1];
+    let mut level: HashSet<[u64; 2]> = HashSet::new();
+    level.insert(Board::initial().unique());
+    for (n, count) in counts.iter_mut().enumerate().skip(4) {
+        *count = level.len();
+        vprintln!(
+            INFO,
+            "info: forward count: discs = {}, positions = {}",
+            n,
+            count
+        );
+        if n == discs {
+            break;
+        }
+        let mut next_level: HashSet<[u64; 2]> = HashSet::new();
+        for &[player, opponent] in &level {
+            let mut moves = get_moves(player, opponent);
+            while moves != 0 {
+                let idx = moves.trailing_zeros();
+                moves &= moves - 1;
+                let flipped = flip(idx as usize, player, opponent);
+                let next = Board {
+                    player: opponent ^ flipped,
+                    opponent: player ^ (flipped | (1u64 << idx)),
+                };
+                if !has_move(next.player, next.opponent) && has_move(next.opponent, next.player) {
+                    let (uni, passed) = next.unique_with_swapped();
+                    next_level.insert(uni);
+                    next_level.insert(passed);
+                } else {
+                    next_level.insert(next.unique());
+                }
+            }
+        }
+        level = next_level;
+    }
+    counts
+}
+
+// ユーティリティ：answer==0 のとき初期化、それ以外は直積結合
+#[inline]
+fn add_direction_sets(
+    answer: &mut usize,
+    result: &mut [u64; 10_000],
+    acc_bits_seq: impl Iterator<Item = u64>,
+) {
+    if *answer == 0 {
+        // 初回：result[0] = 0、以後は累積ORで 1..n-1 を埋める
+        result[0] = 0;
+        *answer = 1;
+        for bits in acc_bits_seq {
+            debug_assert!(*answer < result.len());
+            result[*answer] = result[*answer - 1] | bits;
+            *answer += 1;
+        }
+    } else {
+        // 2 回目以降：既存 0..old_answer-1 に対して各累積方向 bits を OR した新要素を追加
+        let old_answer = *answer;
+        let mut direction: u64 = 0;
+        for bits in acc_bits_seq {
+            direction |= bits;
+            for j in 0..old_answer {
+                debug_assert!(*answer < result.len());
+                result[*answer] = result[j] | direction;
+                *answer += 1;
+            }
+        }
     }
 }
 
@@ -155,53 +375,27 @@ pub fn search(
 /// 「直前の着手が pos だった」と仮定したときに、
 /// その着手であり得る “ひっくり返り集合” を result に列挙して個数を返す。
 /// 返り値が非ゼロのとき `result[0] == 0`（便宜上）。反復時は 1 から使うこと。
+/// `pos` に opponent の石が無ければ直前の着手にはなり得ないので、何も列挙せずに 0 を返す。
+/// `pos < 64` と中央 4 マスでないことは呼び出し側（`reverse_candidates`）が保証する前提で、
+/// デバッグビルドでだけ確かめる。
 pub fn retrospective_flip(
     pos: u32,
     _player: u64,
     opponent: u64,
     result: &mut [u64; 10_000],
 ) -> usize {
-    assert!(pos < 64);
-    assert!(((1u64 << pos) & opponent) != 0);
+    debug_assert!(pos < 64);
     // 中央 4 マスではない（問題文どおり）
-    assert!(((1u64 << pos) & 0x0000_0018_1800_0000u64) == 0);
+    debug_assert!(((1u64 << pos) & CENTER_MASK) == 0);
+    if ((1u64 << pos) & opponent) == 0 {
+        return 0;
+    }
 
     let xpos = (pos % 8) as i32;
     let ypos = (pos / 8) as i32;
 
     let mut answer: usize = 0;
 
-    // ユーティリティ：answer==0 のとき初期化、それ以外は直積結合
-    #[inline]
-    fn add_direction_sets(
-        answer: &mut usize,
-        result: &mut [u64; 10_000],
-        acc_bits_seq: impl Iterator<Item = u64>,
-    ) {
-        if *answer == 0 {
-            // 初回：result[0] = 0、以後は累積ORで 1..n-1 を埋める
-            result[0] = 0;
-            *answer = 1;
-            for bits in acc_bits_seq {
-                debug_assert!(*answer < result.len());
-                result[*answer] = result[*answer - 1] | bits;
-                *answer += 1;
-            }
-        } else {
-            // 2 回目以降：既存 0..old_answer-1 に対して各累積方向 bits を OR した新要素を追加
-            let old_answer = *answer;
-            let mut direction: u64 = 0;
-            for bits in acc_bits_seq {
-                direction |= bits;
-                for j in 0..old_answer {
-                    debug_assert!(*answer < result.len());
-                    result[*answer] = result[j] | direction;
-                    *answer += 1;
-                }
-            }
-        }
-    }
-
     // 上方向（-8）
     if ypos >= 2 {
         let mut length = 0;
@@ -390,12 +584,101 @@ pub fn retrospective_flip(
     answer
 }
 
+/// `board` の 1 手前の局面と、そこから `board` を作る着手位置の組をすべて返す。
+///
+/// `retrospective_flip` の候補のうち、前駆局面から実際に打つと返る石が候補と一致するもの
+/// （すなわち着手を再生すると `board` に戻るもの）だけを残す。前駆局面の `player` が着手側。
+/// パスによる前駆（手番を入れ替えただけの局面）は着手位置が無いので含めない。
+pub fn one_step_predecessors(board: &Board) -> Vec<(Board, usize)> {
+    let mut result = Vec::new();
+    let mut flips = [0u64; 10_000];
+    let mut b = reverse_candidates(board.opponent, CENTER_MASK);
+    while b != 0 {
+        let index = b.trailing_zeros();
+        b &= b - 1;
+        let num = retrospective_flip(index, board.player, board.opponent, &mut flips);
+        for &flipped in flips.iter().take(num).skip(1) {
+            let prev = Board::new(
+                board.opponent ^ (flipped | (1u64 << index)),
+                board.player ^ flipped,
+            );
+            if flip(index as usize, prev.player, prev.opponent) == flipped {
+                result.push((prev, index as usize));
+            }
+        }
+    }
+    result
+}
+
+/// 逆方向探索で「直前に相手が置いた位置」の候補になるマス（中央 `center` を除いた相手石）。
+/// 8x8 盤では `center = CENTER_MASK`、小さな正方盤では `square_center_mask(width)` を渡す。
+#[inline]
+pub const fn reverse_candidates(opponent: u64, center: u64) -> u64 {
+    opponent & !center
+}
+
+pub use crate::othello::square_center_mask;
+
+/// `retrospective_flip` の盤サイズ可変版。
+/// 一辺 `width` マス（`width <= 8`）の正方盤を行ストライド `width` で u64 に詰めた表現を扱う。
+/// 6x6 などの小さな盤で逆方向探索を検証するためのもので、
+/// `width == 8` では `retrospective_flip` と同じ集合を返す（並び順は異なりうる）。
+/// 8x8 版と同様に他方向で挟まれてしまう石は考慮しないので、真の直前局面の候補の上位集合になる。
+/// `pos` に opponent の石が無ければ 0 を返す（8x8 版と同じ）。
+pub fn retrospective_flip_sized(
+    pos: u32,
+    opponent: u64,
+    width: u32,
+    result: &mut [u64; 10_000],
+) -> usize {
+    debug_assert!(width <= 8);
+    debug_assert!(pos < width * width);
+    debug_assert!(((1u64 << pos) & square_center_mask(width)) == 0);
+    if ((1u64 << pos) & opponent) == 0 {
+        return 0;
+    }
+
+    let w = width as i32;
+    let xpos = (pos % width) as i32;
+    let ypos = (pos / width) as i32;
+
+    let mut answer: usize = 0;
+    for d in Direction::all() {
+        let (dx, dy) = d.to_offset();
+        // pos の隣から連続する相手石の数
+        let mut length = 0;
+        loop {
+            let x = xpos + (length + 1) * dx;
+            let y = ypos + (length + 1) * dy;
+            if x < 0 || x >= w || y < 0 || y >= w {
+                break;
+            }
+            if ((1u64 << (y * w + x)) & opponent) == 0 {
+                break;
+            }
+            length += 1;
+        }
+        if length >= 2 {
+            // 1..=length-1 個を候補として累積（最後の 1 個は挟んだ側の石）
+            let seq = (1..length).map(|i| 1u64 << ((ypos + i * dy) * w + xpos + i * dx));
+            add_direction_sets(&mut answer, result, seq);
+        }
+    }
+    answer
+}
+
 /// - `from_pass`: 直前にパスで1手分遡ったか否か
-/// - `discs`: 順方向探索の深さ（石数）
-/// - `leafnode`: 順方向探索で得たuniqueなleafnodeの集合（しきい値以上で合法手があるもの）
+/// - `discs`: 順方向探索の深さ（石数）。照合する石数は `leaf_discs(discs)`
+/// - `leafnode`: 順方向探索で得たuniqueなleafnodeの集合（しきい値の石数に達した局面。パス局面・終局局面を含む）。
+///   初期配置以外の局面から探索した集合（`LeafCache::from_seed`）を渡せば、その局面から到達可能かを判定する
 /// - `retrospective_searched`: 既訪問ユニーク局面
 /// - `retroflips`: ディスク数ごとに使い回す作業バッファ（長さ 10_000 の配列を入れておく）
 ///   インデックスは `num_disc as usize` を想定。必要に応じて拡張する。
+///   着手を 1 手戻した再帰呼び出しは石数が 1 少ないので `retroflips[num_disc - 1]` を使い、
+///   呼び出し元が走査中の `retroflips[num_disc]` を上書きしない。同じ石数で再帰するパス分岐は
+///   `retroflips[num_disc]` を埋める前に済ませる。
+///
+/// `board` が `validate_board` を通らなければ、探索の途中で panic する代わりに最初に `Err` を返す。
 pub fn retrospective_search(
     board: &Board,
     from_pass: bool,
@@ -405,18 +688,187 @@ pub fn retrospective_search(
     retroflips: &mut Vec<[u64; 10_000]>,
     node_count: &mut usize,
     node_limit: usize,
+) -> Result<SearchResult, BoardValidation> {
+    validate_board(board)?;
+    let mut found = None;
+    Ok(retrospective_search_impl(
+        board,
+        board.popcount() as usize,
+        from_pass,
+        discs,
+        leafnode,
+        retrospective_searched,
+        retroflips,
+        node_count,
+        node_limit,
+        usize::MAX,
+        &mut found,
+    ))
+}
+
+/// `retrospective_search` の前に `store` を引き、記録済みならその判定をそのまま返す。
+/// 記録が無ければ `leaf_cache` のリーフ表で探索し、`Found` / `NotFound` になったら `store` に
+/// 書き込む（`Unknown` はノード数の上限次第で変わるので書かない）。
+///
+/// 判定はリーフ表（照合する石数・初期配置・seed）によって変わるので、キーは局面の正規形と
+/// `leaf_cache.fingerprint()` の組（`ReachabilityKey`）にする。しきい値や seed の違うリーフ表で
+/// 同じ `store` を共有しても判定は混ざらない。
+///
+/// `from_pass` が true の根はパスで遡れないという制約付きの判定で、局面そのものの性質ではないので
+/// `store` は使わない。`store` が `None` なら `retrospective_search` と同じ。
+/// 記録から答えたときは `node_count` を増やさない。
+#[allow(clippy::too_many_arguments)]
+pub fn retrospective_search_with_store(
+    board: &Board,
+    from_pass: bool,
+    leaf_cache: &LeafCache,
+    retrospective_searched: &mut Btable,
+    retroflips: &mut Vec<[u64; 10_000]>,
+    node_count: &mut usize,
+    node_limit: usize,
+    store: Option<&mut dyn ReachabilityStore>,
+) -> Result<SearchResult, BoardValidation> {
+    validate_board(board)?;
+    let mut search = || {
+        let mut found = None;
+        retrospective_search_impl(
+            board,
+            board.popcount() as usize,
+            from_pass,
+            leaf_cache.discs(),
+            leaf_cache.leaf(),
+            retrospective_searched,
+            retroflips,
+            node_count,
+            node_limit,
+            usize::MAX,
+            &mut found,
+        )
+    };
+    let Some(store) = store.filter(|_| !from_pass) else {
+        return Ok(search());
+    };
+    let key = ReachabilityKey::new(board, leaf_cache);
+    match store.get(key) {
+        Some(true) => return Ok(SearchResult::Found),
+        Some(false) => return Ok(SearchResult::NotFound),
+        None => {}
+    }
+    let result = search();
+    match result {
+        SearchResult::Found => store.put(key, true),
+        SearchResult::NotFound => store.put(key, false),
+        SearchResult::Unknown => {}
+    }
+    Ok(result)
+}
+
+/// 逆方向探索で見つかった順方向探索のリーフ
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FoundLeaf {
+    /// 見つかったリーフ局面（入力盤面と同じ向き）
+    pub board: Board,
+    /// リーフテーブル上の正規形（`board.unique()`）
+    pub canonical: [u64; 2],
+    /// `board.board_symmetry(symmetry, ..)` が `canonical` になる対称変換の添字。
+    /// 正規形から入力の向きへ戻すには `Board::inverse_symmetry(symmetry)` を使う。
+    pub symmetry: i32,
+}
+
+/// `retrospective_search` に加えて、`Found` のときは到達したリーフを入力盤面の向きで返す。
+/// 不正な盤面は `retrospective_search` と同じく `Err`。
+///
+/// `flip_cap` は 1 つの着手位置について `retrospective_flip` が列挙する直前局面の候補数の上限で、
+/// これを超える局面は展開せずに `Unknown` とする（到達不能と誤って判定しないため `NotFound` にはしない）。
+/// 上限を設けないなら `usize::MAX`（`retrospective_search` はこちら）。
+#[allow(clippy::too_many_arguments)]
+pub fn retrospective_search_found(
+    board: &Board,
+    from_pass: bool,
+    discs: i32,
+    leafnode: &HashSet<[u64; 2]>,
+    retrospective_searched: &mut Btable,
+    retroflips: &mut Vec<[u64; 10_000]>,
+    node_count: &mut usize,
+    node_limit: usize,
+    flip_cap: usize,
+) -> Result<(SearchResult, Option<FoundLeaf>), BoardValidation> {
+    validate_board(board)?;
+    let mut found = None;
+    let result = retrospective_search_impl(
+        board,
+        board.popcount() as usize,
+        from_pass,
+        discs,
+        leafnode,
+        retrospective_searched,
+        retroflips,
+        node_count,
+        node_limit,
+        flip_cap,
+        &mut found,
+    );
+    let leaf = found.map(|leaf: Board| {
+        let (canonical, symmetry) = leaf.unique_with_symmetry();
+        FoundLeaf {
+            board: leaf,
+            canonical,
+            symmetry,
+        }
+    });
+    Ok((result, leaf))
+}
+
+/// 順方向探索のリーフ表が持つ局面の石数。
+///
+/// `discs` は順方向探索を打ち切る石数で、リーフ表（`search` / `search_forward_par` / `LeafCache`）には
+/// 初期局面から到達可能な石数ちょうど `leaf_discs(discs)` の局面が、合法手の有無によらず全て入る
+/// （手番側がパスする局面はパス後の向きも、両者とも打てない終局局面もそのまま）。
+/// 初期局面が 4 石なので、4 未満のしきい値では初期局面だけがリーフになる。
+///
+/// 逆方向探索はこの石数まで遡った局面をリーフ表と照合する。石数がこれより少ない盤面は
+/// リーフ表では到達可能か判定できないので、根に渡されたときは `Unknown` を返す。
+pub const fn leaf_discs(discs: i32) -> i32 {
+    if discs < 4 {
+        4
+    } else {
+        discs
+    }
+}
+
+/// `found` には見つかったリーフ局面（探索中の向きのまま）を書き込む。
+/// `num_disc` は `board` の石数で、毎回数え直さずに再帰のたびに差分で更新する
+/// （パスでは変わらず、着手を 1 手戻すと 1 減る）。
+#[allow(clippy::too_many_arguments)]
+fn retrospective_search_impl(
+    board: &Board,
+    num_disc: usize,
+    from_pass: bool,
+    discs: i32,
+    leafnode: &HashSet<[u64; 2]>,
+    retrospective_searched: &mut Btable,
+    retroflips: &mut Vec<[u64; 10_000]>,
+    node_count: &mut usize,
+    node_limit: usize,
+    flip_cap: usize,
+    found: &mut Option<Board>,
 ) -> SearchResult {
+    debug_assert_eq!(num_disc, board.popcount() as usize);
     let uni = board.unique();
-    let num_disc = board.popcount() as usize;
 
     // 順方向探索の leafnode に含まれているか確認
-    if (num_disc as i32) <= discs {
+    let leaf_discs = leaf_discs(discs);
+    if (num_disc as i32) < leaf_discs {
+        return SearchResult::Unknown;
+    }
+    if num_disc as i32 == leaf_discs {
         return if leafnode.contains(&uni) {
-            println!("info: found unique board in leafnodes:");
-            println!("unique player = {}", uni[0]);
-            println!("unique opponent = {}", uni[1]);
-            println!("board player = {}", board.player);
-            println!("board opponent = {}", board.opponent);
+            vprintln!(DEBUG, "info: found unique board in leafnodes:");
+            vprintln!(DEBUG, "unique player = {}", uni[0]);
+            vprintln!(DEBUG, "unique opponent = {}", uni[1]);
+            vprintln!(DEBUG, "board player = {}", board.player);
+            vprintln!(DEBUG, "board opponent = {}", board.opponent);
+            *found = Some(*board);
             SearchResult::Found
         } else {
             SearchResult::NotFound
@@ -445,17 +897,7 @@ pub fn retrospective_search(
     //    return SearchResult::Unknown;
     //}
 
-    let occupied = board.player | board.opponent;
-    //if !is_connected(occupied) {
-    //    return SearchResult::NotFound;
-    //}
-    //if !check_seg3(occupied) {
-    //    return SearchResult::NotFound;
-    //}
-    if !check_occupancy(occupied) {
-        return SearchResult::NotFound;
-    }
-    if !check_seg3_more(board.player, board.opponent) {
+    if !passes_search_prunings(board.player, board.opponent) {
         return SearchResult::NotFound;
     }
     // let line = board.to_string();
@@ -465,14 +907,24 @@ pub fn retrospective_search(
 
     // パスの処理
     // from_pass==false かつ 相手に合法手が無いならば、1手前に相手がパスしたと仮定
+    //
+    // 連続パス (両者パス = 終局) を遡る必要は無い:
+    // - パスは盤面を変えず手番だけを入れ替えるので、2 回続けて遡ると元の局面に戻るだけで、
+    //   新しい前駆局面は生まれない。from_pass==true で 2 回目のパスを止めても失う経路は無い。
+    // - 終局局面 (両者とも合法手無し) は最後の着手で作られる。その局面をどちらの手番で
+    //   与えられても、着手による前駆はこの下のループで、手番を入れ替えた向きは
+    //   このパス分岐で辿るので、両方の向きが探索される。
+    // - from_pass==true で訪問済みになった局面を後で from_pass==false として再訪しても、
+    //   省略されるのはパス分岐、すなわち既に探索中または探索済みの局面への辺だけである。
     if !from_pass {
-        if get_moves(board.opponent, board.player) == 0 {
+        if !has_move(board.opponent, board.player) {
             let prev = Board {
                 player: board.opponent,
                 opponent: board.player,
             };
-            match retrospective_search(
+            match retrospective_search_impl(
                 &prev,
+                num_disc,
                 true,
                 discs,
                 leafnode,
@@ -480,13 +932,15 @@ pub fn retrospective_search(
                 retroflips,
                 node_count,
                 node_limit,
+                flip_cap,
+                found,
             ) {
                 SearchResult::Found => {
-                    println!("pass found");
+                    vprintln!(DEBUG, "pass found");
                     return SearchResult::Found;
                 }
                 SearchResult::Unknown => {
-                    println!("pass found");
+                    vprintln!(DEBUG, "pass found");
                     return SearchResult::Unknown;
                 }
                 SearchResult::NotFound => {}
@@ -495,12 +949,14 @@ pub fn retrospective_search(
     }
 
     // 相手石（中央4マス以外）を候補として走査
-    let mut b = board.opponent & !0x0000_0018_1800_0000u64;
+    let mut b = reverse_candidates(board.opponent, CENTER_MASK);
     if b == 0 {
         return SearchResult::NotFound;
     }
 
-    // retroflips[num_disc] を使うので、足りなければ拡張
+    // retroflips[num_disc] を使うので、足りなければ拡張。
+    // ここから先の再帰は石数が num_disc - 1 の局面だけなので、この枠は上書きされない
+    // (同じ石数で再帰するパス分岐は上で済ませてある)
     if retroflips.len() <= num_disc {
         retroflips.resize(num_disc + 1, [0u64; 10_000]);
     }
@@ -522,6 +978,10 @@ pub fn retrospective_search(
         if num > 0 {
             // result[0] は 0（便宜上）なので、-1 した数だけ “実 flips” を見た回数として数える
             _searched += (num - 1) as i32;
+            // 直前局面の候補が多すぎる局面は展開せずに判定を諦める
+            if num - 1 > flip_cap {
+                return SearchResult::Unknown;
+            }
         }
 
         for i in 1..num {
@@ -533,9 +993,16 @@ pub fn retrospective_search(
                 player: board.opponent ^ (flipped | (1u64 << index)),
                 opponent: board.player ^ flipped,
             };
+            // index に置いて返るのがちょうど flipped でなければ、この局面の直前ではない。
+            // retrospective_flip は戻す石の組を盤面の線ごとに独立に選ぶので、戻した後の局面で
+            // 別の線も挟めてしまう（返す石が多すぎる）候補が混じる
+            if flip(index as usize, prev.player, prev.opponent) != flipped {
+                continue;
+            }
 
-            match retrospective_search(
+            match retrospective_search_impl(
                 &prev,
+                num_disc - 1,
                 false,
                 discs,
                 leafnode,
@@ -543,6 +1010,8 @@ pub fn retrospective_search(
                 retroflips,
                 node_count,
                 node_limit,
+                flip_cap,
+                found,
             ) {
                 SearchResult::Found => {
                     // println!("{}", index);
@@ -559,3 +1028,647 @@ pub fn retrospective_search(
 
     SearchResult::NotFound
 }
+
+/// `retrospective_search` に加えて、`NotFound` のときはルート局面で棄却した理由を返す。
+/// 不正な盤面は `retrospective_search` と同じく `Err`。
+#[allow(clippy::too_many_arguments)]
+pub fn retrospective_search_with_reason(
+    board: &Board,
+    from_pass: bool,
+    discs: i32,
+    leafnode: &HashSet<[u64; 2]>,
+    retrospective_searched: &mut Btable,
+    retroflips: &mut Vec<[u64; 10_000]>,
+    node_count: &mut usize,
+    node_limit: usize,
+) -> Result<(SearchResult, Option<NotFoundReason>), BoardValidation> {
+    let result = retrospective_search(
+        board,
+        from_pass,
+        discs,
+        leafnode,
+        retrospective_searched,
+        retroflips,
+        node_count,
+        node_limit,
+    )?;
+    if result == SearchResult::NotFound {
+        Ok((result, Some(root_not_found_reason(board, discs))))
+    } else {
+        Ok((result, None))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::reachable::random_reachable_board;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    /// 合流を考えずに全ての手順をたどり、ちょうど `discs` 石の局面を集める（パス局面は両方の向き）
+    fn naive_leaves(board: &Board, discs: u32, leaves: &mut HashSet<[u64; 2]>) {
+        if board.popcount() == discs {
+            leaves.insert(board.unique());
+            if !has_move(board.player, board.opponent) && has_move(board.opponent, board.player) {
+                leaves.insert(Board::new(board.opponent, board.player).unique());
+            }
+            return;
+        }
+        let mut moves = get_moves(board.player, board.opponent);
+        if moves == 0 {
+            if has_move(board.opponent, board.player) {
+                naive_leaves(&Board::new(board.opponent, board.player), discs, leaves);
+            }
+            return;
+        }
+        while moves != 0 {
+            let idx = moves.trailing_zeros();
+            moves &= moves - 1;
+            let flipped = flip(idx as usize, board.player, board.opponent);
+            let next = Board::new(
+                board.opponent ^ flipped,
+                board.player ^ (flipped | (1u64 << idx)),
+            );
+            naive_leaves(&next, discs, leaves);
+        }
+    }
+
+    /// 手番側がパスする（相手には合法手がある）局面を乱択で探す
+    fn random_pass_board(rng: &mut StdRng) -> Board {
+        loop {
+            let discs = rng.random_range(20..=60);
+            let b = random_reachable_board(rng, discs);
+            if !has_move(b.player, b.opponent) && has_move(b.opponent, b.player) {
+                return b;
+            }
+        }
+    }
+
+    #[test]
+    fn forward_leaves_match_exhaustive_enumeration() {
+        for discs in 5..=8 {
+            let mut searched = HashSet::new();
+            let mut leafnode = HashSet::new();
+            search(&Board::initial(), &mut searched, &mut leafnode, discs);
+            let mut expected = HashSet::new();
+            naive_leaves(&Board::initial(), discs as u32, &mut expected);
+            assert_eq!(leafnode, expected, "discs = {}", discs);
+        }
+    }
+
+    #[test]
+    fn pass_at_threshold_registers_both_orientations() {
+        let mut rng = StdRng::seed_from_u64(5);
+        let board = random_pass_board(&mut rng);
+        let mut searched = HashSet::new();
+        let mut leafnode = HashSet::new();
+        search(
+            &board,
+            &mut searched,
+            &mut leafnode,
+            board.popcount() as i32,
+        );
+        assert!(searched.is_empty());
+        assert!(leafnode.contains(&board.unique()));
+        assert!(leafnode.contains(&Board::new(board.opponent, board.player).unique()));
+        assert_eq!(leafnode.len(), 2);
+    }
+
+    #[test]
+    fn terminal_position_at_threshold_is_a_leaf() {
+        // 盤面が埋まった終局局面
+        let terminal = random_reachable_board(&mut StdRng::seed_from_u64(6), 64);
+        assert!(terminal.is_game_over());
+        let mut searched = HashSet::new();
+        let mut leafnode = HashSet::new();
+        search(&terminal, &mut searched, &mut leafnode, 64);
+        assert!(searched.is_empty());
+        assert_eq!(leafnode, HashSet::from([terminal.unique()]));
+    }
+
+    /// 盤の外周 1 列を除いた 6x6 の範囲にランダムに石を置いた、石数 `discs` の盤面
+    fn random_dense_board(rng: &mut StdRng, discs: u32) -> Board {
+        let area: u64 = 0x0000_7E7E_7E7E_7E00 & !CENTER_MASK;
+        let mut player = 0;
+        let mut opponent = 0;
+        for bit in [27, 28, 35, 36] {
+            if rng.random_bool(0.5) {
+                player |= 1u64 << bit;
+            } else {
+                opponent |= 1u64 << bit;
+            }
+        }
+        while (player | opponent).count_ones() < discs {
+            let sq = rng.random_range(0..64);
+            if area & (1u64 << sq) == 0 || (player | opponent) & (1u64 << sq) != 0 {
+                continue;
+            }
+            if rng.random_bool(0.5) {
+                player |= 1u64 << sq;
+            } else {
+                opponent |= 1u64 << sq;
+            }
+        }
+        Board::new(player, opponent)
+    }
+
+    #[test]
+    fn not_found_reports_the_rejecting_filter() {
+        let mut leafnode = HashSet::new();
+        let mut searched_fwd = HashSet::new();
+        search(&Board::initial(), &mut searched_fwd, &mut leafnode, 6);
+        let run = |board: &Board| {
+            let mut searched = Btable::new(1 << 16, 1 << 8);
+            let mut retroflips = vec![];
+            let mut node_count = 0;
+            retrospective_search_with_reason(
+                board,
+                false,
+                6,
+                &leafnode,
+                &mut searched,
+                &mut retroflips,
+                &mut node_count,
+                100_000,
+            )
+            .unwrap()
+        };
+
+        let mut rng = StdRng::seed_from_u64(9);
+        let mut seen = vec![];
+        let mut record = |reason| {
+            if !seen.contains(&reason) {
+                seen.push(reason);
+            }
+        };
+        for _ in 0..2000 {
+            let discs = rng.random_range(7..=10);
+            let board = random_dense_board(&mut rng, discs);
+            let occupied = board.player | board.opponent;
+            let expected = if !is_connected(occupied) {
+                NotFoundReason::Connectivity
+            } else if !check_occupancy(occupied) {
+                NotFoundReason::Occupancy
+            } else if !check_seg3_more(board.player, board.opponent) {
+                NotFoundReason::Seg3
+            } else {
+                match run(&board) {
+                    (SearchResult::NotFound, reason) => {
+                        assert_eq!(reason, Some(NotFoundReason::Exhausted));
+                        record(NotFoundReason::Exhausted);
+                    }
+                    (_, reason) => assert_eq!(reason, None),
+                }
+                continue;
+            };
+            assert_eq!(run(&board), (SearchResult::NotFound, Some(expected)));
+            record(expected);
+        }
+        assert_eq!(seen.len(), 4, "{:?}", seen);
+    }
+
+    #[test]
+    fn found_leaf_is_reported_in_the_input_orientation() {
+        let mut searched = HashSet::new();
+        let mut leafnode = HashSet::new();
+        search(&Board::initial(), &mut searched, &mut leafnode, 8);
+        let mut rng = StdRng::seed_from_u64(609);
+        let board = random_reachable_board(&mut rng, 14);
+        let mut sym = [0u64; 2];
+        for s in 0..8 {
+            board.board_symmetry(s, &mut sym);
+            let rotated = Board::new(sym[0], sym[1]);
+            let mut table = Btable::new(1 << 16, 1 << 8);
+            let (result, leaf) = retrospective_search_found(
+                &rotated,
+                false,
+                8,
+                &leafnode,
+                &mut table,
+                &mut vec![],
+                &mut 0,
+                usize::MAX,
+                usize::MAX,
+            )
+            .unwrap();
+            assert_eq!(result, SearchResult::Found, "symmetry {}", s);
+            let leaf = leaf.unwrap();
+            // 逆方向探索は石を取り除くだけなので、入力の向きのリーフの石は入力盤面の石に含まれる
+            let occupied = rotated.player | rotated.opponent;
+            assert_eq!((leaf.board.player | leaf.board.opponent) & !occupied, 0);
+            assert_eq!(leaf.board.popcount(), 8);
+            assert_eq!(leaf.canonical, leaf.board.unique());
+            assert!(leafnode.contains(&leaf.canonical));
+            leaf.board.board_symmetry(leaf.symmetry, &mut sym);
+            assert_eq!(sym, leaf.canonical);
+            Board::new(sym[0], sym[1])
+                .board_symmetry(Board::inverse_symmetry(leaf.symmetry), &mut sym);
+            assert_eq!(sym, [leaf.board.player, leaf.board.opponent]);
+        }
+    }
+
+    #[test]
+    fn per_level_counts_match_known_values() {
+        let counts = count_reachable_per_level(10);
+        assert_eq!(counts[..4], [0, 0, 0, 0]);
+        // 対称性で同一視した 0〜6 手目の局面数
+        assert_eq!(counts[4..], [1, 1, 3, 14, 60, 322, 1773]);
+        // 順方向探索のリーフ表の大きさと一致する
+        for discs in 5..=10 {
+            let mut searched = HashSet::new();
+            let mut leafnode = HashSet::new();
+            search(&Board::initial(), &mut searched, &mut leafnode, discs);
+            assert_eq!(leafnode.len(), counts[discs as usize], "discs = {}", discs);
+        }
+    }
+
+    #[test]
+    fn position_after_a_pass_is_found() {
+        // 手番側がパスする局面のうち石数の少ないもの。パス後の向きは、手番を入れ替えて 1 回
+        // パスを遡らないと着手で作られた局面に戻れない。
+        let mut rng = StdRng::seed_from_u64(619);
+        let pass = (0..)
+            .map(|_| {
+                let discs = rng.random_range(10..=18);
+                random_reachable_board(&mut rng, discs)
+            })
+            .find(|b| !has_move(b.player, b.opponent) && has_move(b.opponent, b.player))
+            .unwrap();
+        let after_pass = Board::new(pass.opponent, pass.player);
+
+        let mut searched = HashSet::new();
+        let mut leafnode = HashSet::new();
+        search(&Board::initial(), &mut searched, &mut leafnode, 8);
+        let run = |board: &Board, from_pass: bool| {
+            retrospective_search(
+                board,
+                from_pass,
+                8,
+                &leafnode,
+                &mut Btable::new(1 << 16, 1 << 8),
+                &mut vec![],
+                &mut 0,
+                usize::MAX,
+            )
+            .unwrap()
+        };
+        assert_eq!(run(&after_pass, false), SearchResult::Found);
+        assert_eq!(run(&pass, false), SearchResult::Found);
+        // この局面では、パスを遡れないとパス後の向きから初期局面に戻れない
+        assert_eq!(run(&after_pass, true), SearchResult::NotFound);
+    }
+
+    #[test]
+    fn pass_then_descend_does_not_clobber_the_callers_retroflips_slot() {
+        // パス後の向きを根にすると、同じ石数でパスを遡ってから着手を戻す。
+        // 使い回しのバッファにごみが残っていても、新しいバッファと同じ判定・ノード数になる
+        // （デバッグビルドでは戻した局面の石数の debug_assert も通る）。
+        let mut searched = HashSet::new();
+        let mut leafnode = HashSet::new();
+        search(&Board::initial(), &mut searched, &mut leafnode, 10);
+        let mut rng = StdRng::seed_from_u64(645);
+        let passes: Vec<Board> = (0..)
+            .map(|_| {
+                let discs = rng.random_range(10..=18);
+                random_reachable_board(&mut rng, discs)
+            })
+            .filter(|b| !has_move(b.player, b.opponent) && has_move(b.opponent, b.player))
+            .take(5)
+            .collect();
+        let mut reused: Vec<[u64; 10_000]> = vec![[u64::MAX; 10_000]; 65];
+        for pass in passes {
+            let after_pass = Board::new(pass.opponent, pass.player);
+            let run = |retroflips: &mut Vec<[u64; 10_000]>| {
+                let mut node_count = 0;
+                let result = retrospective_search(
+                    &after_pass,
+                    false,
+                    10,
+                    &leafnode,
+                    &mut Btable::new(1 << 16, 1 << 8),
+                    retroflips,
+                    &mut node_count,
+                    100_000,
+                )
+                .unwrap();
+                (result, node_count)
+            };
+            let fresh = run(&mut vec![]);
+            assert!(fresh.1 > 1);
+            assert_eq!(run(&mut reused), fresh);
+        }
+    }
+
+    #[test]
+    fn reverse_candidates_drop_exactly_the_center() {
+        // 8x8 の中央 d4 e4 d5 e5 と、6x6 の中央 c3 d3 c4 d4（行ストライド 6）
+        assert_eq!(CENTER_MASK, 0x0000_0018_1800_0000);
+        assert_eq!(
+            square_center_mask(6),
+            (1 << 14) | (1 << 15) | (1 << 20) | (1 << 21)
+        );
+        let mut rng = StdRng::seed_from_u64(646);
+        for _ in 0..200 {
+            let discs = rng.random_range(4..=64);
+            let b = random_reachable_board(&mut rng, discs);
+            let candidates = reverse_candidates(b.opponent, CENTER_MASK);
+            assert_eq!(candidates, b.opponent & !0x0000_0018_1800_0000u64);
+            assert_eq!(candidates | (b.opponent & CENTER_MASK), b.opponent);
+        }
+    }
+
+    #[test]
+    fn retrospective_flip_of_a_non_opponent_square_is_empty() {
+        let mut result = Box::new([0u64; 10_000]);
+        let mut rng = StdRng::seed_from_u64(682);
+        for _ in 0..50 {
+            let discs = rng.random_range(5..=60);
+            let b = random_reachable_board(&mut rng, discs);
+            // 空きマスと手番側の石（中央以外）
+            let mut squares = !b.opponent & !CENTER_MASK;
+            while squares != 0 {
+                let pos = squares.trailing_zeros();
+                squares &= squares - 1;
+                assert_eq!(
+                    retrospective_flip(pos, b.player, b.opponent, &mut result),
+                    0
+                );
+                assert_eq!(retrospective_flip_sized(pos, b.opponent, 8, &mut result), 0);
+            }
+        }
+    }
+
+    #[test]
+    fn one_step_predecessors_replay_to_the_board() {
+        let mut rng = StdRng::seed_from_u64(648);
+        for _ in 0..10 {
+            let mut board = Board::initial();
+            loop {
+                let moves = get_moves(board.player, board.opponent);
+                if moves == 0 {
+                    if !has_move(board.opponent, board.player) {
+                        break;
+                    }
+                    board = Board::new(board.opponent, board.player);
+                    continue;
+                }
+                let mut m = moves;
+                for _ in 0..rng.random_range(0..moves.count_ones()) {
+                    m &= m - 1;
+                }
+                let pos = m.trailing_zeros() as usize;
+                let flipped = flip(pos, board.player, board.opponent);
+                let next = Board::new(
+                    board.opponent ^ flipped,
+                    board.player ^ (flipped | (1u64 << pos)),
+                );
+
+                let predecessors = one_step_predecessors(&next);
+                // 実際に打った手は必ず含まれる
+                assert!(predecessors.contains(&(board, pos)));
+                for (prev, square) in predecessors {
+                    assert_eq!((prev.player | prev.opponent) & (1u64 << square), 0);
+                    let f = flip(square, prev.player, prev.opponent);
+                    assert_ne!(f, 0);
+                    let replayed =
+                        Board::new(prev.opponent ^ f, prev.player ^ (f | (1u64 << square)));
+                    assert_eq!(replayed, next);
+                }
+                board = next;
+            }
+        }
+    }
+
+    /// 再帰のたびに差分で更新した石数は、各ノードの入口の debug_assert で数え直した値と照合される。
+    /// パスと着手の戻しを両方通る探索を走らせ、食い違いが無いことを確かめる。
+    #[cfg(debug_assertions)]
+    #[test]
+    fn tracked_disc_count_matches_popcount_through_passes_and_moves() {
+        let mut searched = HashSet::new();
+        let mut leafnode = HashSet::new();
+        search(&Board::initial(), &mut searched, &mut leafnode, 8);
+        let mut rng = StdRng::seed_from_u64(649);
+        let mut random_board = || {
+            let discs = rng.random_range(10..=18);
+            random_reachable_board(&mut rng, discs)
+        };
+        let boards: Vec<Board> = (0..10).map(|_| random_board()).collect();
+        // パス後の向き（根でパスを遡る）
+        let after_passes: Vec<Board> = (0..)
+            .map(|_| random_board())
+            .filter(|b| !has_move(b.player, b.opponent) && has_move(b.opponent, b.player))
+            .map(|b| Board::new(b.opponent, b.player))
+            .take(3)
+            .collect();
+        for board in after_passes.iter().chain(&boards) {
+            let _ = retrospective_search(
+                board,
+                false,
+                8,
+                &leafnode,
+                &mut Btable::new(1 << 16, 1 << 8),
+                &mut vec![],
+                &mut 0,
+                20_000,
+            );
+        }
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "assertion")]
+    fn wrong_tracked_disc_count_is_caught_in_debug_builds() {
+        let board = Board::play_sequence(&[37, 43, 18]).unwrap();
+        retrospective_search_impl(
+            &board,
+            board.popcount() as usize + 1,
+            false,
+            4,
+            &HashSet::new(),
+            &mut Btable::new(1 << 8, 1 << 4),
+            &mut vec![],
+            &mut 0,
+            usize::MAX,
+            usize::MAX,
+            &mut None,
+        );
+    }
+
+    #[test]
+    fn malformed_boards_are_errors_not_panics() {
+        let initial = Board::initial();
+        let cases = [
+            // 相手の石と重なった石
+            (
+                Board::new(
+                    initial.player | (1u64 << 19),
+                    initial.opponent | (1u64 << 19),
+                ),
+                BoardValidation::Overlap,
+            ),
+            // 中央が 1 マス空いている
+            (
+                Board::new(initial.player, initial.opponent & !(1u64 << 27)),
+                BoardValidation::MissingCenter,
+            ),
+            // 中央から離れた石だけ
+            (Board::new(1, 1u64 << 63), BoardValidation::MissingCenter),
+        ];
+        let leaf_cache = LeafCache::new(6);
+        for (board, expected) in cases {
+            let mut table = Btable::new(1 << 8, 1 << 4);
+            let mut retroflips = vec![];
+            let mut node_count = 0;
+            for from_pass in [false, true] {
+                let result = retrospective_search(
+                    &board,
+                    from_pass,
+                    6,
+                    leaf_cache.leaf(),
+                    &mut table,
+                    &mut retroflips,
+                    &mut node_count,
+                    usize::MAX,
+                );
+                assert_eq!(result, Err(expected));
+            }
+            let found = retrospective_search_found(
+                &board,
+                false,
+                6,
+                leaf_cache.leaf(),
+                &mut table,
+                &mut retroflips,
+                &mut node_count,
+                usize::MAX,
+                usize::MAX,
+            );
+            assert_eq!(found, Err(expected));
+            let mut store = std::collections::HashMap::new();
+            let with_store = retrospective_search_with_store(
+                &board,
+                false,
+                &leaf_cache,
+                &mut table,
+                &mut retroflips,
+                &mut node_count,
+                usize::MAX,
+                Some(&mut store),
+            );
+            assert_eq!(with_store, Err(expected));
+            assert!(store.is_empty());
+            assert_eq!(node_count, 0);
+        }
+    }
+
+    #[test]
+    fn flip_cap_gives_up_as_unknown_but_never_as_not_found() {
+        let leaf_cache = LeafCache::new(8);
+        let mut table = Btable::new(1 << 16, 1 << 8);
+        let mut retroflips = vec![];
+        let mut run = |board: &Board, flip_cap| {
+            table.clear();
+            retrospective_search_found(
+                board,
+                false,
+                8,
+                leaf_cache.leaf(),
+                &mut table,
+                &mut retroflips,
+                &mut 0,
+                usize::MAX,
+                flip_cap,
+            )
+            .unwrap()
+            .0
+        };
+        let mut rng = StdRng::seed_from_u64(662);
+        for _ in 0..20 {
+            let discs = rng.random_range(11..=16);
+            let board = random_reachable_board(&mut rng, discs);
+            assert_eq!(run(&board, usize::MAX), SearchResult::Found);
+            // 十分大きい上限は上限なしと同じで、候補を 1 つも許さない上限では判定を諦める
+            assert_eq!(run(&board, 10_000), SearchResult::Found);
+            assert_eq!(
+                run(&board, 0),
+                SearchResult::Unknown,
+                "{}",
+                board.to_string()
+            );
+        }
+    }
+
+    #[test]
+    fn boards_below_the_threshold_are_unknown_and_at_it_are_looked_up() {
+        let leaf_cache = LeafCache::new(8);
+        let mut table = Btable::new(1 << 16, 1 << 8);
+        let mut retroflips = vec![];
+        let mut run = |board: &Board, discs, leaf: &HashSet<[u64; 2]>| {
+            table.clear();
+            retrospective_search(
+                board,
+                false,
+                discs,
+                leaf,
+                &mut table,
+                &mut retroflips,
+                &mut 0,
+                usize::MAX,
+            )
+            .unwrap()
+        };
+        let mut rng = StdRng::seed_from_u64(667);
+        let mut at_threshold_not_found = 0;
+        for _ in 0..20 {
+            let board = random_reachable_board(&mut rng, 8);
+            assert_eq!(run(&board, 8, leaf_cache.leaf()), SearchResult::Found);
+            let fewer = rng.random_range(5..8);
+            let smaller = random_reachable_board(&mut rng, fewer);
+            assert_eq!(run(&smaller, 8, leaf_cache.leaf()), SearchResult::Unknown);
+            // 石の色を 1 つ変えた 8 石の盤面は、リーフ表に無ければ遡らずに NotFound
+            let bit = 1u64 << board.opponent.trailing_zeros();
+            let recolored = Board::new(board.player | bit, board.opponent & !bit);
+            let expected = if leaf_cache.leaf().contains(&recolored.unique()) {
+                SearchResult::Found
+            } else {
+                at_threshold_not_found += 1;
+                SearchResult::NotFound
+            };
+            assert_eq!(run(&recolored, 8, leaf_cache.leaf()), expected);
+        }
+        assert!(at_threshold_not_found > 0);
+        // 4 未満のしきい値では初期局面だけがリーフになる
+        assert_eq!(leaf_discs(2), 4);
+        let initial_only = HashSet::from([Board::initial().unique()]);
+        assert_eq!(
+            run(&Board::initial(), 2, &initial_only),
+            SearchResult::Found
+        );
+        let one_move = Board::play_sequence(&[37]).unwrap();
+        assert_eq!(run(&one_move, 2, &initial_only), SearchResult::Found);
+    }
+
+    #[test]
+    fn tuned_sizes_fit_the_memory_budget() {
+        let leaf_cache = LeafCache::new(7);
+        let sample = Board::play_sequence(&[37, 43, 18, 19, 26]).unwrap();
+        for mem_budget in [100, 1 << 16, 1 << 20, 64 << 20] {
+            let (table_size, cache_size) = Btable::tune(&sample, 7, leaf_cache.leaf(), mem_budget);
+            assert!(cache_size >= 1);
+            assert!(
+                table_size * BTABLE_ENTRY_BYTES + cache_size * BTABLE_CACHE_ENTRY_BYTES
+                    <= mem_budget,
+                "{}: {:?}",
+                mem_budget,
+                (table_size, cache_size)
+            );
+            // キャッシュは予算の半分まで、残りは全て表に回す
+            assert!(cache_size * BTABLE_CACHE_ENTRY_BYTES <= mem_budget / 2);
+            assert_eq!(
+                table_size,
+                (mem_budget - cache_size * BTABLE_CACHE_ENTRY_BYTES) / BTABLE_ENTRY_BYTES
+            );
+        }
+    }
+}
@@ -0,0 +1,239 @@
+//! 順方向探索のしきい値（`discs`）を盤面ごとに自動で選ぶ。
+//!
+//! 順方向探索の木と、入力盤面からの逆方向探索の木の大きさを
+//! Knuth の推定法（ランダムな 1 本の経路上の分岐数の積の和）で見積もり、
+//! 和が最小になるしきい値を選ぶ。どちらの推定も置換（合流）を考慮しないので
+//! 実際のノード数より大きめになるが、両者を同じ基準で比べるには十分。
+//! 推定値の分散が大きいので、すべての候補を同じサンプル経路から評価する。
+use std::ops::RangeInclusive;
+
+use rand::Rng;
+
+use crate::othello::{flip, get_moves, has_move, Board, CENTER_MASK};
+use crate::prunings::passes_search_prunings;
+use crate::search::core::retrospective_flip;
+
+/// 自動選択で試すしきい値の既定の範囲
+pub const AUTO_DISCS_RANGE: RangeInclusive<i32> = 6..=14;
+
+/// 推定に使うランダム経路の本数の既定値
+pub const AUTO_DISCS_SAMPLES: usize = 256;
+
+/// 順方向探索の木の大きさの推定値。
+/// 戻り値の `[d]` は石数 `d` 以下の局面数の推定値（`d` は 0..=max_discs）。
+pub fn forward_cost_profile<R: Rng>(max_discs: i32, samples: usize, rng: &mut R) -> Vec<f64> {
+    let len = max_discs.max(0) as usize + 1;
+    let mut profile = vec![0.0; len];
+    for _ in 0..samples {
+        let mut board = Board::initial();
+        let mut weight = 1.0;
+        let mut level = vec![0.0; len];
+        loop {
+            let num_disc = board.popcount() as usize;
+            if num_disc >= len {
+                break;
+            }
+            level[num_disc] += weight;
+            let mut moves = get_moves(board.player, board.opponent);
+            if moves == 0 {
+                if !has_move(board.opponent, board.player) {
+                    break;
+                }
+                board = Board::new(board.opponent, board.player);
+                moves = get_moves(board.player, board.opponent);
+            }
+            let k = moves.count_ones();
+            weight *= k as f64;
+
+            let mut pick = rng.random_range(0..k);
+            while pick > 0 {
+                moves &= moves - 1;
+                pick -= 1;
+            }
+            let pos = moves.trailing_zeros() as usize;
+            let flipped = flip(pos, board.player, board.opponent);
+            board = Board::new(
+                board.opponent ^ flipped,
+                board.player ^ (flipped | (1u64 << pos)),
+            );
+        }
+        let mut acc = 0.0;
+        for (d, w) in level.iter().enumerate() {
+            acc += w;
+            profile[d] += acc;
+        }
+    }
+    let n = samples.max(1) as f64;
+    profile.iter().map(|x| x / n).collect()
+}
+
+/// `board` の 1 手前の候補のうち、逆方向探索の枝刈りを通るもの
+fn pruned_predecessors(
+    board: &Board,
+    from_pass: bool,
+    retroflips: &mut [u64; 10_000],
+) -> Vec<(Board, bool)> {
+    let mut children = vec![];
+    if !from_pass && !has_move(board.opponent, board.player) {
+        children.push((Board::new(board.opponent, board.player), true));
+    }
+    let mut b = board.opponent & !CENTER_MASK;
+    while b != 0 {
+        let index = b.trailing_zeros();
+        b &= b - 1;
+        let num = retrospective_flip(index, board.player, board.opponent, retroflips);
+        // retroflips[0] は 0（便宜上）なので 1 番目から見る
+        for &flipped in retroflips.iter().take(num).skip(1) {
+            let prev = Board::new(
+                board.opponent ^ (flipped | (1u64 << index)),
+                board.player ^ flipped,
+            );
+            if flip(index as usize, prev.player, prev.opponent) == flipped
+                && passes_search_prunings(prev.player, prev.opponent)
+            {
+                children.push((prev, false));
+            }
+        }
+    }
+    children
+}
+
+/// `board` からの逆方向探索の木の大きさの推定値。
+/// 戻り値の `[d]` は石数 `d` 以上の局面数の推定値（`d` は 0..=board の石数）。
+pub fn reverse_cost_profile<R: Rng>(
+    board: &Board,
+    min_discs: i32,
+    samples: usize,
+    rng: &mut R,
+) -> Vec<f64> {
+    let len = board.popcount() as usize + 1;
+    let mut retroflips = [0u64; 10_000];
+    let mut profile = vec![0.0; len];
+    for _ in 0..samples {
+        let mut node = (*board, false);
+        let mut weight = 1.0;
+        let mut level = vec![0.0; len];
+        loop {
+            let num_disc = node.0.popcount() as i32;
+            level[num_disc as usize] += weight;
+            if num_disc <= min_discs {
+                break;
+            }
+            let children = pruned_predecessors(&node.0, node.1, &mut retroflips);
+            if children.is_empty() {
+                break;
+            }
+            weight *= children.len() as f64;
+            node = children[rng.random_range(0..children.len())];
+        }
+        let mut acc = 0.0;
+        for d in (0..len).rev() {
+            acc += level[d];
+            profile[d] += acc;
+        }
+    }
+    let n = samples.max(1) as f64;
+    profile.iter().map(|x| x / n).collect()
+}
+
+/// `candidates` のうち順方向・逆方向の推定コストの和が最小になるしきい値を返す。
+/// 盤面の石数を超えるしきい値は候補から除く（候補が残らなければ石数を返す）。
+pub fn choose_discs<R: Rng>(
+    board: &Board,
+    candidates: RangeInclusive<i32>,
+    samples: usize,
+    rng: &mut R,
+) -> i32 {
+    let num_disc = board.popcount() as i32;
+    let lo = *candidates.start();
+    let hi = (*candidates.end()).min(num_disc);
+    if lo > hi {
+        return num_disc;
+    }
+    let forward = forward_cost_profile(hi, samples, rng);
+    let reverse = reverse_cost_profile(board, lo, samples, rng);
+
+    let mut best = (f64::INFINITY, hi);
+    for discs in lo..=hi {
+        let cost = forward[discs as usize] + reverse[discs as usize];
+        if cost < best.0 {
+            best = (cost, discs);
+        }
+    }
+    best.1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    /// 初期局面から乱択で `discs` 石まで打った局面
+    fn random_board(discs: u32, rng: &mut StdRng) -> Board {
+        loop {
+            let mut board = Board::initial();
+            while board.popcount() < discs {
+                let mut moves = get_moves(board.player, board.opponent);
+                if moves == 0 {
+                    if !has_move(board.opponent, board.player) {
+                        break;
+                    }
+                    board = Board::new(board.opponent, board.player);
+                    continue;
+                }
+                for _ in 0..rng.random_range(0..moves.count_ones()) {
+                    moves &= moves - 1;
+                }
+                let pos = moves.trailing_zeros() as usize;
+                let flipped = flip(pos, board.player, board.opponent);
+                board = Board::new(
+                    board.opponent ^ flipped,
+                    board.player ^ (flipped | (1u64 << pos)),
+                );
+            }
+            if board.popcount() == discs {
+                return board;
+            }
+        }
+    }
+
+    #[test]
+    fn choice_is_deterministic_for_a_seed() {
+        let board = random_board(40, &mut StdRng::seed_from_u64(7));
+        let choose = |samples| {
+            let mut rng = StdRng::seed_from_u64(1);
+            choose_discs(&board, AUTO_DISCS_RANGE, samples, &mut rng)
+        };
+        assert_eq!(choose(32), choose(32));
+        let profile = |seed| reverse_cost_profile(&board, 6, 32, &mut StdRng::seed_from_u64(seed));
+        assert_eq!(profile(3), profile(3));
+    }
+
+    #[test]
+    fn forty_disc_boards_pick_a_small_threshold() {
+        // 40 石の盤面では逆方向の木がほぼ高い石数の段だけで決まり、しきい値を上げても減らないので、
+        // 順方向の表が小さい範囲の下側が選ばれる
+        for seed in 0..4 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let board = random_board(40, &mut rng);
+            let discs = choose_discs(&board, AUTO_DISCS_RANGE, 32, &mut rng);
+            assert!((6..=9).contains(&discs), "seed {}: discs = {}", seed, discs);
+
+            let forward = forward_cost_profile(14, 32, &mut rng);
+            let reverse = reverse_cost_profile(&board, 6, 32, &mut rng);
+            assert!(forward.windows(2).all(|w| w[0] <= w[1]));
+            assert!(reverse.windows(2).all(|w| w[0] >= w[1]));
+            assert!(forward[14] < reverse[14]);
+        }
+    }
+
+    #[test]
+    fn thresholds_above_the_board_are_skipped() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let board = random_board(10, &mut rng);
+        let discs = choose_discs(&board, AUTO_DISCS_RANGE, 16, &mut rng);
+        assert!((6..=10).contains(&discs));
+        assert_eq!(choose_discs(&board, 12..=14, 16, &mut rng), 10);
+    }
+}
@@ -1,41 +1,258 @@
 use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
 
-use crate::othello::Board;
-use crate::search::core::search;
+use crate::othello::{validate_board, Board, Ruleset};
+use crate::search::core::{leaf_discs, search_bounded};
+use crate::search::search_fwd_par::{search_forward_par_from, search_forward_par_with_ruleset};
+
+/// 保存ファイル先頭のマジックナンバー（7 バイト）と形式の版（1 バイト）
+const LEAF_CACHE_MAGIC: &[u8; 7] = b"OTHLEAF";
+
+/// 保存ファイルの形式の版。リーフの定義や形式を変えたら上げ、古い版のファイルは読まずにエラーにする。
+///
+/// - `1`: しきい値で手番側が打てない局面（パス・終局）をリーフに入れていなかった
+/// - `2`: しきい値の石数の局面を、パス後の向きと終局局面も含めて全てリーフに入れる
+/// - `3`: ヘッダに順方向探索の初期配置（`Ruleset`）と開始局面（`from_seed` の seed）を記録する
+const LEAF_CACHE_VERSION: u8 = b'3';
 
 /// 順方向探索の結果をキャッシュする構造体
 pub struct LeafCache {
-    searched: HashSet<[u64; 2]>,
+    discs: i32,
+    /// 順方向探索の初期配置
+    ruleset: Ruleset,
+    /// `from_seed` で初期配置の代わりに探索を始めた局面
+    seed: Option<Board>,
+    searched_count: usize,
     leaf: HashSet<[u64; 2]>,
 }
 
 impl LeafCache {
     pub fn new(discs: i32) -> Self {
+        Self::with_ruleset(discs, &Ruleset::standard())
+    }
+
+    /// `ruleset` の初期配置から順方向探索した結果をキャッシュする
+    pub fn with_ruleset(discs: i32, ruleset: &Ruleset) -> Self {
+        let (searched_count, leaf) = search_forward_par_with_ruleset(discs, ruleset);
+        LeafCache {
+            discs,
+            ruleset: *ruleset,
+            seed: None,
+            searched_count,
+            leaf,
+        }
+    }
+
+    /// 初期配置ではなく任意の局面 `seed` から順方向探索した結果をキャッシュする。
+    ///
+    /// リーフ集合は `seed` から到達可能な `leaf_discs(discs)` 石の局面になるので、逆方向探索の
+    /// `leafnode` に渡すと「盤面 B は `seed` から到達可能か」を判定できる。逆方向探索の枝刈りは
+    /// 初期配置から到達可能であることを前提にしているため、`seed` 自身が初期配置から到達可能な
+    /// 局面でないと `NotFound` が正しいとは限らない。`save` したファイルには `seed` も記録され、
+    /// 読み込むときは `load_with` に同じ `seed` を渡す。
+    ///
+    /// `seed` が `validate_board` を通らないか、石数が `leaf_discs(discs)` を超えていれば
+    /// `ErrorKind::InvalidInput`。
+    pub fn from_seed(discs: i32, seed: &Board) -> io::Result<Self> {
+        validate_board(seed)?;
+        if seed.popcount() as i32 > leaf_discs(discs) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "seed has {} discs, more than discs = {}",
+                    seed.popcount(),
+                    leaf_discs(discs)
+                ),
+            ));
+        }
+        let (searched_count, leaf) = search_forward_par_from(discs, seed);
+        Ok(LeafCache {
+            discs,
+            ruleset: Ruleset::standard(),
+            seed: Some(*seed),
+            searched_count,
+            leaf,
+        })
+    }
+
+    /// 順方向探索で登録する局面数を `max_nodes` までに制限した版。
+    /// 上限を超えた場合はメモリを使い切る前に `ErrorKind::OutOfMemory` を返す。
+    pub fn new_bounded(discs: i32, max_nodes: usize) -> io::Result<Self> {
+        Self::with_ruleset_bounded(discs, &Ruleset::standard(), max_nodes)
+    }
+
+    /// `new_bounded` の初期配置を `ruleset` に置き換えた版
+    pub fn with_ruleset_bounded(
+        discs: i32,
+        ruleset: &Ruleset,
+        max_nodes: usize,
+    ) -> io::Result<Self> {
         let mut searched: HashSet<[u64; 2]> = HashSet::new();
         let mut leafnode: HashSet<[u64; 2]> = HashSet::new();
-        let initial = Board::initial();
-        search(&initial, &mut searched, &mut leafnode, discs);
-        for i in 4..9 {
-            let mut ans = vec![];
-            for s in &searched {
-                if (s[0] | s[1]).count_ones() == i {
-                    ans.push(s);
-                }
-            }
-            println!("i={}, ans.len()={}", i, ans.len());
-            ans.sort();
-            for j in 0..ans.len() {
-                println!("{}", Board::new(ans[j][0], ans[j][1]).to_string());
-            }
+        let initial = ruleset.initial_board();
+        if !search_bounded(&initial, &mut searched, &mut leafnode, discs, max_nodes) {
+            return Err(io::Error::new(
+                io::ErrorKind::OutOfMemory,
+                format!(
+                    "leaf table for discs = {} exceeded {} nodes (internal = {}, leaf = {})",
+                    discs,
+                    max_nodes,
+                    searched.len(),
+                    leafnode.len()
+                ),
+            ));
         }
-        LeafCache {
-            searched,
+        Ok(LeafCache {
+            discs,
+            ruleset: *ruleset,
+            seed: None,
+            searched_count: searched.len(),
             leaf: leafnode,
+        })
+    }
+
+    /// キャッシュを `path` に保存する。
+    ///
+    /// 形式: マジックナンバー(7) / 版(1) / discs(i32) / 初期配置 `ruleset.initial()`([u64;2]) /
+    /// 開始局面 seed([u64;2]、`from_seed` でなければ 0, 0) / leaf 件数(u64) / 内部ノード数(u64) の後に、
+    /// ソート済みの leaf を [u64;2] の連続（ネイティブエンディアン）で書き出す。
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut leaf: Vec<[u64; 2]> = self.leaf.iter().copied().collect();
+        leaf.sort_unstable();
+
+        let mut w = BufWriter::new(File::create(path)?);
+        w.write_all(LEAF_CACHE_MAGIC)?;
+        w.write_all(&[LEAF_CACHE_VERSION])?;
+        w.write_all(&self.discs.to_ne_bytes())?;
+        let seed = self.seed.map_or([0, 0], |b| [b.player, b.opponent]);
+        for word in self.ruleset.initial().iter().chain(&seed) {
+            w.write_all(&word.to_ne_bytes())?;
         }
+        w.write_all(&(leaf.len() as u64).to_ne_bytes())?;
+        w.write_all(&(self.searched_count as u64).to_ne_bytes())?;
+        w.write_all(bytemuck::cast_slice(&leaf))?;
+        w.flush()
+    }
+
+    /// `save` で保存した、標準の初期配置からのキャッシュ（`new` / `new_bounded`）を読み込む。
+    /// ヘッダの discs が `discs` と一致しない場合や、形式の版が古い場合、
+    /// 別の初期配置や seed から作ったキャッシュの場合は `ErrorKind::InvalidData` を返す。
+    pub fn load(path: &Path, discs: i32) -> io::Result<Self> {
+        Self::load_with(path, discs, &Ruleset::standard(), None)
+    }
+
+    /// `load` の、初期配置 `ruleset` と開始局面 `seed`（`from_seed` でなければ `None`）を指定する版。
+    /// ヘッダに記録されたものと一致しなければ `ErrorKind::InvalidData`。
+    pub fn load_with(
+        path: &Path,
+        discs: i32,
+        ruleset: &Ruleset,
+        seed: Option<&Board>,
+    ) -> io::Result<Self> {
+        let mut r = BufReader::new(File::open(path)?);
+
+        let mut magic = [0u8; 8];
+        r.read_exact(&mut magic)?;
+        if &magic[..7] != LEAF_CACHE_MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} is not a leaf cache file", path.display()),
+            ));
+        }
+        if magic[7] != LEAF_CACHE_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "leaf cache {} has format version {}, but version {} is required; delete it to rebuild",
+                    path.display(),
+                    magic[7] as char,
+                    LEAF_CACHE_VERSION as char
+                ),
+            ));
+        }
+
+        let mut buf4 = [0u8; 4];
+        r.read_exact(&mut buf4)?;
+        let file_discs = i32::from_ne_bytes(buf4);
+        if file_discs != discs {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "leaf cache {} was built with discs = {}, but discs = {} was requested",
+                    path.display(),
+                    file_discs,
+                    discs
+                ),
+            ));
+        }
+
+        let mut buf8 = [0u8; 8];
+        let mut header = [0u64; 4];
+        for word in header.iter_mut() {
+            r.read_exact(&mut buf8)?;
+            *word = u64::from_ne_bytes(buf8);
+        }
+        let file_ruleset = Ruleset::new(header[0], header[1]).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("leaf cache {}: {}", path.display(), e),
+            )
+        })?;
+        let file_seed = (header[2] | header[3] != 0).then(|| Board::new(header[2], header[3]));
+        if file_ruleset != *ruleset || file_seed.as_ref() != seed {
+            let describe = |ruleset: &Ruleset, seed: Option<&Board>| match seed {
+                Some(seed) => format!("seed {}", seed.to_string()),
+                None if *ruleset == Ruleset::standard() => {
+                    "the standard initial position".to_string()
+                }
+                None => format!("initial position {}", ruleset.initial_board().to_string()),
+            };
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "leaf cache {} was built from {}, but {} was requested",
+                    path.display(),
+                    describe(&file_ruleset, file_seed.as_ref()),
+                    describe(ruleset, seed)
+                ),
+            ));
+        }
+
+        r.read_exact(&mut buf8)?;
+        let leaf_len = u64::from_ne_bytes(buf8) as usize;
+        r.read_exact(&mut buf8)?;
+        let searched_count = u64::from_ne_bytes(buf8) as usize;
+
+        let mut leaf = vec![[0u64; 2]; leaf_len];
+        r.read_exact(bytemuck::cast_slice_mut(&mut leaf))?;
+
+        Ok(LeafCache {
+            discs,
+            ruleset: file_ruleset,
+            seed: file_seed,
+            searched_count,
+            leaf: leaf.into_iter().collect(),
+        })
+    }
+
+    pub fn discs(&self) -> i32 {
+        self.discs
+    }
+
+    /// 順方向探索の初期配置（`from_seed` では標準の配置）
+    pub fn ruleset(&self) -> &Ruleset {
+        &self.ruleset
+    }
+
+    /// `from_seed` で作ったキャッシュなら探索を始めた局面（`load_with` で読んだときはヘッダの値）
+    pub fn seed(&self) -> Option<&Board> {
+        self.seed.as_ref()
     }
 
     pub fn searched_count(&self) -> usize {
-        self.searched.len()
+        self.searched_count
     }
 
     pub fn leaf_count(&self) -> usize {
@@ -45,4 +262,154 @@ impl LeafCache {
     pub fn leaf(&self) -> &HashSet<[u64; 2]> {
         &self.leaf
     }
+
+    /// リーフ表を作った条件（照合する石数 `leaf_discs(discs)`・初期配置・seed）の指紋。
+    /// 逆方向探索の判定はリーフ表によって変わるので、`ReachabilityStore` のキーに含める。
+    /// 実行や機械をまたいで同じ値になる（FNV-1a、リトルエンディアンでバイト列にする）。
+    pub fn fingerprint(&self) -> u64 {
+        let seed = self.seed.map_or([0, 0], |b| [b.player, b.opponent]);
+        let words = [leaf_discs(self.discs) as u64]
+            .into_iter()
+            .chain(self.ruleset.initial())
+            .chain(seed);
+        words
+            .flat_map(u64::to_le_bytes)
+            .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+                (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::scratch_dir;
+    use std::fs;
+
+    #[test]
+    fn save_load_round_trip() {
+        let dir = scratch_dir("round_trip");
+        let path = dir.join("leaf_cache.bin");
+        let cache = LeafCache::new(8);
+        cache.save(&path).unwrap();
+        let loaded = LeafCache::load(&path, 8).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(loaded.leaf(), cache.leaf());
+        assert_eq!(loaded.searched_count(), cache.searched_count());
+    }
+
+    #[test]
+    fn rejects_wrong_discs() {
+        let dir = scratch_dir("wrong_discs");
+        let path = dir.join("leaf_cache.bin");
+        LeafCache::new(7).save(&path).unwrap();
+        let err = LeafCache::load(&path, 8).err().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn rejects_other_ruleset() {
+        let dir = scratch_dir("ruleset");
+        let path = dir.join("leaf_cache.bin");
+        let parallel = LeafCache::with_ruleset(7, &Ruleset::parallel());
+        parallel.save(&path).unwrap();
+        let standard = LeafCache::load(&path, 7).err().unwrap();
+        let loaded = LeafCache::load_with(&path, 7, &Ruleset::parallel(), None);
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(standard.kind(), io::ErrorKind::InvalidData);
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded.ruleset(), &Ruleset::parallel());
+        assert_eq!(loaded.leaf(), parallel.leaf());
+    }
+
+    #[test]
+    fn bounded_search_fails_fast_on_a_tiny_bound() {
+        let err = LeafCache::new_bounded(30, 1000).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::OutOfMemory);
+        assert!(err.to_string().contains("discs = 30"), "{}", err);
+
+        // 上限に収まるなら制限なしの表と同じ
+        let bounded = LeafCache::new_bounded(8, 1 << 20).unwrap();
+        let unbounded = LeafCache::new(8);
+        assert_eq!(bounded.leaf(), unbounded.leaf());
+        assert_eq!(bounded.searched_count(), unbounded.searched_count());
+    }
+
+    #[test]
+    fn seed_is_kept_across_save_and_load() {
+        let dir = scratch_dir("seed");
+        let path = dir.join("leaf_cache.bin");
+        // f5 f6
+        let seed = Board::play_sequence(&[37, 45]).unwrap();
+        let cache = LeafCache::from_seed(8, &seed).unwrap();
+        assert_eq!(cache.seed(), Some(&seed));
+        cache.save(&path).unwrap();
+        let standard = LeafCache::load(&path, 8).err().unwrap();
+        let loaded = LeafCache::load_with(&path, 8, &Ruleset::standard(), Some(&seed));
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(standard.kind(), io::ErrorKind::InvalidData);
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded.seed(), Some(&seed));
+        assert_eq!(loaded.leaf(), cache.leaf());
+    }
+
+    #[test]
+    fn reachability_from_seed_differs_from_initial() {
+        use crate::search::core::{retrospective_search, Btable, SearchResult};
+
+        // f5 f6（平行）から始めた表と、初期配置からの表
+        let seed = Board::play_sequence(&[37, 45]).unwrap();
+        let from_seed = LeafCache::from_seed(7, &seed).unwrap();
+        let from_initial = LeafCache::new(7);
+        assert!(from_seed.leaf().is_subset(from_initial.leaf()));
+        assert!(from_seed.leaf_count() < from_initial.leaf_count());
+
+        // f5 d6 c3 d3 c4（縦取りの定石）は初期配置からは到達可能だが、f5 f6 からは到達できない
+        let board = Board::play_sequence(&[37, 43, 18, 19, 26]).unwrap();
+        let search = |cache: &LeafCache| {
+            let mut searched = Btable::new(1 << 16, 1 << 8);
+            let mut retroflips = vec![];
+            let mut node_count = 0;
+            retrospective_search(
+                &board,
+                false,
+                7,
+                cache.leaf(),
+                &mut searched,
+                &mut retroflips,
+                &mut node_count,
+                1_000_000,
+            )
+            .unwrap()
+        };
+        assert_eq!(search(&from_initial), SearchResult::Found);
+        assert_eq!(search(&from_seed), SearchResult::NotFound);
+    }
+
+    #[test]
+    fn parallel_opening_gives_a_different_leaf_table() {
+        let standard = LeafCache::new(6);
+        let parallel = LeafCache::with_ruleset(6, &Ruleset::parallel());
+        assert_eq!(parallel.ruleset(), &Ruleset::parallel());
+        assert!(!parallel.leaf().is_empty());
+        assert_ne!(parallel.leaf(), standard.leaf());
+        // 逐次版の上限付き探索とも一致する
+        let bounded = LeafCache::with_ruleset_bounded(6, &Ruleset::parallel(), 1 << 20).unwrap();
+        assert_eq!(bounded.leaf(), parallel.leaf());
+    }
+
+    #[test]
+    fn rejects_old_format_version() {
+        let dir = scratch_dir("old_version");
+        let path = dir.join("leaf_cache.bin");
+        LeafCache::new(7).save(&path).unwrap();
+        let mut bytes = fs::read(&path).unwrap();
+        bytes[7] = b'1';
+        fs::write(&path, bytes).unwrap();
+        let err = LeafCache::load(&path, 7).err().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("version"), "{}", err);
+    }
 }
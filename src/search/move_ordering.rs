@@ -1,9 +1,15 @@
 use std::collections::HashSet;
 
+use clap::ValueEnum;
+
 use crate::{
-    othello::{get_moves, Board, Direction},
-    prunings::{occupancy::check_occupancy, seg3::check_seg3_more},
-    search::core::{retrospective_flip, Btable, SearchResult},
+    othello::{
+        flip, has_move, shift, validate_board, Board, BoardValidation, Direction, CENTER_MASK,
+    },
+    prunings::{occupancy::check_occupancy, passes_search_prunings},
+    search::core::{leaf_discs, retrospective_flip, reverse_candidates, Btable, SearchResult},
+    verbosity::DEBUG,
+    vprintln,
 };
 
 /// in_sq : 内部のみのマスの数(8連結)
@@ -40,7 +46,13 @@ fn features(b: &Board) -> (u16, u16, u16, u16) {
     (in_sq, in_edge, sm_edges_all, sm_edges_min)
 }
 
+/// `h_function` の上限。4 つの項はそれぞれ 1 以下で、石数は 64 以下なので `4 * 2^64` を超えない。
+pub const H_FUNCTION_MAX: f64 = 4.0 * 18_446_744_073_709_551_616.0;
+
 /// boardが到達可能かどうかを計算するヒューリスティック関数
+///
+/// 値は常に `(0, H_FUNCTION_MAX]` の有限値で、NaN や無限大にはならない
+/// (各項の分母は 1 以上、`2^石数` は `powi` で厳密に計算される)。
 pub fn h_function(b: &Board) -> f64 {
     let (in_sq, in_edge, sm_edge_sum, sm_edge_min) = features(b);
     let mut ans = 0.0;
@@ -49,7 +61,84 @@ pub fn h_function(b: &Board) -> f64 {
     ans += 1.0 / (sm_edge_sum + 1) as f64;
     ans += 1.0 / (sm_edge_min + 1) as f64;
     let scount = (b.player | b.opponent).count_ones();
-    ans * 2_f64.powf(scount as f64)
+    let h = ans * 2_f64.powi(scount as i32);
+    debug_assert!(h.is_finite() && h <= H_FUNCTION_MAX);
+    h
+}
+
+/// `h_function` の対数版: `ln(特徴量の和) + 石数 * ln 2`。
+///
+/// `ln` は単調増加なので局面の順位は `h_function` と同じだが、値は `ln 4 + 64 ln 2` (約 45.7)
+/// 以下に収まり、石数の寄与と特徴量の寄与を同じ尺度で比べられる。
+pub fn h_function_log(b: &Board) -> f64 {
+    let (in_sq, in_edge, sm_edge_sum, sm_edge_min) = features(b);
+    let mut ans = 0.0;
+    ans += 1.0 / (in_sq + 1) as f64;
+    ans += 1.0 / (in_edge + 1) as f64;
+    ans += 1.0 / (sm_edge_sum + 1) as f64;
+    ans += 1.0 / (sm_edge_min + 1) as f64;
+    let scount = (b.player | b.opponent).count_ones();
+    ans.ln() + scount as f64 * std::f64::consts::LN_2
+}
+
+/// 優先度付き探索で使うヒューリスティック
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Heuristic {
+    /// feature sum times 2^discs (`h_function`)
+    #[default]
+    Pow2,
+    /// the same score in log space (`h_function_log`)
+    Log,
+}
+
+impl Heuristic {
+    pub fn eval(self, b: &Board) -> f64 {
+        match self {
+            Heuristic::Pow2 => h_function(b),
+            Heuristic::Log => h_function_log(b),
+        }
+    }
+}
+
+/// 逆方向探索の難しさの目安（大きいほど時間がかかりそうな局面）。バッチを易しい順に
+/// 並べたり、局面ごとにノード数の予算を配分したりするための発見的な値で、探索木の大きさの
+/// 推定値ではない。
+///
+/// 探索の枝刈りで即座に落ちる局面は 0。それ以外は次の和（0 以上）:
+/// - 初期局面までの手数 × ln(1 + 直前の着手になりうる相手の石の数)。
+///   「なりうる」は、取り除いても占有パターンが `check_occupancy` を通る中央以外の石
+/// - ln(1 + 空きマスのうち石に隣接するものの数)
+/// - `h_function` の特徴量の和の対数（move ordering で後回しにされる局面ほど大きい）
+pub fn difficulty_estimate(b: &Board) -> f64 {
+    if !passes_search_prunings(b.player, b.opponent) {
+        return 0.0;
+    }
+    let occupied = b.player | b.opponent;
+    let depth = occupied.count_ones().saturating_sub(4) as f64;
+
+    let mut removable = 0u32;
+    let mut candidates = b.opponent & !CENTER_MASK;
+    while candidates != 0 {
+        let bit = candidates & candidates.wrapping_neg();
+        candidates ^= bit;
+        if check_occupancy(occupied ^ bit) {
+            removable += 1;
+        }
+    }
+
+    let neighbors = Direction::all()
+        .into_iter()
+        .fold(0u64, |acc, d| acc | shift(d, occupied));
+    let frontier = (neighbors & !occupied).count_ones() as f64;
+
+    let (in_sq, in_edge, sm_edge_sum, sm_edge_min) = features(b);
+    let feature_sum = 1.0 / (in_sq + 1) as f64
+        + 1.0 / (in_edge + 1) as f64
+        + 1.0 / (sm_edge_sum + 1) as f64
+        + 1.0 / (sm_edge_min + 1) as f64;
+
+    let score = depth * (1.0 + removable as f64).ln() + (1.0 + frontier).ln() + feature_sum.ln();
+    score.max(0.0)
 }
 
 /// retrospective_searchでmove orderingを実行するバージョン
@@ -59,6 +148,8 @@ pub fn h_function(b: &Board) -> f64 {
 /// - `retrospective_searched`: 既訪問ユニーク局面
 /// - `retroflips`: ディスク数ごとに使い回す作業バッファ（長さ 10_000 の配列を入れておく）
 ///   インデックスは `num_disc as usize` を想定。必要に応じて拡張する。
+///
+/// 不正な盤面は探索せずに `Err`。
 pub fn retrospective_search_move_ordering(
     board: &Board,
     from_pass: bool,
@@ -68,18 +159,46 @@ pub fn retrospective_search_move_ordering(
     retroflips: &mut Vec<[u64; 10_000]>,
     node_count: &mut usize,
     node_limit: usize,
+) -> Result<SearchResult, BoardValidation> {
+    validate_board(board)?;
+    Ok(retrospective_search_move_ordering_impl(
+        board,
+        from_pass,
+        discs,
+        leafnode,
+        retrospective_searched,
+        retroflips,
+        node_count,
+        node_limit,
+    ))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn retrospective_search_move_ordering_impl(
+    board: &Board,
+    from_pass: bool,
+    discs: i32,
+    leafnode: &HashSet<[u64; 2]>,
+    retrospective_searched: &mut Btable,
+    retroflips: &mut Vec<[u64; 10_000]>,
+    node_count: &mut usize,
+    node_limit: usize,
 ) -> SearchResult {
     let uni = board.unique();
     let num_disc = board.popcount() as usize;
 
     // 順方向探索の leafnode に含まれているか確認
-    if (num_disc as i32) <= discs {
+    let leaf_discs = leaf_discs(discs);
+    if (num_disc as i32) < leaf_discs {
+        return SearchResult::Unknown;
+    }
+    if num_disc as i32 == leaf_discs {
         return if leafnode.contains(&uni) {
-            println!("info: found unique board in leafnodes:");
-            println!("unique player = {}", uni[0]);
-            println!("unique opponent = {}", uni[1]);
-            println!("board player = {}", board.player);
-            println!("board opponent = {}", board.opponent);
+            vprintln!(DEBUG, "info: found unique board in leafnodes:");
+            vprintln!(DEBUG, "unique player = {}", uni[0]);
+            vprintln!(DEBUG, "unique opponent = {}", uni[1]);
+            vprintln!(DEBUG, "board player = {}", board.player);
+            vprintln!(DEBUG, "board opponent = {}", board.opponent);
             SearchResult::Found
         } else {
             SearchResult::NotFound
@@ -108,17 +227,7 @@ pub fn retrospective_search_move_ordering(
     //    return SearchResult::Unknown;
     //}
 
-    let occupied = board.player | board.opponent;
-    //if !is_connected(occupied) {
-    //    return SearchResult::NotFound;
-    //}
-    //if !check_seg3(occupied) {
-    //    return SearchResult::NotFound;
-    //}
-    if !check_occupancy(occupied) {
-        return SearchResult::NotFound;
-    }
-    if !check_seg3_more(board.player, board.opponent) {
+    if !passes_search_prunings(board.player, board.opponent) {
         return SearchResult::NotFound;
     }
     // let line = board.to_string();
@@ -129,12 +238,12 @@ pub fn retrospective_search_move_ordering(
     // パスの処理
     // from_pass==false かつ 相手に合法手が無いならば、1手前に相手がパスしたと仮定
     if !from_pass {
-        if get_moves(board.opponent, board.player) == 0 {
+        if !has_move(board.opponent, board.player) {
             let prev = Board {
                 player: board.opponent,
                 opponent: board.player,
             };
-            match retrospective_search_move_ordering(
+            match retrospective_search_move_ordering_impl(
                 &prev,
                 true,
                 discs,
@@ -145,11 +254,11 @@ pub fn retrospective_search_move_ordering(
                 node_limit,
             ) {
                 SearchResult::Found => {
-                    println!("pass found");
+                    vprintln!(DEBUG, "pass found");
                     return SearchResult::Found;
                 }
                 SearchResult::Unknown => {
-                    println!("pass found");
+                    vprintln!(DEBUG, "pass found");
                     return SearchResult::Unknown;
                 }
                 SearchResult::NotFound => {}
@@ -158,7 +267,7 @@ pub fn retrospective_search_move_ordering(
     }
 
     // 相手石（中央4マス以外）を候補として走査
-    let mut b = board.opponent & !0x0000_0018_1800_0000u64;
+    let mut b = reverse_candidates(board.opponent, CENTER_MASK);
     if b == 0 {
         return SearchResult::NotFound;
     }
@@ -197,14 +306,19 @@ pub fn retrospective_search_move_ordering(
                 player: board.opponent ^ (flipped | (1u64 << index)),
                 opponent: board.player ^ flipped,
             };
+            if flip(index as usize, prev.player, prev.opponent) != flipped {
+                continue;
+            }
             next_w_score.push((h_function(&prev), prev));
             // next_w_score.push((0.0, prev));
         }
     }
+    // h_function は有限値なので total_cmp は通常の大小比較と一致する。
+    // 同点は局面の辞書順で決まるので、子の並びは実行ごとに変わらない。
     next_w_score.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
     for i in 0..next_w_score.len() {
         let (_, prev) = next_w_score[i];
-        match retrospective_search_move_ordering(
+        match retrospective_search_move_ordering_impl(
             &prev,
             false,
             discs,
@@ -227,3 +341,82 @@ pub fn retrospective_search_move_ordering(
     }
     SearchResult::NotFound
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::reachable::random_reachable_board;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn h_function_is_finite_on_dense_boards() {
+        let mut rng = StdRng::seed_from_u64(622);
+        let mut boards: Vec<Board> = (0..50)
+            .map(|_| random_reachable_board(&mut rng, 60))
+            .collect();
+        boards.push(random_reachable_board(&mut rng, 64));
+        // 全マスが埋まり、特徴量の分母が最小になる局面
+        boards.push(Board::new(u64::MAX, 0));
+        for b in &boards {
+            let h = h_function(b);
+            assert!(h.is_finite() && h > 0.0 && h <= H_FUNCTION_MAX, "{}", h);
+            assert!(ordered_float::NotNan::new(h).is_ok());
+            // 対数版は同じ値の対数
+            let log = h_function_log(b);
+            assert!((log - h.ln()).abs() < 1e-9, "{} vs {}", log, h.ln());
+        }
+    }
+
+    #[test]
+    fn log_heuristic_separates_boards_with_equal_disc_counts() {
+        let mut rng = StdRng::seed_from_u64(623);
+        let a = random_reachable_board(&mut rng, 50);
+        let b = (0..)
+            .map(|_| random_reachable_board(&mut rng, 50))
+            .find(|b| (h_function(b) / h_function(&a)).ln().abs() > 0.1)
+            .unwrap();
+        // 2^50 倍された値では 1 石分の差（2 倍）にも満たないが、
+        let (ha, hb) = (h_function(&a), h_function(&b));
+        assert!(ha.max(hb) / ha.min(hb) < 2.0);
+        // 対数版では特徴量の差がそのまま石数の寄与（ln 2）と同じ尺度の差として残る
+        let (la, lb) = (h_function_log(&a), h_function_log(&b));
+        assert!((la - lb).abs() > 0.1);
+        assert!(((la - lb) - (ha / hb).ln()).abs() < 1e-9);
+        // 順位は変わらない
+        assert_eq!(ha < hb, la < lb);
+        assert_eq!(Heuristic::Log.eval(&a), la);
+        assert_eq!(Heuristic::Pow2.eval(&a), ha);
+    }
+
+    #[test]
+    fn difficulty_grows_from_the_opening_to_dense_boards() {
+        let mut rng = StdRng::seed_from_u64(636);
+        // f5 d6（6 石）
+        let opening = Board::play_sequence(&[37, 43]).unwrap();
+        let easy = difficulty_estimate(&opening);
+        assert!(easy.is_finite() && easy >= 0.0, "{}", easy);
+        for _ in 0..10 {
+            let mid = random_reachable_board(&mut rng, 30);
+            let dense = random_reachable_board(&mut rng, 50);
+            let (mid_score, dense_score) = (difficulty_estimate(&mid), difficulty_estimate(&dense));
+            assert!(
+                easy < mid_score,
+                "{} < {}: {}",
+                easy,
+                mid_score,
+                mid.to_string()
+            );
+            assert!(
+                easy < dense_score,
+                "{} < {}: {}",
+                easy,
+                dense_score,
+                dense.to_string()
+            );
+        }
+        // 枝刈りで即座に落ちる局面（中央から離れた孤立した石）は 0
+        let isolated = Board::new(opening.player | 1, opening.opponent);
+        assert_eq!(difficulty_estimate(&isolated), 0.0);
+    }
+}
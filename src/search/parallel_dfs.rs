@@ -1,13 +1,14 @@
 use dashmap::DashSet;
-use rayon::ThreadPoolBuilder;
 use std::cell::RefCell;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 
-use crate::othello::{get_moves, Board};
-use crate::prunings::occupancy::check_occupancy;
-use crate::prunings::seg3::check_seg3_more;
-use crate::search::core::{retrospective_flip, SearchResult};
+use crate::othello::{flip, has_move, validate_board, Board, BoardValidation, CENTER_MASK};
+use crate::prunings::passes_search_prunings;
+use crate::search::core::{leaf_discs, retrospective_flip, reverse_candidates, SearchResult};
 use crate::search::move_ordering::h_function;
+use crate::search::threads::ThreadConfig;
+use crate::veprintln;
+use crate::verbosity::DEBUG;
 
 // 並列パラメータ（必要なら調整）
 const PAR_MAX_DEPTH: usize = 12; // この深さまでは spawn を許可
@@ -37,21 +38,38 @@ struct ParShared<'a> {
 
     // 早期停止フラグ: 0=進行中, 1=Found, 2=Unknown(上限超過)
     stop: &'a AtomicUsize,
+
+    // true ならリーフが見つかっても止めずに全部の部分木を調べ、見つかったことは found に残す
+    exhaustive: bool,
+    found: &'a AtomicBool,
+}
+
+impl ParShared<'_> {
+    /// リーフが見つかったことを記録する。早期終了するなら全体に停止を通知する
+    fn report_found(&self) {
+        if self.exhaustive {
+            self.found.store(true, Ordering::Relaxed);
+        } else {
+            let _ = self
+                .stop
+                .compare_exchange(0, 1, Ordering::Relaxed, Ordering::Relaxed);
+        }
+    }
 }
 
 // ユーティリティ：スレッドプール初期化（必要なら呼ぶ）
+// `ThreadConfig::new(num_threads).install()` と同じ（`None` なら論理コア数）
 pub fn init_rayon(num_threads: Option<usize>) {
-    use std::sync::Once;
-    static ONCE: Once = Once::new();
-    ONCE.call_once(|| {
-        let mut b = ThreadPoolBuilder::new();
-        if let Some(n) = num_threads {
-            b = b.num_threads(n);
-        }
-        b.build_global().expect("failed to init global rayon pool");
-    });
+    ThreadConfig::new(num_threads).install();
 }
 
+/// 不正な盤面は探索せずに `Err`
+///
+/// 既定（`exhaustive == false`）ではどれかのワーカーがリーフを見つけた時点で全体を止める。
+/// `exhaustive` ならリーフが見つかっても止めずに、ノード数の上限に達するまで全ワーカーが
+/// 部分木を調べ尽くしてから `Found` を返す（探索木の大きさを比べる計測用）。到達不能な盤面と
+/// 同じだけ展開するので、`Found` になる盤面では早期終了より大幅に遅くなることがある。
+/// 調べ尽くす前に上限に達しても、リーフが見つかっていれば結果は `Found`。
 pub fn retrospective_search_parallel(
     board: &Board,
     from_pass: bool,
@@ -59,13 +77,38 @@ pub fn retrospective_search_parallel(
     leafnode: &std::collections::HashSet<[u64; 2]>,
     node_limit: usize,
     table_limit: usize,
-) -> SearchResult {
+    exhaustive: bool,
+) -> Result<SearchResult, BoardValidation> {
+    retrospective_search_parallel_counted(
+        board,
+        from_pass,
+        discs,
+        leafnode,
+        node_limit,
+        table_limit,
+        exhaustive,
+    )
+    .map(|(result, _)| result)
+}
+
+/// `retrospective_search_parallel` と同じだが、走査ノード数も返す
+pub fn retrospective_search_parallel_counted(
+    board: &Board,
+    from_pass: bool,
+    discs: i32,
+    leafnode: &std::collections::HashSet<[u64; 2]>,
+    node_limit: usize,
+    table_limit: usize,
+    exhaustive: bool,
+) -> Result<(SearchResult, usize), BoardValidation> {
+    validate_board(board)?;
     let visited = DashSet::new();
     let node_count = AtomicUsize::new(0);
     let table_count = AtomicUsize::new(0);
     let node_per_stone: [AtomicUsize; 65] = std::array::from_fn(|_| AtomicUsize::new(0));
     let done_per_stone: [AtomicUsize; 65] = std::array::from_fn(|_| AtomicUsize::new(0));
     let stop = AtomicUsize::new(0);
+    let found = AtomicBool::new(false);
 
     let shared = ParShared {
         leafnode,
@@ -78,19 +121,25 @@ pub fn retrospective_search_parallel(
         node_per_stone: &node_per_stone,
         done_per_stone: &done_per_stone,
         stop: &stop,
+        exhaustive,
+        found: &found,
     };
 
     // ルート呼び出し
-    let res = par_retro_core(board, from_pass, &shared, 0);
+    let mut res = par_retro_core(board, from_pass, &shared, 0);
+    if found.load(Ordering::Relaxed) {
+        res = SearchResult::Found;
+    }
     for i in 0..=64 {
-        eprintln!(
+        veprintln!(
+            DEBUG,
             "{}: {} / {}",
             i,
             done_per_stone[i].load(Ordering::Relaxed),
             node_per_stone[i].load(Ordering::Relaxed)
         );
     }
-    res
+    Ok((res, node_count.load(Ordering::Relaxed)))
 }
 
 // 動的並列コア
@@ -108,17 +157,19 @@ fn par_retro_core(board: &Board, from_pass: bool, sh: &ParShared, depth: usize)
     // カウンターの変更
     sh.done_per_stone[num_disc].fetch_add(1, Ordering::Relaxed);
 
-    // しきい以下なら leafnode 照合のみ
-    if (num_disc as i32) <= sh.discs {
+    // しきい以下なら leafnode 照合のみ（しきい未満は根でしか起きず、表では判定できない）
+    let leaf_discs = leaf_discs(sh.discs);
+    if (num_disc as i32) < leaf_discs {
+        return SearchResult::Unknown;
+    }
+    if num_disc as i32 == leaf_discs {
         let r = if sh.leafnode.contains(&uni) {
             SearchResult::Found
         } else {
             SearchResult::NotFound
         };
         if r == SearchResult::Found {
-            let _ = sh
-                .stop
-                .compare_exchange(0, 1, Ordering::Relaxed, Ordering::Relaxed);
+            sh.report_found();
         }
         return r;
     }
@@ -146,16 +197,14 @@ fn par_retro_core(board: &Board, from_pass: bool, sh: &ParShared, depth: usize)
     }
 
     // 形状フィルタ
-    let occupied = board.player | board.opponent;
-    if !check_occupancy(occupied) || !check_seg3_more(board.player, board.opponent) {
-        // !is_connected(occupied) || !check_seg3(occupied)
+    if !passes_search_prunings(board.player, board.opponent) {
         return SearchResult::NotFound;
     }
 
     // ---- 子ノード列挙（パス + 直前着手候補からの retroflips）----
     // 1) パス枝（from_pass==false かつ 相手に合法手無し）
     let mut children: Vec<(Board, bool)> = Vec::new(); // (prev_board, from_pass_prev)
-    if !from_pass && get_moves(board.opponent, board.player) == 0 {
+    if !from_pass && !has_move(board.opponent, board.player) {
         children.push((
             Board {
                 player: board.opponent,
@@ -166,7 +215,7 @@ fn par_retro_core(board: &Board, from_pass: bool, sh: &ParShared, depth: usize)
     }
 
     // 2) 直前着手位置ごとの “可能 flip 集合” 展開
-    let b = board.opponent & !0x0000_0018_1800_0000u64;
+    let b = reverse_candidates(board.opponent, CENTER_MASK);
     if b == 0 && children.is_empty() {
         return SearchResult::NotFound;
     }
@@ -190,6 +239,9 @@ fn par_retro_core(board: &Board, from_pass: bool, sh: &ParShared, depth: usize)
                     player: board.opponent ^ (flipped | (1u64 << index)),
                     opponent: board.player ^ flipped,
                 };
+                if flip(index as usize, prev.player, prev.opponent) != flipped {
+                    continue;
+                }
                 children.push((prev, false));
             }
         }
@@ -229,9 +281,7 @@ fn par_retro_core(board: &Board, from_pass: bool, sh: &ParShared, depth: usize)
                 match r0 {
                     SearchResult::Found => {
                         local_best.store(SearchResult::Found as usize, Ordering::Relaxed);
-                        let _ =
-                            sh.stop
-                                .compare_exchange(0, 1, Ordering::Relaxed, Ordering::Relaxed);
+                        sh.report_found();
                     }
                     SearchResult::Unknown => {
                         if local_best.load(Ordering::Relaxed) == (SearchResult::NotFound as usize) {
@@ -255,12 +305,7 @@ fn par_retro_core(board: &Board, from_pass: bool, sh: &ParShared, depth: usize)
                     match r {
                         SearchResult::Found => {
                             lb_ref.store(SearchResult::Found as usize, Ordering::Relaxed);
-                            let _ = sh_ref.stop.compare_exchange(
-                                0,
-                                1,
-                                Ordering::Relaxed,
-                                Ordering::Relaxed,
-                            );
+                            sh_ref.report_found();
                         }
                         SearchResult::Unknown => {
                             if lb_ref.load(Ordering::Relaxed) == (SearchResult::NotFound as usize) {
@@ -279,10 +324,12 @@ fn par_retro_core(board: &Board, from_pass: bool, sh: &ParShared, depth: usize)
             _ => SearchResult::NotFound,
         }
     } else {
-        // 直列分岐はそのまま
+        // 直列分岐はそのまま（exhaustive なら Found でも残りの子を調べる）
+        let mut found = false;
         for (bd, fp) in children {
             let r = par_retro_core(&bd, fp, sh, depth + 1);
             match r {
+                SearchResult::Found if sh.exhaustive => found = true,
                 SearchResult::Found => return SearchResult::Found,
                 SearchResult::Unknown => return SearchResult::Unknown,
                 SearchResult::NotFound => {}
@@ -293,6 +340,112 @@ fn par_retro_core(board: &Board, from_pass: bool, sh: &ParShared, depth: usize)
                 _ => {}
             }
         }
-        SearchResult::NotFound
+        if found {
+            SearchResult::Found
+        } else {
+            SearchResult::NotFound
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::core::{retrospective_search, search, Btable};
+    use crate::search::reachable::random_reachable_board;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use std::collections::HashSet;
+
+    /// 到達可能な局面と、その石を 1 つ塗り替えた（多くは到達不能な）局面を混ぜた盤面の組
+    fn mixed_batch(rng: &mut StdRng, n: usize) -> Vec<Board> {
+        let mut boards = vec![];
+        while boards.len() < n {
+            let discs = rng.random_range(9..=13);
+            let b = random_reachable_board(rng, discs);
+            boards.push(b);
+            let outer = (b.player | b.opponent) & !CENTER_MASK;
+            let mut m = outer;
+            for _ in 0..rng.random_range(0..outer.count_ones()) {
+                m &= m - 1;
+            }
+            let bit = m & m.wrapping_neg();
+            let recolored = Board::new(b.player ^ bit, b.opponent ^ bit);
+            if validate_board(&recolored).is_ok() {
+                boards.push(recolored);
+            }
+        }
+        boards
+    }
+
+    #[test]
+    fn parallel_and_sequential_searches_agree() {
+        let mut searched = HashSet::new();
+        let mut leafnode = HashSet::new();
+        search(&Board::initial(), &mut searched, &mut leafnode, 8);
+        let mut rng = StdRng::seed_from_u64(616);
+        let mut verdicts = [0usize; 2];
+        for board in mixed_batch(&mut rng, 40) {
+            let mut table = Btable::new(1 << 16, 1 << 8);
+            let sequential = retrospective_search(
+                &board,
+                false,
+                8,
+                &leafnode,
+                &mut table,
+                &mut vec![],
+                &mut 0,
+                usize::MAX,
+            )
+            .unwrap();
+            for exhaustive in [false, true] {
+                let parallel = retrospective_search_parallel(
+                    &board,
+                    false,
+                    8,
+                    &leafnode,
+                    usize::MAX,
+                    usize::MAX,
+                    exhaustive,
+                )
+                .unwrap();
+                assert_eq!(parallel, sequential, "{}", board.to_string());
+            }
+            verdicts[(sequential == SearchResult::Found) as usize] += 1;
+        }
+        // 両方の判定が含まれている
+        assert!(verdicts[0] > 0 && verdicts[1] > 0, "{:?}", verdicts);
+    }
+
+    #[test]
+    fn exhaustive_search_visits_at_least_as_many_nodes_as_early_exit() {
+        let mut searched = HashSet::new();
+        let mut leafnode = HashSet::new();
+        search(&Board::initial(), &mut searched, &mut leafnode, 8);
+        let mut rng = StdRng::seed_from_u64(680);
+        let mut larger = 0;
+        for _ in 0..10 {
+            let discs = rng.random_range(12..=14);
+            let board = random_reachable_board(&mut rng, discs);
+            let run = |exhaustive| {
+                retrospective_search_parallel_counted(
+                    &board,
+                    false,
+                    8,
+                    &leafnode,
+                    usize::MAX,
+                    usize::MAX,
+                    exhaustive,
+                )
+                .unwrap()
+            };
+            let (early, early_nodes) = run(false);
+            let (exhaustive, exhaustive_nodes) = run(true);
+            assert_eq!(early, SearchResult::Found);
+            assert_eq!(exhaustive, SearchResult::Found);
+            assert!(exhaustive_nodes >= early_nodes, "{}", board.to_string());
+            larger += (exhaustive_nodes > early_nodes) as usize;
+        }
+        assert!(larger > 0);
     }
 }
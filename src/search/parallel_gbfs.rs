@@ -1,14 +1,21 @@
 use crossbeam_skiplist::SkipSet;
 use dashmap::DashSet;
 use ordered_float::NotNan;
-use rayon::ThreadPoolBuilder;
-use std::thread;
+use rayon::ThreadPool;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
 
-use crate::othello::{get_moves, Board, CENTER_MASK};
-use crate::prunings::seg3::check_seg3_more;
-use crate::prunings::{linear_programming::check_lp, occupancy::check_occupancy};
+use crate::othello::{
+    flip, has_move, validate_board, Board, BoardKey, BoardValidation, CENTER_MASK,
+};
+use crate::prunings::{linear_programming::check_lp, PruningConfig};
 use crate::search::core::{retrospective_flip, SearchResult};
-use crate::search::move_ordering::h_function;
+use crate::search::move_ordering::Heuristic;
+use crate::search::threads::ThreadConfig;
+use crate::verbosity::{DEBUG, INFO};
+use crate::{veprintln, vprintln};
 
 use std::{
     sync::{
@@ -29,12 +36,10 @@ fn is_leaf(x: [u64; 2], leafnode: &Vec<[u64; 2]>, discs: i32) -> bool {
     false
 }
 
-fn heuristic_function(x: [u64; 2]) -> f64 {
-    h_function(&Board::new(x[0], x[1]))
+fn heuristic_function(heuristic: Heuristic, x: BoardKey) -> f64 {
+    heuristic.eval(&Board::from(x))
 }
 
-const NUM_THREADS: usize = 64; // 64スレッド程度
-
 // retroflips やans のallocateでコストがかかっている．使いまわしをしたほうが節約はできるはず．
 fn prev_states(b: [u64; 2]) -> Vec<[u64; 2]> {
     let board = Board::new(b[0], b[1]);
@@ -51,8 +56,11 @@ fn prev_states(b: [u64; 2]) -> Vec<[u64; 2]> {
                 player: board.opponent ^ (flipped | (1u64 << index)),
                 opponent: board.player ^ flipped,
             };
+            if flip(index as usize, prev.player, prev.opponent) != flipped {
+                continue;
+            }
             ans.push([prev.player, prev.opponent]);
-            if get_moves(prev.opponent, prev.player) == 0 {
+            if !has_move(prev.opponent, prev.player) {
                 ans.push([prev.opponent, prev.player]);
             }
         }
@@ -60,22 +68,184 @@ fn prev_states(b: [u64; 2]) -> Vec<[u64; 2]> {
     ans
 }
 
+/// 局面の列を `BoardKey` の生データ（BFS の `.bin` と同じ形式）で書き出す
+pub fn write_positions(path: &Path, positions: &[BoardKey]) -> io::Result<()> {
+    let mut w = BufWriter::new(File::create(path)?);
+    w.write_all(bytemuck::cast_slice(positions))?;
+    w.flush()
+}
+
+/// `write_positions` で書き出した局面の列を読み込む
+pub fn read_positions(path: &Path) -> io::Result<Vec<BoardKey>> {
+    let mut r = BufReader::new(File::open(path)?);
+    let mut bytes = vec![];
+    r.read_to_end(&mut bytes)?;
+    if bytes.len() % 16 != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{}: size is not a multiple of 16 bytes", path.display()),
+        ));
+    }
+    let mut positions = vec![BoardKey::default(); bytes.len() / 16];
+    bytemuck::cast_slice_mut(&mut positions).copy_from_slice(&bytes);
+    Ok(positions)
+}
+
+/// frontier（優先度キューの中身）と既訪問集合を保存するファイルの組
+#[derive(Debug, Clone)]
+pub struct FrontierFiles {
+    pub frontier: PathBuf,
+    pub visited: PathBuf,
+    /// 書き出した探索の設定（`frontier_header`）。再開前に同じ探索の続きかを確かめる
+    pub header: PathBuf,
+}
+
+impl FrontierFiles {
+    /// `dir` 以下の `index` 番目の盤面用のファイル
+    pub fn in_dir(dir: &Path, index: usize) -> Self {
+        FrontierFiles {
+            frontier: dir.join(format!("frontier_{}.bin", index)),
+            visited: dir.join(format!("visited_{}.bin", index)),
+            header: dir.join(format!("frontier_{}.header", index)),
+        }
+    }
+
+    pub fn exists(&self) -> bool {
+        self.frontier.exists() && self.visited.exists()
+    }
+
+    /// 保存されている frontier が `header` の探索で書き出されたものか。
+    /// 設定の記録が無い（古い形式の）ファイルは別の探索のものとみなす
+    pub fn matches(&self, header: &str) -> bool {
+        fs::read_to_string(&self.header).is_ok_and(|saved| saved == header)
+    }
+}
+
+/// frontier を書き出した探索の設定（正規形の盤面・しきい値・枝刈り・キューの順位付け）。
+/// 盤面の番号だけで再開すると、入力を差し替えたときに別の盤面の frontier から探索してしまう
+pub fn frontier_header(
+    board: &Board,
+    discs: i32,
+    prunings: PruningConfig,
+    opts: &GbfsOptions,
+) -> String {
+    let [player, opponent] = board.unique();
+    format!(
+        "board={:016x}{:016x} discs={} prunings={:?} heuristic={:?} max_nodes_per_disc={:?}\n",
+        player, opponent, discs, prunings, opts.heuristic, opts.max_nodes_per_disc
+    )
+}
+
+/// GBFS の再開・途中保存の設定
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GbfsOptions<'a> {
+    /// 指定されていれば、入力盤面の代わりにここから frontier と既訪問集合を読み込んで始める
+    pub resume_from: Option<&'a FrontierFiles>,
+    /// 指定されていれば、`dump_interval` ノード展開するごとと、上限で打ち切ったときに書き出す
+    pub dump_to: Option<&'a FrontierFiles>,
+    /// 途中保存の間隔（展開ノード数、0 なら打ち切り時のみ）
+    pub dump_interval: usize,
+    /// 優先度キューの順位付けに使うヒューリスティック
+    pub heuristic: Heuristic,
+    /// 指定されていれば、石数ごとにキューへ積む局面数をこの数までに抑える。
+    /// 上限に達した段の子局面は積まずに捨てるので、キューが尽きても `NotFound` ではなく `Unknown` になる。
+    pub max_nodes_per_disc: Option<usize>,
+}
+
+/// 段ごとの局面数 `count` を、上限 `cap` 未満なら 1 増やして true を返す（上限が無ければ常に増やす）
+fn reserve_slot(count: &AtomicUsize, cap: Option<usize>) -> bool {
+    match cap {
+        None => {
+            count.fetch_add(1, Ato::Relaxed);
+            true
+        }
+        Some(cap) => count
+            .fetch_update(Ato::Relaxed, Ato::Relaxed, |c| (c < cap).then_some(c + 1))
+            .is_ok(),
+    }
+}
+
+fn dump_frontier(
+    files: &FrontierFiles,
+    header: &str,
+    pq: &SkipSet<(NotNan<f64>, BoardKey)>,
+    visited: &DashSet<BoardKey>,
+) -> io::Result<()> {
+    let frontier: Vec<BoardKey> = pq.iter().map(|e| e.value().1).collect();
+    let mut seen: Vec<BoardKey> = visited.iter().map(|x| *x).collect();
+    seen.sort_unstable();
+    write_positions(&files.frontier, &frontier)?;
+    write_positions(&files.visited, &seen)?;
+    fs::write(&files.header, header)?;
+    vprintln!(
+        INFO,
+        "info: dumped frontier = {}, visited = {} to '{}'",
+        frontier.len(),
+        seen.len(),
+        files.frontier.display()
+    );
+    Ok(())
+}
+
+/// ワーカの数（`ThreadConfig::global()` のスレッド数）と、その数のスレッドを持つプール
+fn worker_pool() -> (usize, ThreadPool) {
+    let threads = ThreadConfig::global();
+    (threads.num_threads(), threads.build_pool("gbfs-worker"))
+}
+
 /// 並列 Greedy Best-First Search
 /// - start: 初期状態
-/// - 戻り値: 見つかった leaf の状態（見つからなければ None）
+/// - prunings: 子局面に使う枝刈り。`lp` なら展開する局面ごとに LP も解く（LP が探索時間の大半を占める）
+/// - 戻り値: 見つかった leaf の状態（見つからなければ None）。不正な盤面は `Err`
 pub fn parallel_retrospective_greedy_best_first_search(
     board: &Board,
     discs: i32,
     leafnode: &Vec<[u64; 2]>,
     node_limit: usize,
-    use_lp: bool,
-) -> SearchResult {
+    prunings: PruningConfig,
+) -> Result<SearchResult, BoardValidation> {
+    validate_board(board)?;
+    Ok(parallel_retrospective_greedy_best_first_search_with(
+        board,
+        discs,
+        leafnode,
+        node_limit,
+        prunings,
+        GbfsOptions::default(),
+    )
+    .expect("no file I/O without resume/dump files"))
+}
+
+/// `parallel_retrospective_greedy_best_first_search` に frontier の読み込み・書き出しを加えた版。
+/// 不正な盤面は `ErrorKind::InvalidInput`、`resume_from` が別の盤面や設定の探索で書き出されたものなら
+/// `ErrorKind::InvalidData`。
+pub fn parallel_retrospective_greedy_best_first_search_with(
+    board: &Board,
+    discs: i32,
+    leafnode: &Vec<[u64; 2]>,
+    node_limit: usize,
+    prunings: PruningConfig,
+    opts: GbfsOptions,
+) -> io::Result<SearchResult> {
+    validate_board(board)?;
+    let header = frontier_header(board, discs, prunings, &opts);
+    if let Some(files) = opts.resume_from {
+        if !files.matches(&header) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "'{}' was dumped by a search of another board or configuration",
+                    files.frontier.display()
+                ),
+            ));
+        }
+    }
     // 優先度キュー（ロックフリー SkipSet）
-    let pq: Arc<SkipSet<(NotNan<f64>, [u64; 2])>> = Arc::new(SkipSet::new());
+    let pq: Arc<SkipSet<(NotNan<f64>, BoardKey)>> = Arc::new(SkipSet::new());
 
     // 訪問済み（HashSet）
     //let visited: Arc<DashSet<[u64; 2]>> = Arc::new(DashSet::new());
-    let visited: Arc<DashSet<[u64; 2]>> = Arc::new(DashSet::with_capacity(node_limit + 100));
+    let visited: Arc<DashSet<BoardKey>> = Arc::new(DashSet::with_capacity(node_limit + 100));
 
     // 訪問数
     let visited_count = Arc::new(AtomicUsize::new(0));
@@ -91,36 +261,59 @@ pub fn parallel_retrospective_greedy_best_first_search(
     let inflight = Arc::new(AtomicUsize::new(0));
     // 「未発見で探索が完全に枯渇した」ことを示すフラグ
     let notfound = Arc::new(AtomicBool::new(false));
+    // 石数ごとの上限で子局面を捨てたか（捨てていれば枯渇しても NotFound とは言えない）
+    let capped = AtomicBool::new(false);
     // 結果（見つかった leaf）
-    let found: Arc<crossbeam::queue::ArrayQueue<[u64; 2]>> =
+    let found: Arc<crossbeam::queue::ArrayQueue<BoardKey>> =
         Arc::new(crossbeam::queue::ArrayQueue::new(1));
     let mut starts = vec![[board.player, board.opponent]];
-    if get_moves(board.opponent, board.player) == 0 {
+    if !has_move(board.opponent, board.player) {
         starts.push([board.opponent, board.player]);
     }
+    // 再開時は保存済みの既訪問集合を復元し、保存済みの frontier をそのままキューに積む
+    // （frontier の局面は既訪問集合にも含まれている）
+    if let Some(files) = opts.resume_from {
+        for v in read_positions(&files.visited)? {
+            if visited.insert(v) {
+                visited_count.fetch_add(1, Ato::Relaxed);
+                let num_disc = (v.0[0] | v.0[1]).count_ones();
+                node_per_stone[num_disc as usize].fetch_add(1, Ato::Relaxed);
+            }
+        }
+        let frontier = read_positions(&files.frontier)?;
+        for &f in &frontier {
+            let h = NotNan::new(heuristic_function(opts.heuristic, f))
+                .expect("h_function returned NaN");
+            pq.insert((h, f));
+        }
+        vprintln!(
+            INFO,
+            "info: resumed frontier = {}, visited = {} from '{}'",
+            frontier.len(),
+            visited.len(),
+            files.frontier.display()
+        );
+        starts.clear();
+    }
     // 初期ノードを push（重複を避けるため visited にも登録）
     for s in starts {
-        let b = Board::new(s[0], s[1]).unique();
-        let start = [b[0], b[1]];
+        let start = BoardKey(Board::new(s[0], s[1]).unique());
         //let guard = visited.guard();
         //if visited.insert(start, &guard) {
         if visited.insert(start) {
             visited_count.fetch_add(1, Ato::Relaxed);
-            let h = NotNan::new(heuristic_function(start)).expect("h_function returned NaN");
+            let h = NotNan::new(heuristic_function(opts.heuristic, start))
+                .expect("h_function returned NaN");
             pq.insert((h, start));
         }
     }
 
-    // スレッドプール（最大64）
-    let parallelism = thread::available_parallelism()
-        .map(|n| n.get())
-        .unwrap_or(1);
-    let num_threads = std::cmp::min(NUM_THREADS, parallelism);
-    let pool = ThreadPoolBuilder::new()
-        .num_threads(num_threads)
-        .thread_name(|i| format!("gbfs-worker-{i}"))
-        .build()
-        .expect("failed to build thread pool");
+    let (num_threads, pool) = worker_pool();
+
+    // 途中保存中はワーカを止める（各ワーカは 1 ノードの処理中だけ read を持つ）
+    let gate = RwLock::new(());
+    let expanded = AtomicUsize::new(0);
+    let next_dump = AtomicUsize::new(opts.dump_interval);
 
     // ワーカ（busy-poll による前取り／消費）
     pool.scope(|s| {
@@ -134,6 +327,11 @@ pub fn parallel_retrospective_greedy_best_first_search(
             let done_per_stone = done_per_stone.clone();
             let inflight = inflight.clone(); // ← 追加
             let notfound = notfound.clone(); // ← 追加
+            let gate = &gate;
+            let header = &header;
+            let expanded = &expanded;
+            let next_dump = &next_dump;
+            let capped = &capped;
             s.spawn(move |_| {
                 // 各スレッドで flurry の epoch guard を保持
                 //let guard = visited.guard();
@@ -146,6 +344,28 @@ pub fn parallel_retrospective_greedy_best_first_search(
                         break;
                     }
 
+                    // 途中保存（書き出すワーカ自身は read を持たない状態で write を取る）
+                    if let Some(files) = opts.dump_to {
+                        let next = next_dump.load(Ato::Relaxed);
+                        if opts.dump_interval > 0
+                            && expanded.load(Ato::Relaxed) >= next
+                            && next_dump
+                                .compare_exchange(
+                                    next,
+                                    next + opts.dump_interval,
+                                    Ato::AcqRel,
+                                    Ato::Relaxed,
+                                )
+                                .is_ok()
+                        {
+                            let _w = gate.write().unwrap();
+                            if let Err(e) = dump_frontier(files, header, &pq, &visited) {
+                                eprintln!("warning: failed to dump frontier: {}", e);
+                            }
+                        }
+                    }
+                    let _r = gate.read().unwrap();
+
                     // 最小キーを front から取り出し
                     // SkipSet はロックフリー。front() で先頭 Entry にアクセスし、remove() でアトミックに削除。
                     let entry = match pq.front() {
@@ -182,11 +402,12 @@ pub fn parallel_retrospective_greedy_best_first_search(
                     // ===== 追加: このノードを「処理中」としてカウント =====
                     inflight.fetch_add(1, Ato::AcqRel);
                     // ======
-                    let num_disc = (node[0] | node[1]).count_ones() as i32;
+                    let [player, opponent] = node.0;
+                    let num_disc = (player | opponent).count_ones() as i32;
                     let _ = &done_per_stone[num_disc as usize].fetch_add(1, Ato::Relaxed);
                     // 目標判定
-                    if (node[0] | node[1]).count_ones() as i32 == discs {
-                        if is_leaf(node, leafnode, discs) {
+                    if num_disc == discs {
+                        if is_leaf(node.0, leafnode, discs) {
                             // 競合で複数見つかるのを避ける：最初の1つだけ採用
                             if done
                                 .compare_exchange(false, true, Ato::AcqRel, Ato::Relaxed)
@@ -202,23 +423,24 @@ pub fn parallel_retrospective_greedy_best_first_search(
                         // ============================================
                         continue;
                     }
-                    if use_lp && !check_lp(node[0], node[1], false) {
+                    if prunings.lp && !check_lp(player, opponent, false) {
                         // ===== 追加: 処理完了（inflight を減算） =====
                         inflight.fetch_sub(1, Ato::AcqRel);
                         // ============================================
                         continue;
                     }
                     // 展開
-                    let succs = prev_states(node);
+                    let succs = prev_states(node.0);
+                    let mut interrupted = false;
                     for s in succs {
                         if done.load(Ato::Acquire) {
+                            interrupted = true;
                             break;
                         }
-                        let occupied = s[0] | s[1];
-                        if !check_occupancy(occupied) || !check_seg3_more(s[0], s[1]) {
+                        if !prunings.passes_cheap(s[0], s[1]) {
                             continue;
                         }
-                        let succ = Board::new(s[0], s[1]).unique();
+                        let succ = BoardKey(Board::new(s[0], s[1]).unique());
                         // 既訪問チェック
                         //let already = visited.contains(&succ, &guard);
                         let already = visited.contains(&succ);
@@ -226,29 +448,45 @@ pub fn parallel_retrospective_greedy_best_first_search(
                             continue;
                         }
 
+                        // 石数ごとの上限に達した段にはもう積まない
+                        let num_disc = (succ.0[0] | succ.0[1]).count_ones() as usize;
+                        if !reserve_slot(&node_per_stone[num_disc], opts.max_nodes_per_disc) {
+                            capped.store(true, Ato::Relaxed);
+                            continue;
+                        }
+
                         // 先に visited へ CAS 的に登録して重複投入を防ぐ
                         //if visited.insert(succ, &guard) {
-                        if visited.insert(succ) {
-                            let num_disc = (succ[0] | succ[1]).count_ones();
-                            let _ = &node_per_stone[num_disc as usize].fetch_add(1, Ato::Relaxed);
+                        if !visited.insert(succ) {
+                            node_per_stone[num_disc].fetch_sub(1, Ato::Relaxed);
+                        } else {
                             let new_count = visited_count.fetch_add(1, Ato::Relaxed) + 1;
-                            if new_count > node_limit {
-                                done.store(true, Ato::Release);
-                                break;
-                            }
 
                             // ヒューリスティック評価
                             // NaN が来たら panic させずにスキップしても良いが、ここでは早期に気付けるようにする
-                            let h = match NotNan::new(heuristic_function(succ)) {
+                            let h = match NotNan::new(heuristic_function(opts.heuristic, succ)) {
                                 Ok(hh) => hh,
                                 Err(_) => continue, // NaNなら破棄
                             };
 
                             // 優先度キューへ push
+                            // （上限に達した場合も、書き出す frontier から漏れないよう先に push する）
                             pq.insert((h, succ));
+                            if new_count > node_limit {
+                                done.store(true, Ato::Release);
+                                interrupted = true;
+                                break;
+                            }
+                        }
+                    }
+                    // 展開の途中で打ち切ったノードは、再開時に展開し直せるようキューへ戻す
+                    if interrupted {
+                        if let Ok(h) = NotNan::new(heuristic_function(opts.heuristic, node)) {
+                            pq.insert((h, node));
                         }
                     }
                     // ===== 追加: このノードの展開が終わったので inflight を減算 =====
+                    expanded.fetch_add(1, Ato::Relaxed);
                     inflight.fetch_sub(1, Ato::AcqRel);
                     // ============================================================
                 }
@@ -256,7 +494,8 @@ pub fn parallel_retrospective_greedy_best_first_search(
         }
     });
     for i in 0..=64 {
-        eprintln!(
+        veprintln!(
+            DEBUG,
             "{}: {} / {}",
             i,
             done_per_stone[i].load(Ato::Relaxed),
@@ -264,11 +503,175 @@ pub fn parallel_retrospective_greedy_best_first_search(
         );
     }
     // 結果
-    if found.len() > 0 {
+    let result = if !found.is_empty() {
         SearchResult::Found
-    } else if notfound.load(Ato::Acquire) {
+    } else if notfound.load(Ato::Acquire) && !capped.load(Ato::Relaxed) {
         SearchResult::NotFound
     } else {
         SearchResult::Unknown
+    };
+    // 上限で打ち切ったときは続きから再開できるように書き出す
+    if result == SearchResult::Unknown {
+        if let Some(files) = opts.dump_to {
+            dump_frontier(files, &header, &pq, &visited)?;
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::search_fwd_par::make_fwd_table;
+    use crate::test_util::scratch_dir;
+    use std::fs;
+
+    fn sorted(mut positions: Vec<BoardKey>) -> Vec<BoardKey> {
+        positions.sort_unstable();
+        positions
+    }
+
+    #[test]
+    fn worker_pool_has_the_configured_thread_count() {
+        let (num_threads, pool) = worker_pool();
+        assert_eq!(num_threads, ThreadConfig::global().num_threads());
+        assert_eq!(pool.current_num_threads(), num_threads);
+        let mut names = pool.broadcast(|_| std::thread::current().name().unwrap().to_string());
+        names.sort();
+        let mut expected: Vec<String> = (0..num_threads)
+            .map(|i| format!("gbfs-worker-{i}"))
+            .collect();
+        expected.sort();
+        assert_eq!(names, expected);
+    }
+
+    #[test]
+    fn dump_and_resume_preserve_visited_positions() {
+        let dir = scratch_dir("resume");
+        let first = FrontierFiles::in_dir(&dir, 0);
+        let second = FrontierFiles::in_dir(&dir, 1);
+        // f5 d6 c3 d3 c4 f4 f6 f3 e6 e7（14 石）
+        let board = Board::play_sequence(&[37, 43, 18, 19, 26, 29, 45, 21, 44, 52]).unwrap();
+        let discs = 8;
+        let leaf = make_fwd_table(&[board.player, board.opponent], discs);
+        let search = |node_limit, resume_from, dump_to| {
+            let opts = GbfsOptions {
+                resume_from,
+                dump_to,
+                ..GbfsOptions::default()
+            };
+            parallel_retrospective_greedy_best_first_search_with(
+                &board,
+                discs,
+                &leaf,
+                node_limit,
+                PruningConfig::SEARCH,
+                opts,
+            )
+            .unwrap()
+        };
+
+        // 上限で打ち切ると frontier と既訪問集合が書き出される
+        assert_eq!(search(50, None, Some(&first)), SearchResult::Unknown);
+        let visited = sorted(read_positions(&first.visited).unwrap());
+        let frontier = sorted(read_positions(&first.frontier).unwrap());
+        assert!(visited.len() > 50);
+        assert!(frontier.iter().all(|f| visited.binary_search(f).is_ok()));
+
+        // 読み込んだ直後に打ち切れば、同じ集合がそのまま書き出される
+        assert_eq!(
+            search(0, Some(&first), Some(&second)),
+            SearchResult::Unknown
+        );
+        let visited_again = sorted(read_positions(&second.visited).unwrap());
+        let frontier_again = sorted(read_positions(&second.frontier).unwrap());
+
+        // 再開して最後まで探索すれば、最初から探索したときと同じ結果になる
+        let resumed = search(1_000_000, Some(&first), None);
+        let fresh = search(1_000_000, None, None);
+        // 別のしきい値の探索は、書き出した frontier から再開できない
+        let other_discs = parallel_retrospective_greedy_best_first_search_with(
+            &board,
+            discs - 1,
+            &leaf,
+            1_000_000,
+            PruningConfig::SEARCH,
+            GbfsOptions {
+                resume_from: Some(&first),
+                ..GbfsOptions::default()
+            },
+        );
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(other_discs.unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        assert_eq!(visited_again, visited);
+        assert_eq!(frontier_again, frontier);
+        assert_eq!(fresh, SearchResult::Found);
+        assert_eq!(resumed, fresh);
+    }
+
+    #[test]
+    fn slots_are_reserved_up_to_the_cap() {
+        let count = AtomicUsize::new(0);
+        assert!(reserve_slot(&count, Some(2)));
+        assert!(reserve_slot(&count, Some(2)));
+        assert!(!reserve_slot(&count, Some(2)));
+        assert_eq!(count.load(Ato::Relaxed), 2);
+        assert!(reserve_slot(&count, None));
+        assert_eq!(count.load(Ato::Relaxed), 3);
+        assert!(!reserve_slot(&AtomicUsize::new(0), Some(0)));
+    }
+
+    #[test]
+    fn per_disc_cap_turns_an_exhausted_search_into_unknown() {
+        use crate::search::reachable::random_reachable_board;
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        let discs = 8;
+        let search_limited = |board: &Board, node_limit, max_nodes_per_disc| {
+            let leaf = make_fwd_table(&[board.player, board.opponent], discs);
+            let opts = GbfsOptions {
+                max_nodes_per_disc,
+                ..GbfsOptions::default()
+            };
+            parallel_retrospective_greedy_best_first_search_with(
+                board,
+                discs,
+                &leaf,
+                node_limit,
+                PruningConfig::SEARCH,
+                opts,
+            )
+            .unwrap()
+        };
+        let search = |board: &Board, max_nodes_per_disc| {
+            search_limited(board, 1_000_000, max_nodes_per_disc)
+        };
+        // 石の色を 1 つ変えた盤面から、逆方向の木を探索し尽くして NotFound になるものを探す。
+        // 12 石からリーフの 8 石までの 4 段に 5 局面より多くあれば、どこかの段に 2 局面以上ある
+        let mut rng = StdRng::seed_from_u64(671);
+        let unreachable = (0..)
+            .map(|_| {
+                let b = random_reachable_board(&mut rng, 12);
+                let bit = 1u64 << b.opponent.trailing_zeros();
+                Board::new(b.player | bit, b.opponent & !bit)
+            })
+            .find(|b| {
+                PruningConfig::SEARCH.passes_cheap(b.player, b.opponent)
+                    && search(b, None) == SearchResult::NotFound
+                    && search_limited(b, 5, None) == SearchResult::Unknown
+            })
+            .unwrap();
+        // 捨てた子局面があれば、キューが尽きても到達不能とは言えない
+        assert_eq!(search(&unreachable, Some(1)), SearchResult::Unknown);
+        assert_eq!(
+            search(&unreachable, Some(1_000_000)),
+            SearchResult::NotFound
+        );
+
+        let reachable = Board::play_sequence(&[37, 43, 18, 19, 26, 29, 45, 21]).unwrap();
+        assert_eq!(search(&reachable, Some(1_000_000)), SearchResult::Found);
     }
 }
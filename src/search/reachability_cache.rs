@@ -0,0 +1,371 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::hash::BuildHasher;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::othello::Board;
+use crate::search::core::SearchResult;
+use crate::search::leaf_cache::LeafCache;
+
+/// 保存ファイル先頭のマジックナンバー（7 バイト）と形式の版（1 バイト）
+const REACHABILITY_CACHE_MAGIC: &[u8; 7] = b"OTHREAC";
+
+/// 保存ファイルの形式の版。古い版のファイルは読まずにエラーにする。
+///
+/// - `1`: キーが局面の正規形だけで、どのリーフ表で判定したかを記録していなかった
+/// - `2`: キーにリーフ表の指紋（`LeafCache::fingerprint`）を含める
+const REACHABILITY_CACHE_VERSION: u8 = b'2';
+
+/// 判定結果を記録するキー。
+///
+/// 逆方向探索の判定は局面だけでなく、照合したリーフ表（照合する石数・初期配置・seed）にもよるので、
+/// 局面の正規形（`Board::unique()`）とリーフ表の指紋（`LeafCache::fingerprint`）の組にする。
+/// 別のリーフ表で付いた判定は、同じ置き場に入っていても引かれない。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ReachabilityKey {
+    pub board: [u64; 2],
+    pub leaf_table: u64,
+}
+
+impl ReachabilityKey {
+    /// `board` を `leaf_cache` のリーフ表で判定したときのキー
+    pub fn new(board: &Board, leaf_cache: &LeafCache) -> Self {
+        ReachabilityKey {
+            board: board.unique(),
+            leaf_table: leaf_cache.fingerprint(),
+        }
+    }
+
+    fn to_words(self) -> [u64; 3] {
+        [self.board[0], self.board[1], self.leaf_table]
+    }
+
+    fn from_words(words: [u64; 3]) -> Self {
+        ReachabilityKey {
+            board: [words[0], words[1]],
+            leaf_table: words[2],
+        }
+    }
+}
+
+/// 到達可能性の判定結果を蓄えておく場所。`retrospective_search_with_store` が探索の前に引き、
+/// 判定が付いたら書き込む。
+///
+/// 値は、キーのリーフ表の開始局面から到達可能なら true。
+/// メモリ上の `HashMap`、ファイルに保存する `ReachabilityCache` の実装があり、
+/// 外部の KV ストアなどを使うときはこの trait を実装する。
+pub trait ReachabilityStore {
+    /// 記録済みなら到達可能かどうかを返す
+    fn get(&self, key: ReachabilityKey) -> Option<bool>;
+    /// 判定結果を記録する
+    fn put(&mut self, key: ReachabilityKey, reachable: bool);
+}
+
+/// メモリ上だけの実装
+impl<S: BuildHasher> ReachabilityStore for HashMap<ReachabilityKey, bool, S> {
+    fn get(&self, key: ReachabilityKey) -> Option<bool> {
+        HashMap::get(self, &key).copied()
+    }
+
+    fn put(&mut self, key: ReachabilityKey, reachable: bool) {
+        self.insert(key, reachable);
+    }
+}
+
+/// 逆方向探索で判定が付いた局面を実行をまたいで蓄えておく表。
+///
+/// 同じリーフ表で探索すれば判定は入力の順序やノード数の上限によらないので、一度 `Found` /
+/// `NotFound` になった局面は次回以降探索せずに答えを返せる。キーにはリーフ表の指紋を含めるので、
+/// しきい値や開始局面を変えた実行と同じファイルを共有しても判定は混ざらない。
+/// `Unknown` はノード数の上限次第で変わるので記録しない。
+#[derive(Debug, Default)]
+pub struct ReachabilityCache {
+    ok: HashSet<ReachabilityKey>,
+    ng: HashSet<ReachabilityKey>,
+}
+
+impl ReachabilityCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `path` から読み込む。ファイルが無ければ空の表を返す。
+    pub fn load_or_new(path: &Path) -> io::Result<Self> {
+        if path.exists() {
+            Self::load(path)
+        } else {
+            Ok(Self::new())
+        }
+    }
+
+    /// `save` で保存した表を読み込む。形式の版が古ければ `ErrorKind::InvalidData`。
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let mut r = BufReader::new(File::open(path)?);
+
+        let mut magic = [0u8; 8];
+        r.read_exact(&mut magic)?;
+        if &magic[..7] != REACHABILITY_CACHE_MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} is not a reachability cache file", path.display()),
+            ));
+        }
+        if magic[7] != REACHABILITY_CACHE_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "reachability cache {} has format version {}, but version {} is required; delete it to rebuild",
+                    path.display(),
+                    magic[7] as char,
+                    REACHABILITY_CACHE_VERSION as char
+                ),
+            ));
+        }
+
+        let mut buf8 = [0u8; 8];
+        r.read_exact(&mut buf8)?;
+        let ok_len = u64::from_ne_bytes(buf8) as usize;
+        r.read_exact(&mut buf8)?;
+        let ng_len = u64::from_ne_bytes(buf8) as usize;
+
+        let mut ok = vec![[0u64; 3]; ok_len];
+        r.read_exact(bytemuck::cast_slice_mut(&mut ok))?;
+        let mut ng = vec![[0u64; 3]; ng_len];
+        r.read_exact(bytemuck::cast_slice_mut(&mut ng))?;
+
+        Ok(ReachabilityCache {
+            ok: ok.into_iter().map(ReachabilityKey::from_words).collect(),
+            ng: ng.into_iter().map(ReachabilityKey::from_words).collect(),
+        })
+    }
+
+    /// 表を `path` に保存する（一時ファイルに書いてから置き換える）。
+    ///
+    /// 形式: マジックナンバー(7) / 版(1) / OK 件数(u64) / NG 件数(u64) の後に、ソート済みの OK、
+    /// ソート済みの NG の順に、局面の正規形 [u64;2] とリーフ表の指紋 u64 の組を連続
+    /// （ネイティブエンディアン）で書き出す。
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let sorted = |set: &HashSet<ReachabilityKey>| {
+            let mut words: Vec<[u64; 3]> = set.iter().map(|k| k.to_words()).collect();
+            words.sort_unstable();
+            words
+        };
+        let ok = sorted(&self.ok);
+        let ng = sorted(&self.ng);
+
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let tmp = Path::new(&tmp);
+        let mut w = BufWriter::new(File::create(tmp)?);
+        w.write_all(REACHABILITY_CACHE_MAGIC)?;
+        w.write_all(&[REACHABILITY_CACHE_VERSION])?;
+        w.write_all(&(ok.len() as u64).to_ne_bytes())?;
+        w.write_all(&(ng.len() as u64).to_ne_bytes())?;
+        w.write_all(bytemuck::cast_slice(&ok))?;
+        w.write_all(bytemuck::cast_slice(&ng))?;
+        w.flush()?;
+        drop(w);
+        fs::rename(tmp, path)
+    }
+
+    /// `leaf_cache` のリーフ表での判定が記録済みなら `Found` / `NotFound` を返す
+    pub fn get(&self, board: &Board, leaf_cache: &LeafCache) -> Option<SearchResult> {
+        ReachabilityStore::get(self, ReachabilityKey::new(board, leaf_cache)).map(|reachable| {
+            if reachable {
+                SearchResult::Found
+            } else {
+                SearchResult::NotFound
+            }
+        })
+    }
+
+    /// `leaf_cache` のリーフ表での判定結果を記録する。`Unknown` は無視する。
+    pub fn insert(&mut self, board: &Board, leaf_cache: &LeafCache, result: SearchResult) {
+        let key = ReachabilityKey::new(board, leaf_cache);
+        match result {
+            SearchResult::Found => self.put(key, true),
+            SearchResult::NotFound => self.put(key, false),
+            SearchResult::Unknown => {}
+        }
+    }
+
+    pub fn ok_count(&self) -> usize {
+        self.ok.len()
+    }
+
+    pub fn ng_count(&self) -> usize {
+        self.ng.len()
+    }
+}
+
+/// `save` / `load` でソート済みの `.bin` ファイルに保存できる実装
+impl ReachabilityStore for ReachabilityCache {
+    fn get(&self, key: ReachabilityKey) -> Option<bool> {
+        if self.ok.contains(&key) {
+            Some(true)
+        } else if self.ng.contains(&key) {
+            Some(false)
+        } else {
+            None
+        }
+    }
+
+    fn put(&mut self, key: ReachabilityKey, reachable: bool) {
+        if reachable {
+            self.ng.remove(&key);
+            self.ok.insert(key);
+        } else {
+            self.ok.remove(&key);
+            self.ng.insert(key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::core::{retrospective_search_with_store, Btable};
+    use crate::test_util::scratch_dir;
+
+    /// `store` を通して探索し、判定と展開したノード数を返す
+    fn search_with(
+        board: &Board,
+        leaf_cache: &LeafCache,
+        node_limit: usize,
+        store: &mut dyn ReachabilityStore,
+    ) -> (SearchResult, usize) {
+        let mut searched = Btable::new(1 << 16, 1 << 8);
+        let mut retroflips = vec![];
+        let mut node_count = 0;
+        let result = retrospective_search_with_store(
+            board,
+            false,
+            leaf_cache,
+            &mut searched,
+            &mut retroflips,
+            &mut node_count,
+            node_limit,
+            Some(store),
+        )
+        .unwrap();
+        (result, node_count)
+    }
+
+    #[test]
+    fn found_board_is_answered_on_the_next_run_without_search() {
+        let dir = scratch_dir("next_run");
+        let path = dir.join("reachability_cache.bin");
+        let leaf_cache = LeafCache::new(7);
+        // f5 d6 c3 d3 c4
+        let board = Board::play_sequence(&[37, 43, 18, 19, 26]).unwrap();
+
+        let mut first = ReachabilityCache::load_or_new(&path).unwrap();
+        let (result, nodes) = search_with(&board, &leaf_cache, 1_000_000, &mut first);
+        assert_eq!(result, SearchResult::Found);
+        assert!(nodes > 0);
+        first.save(&path).unwrap();
+
+        let mut second = ReachabilityCache::load_or_new(&path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(second.get(&board, &leaf_cache), Some(SearchResult::Found));
+        // 対称な局面も同じキーで引ける
+        let mirrored = board.symmetry_orbit()[3];
+        assert_eq!(
+            search_with(&mirrored, &leaf_cache, 1_000_000, &mut second),
+            (SearchResult::Found, 0)
+        );
+    }
+
+    #[test]
+    fn unknown_is_not_stored() {
+        let leaf_cache = LeafCache::new(7);
+        let board = Board::play_sequence(&[37, 43, 18, 19, 26]).unwrap();
+        let mut cache = ReachabilityCache::new();
+        let (result, _) = search_with(&board, &leaf_cache, 1, &mut cache);
+        assert_eq!(result, SearchResult::Unknown);
+        assert_eq!((cache.ok_count(), cache.ng_count()), (0, 0));
+
+        cache.insert(&board, &leaf_cache, SearchResult::Unknown);
+        assert_eq!(cache.get(&board, &leaf_cache), None);
+    }
+
+    #[test]
+    fn verdicts_are_kept_per_leaf_table() {
+        let board = Board::play_sequence(&[37, 43, 18, 19, 26]).unwrap();
+        let seven = LeafCache::new(7);
+        let eight = LeafCache::new(8);
+        assert_ne!(seven.fingerprint(), eight.fingerprint());
+
+        let mut cache = ReachabilityCache::new();
+        cache.insert(&board, &seven, SearchResult::Found);
+        assert_eq!(cache.get(&board, &seven), Some(SearchResult::Found));
+        assert_eq!(cache.get(&board, &eight), None);
+    }
+
+    #[test]
+    fn caches_with_different_seeds_or_discs_share_a_store_without_stale_verdicts() {
+        // f5 d6 c3 d3 c4 は f5 d6 からは到達できるが f5 f6 からは到達できない
+        let board = Board::play_sequence(&[37, 43, 18, 19, 26]).unwrap();
+        let f5d6 = Board::play_sequence(&[37, 43]).unwrap();
+        let f5f6 = Board::play_sequence(&[37, 45]).unwrap();
+        let caches = [
+            (LeafCache::new(7), SearchResult::Found),
+            (
+                LeafCache::from_seed(7, &f5f6).unwrap(),
+                SearchResult::NotFound,
+            ),
+            (LeafCache::from_seed(7, &f5d6).unwrap(), SearchResult::Found),
+            (LeafCache::new(8), SearchResult::Found),
+        ];
+        let mut store: HashMap<ReachabilityKey, bool> = HashMap::new();
+        for (leaf_cache, expected) in &caches {
+            let (result, nodes) = search_with(&board, leaf_cache, 1_000_000, &mut store);
+            assert_eq!(result, *expected, "{}", leaf_cache.discs());
+            // 他のリーフ表の判定は使わずに探索する
+            assert!(nodes > 0);
+        }
+        assert_eq!(store.len(), caches.len());
+        for (leaf_cache, expected) in &caches {
+            assert_eq!(
+                search_with(&board, leaf_cache, 1_000_000, &mut store),
+                (*expected, 0)
+            );
+        }
+    }
+
+    #[test]
+    fn save_load_round_trip() {
+        let dir = scratch_dir("round_trip");
+        let path = dir.join("reachability_cache.bin");
+        let leaf_cache = LeafCache::new(7);
+        let found = Board::play_sequence(&[37, 43, 18]).unwrap();
+        let not_found = Board::play_sequence(&[37, 45, 44]).unwrap();
+        let mut cache = ReachabilityCache::new();
+        cache.insert(&found, &leaf_cache, SearchResult::Found);
+        cache.insert(&not_found, &leaf_cache, SearchResult::NotFound);
+        cache.save(&path).unwrap();
+        let loaded = ReachabilityCache::load(&path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!((loaded.ok_count(), loaded.ng_count()), (1, 1));
+        assert_eq!(loaded.get(&found, &leaf_cache), Some(SearchResult::Found));
+        assert_eq!(
+            loaded.get(&not_found, &leaf_cache),
+            Some(SearchResult::NotFound)
+        );
+    }
+
+    #[test]
+    fn rejects_old_format_version() {
+        let dir = scratch_dir("old_version");
+        let path = dir.join("reachability_cache.bin");
+        ReachabilityCache::new().save(&path).unwrap();
+        let mut bytes = fs::read(&path).unwrap();
+        bytes[7] = b'1';
+        fs::write(&path, bytes).unwrap();
+        let err = ReachabilityCache::load(&path).err().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("version"), "{}", err);
+    }
+}
@@ -0,0 +1,389 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use rand::Rng;
+
+use crate::othello::{flip, get_moves, validate_board, Board};
+use crate::search::core::{retrospective_search, Btable, SearchResult};
+use crate::search::leaf_cache::LeafCache;
+use crate::search::parallel_dfs::retrospective_search_parallel;
+
+/// 順方向探索の既定のしきい値（CLI の `--discs` の既定値と同じ）
+pub const DEFAULT_DISCS: i32 = 10;
+
+/// 逆方向探索の既定のノード上限（CLI の `--max-nodes` の既定値と同じ）
+pub const DEFAULT_NODE_LIMIT: usize = 1_000_000;
+
+/// しきい値ごとのリーフテーブル（プロセス内で使い回す）
+///
+/// 構築は数秒かかることがあるので、表全体のロックはスロットの取得だけに使い、
+/// 構築そのものはしきい値ごとの `OnceLock` で行う（別のしきい値の利用者を待たせない）。
+type LeafCacheSlot = Arc<OnceLock<Arc<LeafCache>>>;
+
+fn leaf_caches() -> &'static Mutex<HashMap<i32, LeafCacheSlot>> {
+    static CACHES: OnceLock<Mutex<HashMap<i32, LeafCacheSlot>>> = OnceLock::new();
+    CACHES.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+pub(crate) fn leaf_cache_for(discs: i32) -> Arc<LeafCache> {
+    let slot = leaf_caches()
+        .lock()
+        .unwrap()
+        .entry(discs)
+        .or_default()
+        .clone();
+    slot.get_or_init(|| Arc::new(LeafCache::new(discs))).clone()
+}
+
+/// 盤面 `board` が初期配置から到達可能かを既定の設定で判定する。
+///
+/// しきい値は `min(石数, DEFAULT_DISCS)`、ノード上限は `DEFAULT_NODE_LIMIT` で
+/// 逐次 DFS（`retrospective_search`）を実行する。不正な盤面は `NotFound`。
+pub fn is_reachable(board: &Board) -> SearchResult {
+    if validate_board(board).is_err() {
+        return SearchResult::NotFound;
+    }
+    let discs = (board.popcount() as i32).min(DEFAULT_DISCS);
+    let leaf_cache = leaf_cache_for(discs);
+
+    let mut retrospective_searched = Btable::new(DEFAULT_NODE_LIMIT, 0x10000);
+    let mut retroflips: Vec<[u64; 10_000]> = vec![];
+    let mut node_count: usize = 0;
+    retrospective_search(
+        board,
+        false,
+        discs,
+        leaf_cache.leaf(),
+        &mut retrospective_searched,
+        &mut retroflips,
+        &mut node_count,
+        DEFAULT_NODE_LIMIT,
+    )
+    .unwrap_or(SearchResult::Unknown)
+}
+
+/// 初期局面からランダムに指し進め、石数がちょうど `target_discs` の局面を返す。
+///
+/// 手番側に合法手が無ければパスし、`target_discs` に届く前に終局したらやり直すので、
+/// 返る局面は必ず順方向に到達可能。`target_discs` は 4 以上 64 以下。
+pub fn random_reachable_board<R: Rng + ?Sized>(rng: &mut R, target_discs: u32) -> Board {
+    assert!(
+        (4..=64).contains(&target_discs),
+        "target_discs must be in 4..=64, got {}",
+        target_discs
+    );
+    'retry: loop {
+        let mut b = Board::initial();
+        while b.popcount() < target_discs {
+            let mut moves = get_moves(b.player, b.opponent);
+            if moves == 0 {
+                b = Board::new(b.opponent, b.player);
+                moves = get_moves(b.player, b.opponent);
+                if moves == 0 {
+                    continue 'retry;
+                }
+            }
+            for _ in 0..rng.random_range(0..moves.count_ones()) {
+                moves &= moves - 1;
+            }
+            let idx = moves.trailing_zeros();
+            let flipped = flip(idx as usize, b.player, b.opponent);
+            b = Board::new(b.opponent ^ flipped, b.player ^ (flipped | (1u64 << idx)));
+        }
+        return b;
+    }
+}
+
+/// 2 つの独立した実装で判定が食い違ったことを表す
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Disagreement {
+    /// 逐次 DFS（`retrospective_search`）の結果
+    pub a: SearchResult,
+    /// 並列 DFS（`retrospective_search_parallel`）の結果
+    pub b: SearchResult,
+}
+
+/// 逐次 DFS と並列 DFS の両方で `board` を判定し、結果を突き合わせる。
+///
+/// 片方が `Unknown` の場合はもう片方の結果を採用する。
+/// `Found` と `NotFound` が食い違った場合は `Err(Disagreement)` を返す。不正な盤面は `NotFound`。
+pub fn cross_check(
+    board: &Board,
+    discs: i32,
+    node_limit: usize,
+) -> Result<SearchResult, Disagreement> {
+    let leaf_cache = leaf_cache_for(discs);
+
+    let mut retrospective_searched = Btable::new(node_limit.min(DEFAULT_NODE_LIMIT), 0x10000);
+    let mut retroflips: Vec<[u64; 10_000]> = vec![];
+    let mut node_count: usize = 0;
+    let a = retrospective_search(
+        board,
+        false,
+        discs,
+        leaf_cache.leaf(),
+        &mut retrospective_searched,
+        &mut retroflips,
+        &mut node_count,
+        node_limit,
+    )
+    .unwrap_or(SearchResult::NotFound);
+    let b = retrospective_search_parallel(
+        board,
+        false,
+        discs,
+        leaf_cache.leaf(),
+        node_limit,
+        node_limit,
+        false,
+    )
+    .unwrap_or(SearchResult::NotFound);
+
+    match (a, b) {
+        (a, b) if a == b => Ok(a),
+        (SearchResult::Unknown, r) | (r, SearchResult::Unknown) => Ok(r),
+        (a, b) => Err(Disagreement { a, b }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::othello::{BoardValidation, CENTER_MASK};
+    use crate::prunings::occupancy::check_occupancy;
+    use crate::prunings::seg3::check_seg3_more;
+
+    /// `forward_oracle_check` で見つかった、順方向の全列挙と食い違う判定
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum OracleMismatch {
+        /// `retrospective_search` の判定が列挙の結果（`expected`）と逆だった
+        Search {
+            board: Board,
+            expected: SearchResult,
+            got: SearchResult,
+        },
+        /// 到達可能な盤面を枝刈り `pruning` が到達不能とした（健全性の違反）
+        Pruned { board: Board, pruning: &'static str },
+        /// 列挙した（あるいはそれを 1 石変えた）盤面を `retrospective_search` が不正とした
+        Invalid {
+            board: Board,
+            error: BoardValidation,
+        },
+    }
+
+    /// `forward_oracle_check` の集計
+    #[derive(Debug, Clone, Default)]
+    struct OracleReport {
+        /// 調べた到達可能な盤面の数
+        reachable: usize,
+        /// 調べた到達不能な盤面の数
+        unreachable: usize,
+        /// ノード数の上限で判定できなかった盤面の数（食い違いには数えない）
+        unknown: usize,
+        mismatches: Vec<OracleMismatch>,
+    }
+
+    /// 石数 `k` の到達可能な局面を順方向に全列挙し、それを正解として逆方向探索と枝刈りを検査する。
+    ///
+    /// 列挙した局面から `samples` 個を選び、各盤面について
+    /// - `check_occupancy` / `check_seg3_more` が到達不能と判定しないこと
+    /// - しきい値 `discs` の `retrospective_search` が `NotFound` を返さないこと
+    ///
+    /// を確かめる。さらに同じ盤面の中央以外の石を 1 つ裏返し、正規形が列挙に無い
+    /// （到達不能な）盤面になれば `Found` を返さないことを確かめる。
+    /// 列挙の大きさは `k` で決まるので、`k` を小さくすれば（8 程度）すぐに終わる。
+    fn forward_oracle_check<R: Rng + ?Sized>(
+        rng: &mut R,
+        k: i32,
+        discs: i32,
+        samples: usize,
+        node_limit: usize,
+    ) -> OracleReport {
+        assert!(
+            4 <= discs && discs <= k,
+            "discs must be in 4..=k, got discs = {}, k = {}",
+            discs,
+            k
+        );
+        let truth = LeafCache::new(k);
+        let reachable: Vec<[u64; 2]> = truth.leaf().iter().copied().collect();
+        let leaf_cache = leaf_cache_for(discs);
+        let mut retrospective_searched = Btable::new(node_limit.min(DEFAULT_NODE_LIMIT), 0x10000);
+        let mut retroflips: Vec<[u64; 10_000]> = vec![];
+        let mut report = OracleReport::default();
+
+        let mut search = |board: &Board| {
+            retrospective_searched.clear();
+            let mut node_count: usize = 0;
+            retrospective_search(
+                board,
+                false,
+                discs,
+                leaf_cache.leaf(),
+                &mut retrospective_searched,
+                &mut retroflips,
+                &mut node_count,
+                node_limit,
+            )
+        };
+
+        for _ in 0..samples.min(reachable.len()) {
+            let [player, opponent] = reachable[rng.random_range(0..reachable.len())];
+            let board = Board::new(player, opponent);
+
+            report.reachable += 1;
+            if !check_occupancy(player | opponent) {
+                report.mismatches.push(OracleMismatch::Pruned {
+                    board,
+                    pruning: "check_occupancy",
+                });
+            }
+            if !check_seg3_more(player, opponent) {
+                report.mismatches.push(OracleMismatch::Pruned {
+                    board,
+                    pruning: "check_seg3_more",
+                });
+            }
+            match search(&board) {
+                Ok(SearchResult::Found) => {}
+                Ok(SearchResult::Unknown) => report.unknown += 1,
+                Ok(got) => report.mismatches.push(OracleMismatch::Search {
+                    board,
+                    expected: SearchResult::Found,
+                    got,
+                }),
+                Err(error) => report
+                    .mismatches
+                    .push(OracleMismatch::Invalid { board, error }),
+            }
+
+            // 中央以外の石を 1 つ裏返す
+            let mut discs_off_center = (player | opponent) & !CENTER_MASK;
+            if discs_off_center == 0 {
+                continue;
+            }
+            for _ in 0..rng.random_range(0..discs_off_center.count_ones()) {
+                discs_off_center &= discs_off_center - 1;
+            }
+            let bit = discs_off_center & discs_off_center.wrapping_neg();
+            let altered = Board::new(player ^ bit, opponent ^ bit);
+            if truth.leaf().contains(&altered.unique()) {
+                continue;
+            }
+            report.unreachable += 1;
+            match search(&altered) {
+                Ok(SearchResult::NotFound) => {}
+                Ok(SearchResult::Unknown) => report.unknown += 1,
+                Ok(got) => report.mismatches.push(OracleMismatch::Search {
+                    board: altered,
+                    expected: SearchResult::NotFound,
+                    got,
+                }),
+                Err(error) => report.mismatches.push(OracleMismatch::Invalid {
+                    board: altered,
+                    error,
+                }),
+            }
+        }
+        report
+    }
+
+    #[test]
+    fn initial_and_random_play_boards_are_reachable() {
+        assert_eq!(is_reachable(&Board::initial()), SearchResult::Found);
+        let mut rng = StdRng::seed_from_u64(2);
+        for discs in [8, 16, 24] {
+            let board = random_reachable_board(&mut rng, discs);
+            assert_eq!(
+                is_reachable(&board),
+                SearchResult::Found,
+                "{}",
+                board.to_string()
+            );
+        }
+        // 不正な盤面はパニックせず NotFound
+        assert_eq!(
+            is_reachable(&Board::new(CENTER_MASK, CENTER_MASK)),
+            SearchResult::NotFound
+        );
+        assert_eq!(is_reachable(&Board::new(0, 1)), SearchResult::NotFound);
+    }
+
+    #[test]
+    fn leaf_cache_for_builds_each_threshold_once_across_threads() {
+        let tables: Vec<Arc<LeafCache>> = std::thread::scope(|scope| {
+            let handles: Vec<_> = [5, 6, 5, 6]
+                .into_iter()
+                .map(|discs| scope.spawn(move || leaf_cache_for(discs)))
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        assert!(Arc::ptr_eq(&tables[0], &tables[2]));
+        assert!(Arc::ptr_eq(&tables[1], &tables[3]));
+        assert!(!Arc::ptr_eq(&tables[0], &tables[1]));
+        assert!(Arc::ptr_eq(&tables[0], &leaf_cache_for(5)));
+    }
+
+    #[test]
+    fn cross_check_finds_no_disagreement_on_random_play_boards() {
+        let mut rng = StdRng::seed_from_u64(4);
+        let mut verdicts = vec![];
+        for i in 0..24 {
+            let board = random_reachable_board(&mut rng, 10 + i % 12);
+            assert_eq!(cross_check(&board, 8, 200_000), Ok(SearchResult::Found));
+            // 相手の石を 1 つ手番側に変えた盤面（到達不能なものも混ざる）
+            let bit = 1u64 << board.opponent.trailing_zeros();
+            let altered = Board::new(board.player | bit, board.opponent & !bit);
+            let verdict = cross_check(&altered, 8, 200_000);
+            assert!(verdict.is_ok(), "{}: {:?}", altered.to_string(), verdict);
+            verdicts.push(verdict.unwrap());
+        }
+        assert!(verdicts.contains(&SearchResult::NotFound));
+    }
+
+    /// 石数 `k` の局面を全列挙した結果と、しきい値 6 の逆方向探索が食い違わないこと
+    #[test]
+    fn forward_oracle_agrees_with_reverse_search() {
+        for k in [8, 9, 10] {
+            let mut rng = StdRng::seed_from_u64(1);
+            let report = forward_oracle_check(&mut rng, k, 6, 2000, 2000);
+            assert!(report.reachable > 0 && report.unreachable > 0);
+            assert!(
+                report.mismatches.is_empty(),
+                "k = {}: {} mismatch(es), first: {:?}",
+                k,
+                report.mismatches.len(),
+                report.mismatches[0]
+            );
+        }
+    }
+
+    #[test]
+    fn random_reachable_boards_at_20_discs_are_found() {
+        let leaf_cache = leaf_cache_for(DEFAULT_DISCS);
+        let mut rng = StdRng::seed_from_u64(624);
+        for _ in 0..5 {
+            let board = random_reachable_board(&mut rng, 20);
+            assert_eq!(board.popcount(), 20);
+            let result = retrospective_search(
+                &board,
+                false,
+                DEFAULT_DISCS,
+                leaf_cache.leaf(),
+                &mut Btable::new(1 << 20, 0x10000),
+                &mut vec![],
+                &mut 0,
+                DEFAULT_NODE_LIMIT,
+            );
+            assert_eq!(result, Ok(SearchResult::Found), "{}", board.to_string());
+        }
+        // 同じシードからは同じ局面
+        assert_eq!(
+            random_reachable_board(&mut StdRng::seed_from_u64(7), 20),
+            random_reachable_board(&mut StdRng::seed_from_u64(7), 20)
+        );
+    }
+}
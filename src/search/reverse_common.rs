@@ -1,23 +1,41 @@
+use std::collections::HashSet;
 use std::env;
-use std::fs;
-use std::io;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
 
-use crate::io::{ensure_outputs, parse_file_to_boards};
-use crate::othello::validate_board;
+use clap::ValueEnum;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::io::{create_output, ensure_outputs, parse_file_to_boards, OutputNames};
+use crate::othello::{validate_board, Board, BoardValidation, Ruleset};
+use crate::prunings::{linear_programming::LP_AVAILABLE, PruningConfig};
+use crate::verbosity::{set_verbosity, DEBUG, INFO};
+use crate::{veprintln, vprintln};
 
 use crate::search::{
     bfs::{
-        retrospective_search_bfs, retrospective_search_bfs_par,
-        retrospective_search_bfs_par_resume, Cfg as BfsCfg,
+        bfs_join_chain, retrospective_search_bfs, retrospective_search_bfs_par,
+        retrospective_search_bfs_par_resume, BfsStats, Cfg as BfsCfg,
     },
-    core::{retrospective_search, Btable},
+    core::{retrospective_search, retrospective_search_found, Btable, FoundLeaf, SearchResult},
+    discs_auto::{choose_discs, reverse_cost_profile, AUTO_DISCS_RANGE, AUTO_DISCS_SAMPLES},
     leaf_cache::LeafCache,
-    move_ordering::retrospective_search_move_ordering,
-    parallel_dfs::{init_rayon, retrospective_search_parallel},
-    parallel_gbfs::parallel_retrospective_greedy_best_first_search,
+    move_ordering::{retrospective_search_move_ordering, Heuristic},
+    parallel_dfs::{init_rayon, retrospective_search_parallel_counted},
+    parallel_gbfs::{
+        frontier_header, parallel_retrospective_greedy_best_first_search_with, FrontierFiles,
+        GbfsOptions,
+    },
+    reachability_cache::ReachabilityCache,
+    reachable::leaf_cache_for,
     search_fwd_par::make_fwd_table,
+    sized::{board_to_string_sized, is_valid_sized, parse_board_sized, retrospective_search_sized},
+    threads::ThreadConfig,
 };
 
 pub fn default_input_path() -> PathBuf {
@@ -38,27 +56,454 @@ where
         .unwrap_or(default)
 }
 
+/// `cache_path` が指定されていればそこからリーフテーブルを読み込み、
+/// ファイルが無ければ生成して保存する。未指定なら毎回生成する。
+/// リーフテーブルは初期配置 `ruleset` からの順方向探索で作り、読み込むファイルも同じ初期配置でなければ `Err`。
+pub fn load_or_build_leaf_cache(
+    cache_path: Option<&Path>,
+    discs: i32,
+    ruleset: &Ruleset,
+) -> io::Result<LeafCache> {
+    let Some(path) = cache_path else {
+        return Ok(LeafCache::with_ruleset(discs, ruleset));
+    };
+    if path.exists() {
+        vprintln!(INFO, "info: loading leaf cache from '{}'", path.display());
+        return LeafCache::load_with(path, discs, ruleset, None);
+    }
+    let leaf_cache = LeafCache::with_ruleset(discs, ruleset);
+    leaf_cache.save(path)?;
+    vprintln!(INFO, "info: saved leaf cache to '{}'", path.display());
+    Ok(leaf_cache)
+}
+
+/// 盤面ごとの探索時間の記録。`run_dfs` / `run_dfs_move_ordering` / `run_parallel_dfs` が使う。
+///
+/// 詳細度 2 (DEBUG) では全盤面の `board= discs= nodes= result= elapsed_ms=` を、
+/// `threshold` を超えた盤面は詳細度 1 (INFO) でも `slow board:` を付けて標準エラー出力に書く。
+/// 1 つだけ極端に遅い盤面をバッチの中から見つけるためのもの。
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct SlowBoardLog {
+    threshold: Option<Duration>,
+}
+
+static SLOW_BOARD_LOG: OnceLock<SlowBoardLog> = OnceLock::new();
+
+impl SlowBoardLog {
+    /// `threshold` が `None` なら遅い盤面の警告は出さない
+    pub fn new(threshold: Option<Duration>) -> Self {
+        SlowBoardLog { threshold }
+    }
+
+    /// この設定をプロセス全体の設定にする。設定できるのは最初の 1 回だけで、
+    /// 以降に違う値を渡すと警告を出して無視する。
+    pub fn install(self) {
+        if let Err(rejected) = SLOW_BOARD_LOG.set(self) {
+            let current = SLOW_BOARD_LOG.get().unwrap();
+            if *current != rejected {
+                eprintln!(
+                    "warning: slow-board threshold is already set to {:?}; ignoring {:?}",
+                    current.threshold, rejected.threshold
+                );
+            }
+        }
+    }
+
+    /// `install` 済みならその設定、まだなら閾値なし
+    pub fn global() -> Self {
+        SLOW_BOARD_LOG.get().copied().unwrap_or_default()
+    }
+
+    pub fn threshold(&self) -> Option<Duration> {
+        self.threshold
+    }
+
+    /// `elapsed` が閾値を超えているか
+    pub fn is_slow(&self, elapsed: Duration) -> bool {
+        self.threshold.is_some_and(|t| elapsed > t)
+    }
+
+    /// 盤面 1 つの探索結果と時間を記録し、閾値を超えていれば true を返す
+    pub fn record(
+        &self,
+        board: &Board,
+        result: SearchResult,
+        nodes: usize,
+        elapsed: Duration,
+    ) -> bool {
+        let slow = self.is_slow(elapsed);
+        let level = if slow { INFO } else { DEBUG };
+        veprintln!(
+            level,
+            "{}board={} discs={} nodes={} result={:?} elapsed_ms={:.3}",
+            if slow { "slow board: " } else { "" },
+            board.to_string(),
+            board.popcount(),
+            nodes,
+            result,
+            elapsed.as_secs_f64() * 1000.0
+        );
+        slow
+    }
+}
+
+/// 入力ファイルを順に読み、盤面を 1 つの列につなげる（複数ファイルを 1 回の実行でまとめて判定する）。
+/// どれか 1 つでも盤面を読めないファイルがあれば `Err`。
+pub fn read_input_boards(inputs: &[PathBuf]) -> io::Result<Vec<Board>> {
+    let mut boards = Vec::new();
+    for input in inputs {
+        let read = parse_file_to_boards(&input.to_string_lossy())
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", input.display(), e)))?;
+        vprintln!(
+            INFO,
+            "info: read {} board(s) from '{}'.",
+            read.len(),
+            input.display()
+        );
+        boards.extend(read);
+    }
+    if inputs.len() > 1 {
+        vprintln!(
+            INFO,
+            "info: read {} board(s) from {} input file(s).",
+            boards.len(),
+            inputs.len()
+        );
+    }
+    Ok(boards)
+}
+
 /// pure dfs
-pub fn run_dfs(input: &Path, out_dir: &Path, discs: i32, node_limit: usize) -> io::Result<()> {
-    let boards = parse_file_to_boards(&input.to_string_lossy())?;
-    let total_input = boards.len();
-    println!(
-        "info: read {} board(s) from '{}'.",
-        total_input,
-        input.display()
+///
+/// `keep_table` のときは、直前の盤面が `NotFound` だった場合に限り既訪問テーブルを消さずに
+/// 次の盤面の探索に引き継ぐ。最後まで探索して `NotFound` になったときに表に残っている局面は
+/// どれも（入力によらず）初期局面に戻れないことが示されているので、続く盤面で再訪を省いてよい。
+/// `Found` / `Unknown` のときは探索途中の局面が混ざるので消す。
+///
+/// `reachability_cache_path` を指定すると、そこに蓄えた判定済みの局面は探索せずに書き出し、
+/// 新たに `Found` / `NotFound` になった局面を追記して最後に保存する。判定は使ったリーフ表ごとに
+/// 記録するので、`discs` の違う実行で同じファイルを使っても混ざらない。
+///
+/// `flip_cap` は `retrospective_search_found` と同じ（直前局面の候補が多すぎる局面は `Unknown`）。
+#[allow(clippy::too_many_arguments)]
+pub fn run_dfs(
+    inputs: &[PathBuf],
+    out_dir: &Path,
+    discs: i32,
+    node_limit: usize,
+    flip_cap: usize,
+    ruleset: &Ruleset,
+    leaf_cache_path: Option<&Path>,
+    keep_table: bool,
+    reachability_cache_path: Option<&Path>,
+) -> io::Result<()> {
+    let boards = read_input_boards(inputs)?;
+
+    let mut outputs = ensure_outputs(out_dir)?;
+    vprintln!(INFO, "info: writing outputs under '{}'", out_dir.display());
+
+    let leaf_cache = load_or_build_leaf_cache(leaf_cache_path, discs, ruleset)?;
+    vprintln!(
+        INFO,
+        "info: discs = {}: internal = {}, leaf = {}",
+        discs,
+        leaf_cache.searched_count(),
+        leaf_cache.leaf_count()
     );
 
+    let mut reachability_cache = match reachability_cache_path {
+        Some(path) => {
+            let cache = ReachabilityCache::load_or_new(path)?;
+            vprintln!(
+                INFO,
+                "info: reachability cache '{}': OK = {}, NG = {}",
+                path.display(),
+                cache.ok_count(),
+                cache.ng_count()
+            );
+            Some(cache)
+        }
+        None => None,
+    };
+    let mut cache_hits = 0usize;
+
+    let mut retrospective_searched: Btable = Btable::new(0x100000000, 0x10000);
+    let mut retroflips: Vec<[u64; 10_000]> = vec![];
+    let mut table_proven_ng = false;
+    let slow_boards = SlowBoardLog::global();
+
+    for board in boards {
+        let line = board.to_string();
+
+        if validate_board(&board).is_err() {
+            outputs.write_invalid(&board, &line)?;
+            continue;
+        }
+
+        if let Some(result) = reachability_cache
+            .as_ref()
+            .and_then(|c| c.get(&board, &leaf_cache))
+        {
+            cache_hits += 1;
+            outputs.write_result(result, &board, &line)?;
+            continue;
+        }
+
+        if !(keep_table && table_proven_ng) {
+            retrospective_searched.clear();
+        }
+        let mut node_count: usize = 0;
+
+        let start = Instant::now();
+        let (result, found) = retrospective_search_found(
+            &board,
+            false,
+            discs,
+            leaf_cache.leaf(),
+            &mut retrospective_searched,
+            &mut retroflips,
+            &mut node_count,
+            node_limit,
+            flip_cap,
+        )?;
+        slow_boards.record(&board, result, node_count, start.elapsed());
+        table_proven_ng = result == SearchResult::NotFound;
+        if let Some(cache) = reachability_cache.as_mut() {
+            cache.insert(&board, &leaf_cache, result);
+        }
+        outputs.write_result(result, &board, &line)?;
+        if let Some(found) = found {
+            outputs.write_found_leaf(&line, &found)?;
+        }
+        outputs.flush()?;
+    }
+
+    if let (Some(cache), Some(path)) = (&reachability_cache, reachability_cache_path) {
+        cache.save(path)?;
+        vprintln!(
+            INFO,
+            "info: {} board(s) answered from the reachability cache; saved OK = {}, NG = {}",
+            cache_hits,
+            cache.ok_count(),
+            cache.ng_count()
+        );
+    }
+
+    outputs.flush()
+}
+
+/// pure dfs（`Unknown` の盤面だけノード数の上限を上げて再探索する）
+///
+/// 1 周目は `node_limit` で全盤面を探索し、判定が付いた盤面はその場で書き出す。
+/// `Unknown` の盤面は上限を `factor` 倍（`max_node_limit` で頭打ち）にして再探索し、
+/// 上限が `max_node_limit` に達しても `Unknown` のものだけを `reverse_UNKNOWN.txt` に書く。
+#[allow(clippy::too_many_arguments)]
+pub fn run_dfs_escalating(
+    inputs: &[PathBuf],
+    out_dir: &Path,
+    discs: i32,
+    node_limit: usize,
+    max_node_limit: usize,
+    factor: usize,
+    flip_cap: usize,
+    ruleset: &Ruleset,
+    leaf_cache_path: Option<&Path>,
+) -> io::Result<()> {
+    if factor < 2 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("escalation factor must be at least 2, got {}", factor),
+        ));
+    }
+    let boards = read_input_boards(inputs)?;
+    let total_input = boards.len();
+
     let mut outputs = ensure_outputs(out_dir)?;
-    println!("info: writing outputs under '{}'", out_dir.display());
+    vprintln!(INFO, "info: writing outputs under '{}'", out_dir.display());
 
-    let leaf_cache = LeafCache::new(discs);
-    println!(
+    let leaf_cache = load_or_build_leaf_cache(leaf_cache_path, discs, ruleset)?;
+    vprintln!(
+        INFO,
         "info: discs = {}: internal = {}, leaf = {}",
         discs,
         leaf_cache.searched_count(),
         leaf_cache.leaf_count()
     );
 
+    let mut pending = Vec::with_capacity(total_input);
+    for board in boards {
+        if validate_board(&board).is_err() {
+            outputs.write_invalid(&board, &board.to_string())?;
+        } else {
+            pending.push(board);
+        }
+    }
+
+    let mut retrospective_searched: Btable = Btable::new(0x100000000, 0x10000);
+    search_escalating(
+        pending,
+        discs,
+        leaf_cache.leaf(),
+        &mut retrospective_searched,
+        node_limit,
+        max_node_limit,
+        factor,
+        flip_cap,
+        |board, result, found| {
+            let line = board.to_string();
+            outputs.write_result(result, board, &line)?;
+            if let Some(found) = found {
+                outputs.write_found_leaf(&line, &found)?;
+            }
+            outputs.flush()
+        },
+    )?;
+
+    outputs.flush()
+}
+
+/// `run_dfs_escalating` の探索部分。`pending` を上限 `node_limit` で探索し、`Unknown` の盤面だけを
+/// 上限を `factor` 倍（`max_node_limit` で頭打ち）にして再探索する。判定が付いた盤面（上限が
+/// `max_node_limit` に達しても `Unknown` のものを含む）から順に `emit` に渡す。
+#[allow(clippy::too_many_arguments)]
+fn search_escalating(
+    mut pending: Vec<Board>,
+    discs: i32,
+    leafnode: &HashSet<[u64; 2]>,
+    retrospective_searched: &mut Btable,
+    node_limit: usize,
+    max_node_limit: usize,
+    factor: usize,
+    flip_cap: usize,
+    mut emit: impl FnMut(&Board, SearchResult, Option<FoundLeaf>) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut retroflips: Vec<[u64; 10_000]> = vec![];
+    let mut limit = node_limit;
+    loop {
+        vprintln!(
+            INFO,
+            "info: searching {} board(s) with max nodes = {}",
+            pending.len(),
+            limit
+        );
+        let mut unknown = Vec::new();
+        for board in pending {
+            retrospective_searched.clear();
+            let mut node_count: usize = 0;
+
+            let (result, found) = retrospective_search_found(
+                &board,
+                false,
+                discs,
+                leafnode,
+                retrospective_searched,
+                &mut retroflips,
+                &mut node_count,
+                limit,
+                flip_cap,
+            )?;
+            if result == SearchResult::Unknown && limit < max_node_limit {
+                unknown.push(board);
+                continue;
+            }
+            emit(&board, result, found)?;
+        }
+        if unknown.is_empty() {
+            return Ok(());
+        }
+        pending = unknown;
+        limit = limit.saturating_mul(factor).min(max_node_limit);
+    }
+}
+
+/// 一辺 `width` マスの盤での pure dfs（`sized::retrospective_search_sized`）。
+/// 入力は 1 行に `width * width` 個の 'X', 'O', '-' を並べた盤面で、出力も同じ形式で書く。
+pub fn run_dfs_sized(
+    inputs: &[PathBuf],
+    out_dir: &Path,
+    width: u32,
+    node_limit: usize,
+) -> io::Result<()> {
+    let mut boards: Vec<Board> = Vec::new();
+    for input in inputs {
+        let text = fs::read_to_string(input)?;
+        let before = boards.len();
+        boards.extend(
+            text.lines()
+                .filter_map(|line| parse_board_sized(line, width)),
+        );
+        if boards.len() == before {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "{}: failed to parse any {}-cell X/O/- board(s)",
+                    input.display(),
+                    width * width
+                ),
+            ));
+        }
+        vprintln!(
+            INFO,
+            "info: read {} {}x{} board(s) from '{}'.",
+            boards.len() - before,
+            width,
+            width,
+            input.display()
+        );
+    }
+
+    let mut outputs = ensure_outputs(out_dir)?;
+    vprintln!(INFO, "info: writing outputs under '{}'", out_dir.display());
+
+    let mut visited = HashSet::new();
+    let mut retroflips: Vec<[u64; 10_000]> = vec![];
+    for board in boards {
+        let line = board_to_string_sized(&board, width);
+        if !is_valid_sized(&board, width) {
+            outputs.write_invalid(&board, &line)?;
+            continue;
+        }
+        visited.clear();
+        let mut node_count: usize = 0;
+        let result = retrospective_search_sized(
+            &board,
+            width,
+            false,
+            &mut visited,
+            &mut retroflips,
+            &mut node_count,
+            node_limit,
+        );
+        vprintln!(DEBUG, "result={:?}, nodes={}", result, node_count);
+        outputs.write_result(result, &board, &line)?;
+        outputs.flush()?;
+    }
+    outputs.flush()
+}
+
+/// pure dfs（しきい値を盤面ごとに `discs_auto::choose_discs` で選ぶ）。
+/// 推定には盤面ごとに `samples` 本のランダム経路を使う。`seed` を省くと乱数で決め、使った値をログに出す。
+pub fn run_dfs_discs_auto(
+    inputs: &[PathBuf],
+    out_dir: &Path,
+    node_limit: usize,
+    flip_cap: usize,
+    samples: usize,
+    seed: Option<u64>,
+) -> io::Result<()> {
+    let boards = read_input_boards(inputs)?;
+
+    let mut outputs = ensure_outputs(out_dir)?;
+    vprintln!(INFO, "info: writing outputs under '{}'", out_dir.display());
+
+    let seed = seed.unwrap_or_else(|| rand::rng().random());
+    vprintln!(
+        INFO,
+        "info: discs-auto: samples = {}, seed = {}",
+        samples,
+        seed
+    );
+    let mut rng = StdRng::seed_from_u64(seed);
     let mut retrospective_searched: Btable = Btable::new(0x100000000, 0x10000);
     let mut retroflips: Vec<[u64; 10_000]> = vec![];
 
@@ -66,14 +511,24 @@ pub fn run_dfs(input: &Path, out_dir: &Path, discs: i32, node_limit: usize) -> i
         let line = board.to_string();
 
         if validate_board(&board).is_err() {
-            outputs.write_invalid(&line)?;
+            outputs.write_invalid(&board, &line)?;
             continue;
         }
 
+        let discs = choose_discs(&board, AUTO_DISCS_RANGE, samples, &mut rng);
+        let leaf_cache = leaf_cache_for(discs);
+        vprintln!(
+            INFO,
+            "info: discs = {} (auto): internal = {}, leaf = {}",
+            discs,
+            leaf_cache.searched_count(),
+            leaf_cache.leaf_count()
+        );
+
         retrospective_searched.clear();
         let mut node_count: usize = 0;
 
-        let result = retrospective_search(
+        let (result, found) = retrospective_search_found(
             &board,
             false,
             discs,
@@ -82,8 +537,12 @@ pub fn run_dfs(input: &Path, out_dir: &Path, discs: i32, node_limit: usize) -> i
             &mut retroflips,
             &mut node_count,
             node_limit,
-        );
-        outputs.write_result(result, &line)?;
+            flip_cap,
+        )?;
+        outputs.write_result(result, &board, &line)?;
+        if let Some(found) = found {
+            outputs.write_found_leaf(&line, &found)?;
+        }
         outputs.flush()?;
     }
 
@@ -92,24 +551,20 @@ pub fn run_dfs(input: &Path, out_dir: &Path, discs: i32, node_limit: usize) -> i
 
 /// dfs + move ordering
 pub fn run_dfs_move_ordering(
-    input: &Path,
+    inputs: &[PathBuf],
     out_dir: &Path,
     discs: i32,
     node_limit: usize,
+    leaf_cache_path: Option<&Path>,
 ) -> io::Result<()> {
-    let boards = parse_file_to_boards(&input.to_string_lossy())?;
-    let total_input = boards.len();
-    println!(
-        "info: read {} board(s) from '{}'.",
-        total_input,
-        input.display()
-    );
+    let boards = read_input_boards(inputs)?;
 
     let mut outputs = ensure_outputs(out_dir)?;
-    println!("info: writing outputs under '{}'", out_dir.display());
+    vprintln!(INFO, "info: writing outputs under '{}'", out_dir.display());
 
-    let leaf_cache = LeafCache::new(discs);
-    println!(
+    let leaf_cache = load_or_build_leaf_cache(leaf_cache_path, discs, &Ruleset::standard())?;
+    vprintln!(
+        INFO,
         "info: discs = {}: internal = {}, leaf = {}",
         discs,
         leaf_cache.searched_count(),
@@ -118,18 +573,20 @@ pub fn run_dfs_move_ordering(
 
     let mut retrospective_searched: Btable = Btable::new(0x100000000, 0x10000);
     let mut retroflips: Vec<[u64; 10_000]> = vec![];
+    let slow_boards = SlowBoardLog::global();
 
     for board in boards {
         let line = board.to_string();
 
         if validate_board(&board).is_err() {
-            outputs.write_invalid(&line)?;
+            outputs.write_invalid(&board, &line)?;
             continue;
         }
 
         retrospective_searched.clear();
         let mut node_count: usize = 0;
 
+        let start = Instant::now();
         let result = retrospective_search_move_ordering(
             &board,
             false,
@@ -139,8 +596,9 @@ pub fn run_dfs_move_ordering(
             &mut retroflips,
             &mut node_count,
             node_limit,
-        );
-        outputs.write_result(result, &line)?;
+        )?;
+        slow_boards.record(&board, result, node_count, start.elapsed());
+        outputs.write_result(result, &board, &line)?;
         outputs.flush()?;
     }
 
@@ -149,51 +607,51 @@ pub fn run_dfs_move_ordering(
 
 /// parallel dfs
 pub fn run_parallel_dfs(
-    input: &Path,
+    inputs: &[PathBuf],
     out_dir: &Path,
     discs: i32,
     node_limit: usize,
     table_limit: usize,
     rayon_threads: Option<usize>,
+    leaf_cache_path: Option<&Path>,
 ) -> io::Result<()> {
-    let boards = parse_file_to_boards(&input.to_string_lossy())?;
-    let total_input = boards.len();
-    println!(
-        "info: read {} board(s) from '{}'.",
-        total_input,
-        input.display()
-    );
+    let boards = read_input_boards(inputs)?;
 
     let mut outputs = ensure_outputs(out_dir)?;
-    println!("info: writing outputs under '{}'", out_dir.display());
+    vprintln!(INFO, "info: writing outputs under '{}'", out_dir.display());
 
-    let leaf_cache = LeafCache::new(discs);
-    println!(
+    // 順方向のリーフ表の作成も同じスレッド数で行う
+    init_rayon(rayon_threads);
+    let leaf_cache = load_or_build_leaf_cache(leaf_cache_path, discs, &Ruleset::standard())?;
+    vprintln!(
+        INFO,
         "info: discs = {}: internal = {}, leaf = {}",
         discs,
         leaf_cache.searched_count(),
         leaf_cache.leaf_count()
     );
-
-    init_rayon(rayon_threads);
+    let slow_boards = SlowBoardLog::global();
 
     for board in boards {
         let line = board.to_string();
 
         if validate_board(&board).is_err() {
-            outputs.write_invalid(&line)?;
+            outputs.write_invalid(&board, &line)?;
             continue;
         }
 
-        let result = retrospective_search_parallel(
+        let start = Instant::now();
+        let (result, node_count) = retrospective_search_parallel_counted(
             &board,
             false,
             discs,
             leaf_cache.leaf(),
             node_limit,
             table_limit,
-        );
-        outputs.write_result(result, &line)?;
+            false,
+        )?;
+        slow_boards.record(&board, result, node_count, start.elapsed());
+        outputs.write_result(result, &board, &line)?;
         outputs.flush()?;
     }
 
@@ -201,24 +659,29 @@ pub fn run_parallel_dfs(
 }
 
 /// parallel greedy best first search + priority queue (skiplist)
+///
+/// `frontier_dir` が指定されていれば、`index` 番目の盤面の frontier と既訪問集合を
+/// `frontier_{index}.bin` / `visited_{index}.bin` に書き出し、同じ盤面・設定で書き出したものが
+/// 既にあればそこから再開する（別の盤面や設定のものは警告して捨てる）。
+/// `max_nodes_per_disc` は石数ごとにキューへ積む局面数の上限（`GbfsOptions::max_nodes_per_disc`）。
+/// `prunings` は各局面に使う枝刈り（`prunings.lp` なら LP も解く）。
+#[allow(clippy::too_many_arguments)]
 pub fn run_parallel_gbfs(
-    input: &Path,
+    inputs: &[PathBuf],
     out_dir: &Path,
     discs: i32,
     node_limit: usize,
-    use_lp: bool,
+    prunings: PruningConfig,
     rayon_threads: Option<usize>,
+    frontier_dir: Option<&Path>,
+    dump_interval: usize,
+    heuristic: Heuristic,
+    max_nodes_per_disc: Option<usize>,
 ) -> io::Result<()> {
-    let boards = parse_file_to_boards(&input.to_string_lossy())?;
-    let total_input = boards.len();
-    println!(
-        "info: read {} board(s) from '{}'.",
-        total_input,
-        input.display()
-    );
+    let boards = read_input_boards(inputs)?;
 
     let mut outputs = ensure_outputs(out_dir)?;
-    println!("info: writing outputs under '{}'", out_dir.display());
+    vprintln!(INFO, "info: writing outputs under '{}'", out_dir.display());
 
     //let leaf_cache = LeafCache::new(discs);
     //println!(
@@ -228,21 +691,47 @@ pub fn run_parallel_gbfs(
     //    leaf_cache.leaf_count()
     //);
 
+    if prunings.lp && !LP_AVAILABLE {
+        eprintln!("warning: built without LP support; --use-lp has no effect");
+    }
+
     init_rayon(rayon_threads);
+    if let Some(dir) = frontier_dir {
+        fs::create_dir_all(dir)?;
+    }
 
-    for board in boards {
-        let leaf = make_fwd_table(&[board.player, board.opponent], discs);
+    for (index, board) in boards.iter().enumerate() {
         let line = board.to_string();
 
-        if validate_board(&board).is_err() {
-            outputs.write_invalid(&line)?;
+        if validate_board(board).is_err() {
+            outputs.write_invalid(board, &line)?;
             continue;
         }
+        let leaf = make_fwd_table(&[board.player, board.opponent], discs);
 
-        let result = parallel_retrospective_greedy_best_first_search(
-            &board, discs, &leaf, node_limit, use_lp,
-        );
-        outputs.write_result(result, &line)?;
+        let files = frontier_dir.map(|dir| FrontierFiles::in_dir(dir, index));
+        let mut opts = GbfsOptions {
+            resume_from: None,
+            dump_to: files.as_ref(),
+            dump_interval,
+            heuristic,
+            max_nodes_per_disc,
+        };
+        // 別の盤面や設定で書き出された frontier は使わずに最初から探索する（上書きされる）
+        if let Some(files) = files.as_ref().filter(|f| f.exists()) {
+            if files.matches(&frontier_header(board, discs, prunings, &opts)) {
+                opts.resume_from = Some(files);
+            } else {
+                eprintln!(
+                    "warning: ignoring '{}' dumped by a search of another board or configuration",
+                    files.frontier.display()
+                );
+            }
+        }
+        let result = parallel_retrospective_greedy_best_first_search_with(
+            board, discs, &leaf, node_limit, prunings, opts,
+        )?;
+        outputs.write_result(result, board, &line)?;
         outputs.flush()?;
     }
 
@@ -251,11 +740,16 @@ pub fn run_parallel_gbfs(
 
 /// sequential bfs
 pub fn run_bfs(cfg: &BfsCfg) -> io::Result<()> {
-    println!("cfg={:?}", cfg);
+    set_verbosity(cfg.verbose);
+    vprintln!(DEBUG, "cfg={:?}", cfg);
+    if cfg.plan {
+        return print_bfs_plan(cfg);
+    }
     let boards = parse_file_to_boards(&cfg.input.to_string_lossy())?;
     let discs = cfg.discs as i32;
     let total_input = boards.len();
-    println!(
+    vprintln!(
+        INFO,
         "info: read {} board(s) from '{}'.",
         total_input,
         cfg.input.display()
@@ -265,10 +759,15 @@ pub fn run_bfs(cfg: &BfsCfg) -> io::Result<()> {
     fs::create_dir_all(&cfg.tmp_dir)?;
 
     let mut outputs = ensure_outputs(&cfg.out_dir)?;
-    println!("info: writing outputs under '{}'", cfg.out_dir.display());
+    vprintln!(
+        INFO,
+        "info: writing outputs under '{}'",
+        cfg.out_dir.display()
+    );
 
-    let leaf_cache = LeafCache::new(discs);
-    println!(
+    let leaf_cache = LeafCache::with_ruleset(discs, &cfg.ruleset);
+    vprintln!(
+        INFO,
         "info: discs = {}: internal = {}, leaf = {}",
         cfg.discs,
         leaf_cache.searched_count(),
@@ -279,30 +778,122 @@ pub fn run_bfs(cfg: &BfsCfg) -> io::Result<()> {
         let line = board.to_string();
 
         if validate_board(&board).is_err() {
-            outputs.write_invalid(&line)?;
+            outputs.write_invalid(&board, &line)?;
             continue;
         }
 
-        let stat = retrospective_search_bfs(cfg, &board, discs, leaf_cache.leaf())?;
-        outputs.write_result(stat, &line)?;
+        let stats = retrospective_search_bfs(cfg, &board, discs, leaf_cache.leaf())?;
+        vprintln!(INFO, "info: {} reverse position(s) in total", stats.total);
+        report_bfs_leaf(cfg, discs, &stats);
+        outputs.write_result(stats.result, &board, &line)?;
         outputs.flush()?;
     }
 
     outputs.flush()
 }
 
+/// `--plan`: 一時ファイルを作らずに、盤面ごとに展開する段 (`r_{s+1}.bin` → `r_{s}.bin`) と
+/// 各段の局面数・ファイルサイズの見積もり、順方向のリーフテーブルの大きさを表示する。
+/// 見積もりは `reverse_cost_profile` による逆方向の木の大きさで、重複を除く前の値（上限の目安）。
+fn print_bfs_plan(cfg: &BfsCfg) -> io::Result<()> {
+    let boards = parse_file_to_boards(&cfg.input.to_string_lossy())?;
+    let discs = cfg.discs as i32;
+    let leaf_cache = LeafCache::with_ruleset(discs, &cfg.ruleset);
+    println!(
+        "forward leaf table: discs = {}, internal = {}, leaf = {}",
+        discs,
+        leaf_cache.searched_count(),
+        leaf_cache.leaf_count()
+    );
+    println!(
+        "tmp files: {}/r_{{s}}.bin (16 bytes per position{}), blocks b_{{s}}_{{i}}.bin of up to {} positions",
+        cfg.tmp_dir.display(),
+        if cfg.compress {
+            " before zstd compression"
+        } else {
+            ""
+        },
+        cfg.block_size
+    );
+
+    let mut rng = rand::rng();
+    for (i, board) in boards.iter().enumerate() {
+        let num_disc = board.popcount() as i32;
+        println!("board {}: {} ({} discs)", i, board.to_string(), num_disc);
+        if validate_board(board).is_err() {
+            println!(
+                "  invalid board, written to {}",
+                OutputNames::global().file_name("NG")
+            );
+            continue;
+        }
+        if num_disc <= discs {
+            println!("  looked up in the leaf table directly");
+            continue;
+        }
+        let profile = reverse_cost_profile(board, discs, AUTO_DISCS_SAMPLES, &mut rng);
+        for s in (discs..num_disc).rev() {
+            let estimate = profile[s as usize] - profile[s as usize + 1];
+            println!(
+                "  r_{}.bin -> r_{}.bin: ~{:.0} position(s), ~{:.0} bytes",
+                s + 1,
+                s,
+                estimate,
+                estimate * 16.0
+            );
+        }
+    }
+    Ok(())
+}
+
+/// BFS が `Found` のとき、一致したリーフと入力局面の段までの連鎖をログに出す
+fn report_bfs_leaf(cfg: &BfsCfg, discs: i32, stats: &BfsStats) {
+    let Some(leaf) = stats.leaf else {
+        return;
+    };
+    vprintln!(
+        INFO,
+        "info: matched leaf {}",
+        Board::new(leaf[0], leaf[1]).to_string()
+    );
+    let num_disc = stats.per_level[0].0;
+    match bfs_join_chain(&cfg.tmp_dir, leaf, discs, num_disc) {
+        Ok(chain) => {
+            for (s, b) in (discs..).zip(chain) {
+                vprintln!(
+                    INFO,
+                    "info:   {}: {}",
+                    s,
+                    Board::new(b[0], b[1]).to_string()
+                );
+            }
+        }
+        Err(e) => eprintln!("warning: failed to reconstruct the join chain: {}", e),
+    }
+}
+
 /// parallel bfs
 pub fn run_parallel_bfs(cfg: &BfsCfg) -> io::Result<()> {
-    println!("cfg={:?}", cfg);
+    set_verbosity(cfg.verbose);
+    vprintln!(DEBUG, "cfg={:?}", cfg);
+    ThreadConfig::new(Some(cfg.jobs)).install();
+    if cfg.plan {
+        return print_bfs_plan(cfg);
+    }
 
     fs::create_dir_all(&cfg.out_dir)?;
     fs::create_dir_all(&cfg.tmp_dir)?;
     let mut outputs = ensure_outputs(&cfg.out_dir)?;
-    println!("info: writing outputs under '{}'", cfg.out_dir.display());
+    vprintln!(
+        INFO,
+        "info: writing outputs under '{}'",
+        cfg.out_dir.display()
+    );
 
     let discs = cfg.discs as i32;
-    let leaf_cache = LeafCache::new(discs);
-    println!(
+    let leaf_cache = LeafCache::with_ruleset(discs, &cfg.ruleset);
+    vprintln!(
+        INFO,
         "info: discs = {}: internal = {}, leaf = {}",
         cfg.discs,
         leaf_cache.searched_count(),
@@ -318,7 +909,7 @@ pub fn run_parallel_bfs(cfg: &BfsCfg) -> io::Result<()> {
         let last = parts
             .last()
             .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "input path is empty"))?;
-        println!("last={}", last);
+        vprintln!(DEBUG, "last={}", last);
         let sp_under: Vec<&str> = last.split_terminator('_').collect();
         if sp_under.len() < 2 {
             return Err(io::Error::new(
@@ -333,13 +924,15 @@ pub fn run_parallel_bfs(cfg: &BfsCfg) -> io::Result<()> {
                 format!("failed to parse disc count from {}: {e}", last),
             )
         })?;
-        retrospective_search_bfs_par_resume(cfg, num_disc, discs, leaf_cache.leaf())?;
+        let stats = retrospective_search_bfs_par_resume(cfg, num_disc, discs, leaf_cache.leaf())?;
+        report_bfs_leaf(cfg, discs, &stats);
         return outputs.flush();
     }
 
     let boards = parse_file_to_boards(&cfg.input.to_string_lossy())?;
     let total_input = boards.len();
-    println!(
+    vprintln!(
+        INFO,
         "info: read {} board(s) from '{}'.",
         total_input,
         cfg.input.display()
@@ -349,14 +942,717 @@ pub fn run_parallel_bfs(cfg: &BfsCfg) -> io::Result<()> {
         let line = board.to_string();
 
         if validate_board(&board).is_err() {
-            outputs.write_invalid(&line)?;
+            outputs.write_invalid(&board, &line)?;
             continue;
         }
 
-        let stat = retrospective_search_bfs_par(cfg, &board, discs, leaf_cache.leaf())?;
-        outputs.write_result(stat, &line)?;
+        let stats = retrospective_search_bfs_par(cfg, &board, discs, leaf_cache.leaf())?;
+        vprintln!(INFO, "info: {} reverse position(s) in total", stats.total);
+        report_bfs_leaf(cfg, discs, &stats);
+        outputs.write_result(stats.result, &board, &line)?;
         outputs.flush()?;
     }
 
     outputs.flush()
 }
+
+/// compare で比較する探索戦略
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Strategy {
+    /// 逐次 DFS
+    Dfs,
+    /// 逐次 DFS + move ordering
+    #[value(name = "dfs-move-ordering")]
+    MoveOrdering,
+    /// 並列 DFS
+    #[value(name = "dfs-parallel")]
+    Parallel,
+}
+
+impl Strategy {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Strategy::Dfs => "dfs",
+            Strategy::MoveOrdering => "dfs-move-ordering",
+            Strategy::Parallel => "dfs-parallel",
+        }
+    }
+}
+
+/// `strategy` で `board` を探索し、結果と展開したノード数を返す。
+/// `retrospective_searched` / `retroflips` は逐次版の作業領域（探索前に表を消す）、
+/// `table_limit` は並列版の既訪問表の大きさ。並列版を使う前に `init_rayon` を呼んでおくこと。
+/// 不正な盤面は `Err`。
+#[allow(clippy::too_many_arguments)]
+pub fn search_with_strategy(
+    strategy: Strategy,
+    board: &Board,
+    discs: i32,
+    leafnode: &HashSet<[u64; 2]>,
+    retrospective_searched: &mut Btable,
+    retroflips: &mut Vec<[u64; 10_000]>,
+    node_limit: usize,
+    table_limit: usize,
+) -> Result<(SearchResult, usize), BoardValidation> {
+    match strategy {
+        Strategy::Dfs | Strategy::MoveOrdering => {
+            retrospective_searched.clear();
+            let mut node_count: usize = 0;
+            let search = if strategy == Strategy::Dfs {
+                retrospective_search
+            } else {
+                retrospective_search_move_ordering
+            };
+            let result = search(
+                board,
+                false,
+                discs,
+                leafnode,
+                retrospective_searched,
+                retroflips,
+                &mut node_count,
+                node_limit,
+            )?;
+            Ok((result, node_count))
+        }
+        Strategy::Parallel => retrospective_search_parallel_counted(
+            board,
+            false,
+            discs,
+            leafnode,
+            node_limit,
+            table_limit,
+            false,
+        ),
+    }
+}
+
+/// 同じ入力を複数の戦略で探索し、`compare.tsv` に
+/// `board, strategy, result, nodes, elapsed_ms` を書き出す。
+/// Found と NotFound が食い違った盤面は `compare_disagree.txt` に書き出す（Unknown は対象外）。
+#[allow(clippy::too_many_arguments)]
+pub fn run_compare(
+    inputs: &[PathBuf],
+    out_dir: &Path,
+    discs: i32,
+    node_limit: usize,
+    table_limit: usize,
+    rayon_threads: Option<usize>,
+    strategies: &[Strategy],
+    leaf_cache_path: Option<&Path>,
+) -> io::Result<()> {
+    let boards = read_input_boards(inputs)?;
+
+    fs::create_dir_all(out_dir)?;
+    let force = OutputNames::global().force();
+    let mut table = BufWriter::new(create_output(&out_dir.join("compare.tsv"), force)?);
+    let mut disagree = BufWriter::new(create_output(&out_dir.join("compare_disagree.txt"), force)?);
+    writeln!(table, "board\tstrategy\tresult\tnodes\telapsed_ms")?;
+    vprintln!(INFO, "info: writing outputs under '{}'", out_dir.display());
+
+    if strategies.contains(&Strategy::Parallel) {
+        init_rayon(rayon_threads);
+    }
+    let leaf_cache = load_or_build_leaf_cache(leaf_cache_path, discs, &Ruleset::standard())?;
+    vprintln!(
+        INFO,
+        "info: discs = {}: internal = {}, leaf = {}",
+        discs,
+        leaf_cache.searched_count(),
+        leaf_cache.leaf_count()
+    );
+
+    let mut retrospective_searched: Btable = Btable::new(0x100000000, 0x10000);
+    let mut retroflips: Vec<[u64; 10_000]> = vec![];
+    let mut num_disagree = 0;
+
+    for board in boards {
+        let line = board.to_string();
+
+        if validate_board(&board).is_err() {
+            vprintln!(INFO, "info: skipping invalid board {}", line);
+            continue;
+        }
+
+        let mut verdicts: Vec<SearchResult> = vec![];
+        for &strategy in strategies {
+            let start = Instant::now();
+            let (result, nodes) = search_with_strategy(
+                strategy,
+                &board,
+                discs,
+                leaf_cache.leaf(),
+                &mut retrospective_searched,
+                &mut retroflips,
+                node_limit,
+                table_limit,
+            )?;
+            let elapsed = start.elapsed().as_secs_f64() * 1000.0;
+            writeln!(
+                table,
+                "{}\t{}\t{:?}\t{}\t{:.3}",
+                line,
+                strategy.name(),
+                result,
+                nodes,
+                elapsed
+            )?;
+            if result != SearchResult::Unknown {
+                verdicts.push(result);
+            }
+        }
+
+        if verdicts.windows(2).any(|w| w[0] != w[1]) {
+            num_disagree += 1;
+            eprintln!("warning: strategies disagree on {}", line);
+            writeln!(disagree, "{}", line)?;
+        }
+        table.flush()?;
+        disagree.flush()?;
+    }
+
+    vprintln!(
+        INFO,
+        "info: {} board(s) with disagreeing verdicts",
+        num_disagree
+    );
+    table.flush()?;
+    disagree.flush()
+}
+
+/// `run_meta.json` に書く実行条件。結果ディレクトリだけから実行を再現できるようにする。
+///
+/// 判定ごとの盤面数は書き出し時に `reverse_{OK,NG,UNKNOWN}.txt` の行数から数える
+/// （`compare` のようにこれらを作らない実行では `null`）。
+#[derive(Debug, Clone)]
+pub struct RunMeta {
+    /// サブコマンド名（`dfs`, `bfs-parallel` など）
+    pub strategy: String,
+    /// 入力ファイル。複数あれば `input` に先頭を、`inputs` に全てを書く
+    pub inputs: Vec<PathBuf>,
+    /// 順方向探索のしきい値（`--discs-auto` や `--size 6` のように固定しない実行では `None`）
+    pub discs: Option<i32>,
+    /// 1 盤面あたりのノード数の上限（BFS のように上限の無い実行では `None`）
+    pub node_limit: Option<usize>,
+    pub threads: usize,
+}
+
+impl RunMeta {
+    /// `out_dir/run_meta.json` に `elapsed` とクレートのバージョンを添えて書き出す
+    pub fn write(&self, out_dir: &Path, elapsed: Duration) -> io::Result<()> {
+        let names = OutputNames::global();
+        let count = |result: SearchResult| -> io::Result<Option<usize>> {
+            match fs::read_to_string(names.result_path(out_dir, result)) {
+                Ok(text) => Ok(Some(text.lines().count())),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+                Err(e) => Err(e),
+            }
+        };
+        let ok = count(SearchResult::Found)?;
+        let ng = count(SearchResult::NotFound)?;
+        let unknown = count(SearchResult::Unknown)?;
+        let or_null = |v: Option<String>| v.unwrap_or_else(|| "null".to_string());
+
+        fs::create_dir_all(out_dir)?;
+        let mut w = BufWriter::new(File::create(out_dir.join("run_meta.json"))?);
+        writeln!(w, "{{")?;
+        writeln!(
+            w,
+            "  \"version\": {},",
+            json_string(env!("CARGO_PKG_VERSION"))
+        )?;
+        writeln!(w, "  \"strategy\": {},", json_string(&self.strategy))?;
+        let inputs: Vec<String> = self
+            .inputs
+            .iter()
+            .map(|input| json_string(&input.to_string_lossy()))
+            .collect();
+        writeln!(w, "  \"input\": {},", or_null(inputs.first().cloned()))?;
+        writeln!(w, "  \"inputs\": [{}],", inputs.join(", "))?;
+        writeln!(
+            w,
+            "  \"discs\": {},",
+            or_null(self.discs.map(|d| d.to_string()))
+        )?;
+        writeln!(
+            w,
+            "  \"node_limit\": {},",
+            or_null(self.node_limit.map(|n| n.to_string()))
+        )?;
+        writeln!(w, "  \"threads\": {},", self.threads)?;
+        writeln!(w, "  \"elapsed_sec\": {:.3},", elapsed.as_secs_f64())?;
+        writeln!(w, "  \"ok\": {},", or_null(ok.map(|n| n.to_string())))?;
+        writeln!(w, "  \"ng\": {},", or_null(ng.map(|n| n.to_string())))?;
+        writeln!(
+            w,
+            "  \"unknown\": {}",
+            or_null(unknown.map(|n| n.to_string()))
+        )?;
+        writeln!(w, "}}")?;
+        w.flush()
+    }
+}
+
+/// JSON の文字列リテラルにする（`"` と `\` と制御文字をエスケープ）
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::leaf_cache::LeafCache;
+    use crate::search::reachable::random_reachable_board;
+    use crate::test_util::scratch_dir;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn dfs_and_move_ordering_agree_on_fixed_boards() {
+        let discs = 8;
+        let leaf_cache = LeafCache::new(discs);
+        let mut retrospective_searched = Btable::new(1 << 16, 1 << 8);
+        let mut retroflips: Vec<[u64; 10_000]> = vec![];
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut boards: Vec<Board> = (0..8)
+            .map(|i| random_reachable_board(&mut rng, 12 + i))
+            .collect();
+        // 1 石だけ色を変えた盤面（到達不能なものも混ざる）
+        let altered: Vec<Board> = boards
+            .iter()
+            .map(|b| {
+                let bit = 1u64 << b.opponent.trailing_zeros();
+                Board::new(b.player | bit, b.opponent & !bit)
+            })
+            .collect();
+        boards.extend(altered);
+
+        let mut verdicts = vec![];
+        for board in &boards {
+            let mut run = |strategy| {
+                search_with_strategy(
+                    strategy,
+                    board,
+                    discs,
+                    leaf_cache.leaf(),
+                    &mut retrospective_searched,
+                    &mut retroflips,
+                    1_000_000,
+                    1_000_000,
+                )
+                .unwrap()
+            };
+            let (dfs, dfs_nodes) = run(Strategy::Dfs);
+            let (ordered, _) = run(Strategy::MoveOrdering);
+            assert_ne!(dfs, SearchResult::Unknown, "{}", board.to_string());
+            assert_eq!(dfs, ordered, "{}", board.to_string());
+            assert!(dfs_nodes > 0);
+            verdicts.push(dfs);
+        }
+        assert!(verdicts.contains(&SearchResult::Found));
+        assert!(verdicts.contains(&SearchResult::NotFound));
+    }
+
+    #[test]
+    fn unknown_boards_are_found_after_escalation() {
+        let leaf_cache = LeafCache::new(8);
+        // f5 d6 c3 d3 c4 f4 f6 f3 e6 e7（14 石）
+        let board = Board::play_sequence(&[37, 43, 18, 19, 26, 29, 45, 21, 44, 52]).unwrap();
+        let mut table = Btable::new(1 << 16, 1 << 8);
+        let run = |table: &mut Btable, max_node_limit: usize| {
+            let mut results = vec![];
+            search_escalating(
+                vec![board],
+                8,
+                leaf_cache.leaf(),
+                table,
+                1,
+                max_node_limit,
+                10,
+                usize::MAX,
+                |b, result, found| {
+                    results.push((*b, result, found.is_some()));
+                    Ok(())
+                },
+            )
+            .unwrap();
+            results
+        };
+        // 上限 1 のままでは判定できない
+        assert_eq!(run(&mut table, 1), [(board, SearchResult::Unknown, false)]);
+        // 1, 10, 100, ... と上げていけば見つかる
+        assert_eq!(
+            run(&mut table, 1_000_000),
+            [(board, SearchResult::Found, true)]
+        );
+
+        let err = run_dfs_escalating(
+            &[],
+            Path::new("unused"),
+            8,
+            1,
+            10,
+            1,
+            usize::MAX,
+            &Ruleset::standard(),
+            None,
+        )
+        .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn plan_creates_no_tmp_files() {
+        let dir = scratch_dir("plan");
+        let input = dir.join("board.txt");
+        // f5 d6 c3 d3 c4 f4 f6（11 石）
+        let board = Board::play_sequence(&[37, 43, 18, 19, 26, 29, 45]).unwrap();
+        fs::write(&input, format!("{}\n", board.to_string())).unwrap();
+        let cfg = BfsCfg::builder(&input)
+            .out_dir(dir.join("result"))
+            .tmp_dir(dir.join("tmp"))
+            .discs(8)
+            .plan(true)
+            .build()
+            .unwrap();
+        run_bfs(&cfg).unwrap();
+        run_parallel_bfs(&cfg).unwrap();
+        assert!(!dir.join("tmp").exists());
+        assert!(!dir.join("result").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn kept_table_after_not_found_saves_nodes_on_a_successor() {
+        use crate::othello::{flip, get_moves};
+        let discs = 8;
+        let leaf_cache = LeafCache::new(discs);
+        let mut rng = StdRng::seed_from_u64(638);
+        let mut retroflips: Vec<[u64; 10_000]> = vec![];
+        let mut search = |board: &Board, table: &mut Btable| {
+            let mut nodes = 0;
+            let result = retrospective_search(
+                board,
+                false,
+                discs,
+                leaf_cache.leaf(),
+                table,
+                &mut retroflips,
+                &mut nodes,
+                1_000_000,
+            )
+            .unwrap();
+            (result, nodes)
+        };
+        let mut saved = 0;
+        for i in 0..20 {
+            // 1 石だけ色を変えた盤面と、そこから 1 手進めた盤面（直前の盤面を前駆に持つ）
+            let b = random_reachable_board(&mut rng, 14 + i % 8);
+            let bit = 1u64 << b.opponent.trailing_zeros();
+            let first = Board::new(b.player | bit, b.opponent & !bit);
+            let moves = get_moves(first.player, first.opponent);
+            if moves == 0 {
+                continue;
+            }
+            let pos = moves.trailing_zeros() as usize;
+            let flipped = flip(pos, first.player, first.opponent);
+            let second = Board::new(
+                first.opponent ^ flipped,
+                first.player ^ (flipped | (1u64 << pos)),
+            );
+
+            let mut kept = Btable::new(1 << 20, 1 << 10);
+            let (result, first_nodes) = search(&first, &mut kept);
+            if result != SearchResult::NotFound {
+                continue;
+            }
+            let (fresh_result, fresh_nodes) = search(&second, &mut Btable::new(1 << 20, 1 << 10));
+            let (kept_result, kept_nodes) = search(&second, &mut kept);
+            assert_eq!(kept_result, fresh_result, "{}", second.to_string());
+            assert!(kept_nodes <= fresh_nodes);
+            if first_nodes > 1 {
+                assert!(kept_nodes < fresh_nodes, "{}", second.to_string());
+                saved += 1;
+            }
+        }
+        assert!(saved > 0);
+    }
+
+    #[test]
+    fn every_strategy_rejects_malformed_boards() {
+        let initial = Board::initial();
+        let overlap = Board::new(
+            initial.player | (1u64 << 19),
+            initial.opponent | (1u64 << 19),
+        );
+        let missing_center = Board::new(initial.player, initial.opponent & !(1u64 << 27));
+        let leaf_cache = LeafCache::new(6);
+        let mut table = Btable::new(1 << 8, 1 << 4);
+        let mut retroflips = vec![];
+        for strategy in Strategy::value_variants() {
+            for (board, expected) in [
+                (overlap, BoardValidation::Overlap),
+                (missing_center, BoardValidation::MissingCenter),
+            ] {
+                let result = search_with_strategy(
+                    *strategy,
+                    &board,
+                    6,
+                    leaf_cache.leaf(),
+                    &mut table,
+                    &mut retroflips,
+                    usize::MAX,
+                    1 << 8,
+                );
+                assert_eq!(result, Err(expected), "{}", strategy.name());
+            }
+        }
+    }
+
+    /// 逆方向の 1 手で戻す石の組が、その手を実際に打ったときに返る石と一致しない局面は直前局面ではない。
+    /// 照合せずに展開すると、到達不能な盤面が `Found` になる（順方向の全列挙で見つかった盤面）
+    #[test]
+    fn every_search_rejects_a_reverse_step_that_flips_fewer_discs() {
+        use crate::othello::flip;
+        use crate::search::core::{one_step_predecessors, retrospective_flip};
+
+        // 9 石、初期局面から到達不能:
+        // ------O-
+        // ---XXO--
+        // ---OO---
+        // --OXO---
+        let board = Board::new(0x0000_0800_1800_0000, 0x0000_1418_2040_0000);
+        // 直前の手は g3 だけ
+        let preds = one_step_predecessors(&board);
+        assert_eq!(preds.len(), 1);
+        let (before, g3) = preds[0];
+        assert_eq!(g3, 22);
+        // その 8 石の局面から、f4 の手で e4 だけが返ったものとして戻した局面は
+        // `retrospective_flip` の候補に現れるが、そこで f4 に打つと e5 も（d6 の石に挟まれて）返る
+        let (f4, e4, e5) = (29, 1u64 << 28, 1u64 << 36);
+        let mut flips = [0u64; 10_000];
+        let num = retrospective_flip(f4 as u32, before.player, before.opponent, &mut flips);
+        assert!(flips[1..num].contains(&e4));
+        let not_prev = Board::new(before.opponent ^ (e4 | 1u64 << f4), before.player ^ e4);
+        assert_eq!(flip(f4, not_prev.player, not_prev.opponent), e4 | e5);
+        assert!(one_step_predecessors(&before)
+            .iter()
+            .all(|&(prev, _)| prev != not_prev));
+
+        let discs = 6;
+        let leaf_cache = LeafCache::new(discs);
+        let mut table = Btable::new(1 << 16, 1 << 8);
+        let mut retroflips = vec![];
+        for strategy in Strategy::value_variants() {
+            let (result, _) = search_with_strategy(
+                *strategy,
+                &board,
+                discs,
+                leaf_cache.leaf(),
+                &mut table,
+                &mut retroflips,
+                1_000_000,
+                1 << 16,
+            )
+            .unwrap();
+            assert_eq!(result, SearchResult::NotFound, "{}", strategy.name());
+        }
+        let leaf = make_fwd_table(&[board.player, board.opponent], discs);
+        let gbfs = parallel_retrospective_greedy_best_first_search_with(
+            &board,
+            discs,
+            &leaf,
+            1_000_000,
+            PruningConfig::SEARCH,
+            GbfsOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(gbfs, SearchResult::NotFound, "gbfs");
+        let tmp = scratch_dir("flip_set_check");
+        let cfg = BfsCfg::builder("unused")
+            .tmp_dir(&tmp)
+            .jobs(1)
+            .build()
+            .unwrap();
+        let bfs = retrospective_search_bfs(&cfg, &board, discs, leaf_cache.leaf()).unwrap();
+        let bfs_par = retrospective_search_bfs_par(&cfg, &board, discs, leaf_cache.leaf()).unwrap();
+        fs::remove_dir_all(&tmp).unwrap();
+        assert_eq!(bfs.result, SearchResult::NotFound, "bfs");
+        assert_eq!(bfs_par.result, SearchResult::NotFound, "bfs_par");
+    }
+
+    #[test]
+    fn several_inputs_are_judged_into_one_set_of_outputs() {
+        let dir = scratch_dir("several_inputs");
+        let mut rng = StdRng::seed_from_u64(688);
+        let mut inputs = vec![];
+        for (i, count) in [3, 4].into_iter().enumerate() {
+            let lines: Vec<String> = (0..count)
+                .flat_map(|j| {
+                    let b = random_reachable_board(&mut rng, 12 + j);
+                    // 1 石だけ色を変えた盤面（到達不能なものも混ざる）
+                    let bit = 1u64 << b.opponent.trailing_zeros();
+                    let altered = Board::new(b.player | bit, b.opponent & !bit);
+                    [b.to_string(), altered.to_string()]
+                })
+                .collect();
+            let input = dir.join(format!("input{}.txt", i));
+            fs::write(&input, lines.join("\n") + "\n").unwrap();
+            inputs.push(input);
+        }
+        assert_eq!(read_input_boards(&inputs).unwrap().len(), 14);
+
+        let names = OutputNames::global();
+        let run = |inputs: &[PathBuf], name: &str| {
+            let out_dir = dir.join(name);
+            run_parallel_dfs(inputs, &out_dir, 8, 1_000_000, 1 << 20, None, None).unwrap();
+            [
+                SearchResult::Found,
+                SearchResult::NotFound,
+                SearchResult::Unknown,
+            ]
+            .map(|result| {
+                fs::read_to_string(names.result_path(&out_dir, result))
+                    .unwrap()
+                    .lines()
+                    .count()
+            })
+        };
+        let first = run(&inputs[..1], "first");
+        let second = run(&inputs[1..], "second");
+        let both = run(&inputs, "both");
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(both, [0, 1, 2].map(|i| first[i] + second[i]));
+        assert_eq!(both.iter().sum::<usize>(), 14);
+        assert!(both[0] > 0 && both[1] > 0, "{:?}", both);
+    }
+
+    #[test]
+    fn rerunning_a_frontier_dir_with_another_board_starts_afresh() {
+        use crate::search::parallel_gbfs::read_positions;
+
+        let dir = scratch_dir("frontier_rerun");
+        // f5 d6 c3 d3 c4 f4 f6 f3 e6 e7（14 石）と、乱択プレイアウトの 16 石の局面
+        let first = Board::play_sequence(&[37, 43, 18, 19, 26, 29, 45, 21, 44, 52]).unwrap();
+        let second = random_reachable_board(&mut StdRng::seed_from_u64(612), 16);
+        let input = dir.join("input.txt");
+        // リーフより十分手前の 10 ノードで打ち切って `frontier_0.*` を書き出し、既訪問集合を返す
+        let run = |board: &Board, frontier_dir: &Path| {
+            fs::write(&input, board.to_string() + "\n").unwrap();
+            run_parallel_gbfs(
+                std::slice::from_ref(&input),
+                &dir.join("out"),
+                8,
+                10,
+                PruningConfig::SEARCH,
+                Some(1),
+                Some(frontier_dir),
+                0,
+                Heuristic::default(),
+                None,
+            )
+            .unwrap();
+            let files = FrontierFiles::in_dir(frontier_dir, 0);
+            let mut visited = read_positions(&files.visited).unwrap();
+            visited.sort_unstable();
+            (visited, fs::read_to_string(&files.header).unwrap())
+        };
+
+        let shared = dir.join("shared");
+        let (stale, _) = run(&first, &shared);
+        // 同じディレクトリで別の盤面を探索しても、前の盤面の frontier からは再開しない
+        let (rerun, header) = run(&second, &shared);
+        let (fresh, fresh_header) = run(&second, &dir.join("fresh"));
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_ne!(stale, fresh);
+        assert_eq!(rerun, fresh);
+        assert_eq!(header, fresh_header);
+        assert!(header.contains(&format!("{:016x}", second.unique()[0])));
+    }
+
+    #[test]
+    fn run_meta_records_the_configuration_and_verdict_counts() {
+        let dir = scratch_dir("run_meta");
+        let names = OutputNames::global();
+        fs::write(names.result_path(&dir, SearchResult::Found), "a\nb\n").unwrap();
+        fs::write(names.result_path(&dir, SearchResult::NotFound), "").unwrap();
+        let meta = RunMeta {
+            strategy: "dfs-parallel".to_string(),
+            inputs: vec![PathBuf::from("in \"1\".txt"), PathBuf::from("in2.txt")],
+            discs: Some(7),
+            node_limit: None,
+            threads: 3,
+        };
+        meta.write(&dir, Duration::from_millis(1500)).unwrap();
+        let json = fs::read_to_string(dir.join("run_meta.json")).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let lines: Vec<&str> = json.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                "{",
+                &format!("  \"version\": \"{}\",", env!("CARGO_PKG_VERSION")),
+                "  \"strategy\": \"dfs-parallel\",",
+                "  \"input\": \"in \\\"1\\\".txt\",",
+                "  \"inputs\": [\"in \\\"1\\\".txt\", \"in2.txt\"],",
+                "  \"discs\": 7,",
+                "  \"node_limit\": null,",
+                "  \"threads\": 3,",
+                "  \"elapsed_sec\": 1.500,",
+                "  \"ok\": 2,",
+                "  \"ng\": 0,",
+                // 書かれていない判定ファイルは null
+                "  \"unknown\": null",
+                "}",
+            ]
+        );
+    }
+
+    #[test]
+    fn boards_are_flagged_only_past_the_slow_threshold() {
+        let board = Board::play_sequence(&[37, 43, 18]).unwrap();
+        let log = SlowBoardLog::new(Some(Duration::from_millis(10)));
+        assert_eq!(log.threshold(), Some(Duration::from_millis(10)));
+        assert!(!log.record(&board, SearchResult::Found, 3, Duration::from_millis(9)));
+        assert!(!log.record(&board, SearchResult::Found, 3, Duration::from_millis(10)));
+        assert!(log.record(&board, SearchResult::Unknown, 3, Duration::from_millis(11)));
+        // 閾値なしなら何秒かかっても警告しない
+        let never = SlowBoardLog::default();
+        assert!(!never.is_slow(Duration::from_secs(3600)));
+
+        // 実際に探索した時間でも、閾値 0 なら必ず引っかかる
+        let leaf_cache = LeafCache::new(8);
+        let mut table = Btable::new(1 << 16, 1 << 8);
+        let mut retroflips: Vec<[u64; 10_000]> = vec![];
+        let start = Instant::now();
+        let (result, nodes) = search_with_strategy(
+            Strategy::Dfs,
+            &board,
+            8,
+            leaf_cache.leaf(),
+            &mut table,
+            &mut retroflips,
+            1_000_000,
+            1_000_000,
+        )
+        .unwrap();
+        let elapsed = start.elapsed();
+        assert!(SlowBoardLog::new(Some(Duration::ZERO)).record(&board, result, nodes, elapsed));
+    }
+}
@@ -1,11 +1,13 @@
-use crate::othello::{flip, get_moves, Board, Direction};
+use crate::othello::{flip, get_moves, has_move, Board, Direction, Ruleset};
+use crate::search::threads::ThreadConfig;
+use crate::verbosity::{DEBUG, INFO};
+use crate::vprintln;
 use dashmap::DashSet;
-use rayon::ThreadPoolBuilder;
+use std::collections::HashSet;
 use std::sync::{
     atomic::{AtomicUsize, Ordering},
     Arc,
 };
-const NUM_THREADS: usize = 64; // 64スレッド程度
 
 fn get_stable_discs(occupied: u64, t_occupied: u64) -> u64 {
     let mut ans = 0;
@@ -67,26 +69,28 @@ fn check_fwd(b: &[u64; 2], target: &[[u64; 2]; 8]) -> bool {
 /// 初期配置からdiscs手までの到達可能な序盤盤面を列挙する
 /// 確定石を使って、目的配置bへのパスが明らかに存在しない盤面を枝刈りする
 pub fn make_fwd_table(b: &[u64; 2], discs: i32) -> Vec<[u64; 2]> {
+    make_fwd_table_with_ruleset(b, discs, &Ruleset::standard())
+}
+
+/// `make_fwd_table` の初期配置を `ruleset` に置き換えた版
+pub fn make_fwd_table_with_ruleset(b: &[u64; 2], discs: i32, ruleset: &Ruleset) -> Vec<[u64; 2]> {
     let board = Board::new(b[0], b[1]);
-    println!("b=\n{}\n, discs={}", board.show(), discs);
+    vprintln!(DEBUG, "b=\n{}\n, discs={}", board.show(), discs);
     let mut target = [*b; 8];
     for i in 1..8 {
         board.board_symmetry(i, &mut target[i as usize]);
     }
-    let initial = Board::initial();
+    let initial = ruleset.initial_board();
     let mut ans = Arc::new(vec![[initial.player, initial.opponent]]);
+    let threads = ThreadConfig::global();
     for i in 4..discs {
         let visited: Arc<DashSet<[u64; 2]>> = Arc::new(DashSet::new());
         let next = Arc::new(AtomicUsize::new(0));
         let mut anslen = ans.len();
         //println!("anslen={}", anslen);
-        let pool = ThreadPoolBuilder::new()
-            .num_threads(NUM_THREADS)
-            .thread_name(|i| format!("gbfs-worker-{i}"))
-            .build()
-            .expect("failed to build thread pool");
+        let pool = threads.build_pool("fwd-table-worker");
         pool.scope(|s| {
-            for _tid in 0..NUM_THREADS {
+            for _tid in 0..threads.num_threads() {
                 let visited = visited.clone();
                 let ans = ans.clone();
                 let next = next.clone();
@@ -107,7 +111,8 @@ pub fn make_fwd_table(b: &[u64; 2], discs: i32) -> Vec<[u64; 2]> {
                             let flipped = flip(idx as usize, b[0], b[1]);
                             //println!("idx={}, flipped=0b{:b}", idx, flipped);
                             if flipped == 0 {
-                                println!(
+                                vprintln!(
+                                    DEBUG,
                                     "flipped==0, idx={}, board=\n{}",
                                     idx,
                                     Board::new(b[0], b[1]).show()
@@ -122,12 +127,12 @@ pub fn make_fwd_table(b: &[u64; 2], discs: i32) -> Vec<[u64; 2]> {
                                 //println!("ng = \n{}", Board::new(next.player, next.opponent).show());
                                 continue;
                             }
-                            let uni = next.unique();
+                            let (uni, swapped) = next.unique_with_swapped();
                             //println!("insert visited uni={}", Board::new(uni[0], uni[1]).to_string());
                             //let guard = visited.guard();
                             //visited.insert(uni, &guard);
                             visited.insert(uni);
-                            if get_moves(uni[0], uni[1]) == 0 {
+                            if !has_move(uni[0], uni[1]) {
                                 let next1 = Board {
                                     player: uni[1],
                                     opponent: uni[0],
@@ -136,7 +141,8 @@ pub fn make_fwd_table(b: &[u64; 2], discs: i32) -> Vec<[u64; 2]> {
                                     //println!("ng = \n{}", Board::new(next.player, next.opponent).show());
                                     continue;
                                 }
-                                let uni = next1.unique();
+                                // next1 は next の対称形の手番入れ替えなので unique は swapped と一致する
+                                let uni = swapped;
                                 //println!("insert visited uni={}", Board::new(uni[0], uni[1]).to_string());
                                 //let guard = visited.guard();
                                 //visited.insert(uni, &guard);
@@ -147,7 +153,7 @@ pub fn make_fwd_table(b: &[u64; 2], discs: i32) -> Vec<[u64; 2]> {
                 });
             }
         });
-        println!("before collect");
+        vprintln!(DEBUG, "before collect");
         let mut newans = vec![];
         //let guard = visited.guard();
         //for node in visited.iter(&guard) {
@@ -156,15 +162,116 @@ pub fn make_fwd_table(b: &[u64; 2], discs: i32) -> Vec<[u64; 2]> {
         for node in visited.iter() {
             newans.push(*node);
         }
-        println!("after collect()");
+        vprintln!(DEBUG, "after collect()");
         newans.sort();
 
-        println!("i={}, newans.len() = {}", i, newans.len());
+        vprintln!(INFO, "i={}, newans.len() = {}", i, newans.len());
         //for j in 0..newans.len() {
         //    println!("{}", Board::new(newans[j][0], newans[j][1]).to_string());
         //}
         ans = Arc::new(newans);
-        println!("after Arc::new(newans)");
+        vprintln!(DEBUG, "after Arc::new(newans)");
     }
     ans.to_vec()
 }
+
+/// `core::search` の並列版。初期配置から石数ごとに幅優先で展開し、
+/// (内部ノード数, discs 石のリーフ集合) を返す。リーフ集合は逐次版と一致する。
+pub fn search_forward_par(discs: i32) -> (usize, HashSet<[u64; 2]>) {
+    search_forward_par_with_ruleset(discs, &Ruleset::standard())
+}
+
+/// `search_forward_par` の初期配置を `ruleset` に置き換えた版
+pub fn search_forward_par_with_ruleset(
+    discs: i32,
+    ruleset: &Ruleset,
+) -> (usize, HashSet<[u64; 2]>) {
+    search_forward_par_from(discs, &ruleset.initial_board())
+}
+
+/// `search_forward_par` の起点を任意の局面 `seed` に置き換えた版。
+/// リーフ集合は `seed` から到達可能な `discs` 石の局面になる（`seed` の手番側がパスする局面なら
+/// パス後の向きからも展開する）。`seed` が `discs` 石以上なら `seed` 自身だけがリーフになる。
+pub fn search_forward_par_from(discs: i32, seed: &Board) -> (usize, HashSet<[u64; 2]>) {
+    let initial = *seed;
+    // 手番側に合法手がある局面だけを次の展開対象にする（パスは展開時に処理する）
+    let mut level: Vec<[u64; 2]> = if !has_move(initial.player, initial.opponent)
+        && has_move(initial.opponent, initial.player)
+    {
+        let (uni, passed) = initial.unique_with_swapped();
+        vec![uni, passed]
+    } else {
+        vec![initial.unique()]
+    };
+    let mut searched_count = 0;
+    let threads = ThreadConfig::global();
+    let pool = threads.build_pool("fwd-worker");
+    for i in (initial.popcount() as i32)..discs {
+        searched_count += level.len();
+        let frontier: Arc<Vec<[u64; 2]>> =
+            Arc::new(level.into_iter().filter(|b| has_move(b[0], b[1])).collect());
+        let visited: Arc<DashSet<[u64; 2]>> = Arc::new(DashSet::new());
+        let next = Arc::new(AtomicUsize::new(0));
+        pool.scope(|s| {
+            for _tid in 0..threads.num_threads() {
+                let visited = visited.clone();
+                let frontier = frontier.clone();
+                let next = next.clone();
+                s.spawn(move |_| loop {
+                    let j = next.fetch_add(1, Ordering::Relaxed);
+                    if j >= frontier.len() {
+                        break; // 仕事がなくなった
+                    }
+                    let b: [u64; 2] = frontier[j];
+
+                    let mut moves = get_moves(b[0], b[1]);
+                    while moves != 0 {
+                        let idx = moves.trailing_zeros();
+                        moves &= moves - 1;
+                        let flipped = flip(idx as usize, b[0], b[1]);
+                        if flipped == 0 {
+                            continue;
+                        }
+                        let child = Board {
+                            player: b[1] ^ flipped,
+                            opponent: b[0] ^ (flipped | (1u64 << idx)),
+                        };
+                        if !has_move(child.player, child.opponent)
+                            && has_move(child.opponent, child.player)
+                        {
+                            // パス後の向きも同じ対称変換から求める
+                            let (uni, passed) = child.unique_with_swapped();
+                            visited.insert(uni);
+                            visited.insert(passed);
+                        } else {
+                            visited.insert(child.unique());
+                        }
+                    }
+                });
+            }
+        });
+        level = visited.iter().map(|x| *x).collect();
+        vprintln!(INFO, "i={}, level.len() = {}", i + 1, level.len());
+    }
+    // しきい値の石数に達した局面は合法手の有無によらずすべてリーフ（逐次版 `search` と同じ定義）
+    let leaf = level.into_iter().collect();
+    (searched_count, leaf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::core::search;
+
+    #[test]
+    fn parallel_forward_search_matches_sequential() {
+        for discs in [5, 8, 10] {
+            let mut searched = HashSet::new();
+            let mut leafnode = HashSet::new();
+            search(&Board::initial(), &mut searched, &mut leafnode, discs);
+            let (count, leaf) = search_forward_par(discs);
+            assert_eq!(leaf, leafnode, "discs = {}", discs);
+            assert_eq!(count, searched.len(), "discs = {}", discs);
+        }
+    }
+}
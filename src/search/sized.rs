@@ -0,0 +1,374 @@
+//! 一辺 `width` マス（`width <= 8`）の正方盤での逆方向探索。
+//!
+//! 盤面は `Board` を流用し、行ストライド `width` で u64 の下位 `width * width` ビットに詰める
+//! （`retrospective_flip_sized` と同じ表現）。6x6 盤は状態数が小さく初期局面まで遡り切れるので、
+//! 順方向のリーフ表を使わず初期局面そのものに戻れるかを判定する。8x8 用の枝刈りは使わない。
+use std::collections::HashSet;
+
+pub use crate::othello::{shift_sized, square_board_mask};
+use crate::othello::{Board, Direction};
+use crate::search::core::{
+    retrospective_flip_sized, reverse_candidates, square_center_mask, SearchResult,
+};
+
+/// 標準配置の初期局面（中央 4 マスに斜めに交差した配置、手番側が先手）。
+/// `width == 8` では `Board::initial()` と一致する。
+pub const fn initial_board_sized(width: u32) -> Board {
+    let a = (width / 2 - 1) * width + width / 2 - 1;
+    Board {
+        player: (1u64 << (a + 1)) | (1u64 << (a + width)),
+        opponent: (1u64 << a) | (1u64 << (a + width + 1)),
+    }
+}
+
+/// `flip` の盤サイズ可変版。`pos` に `player` が打ったときに返る石の集合。
+pub fn flip_sized(pos: u32, player: u64, opponent: u64, width: u32) -> u64 {
+    let w = width as i32;
+    let xpos = (pos % width) as i32;
+    let ypos = (pos / width) as i32;
+    let mut flipped = 0u64;
+    for d in Direction::all() {
+        let (dx, dy) = d.to_offset();
+        let (mut x, mut y) = (xpos + dx, ypos + dy);
+        let mut ray = 0u64;
+        while 0 <= x && x < w && 0 <= y && y < w {
+            let bit = 1u64 << (y * w + x);
+            if opponent & bit != 0 {
+                ray |= bit;
+            } else {
+                if player & bit != 0 {
+                    flipped |= ray;
+                }
+                break;
+            }
+            x += dx;
+            y += dy;
+        }
+    }
+    flipped
+}
+
+/// `has_move` の盤サイズ可変版
+pub fn has_move_sized(player: u64, opponent: u64, width: u32) -> bool {
+    get_moves_fast_sized(player, opponent, width) != 0
+}
+
+/// `get_moves` の盤サイズ可変版。空きマスごとに `flip_sized` を呼ぶ素直な実装で、
+/// `get_moves_fast_sized` の照合用。
+pub fn get_moves_sized(player: u64, opponent: u64, width: u32) -> u64 {
+    let mut moves = 0u64;
+    let mut empty = !(player | opponent) & square_board_mask(width);
+    while empty != 0 {
+        let pos = empty.trailing_zeros();
+        empty &= empty - 1;
+        if flip_sized(pos, player, opponent, width) != 0 {
+            moves |= 1u64 << pos;
+        }
+    }
+    moves
+}
+
+/// `get_moves_sized` のビットシフト版。方向ごとに相手石の連続を広げて合法手を求める。
+/// 一辺 `width` マスなら、打った石と挟む石の間に並ぶ相手石は高々 `width - 2` 個。
+pub fn get_moves_fast_sized(player: u64, opponent: u64, width: u32) -> u64 {
+    let empty = !(player | opponent) & square_board_mask(width);
+    let mut moves = 0u64;
+    for d in Direction::all() {
+        let (dx, dy) = d.to_offset();
+        let mut run = shift_sized(player, dx, dy, width) & opponent;
+        for _ in 1..width.saturating_sub(2) {
+            run |= shift_sized(run, dx, dy, width) & opponent;
+        }
+        moves |= shift_sized(run, dx, dy, width) & empty;
+    }
+    moves
+}
+
+/// `width * width` 個の 'X', 'O', '-' からなる文字列を盤面に変換。それ以外の文字は読み飛ばす。
+pub fn parse_board_sized(line: &str, width: u32) -> Option<Board> {
+    let cells: Vec<char> = line
+        .chars()
+        .filter(|&c| c == 'X' || c == 'O' || c == '-')
+        .collect();
+    if cells.len() != (width * width) as usize {
+        return None;
+    }
+    let mut board = Board::new(0, 0);
+    for (i, c) in cells.into_iter().enumerate() {
+        match c {
+            'X' => board.player |= 1u64 << i,
+            'O' => board.opponent |= 1u64 << i,
+            _ => {}
+        }
+    }
+    Some(board)
+}
+
+/// `parse_board_sized` の逆
+pub fn board_to_string_sized(board: &Board, width: u32) -> String {
+    (0..width * width)
+        .map(|i| {
+            let m = 1u64 << i;
+            if board.player & m != 0 {
+                'X'
+            } else if board.opponent & m != 0 {
+                'O'
+            } else {
+                '-'
+            }
+        })
+        .collect()
+}
+
+/// 石の重なりが無く、中央 4 マスが埋まり、盤外のビットが立っていないか
+pub fn is_valid_sized(board: &Board, width: u32) -> bool {
+    let occupied = board.player | board.opponent;
+    board.player & board.opponent == 0
+        && occupied & !square_board_mask(width) == 0
+        && occupied & square_center_mask(width) == square_center_mask(width)
+}
+
+/// `board` から初期局面 `initial_board_sized(width)` まで逆方向に DFS する。
+///
+/// 8x8 の `retrospective_search` と違い、対称形はまとめず初期局面との完全一致だけを `Found` とし、
+/// 直前局面の候補は着手を再生して `board` に戻るものだけを辿る。
+/// `visited` は盤面そのもので引く既訪問表で、呼び出し側が盤面ごとに空にする。
+/// `retroflips` は `retrospective_search` と同じく石数ごとに使い回す作業バッファ。
+/// 展開したノード数が `node_limit` を超えたら `Unknown`。
+#[allow(clippy::too_many_arguments)]
+pub fn retrospective_search_sized(
+    board: &Board,
+    width: u32,
+    from_pass: bool,
+    visited: &mut HashSet<[u64; 2]>,
+    retroflips: &mut Vec<[u64; 10_000]>,
+    node_count: &mut usize,
+    node_limit: usize,
+) -> SearchResult {
+    if board.popcount() <= 4 {
+        return if *board == initial_board_sized(width) {
+            SearchResult::Found
+        } else {
+            SearchResult::NotFound
+        };
+    }
+    if !visited.insert([board.player, board.opponent]) {
+        return SearchResult::NotFound;
+    }
+    *node_count += 1;
+    if *node_count > node_limit {
+        return SearchResult::Unknown;
+    }
+
+    // パスの扱いは `retrospective_search` と同じく 1 回だけ遡る
+    if !from_pass && !has_move_sized(board.opponent, board.player, width) {
+        let prev = Board::new(board.opponent, board.player);
+        match retrospective_search_sized(
+            &prev, width, true, visited, retroflips, node_count, node_limit,
+        ) {
+            SearchResult::NotFound => {}
+            result => return result,
+        }
+    }
+
+    let num_disc = board.popcount() as usize;
+    if retroflips.len() <= num_disc {
+        retroflips.resize(num_disc + 1, [0u64; 10_000]);
+    }
+    let mut b = reverse_candidates(board.opponent, square_center_mask(width));
+    while b != 0 {
+        let index = b.trailing_zeros();
+        b &= b - 1;
+        let num = retrospective_flip_sized(index, board.opponent, width, &mut retroflips[num_disc]);
+        for i in 1..num {
+            let flipped = retroflips[num_disc][i];
+            let prev = Board::new(
+                board.opponent ^ (flipped | (1u64 << index)),
+                board.player ^ flipped,
+            );
+            if flip_sized(index, prev.player, prev.opponent, width) != flipped {
+                continue;
+            }
+            match retrospective_search_sized(
+                &prev, width, false, visited, retroflips, node_count, node_limit,
+            ) {
+                SearchResult::NotFound => {}
+                result => return result,
+            }
+        }
+    }
+    SearchResult::NotFound
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::XorShift;
+    use std::collections::HashMap;
+
+    const WIDTH: u32 = 6;
+
+    /// 手数ごとの局面から、着手でその局面に至る直前局面の集合への表
+    type Level = HashMap<[u64; 2], HashSet<[u64; 2]>>;
+
+    /// 初期局面から `plies` 手まで着手を全列挙する（パスは含めない）
+    fn forward_levels(plies: usize) -> Vec<Level> {
+        let initial = initial_board_sized(WIDTH);
+        let mut levels = vec![Level::from([(
+            [initial.player, initial.opponent],
+            HashSet::new(),
+        )])];
+        for _ in 0..plies {
+            let mut next = Level::new();
+            for &[player, opponent] in levels.last().unwrap().keys() {
+                let mut moves = get_moves_sized(player, opponent, WIDTH);
+                while moves != 0 {
+                    let pos = moves.trailing_zeros();
+                    moves &= moves - 1;
+                    let flipped = flip_sized(pos, player, opponent, WIDTH);
+                    next.entry([opponent ^ flipped, player ^ flipped ^ (1u64 << pos)])
+                        .or_default()
+                        .insert([player, opponent]);
+                }
+            }
+            levels.push(next);
+        }
+        levels
+    }
+
+    /// `retrospective_search_sized` と同じ手順で求めた、着手で `board` に至る直前局面の候補
+    fn reverse_predecessors(board: &Board) -> HashSet<[u64; 2]> {
+        let mut retroflips = [0u64; 10_000];
+        let mut preds = HashSet::new();
+        let mut b = reverse_candidates(board.opponent, square_center_mask(WIDTH));
+        while b != 0 {
+            let index = b.trailing_zeros();
+            b &= b - 1;
+            let num = retrospective_flip_sized(index, board.opponent, WIDTH, &mut retroflips);
+            for &flipped in retroflips.iter().take(num).skip(1) {
+                let prev = Board::new(
+                    board.opponent ^ (flipped | (1u64 << index)),
+                    board.player ^ flipped,
+                );
+                if flip_sized(index, prev.player, prev.opponent, WIDTH) == flipped {
+                    preds.insert([prev.player, prev.opponent]);
+                }
+            }
+        }
+        preds
+    }
+
+    #[test]
+    fn reverse_predecessors_match_forward_enumeration() {
+        let levels = forward_levels(8);
+        for depth in 1..levels.len() {
+            for (&[player, opponent], parents) in &levels[depth] {
+                let board = Board::new(player, opponent);
+                let preds = reverse_predecessors(&board);
+                // 逆方向の候補は到達不能な局面も含むので、1 手前に列挙された局面に絞って比べる
+                let reachable: HashSet<[u64; 2]> = preds
+                    .iter()
+                    .filter(|p| levels[depth - 1].contains_key(*p))
+                    .copied()
+                    .collect();
+                assert_eq!(
+                    &reachable,
+                    parents,
+                    "{}",
+                    board_to_string_sized(&board, WIDTH)
+                );
+                // 候補はどれも 1 手で board に戻る
+                for &[p, o] in &preds {
+                    let pos = (p | o) ^ (player | opponent);
+                    assert_eq!(pos.count_ones(), 1);
+                    let flipped = flip_sized(pos.trailing_zeros(), p, o, WIDTH);
+                    assert_ne!(flipped, 0);
+                    assert_eq!([o ^ flipped, p ^ flipped ^ pos], [player, opponent]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn forward_positions_reach_the_initial_board() {
+        let levels = forward_levels(8);
+        let mut retroflips = vec![];
+        for &[player, opponent] in levels[8].keys().step_by(25) {
+            let board = Board::new(player, opponent);
+            assert_eq!(
+                get_moves_fast_sized(player, opponent, WIDTH),
+                get_moves_sized(player, opponent, WIDTH)
+            );
+            let mut visited = HashSet::new();
+            let mut node_count = 0;
+            let result = retrospective_search_sized(
+                &board,
+                WIDTH,
+                false,
+                &mut visited,
+                &mut retroflips,
+                &mut node_count,
+                usize::MAX,
+            );
+            assert_eq!(
+                result,
+                SearchResult::Found,
+                "{}",
+                board_to_string_sized(&board, WIDTH)
+            );
+        }
+    }
+
+    /// 8x8 では `is_valid_sized` と `validate_board` が同じ盤面を受け付ける
+    #[test]
+    fn is_valid_sized_agrees_with_validate_board_on_8x8() {
+        let initial = initial_board_sized(8);
+        assert_eq!(initial, Board::initial());
+        let center = square_center_mask(8);
+        let mut rng = XorShift::new(0x3C6E_F372_FE94_F82B);
+        let mut verdicts = [0usize; 2];
+        for _ in 0..2000 {
+            let state = rng.next_u64();
+            // 初期局面から中央の石をいくつか抜き、ときどき 1 マスに両方の石を置く
+            let removed = center & state & (state >> 8);
+            let overlap = if state >> 63 != 0 {
+                1u64 << (state >> 32 & 63)
+            } else {
+                0
+            };
+            let board = Board::new(
+                (initial.player & !removed) | overlap,
+                (initial.opponent & !removed) | overlap,
+            );
+            let valid = crate::othello::validate_board(&board).is_ok();
+            assert_eq!(is_valid_sized(&board, 8), valid, "{}", board.to_string());
+            verdicts[valid as usize] += 1;
+        }
+        assert!(verdicts[0] > 0 && verdicts[1] > 0, "{:?}", verdicts);
+    }
+
+    #[test]
+    fn fast_move_generation_matches_the_per_square_reference() {
+        let mut rng = XorShift::new(0x6A09_E667_F3BC_C908);
+        let mut next = || rng.next_u64();
+        for width in [4, 6, 8] {
+            let mask = square_board_mask(width);
+            let mut with_moves = 0;
+            for _ in 0..3000 {
+                // 盤の端をまたぐ並びも出るよう、盤全体に乱択で石を置く
+                let occupied = (next() | next()) & mask;
+                let player = occupied & next();
+                let opponent = occupied & !player;
+                let fast = get_moves_fast_sized(player, opponent, width);
+                assert_eq!(fast, get_moves_sized(player, opponent, width), "{}", width);
+                assert_eq!(fast & !mask, 0);
+                if width == 8 {
+                    assert_eq!(fast, crate::othello::get_moves(player, opponent));
+                }
+                assert_eq!(has_move_sized(player, opponent, width), fast != 0);
+                with_moves += (fast != 0) as usize;
+            }
+            assert!(with_moves > 0, "{}", width);
+        }
+    }
+}
@@ -0,0 +1,113 @@
+//! 並列探索のスレッド数の設定
+use std::num::NonZeroUsize;
+use std::sync::OnceLock;
+
+use rayon::{ThreadPool, ThreadPoolBuilder};
+
+/// プロセス全体で使うスレッド数（`ThreadConfig::install` で設定する）
+static GLOBAL: OnceLock<ThreadConfig> = OnceLock::new();
+
+/// 並列探索のスレッド数。
+///
+/// 並列 DFS・GBFS・並列 BFS・順方向探索のリーフ表作成はどれも `ThreadConfig::global()` の値で
+/// ワーカを立てる。論理コア数を超える指定は警告を出して論理コア数に切り詰める。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThreadConfig {
+    num_threads: usize,
+}
+
+impl ThreadConfig {
+    /// `requested` スレッドの設定を作る。`None` または 0 なら論理コア数。
+    pub fn new(requested: Option<usize>) -> Self {
+        let available = available_parallelism();
+        let num_threads = match requested {
+            None | Some(0) => available,
+            Some(n) if n > available => {
+                eprintln!(
+                    "warning: {} threads requested but only {} are available; using {}",
+                    n, available, available
+                );
+                available
+            }
+            Some(n) => n,
+        };
+        ThreadConfig { num_threads }
+    }
+
+    pub fn num_threads(&self) -> usize {
+        self.num_threads
+    }
+
+    /// この設定をプロセス全体の設定にし、rayon のグローバルプールも同じスレッド数で作る。
+    /// 設定できるのは最初の 1 回だけで、以降に違う値を渡すと警告を出して無視する。
+    pub fn install(self) {
+        if GLOBAL.set(self).is_err() {
+            let current = GLOBAL.get().unwrap();
+            if *current != self {
+                eprintln!(
+                    "warning: thread count is already set to {}; ignoring {}",
+                    current.num_threads, self.num_threads
+                );
+            }
+            return;
+        }
+        if let Err(e) = ThreadPoolBuilder::new()
+            .num_threads(self.num_threads)
+            .build_global()
+        {
+            eprintln!("warning: failed to init global rayon pool: {}", e);
+        }
+    }
+
+    /// `install` 済みならその設定、まだなら論理コア数
+    pub fn global() -> Self {
+        GLOBAL
+            .get()
+            .copied()
+            .unwrap_or_else(|| ThreadConfig::new(None))
+    }
+
+    /// `num_threads` 本のワーカを持つ rayon のプールを作る（スレッド名は `{name}-{i}`）
+    pub fn build_pool(&self, name: &'static str) -> ThreadPool {
+        ThreadPoolBuilder::new()
+            .num_threads(self.num_threads)
+            .thread_name(move |i| format!("{name}-{i}"))
+            .build()
+            .expect("failed to build thread pool")
+    }
+}
+
+fn available_parallelism() -> usize {
+    std::thread::available_parallelism()
+        .map(NonZeroUsize::get)
+        .unwrap_or(1) // 取得失敗時のフォールバック
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_are_clamped_to_available_parallelism() {
+        let available = available_parallelism();
+        assert_eq!(ThreadConfig::new(None).num_threads(), available);
+        assert_eq!(ThreadConfig::new(Some(0)).num_threads(), available);
+        assert_eq!(ThreadConfig::new(Some(1)).num_threads(), 1);
+        assert_eq!(
+            ThreadConfig::new(Some(available + 4)).num_threads(),
+            available
+        );
+    }
+
+    #[test]
+    fn built_pool_has_the_configured_thread_count() {
+        for n in 1..=available_parallelism().min(4) {
+            let pool = ThreadConfig::new(Some(n)).build_pool("test-worker");
+            assert_eq!(pool.current_num_threads(), n);
+            let names = pool.broadcast(|_| std::thread::current().name().map(str::to_string));
+            assert!(names
+                .iter()
+                .all(|name| name.as_deref().unwrap().starts_with("test-worker-")));
+        }
+    }
+}
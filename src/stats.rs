@@ -0,0 +1,151 @@
+//! 標本から |R|（初期局面から到達可能な盤面の数）の割合を区間推定するための計算。
+//!
+//! ```
+//! use othello_complexity_rs::stats::{reachability_interval, wilson_interval};
+//!
+//! // 10 回中 5 回成功の 95% Wilson 区間は [0.2366, 0.7634]
+//! let (lower, upper) = wilson_interval(5, 10, 1.959964);
+//! assert!((lower - 0.2366).abs() < 1e-4 && (upper - 0.7634).abs() < 1e-4);
+//!
+//! // 成功 0 回でも上限は 0 にならない（10 回中 0 回なら 0.2775）
+//! let (lower, upper) = wilson_interval(0, 10, 1.959964);
+//! assert_eq!(lower, 0.0);
+//! assert!((upper - 0.2775).abs() < 1e-4);
+//!
+//! // UNKNOWN は下限では NG、上限では OK として数える
+//! let interval = reachability_interval(0, 8, 2, 0.05).unwrap();
+//! assert_eq!(interval.lower, 0.0);
+//! assert_eq!(interval.upper, wilson_interval(2, 10, interval.z).1);
+//! ```
+
+use std::io;
+
+use statrs::distribution::{ContinuousCDF, Normal};
+
+/// Wilson スコア区間 `(下限, 上限)`。`x` は成功数、`n` は標本数、`z` は標準正規分布の分位点。
+///
+/// source:
+/// - https://en.wikipedia.org/wiki/Binomial_proportion_confidence_interval#Wilson_score_interval
+/// - https://www.itl.nist.gov/div898/handbook/prc/section2/prc241.htm
+pub fn wilson_bounds(x: f64, n: f64, z: f64) -> (f64, f64) {
+    let p_hat = x / n;
+    let z2 = z * z;
+    let denom = 1.0 + z2 / n;
+    let center = p_hat + z2 / (2.0 * n);
+    let rad = z * ((p_hat * (1.0 - p_hat)) / n + z2 / (4.0 * n * n)).sqrt();
+    let lower = (center - rad) / denom;
+    let upper = (center + rad) / denom;
+    (lower, upper)
+}
+
+/// `n` 回中 `successes` 回成功したときの成功確率の Wilson スコア区間 `(下限, 上限)`。
+///
+/// `wilson_bounds` の丸め誤差で `[0, 1]` をはみ出さないように切り詰める
+/// （`successes == 0` の下限はちょうど 0、`successes == n` の上限はちょうど 1）。
+/// `n == 0` なら何も分からないので `(0.0, 1.0)`。
+pub fn wilson_interval(successes: u64, n: u64, z: f64) -> (f64, f64) {
+    assert!(successes <= n, "successes ({}) > n ({})", successes, n);
+    if n == 0 {
+        return (0.0, 1.0);
+    }
+    let (lower, upper) = wilson_bounds(successes as f64, n as f64, z);
+    let lower = if successes == 0 { 0.0 } else { lower.max(0.0) };
+    let upper = if successes == n { 1.0 } else { upper.min(1.0) };
+    (lower, upper)
+}
+
+/// 両側有意水準 `alpha` に対応する標準正規分布の分位点 `z = Φ^{-1}(1 - alpha / 2)`
+pub fn z_for_alpha(alpha: f64) -> f64 {
+    Normal::new(0.0, 1.0)
+        .unwrap()
+        .inverse_cdf(1.0 - alpha / 2.0)
+}
+
+/// `reachability_interval` の結果
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReachabilityInterval {
+    /// 到達可能な盤面の割合の下限
+    pub lower: f64,
+    /// 到達可能な盤面の割合の上限
+    pub upper: f64,
+    /// 使った分位点
+    pub z: f64,
+    /// 信頼水準（%）。`100 * (1 - alpha)`
+    pub conf_level: f64,
+}
+
+/// 一様に抽出した `ok + ng + unknown` 個の盤面の判定結果から、母集団のうち到達可能な盤面の割合の
+/// 信頼水準 `1 - alpha` の区間を求める。
+///
+/// 探索が打ち切られた UNKNOWN の盤面は到達可能かどうか分からないので、悲観的に両側へ倒す:
+/// 下限は UNKNOWN を全て NG とみなした `wilson_interval(ok, n)` の下限、
+/// 上限は全て OK とみなした `wilson_interval(ok + unknown, n)` の上限。
+/// UNKNOWN が 0 なら通常の Wilson 区間と同じで、UNKNOWN が多いほど区間は広がる。
+/// 割合に母集団の大きさ（`sampling::population_size`）を掛ければ |R| の区間になる。
+///
+/// 標本が空か `alpha` が `(0, 1)` に無ければ `InvalidInput`。
+pub fn reachability_interval(
+    ok: u64,
+    ng: u64,
+    unknown: u64,
+    alpha: f64,
+) -> io::Result<ReachabilityInterval> {
+    let n = ok + ng + unknown;
+    if n == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "sample size N = ok + ng + unknown must be > 0",
+        ));
+    }
+    if !(alpha > 0.0 && alpha < 1.0) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("alpha must be in (0,1), got {}", alpha),
+        ));
+    }
+    let z = z_for_alpha(alpha);
+    Ok(ReachabilityInterval {
+        lower: wilson_interval(ok, n, z).0,
+        upper: wilson_interval(ok + unknown, n, z).1,
+        z,
+        conf_level: 100.0 * (1.0 - alpha),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_sample_gives_the_whole_range() {
+        let (lower, upper) = wilson_interval(0, 0, z_for_alpha(0.05));
+        assert_eq!((lower, upper), (0.0, 1.0));
+        assert!(reachability_interval(0, 0, 0, 0.05).is_err());
+    }
+
+    #[test]
+    fn all_or_nothing_is_clamped_to_the_unit_interval() {
+        let z = z_for_alpha(0.05);
+        for n in [1, 2, 10, 1000, 1 << 40] {
+            let (lower, upper) = wilson_interval(0, n, z);
+            assert_eq!(lower, 0.0);
+            assert!(upper > 0.0 && upper < 1.0, "n = {}: {}", n, upper);
+            let (lower, upper) = wilson_interval(n, n, z);
+            assert!(lower > 0.0 && lower < 1.0, "n = {}: {}", n, lower);
+            assert_eq!(upper, 1.0);
+        }
+    }
+
+    #[test]
+    fn matches_the_textbook_interval() {
+        let z = z_for_alpha(0.05);
+        assert!((z - 1.959964).abs() < 1e-6, "{}", z);
+        let (lower, upper) = wilson_interval(81, 263, z);
+        assert!((lower - 0.2553).abs() < 1e-4, "{}", lower);
+        assert!((upper - 0.3662).abs() < 1e-4, "{}", upper);
+
+        let interval = reachability_interval(81, 182, 0, 0.05).unwrap();
+        assert_eq!((interval.lower, interval.upper), (lower, upper));
+        assert!((interval.conf_level - 95.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,65 @@
+//! テストで共有する再現可能な乱数・乱択プレイアウトと一時ディレクトリ。
+//! 乱数は no_std のテストでも使えるよう xorshift64 にしている。
+
+use alloc::vec::Vec;
+
+use crate::othello::{flip, get_moves, has_move, Board};
+
+#[cfg(feature = "std")]
+mod scratch;
+#[cfg(feature = "std")]
+pub(crate) use scratch::scratch_dir;
+
+/// 再現可能な疑似乱数列（xorshift64）。`seed` は 0 以外
+#[derive(Debug, Clone)]
+pub(crate) struct XorShift(u64);
+
+impl XorShift {
+    pub(crate) const fn new(seed: u64) -> Self {
+        XorShift(seed)
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// `moves` の立っているビットから 1 つ選んだマス（`moves` は 0 以外）
+    pub(crate) fn pick(&mut self, moves: u64) -> usize {
+        let mut m = moves;
+        for _ in 0..self.next_u64() % moves.count_ones() as u64 {
+            m &= m - 1;
+        }
+        m.trailing_zeros() as usize
+    }
+}
+
+/// 手番側が `pos` に打った後の局面（手番は相手に移る）
+pub(crate) fn play(b: &Board, pos: usize) -> Board {
+    let flipped = flip(pos, b.player, b.opponent);
+    Board::new(b.opponent ^ flipped, b.player ^ flipped ^ (1u64 << pos))
+}
+
+/// 初期局面から `games` 局を乱択で終局まで打ち、現れた局面を順に返す
+/// （パスした局面は、パス前と手番を入れ替えた後の両方を含む）
+pub(crate) fn playout_boards(rng: &mut XorShift, games: usize) -> Vec<Board> {
+    let mut boards = Vec::new();
+    for _ in 0..games {
+        let mut b = Board::initial();
+        loop {
+            boards.push(b);
+            let moves = get_moves(b.player, b.opponent);
+            if moves == 0 {
+                if !has_move(b.opponent, b.player) {
+                    break;
+                }
+                b = Board::new(b.opponent, b.player);
+                continue;
+            }
+            b = play(&b, rng.pick(moves));
+        }
+    }
+    boards
+}
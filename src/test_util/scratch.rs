@@ -0,0 +1,21 @@
+//! テストごとの一時ディレクトリ。結合テスト（`tests/`）からも `#[path]` で読み込むので、
+//! このファイルはクレートの他の部分に依存しない。
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// テスト用の一時ディレクトリ（呼び出し元のファイル・名前・プロセスごとに分け、前回の残りは消して作り直す）
+#[track_caller]
+pub(crate) fn scratch_dir(name: &str) -> PathBuf {
+    let caller = Path::new(std::panic::Location::caller().file());
+    let module = caller.file_stem().unwrap().to_string_lossy();
+    let dir = std::env::temp_dir().join(format!(
+        "othello_complexity_{}_{}_{}",
+        module,
+        name,
+        std::process::id()
+    ));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
@@ -0,0 +1,99 @@
+//! 診断出力の詳細度。
+//!
+//! 探索の結果はファイルに書き出すので、標準出力・標準エラーへの出力はすべて
+//! ここで設定した詳細度で絞り込む。既定値は `INFO`。
+use core::sync::atomic::{AtomicU8, Ordering};
+
+/// 結果以外は何も出さない
+pub const QUIET: u8 = 0;
+/// 進捗や集計などの情報
+pub const INFO: u8 = 1;
+/// 探索中の細かいデバッグ出力（パス検出、石数ごとのノード数など）
+pub const DEBUG: u8 = 2;
+
+static VERBOSITY: AtomicU8 = AtomicU8::new(INFO);
+
+pub fn set_verbosity(level: u8) {
+    VERBOSITY.store(level, Ordering::Relaxed);
+}
+
+pub fn verbosity() -> u8 {
+    VERBOSITY.load(Ordering::Relaxed)
+}
+
+/// 詳細度 `level` の出力を表示するかどうか
+pub fn enabled(level: u8) -> bool {
+    verbosity() >= level
+}
+
+/// 詳細度が `level` 以上のときだけ `println!` する
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! vprintln {
+    ($level:expr, $($arg:tt)*) => {
+        if $crate::verbosity::enabled($level) {
+            println!($($arg)*);
+        }
+    };
+}
+
+/// 詳細度が `level` 以上のときだけ `eprintln!` する
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! veprintln {
+    ($level:expr, $($arg:tt)*) => {
+        if $crate::verbosity::enabled($level) {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+/// no_std では出力先が無いので、引数の型検査だけして何も出さない
+#[cfg(not(feature = "std"))]
+#[macro_export]
+macro_rules! vprintln {
+    ($level:expr, $($arg:tt)*) => {
+        if false && $crate::verbosity::enabled($level) {
+            let _ = ::core::format_args!($($arg)*);
+        }
+    };
+}
+
+/// no_std では出力先が無いので、引数の型検査だけして何も出さない
+#[cfg(not(feature = "std"))]
+#[macro_export]
+macro_rules! veprintln {
+    ($level:expr, $($arg:tt)*) => {
+        $crate::vprintln!($level, $($arg)*)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quiet_level_skips_info_and_debug_output() {
+        let before = verbosity();
+        let evaluated = core::cell::Cell::new(0);
+        let count = || {
+            evaluated.set(evaluated.get() + 1);
+            evaluated.get()
+        };
+
+        set_verbosity(QUIET);
+        assert!(enabled(QUIET) && !enabled(INFO) && !enabled(DEBUG));
+        // 表示しない出力は引数も評価しない（"pass found" などの DEBUG 出力は -v 0 で何も起きない）
+        crate::vprintln!(DEBUG, "pass found {}", count());
+        crate::veprintln!(INFO, "info: {}", count());
+        let skipped = evaluated.get();
+
+        set_verbosity(DEBUG);
+        assert!(enabled(INFO) && enabled(DEBUG));
+        crate::vprintln!(DEBUG, "pass found {}", count());
+        set_verbosity(before);
+
+        assert_eq!(skipped, 0);
+        assert_eq!(evaluated.get(), if cfg!(feature = "std") { 1 } else { 0 });
+    }
+}
@@ -0,0 +1,262 @@
+//! ブラウザ向けの軽量 API (`wasm` feature)。
+//!
+//! 盤面は `Board::to_string` と同じ 64 文字 ('X' = 手番側, 'O' = 相手, '-' = 空き) で受け取る。
+//! 外部ソルバや std に依存しない部分 (`othello`, `prunings::{connectivity, occupancy, seg3}`)
+//! だけで組み立てているので、`wasm32-unknown-unknown` 向けには既定の feature を外してビルドする:
+//!
+//! ```text
+//! cargo rustc --lib --crate-type cdylib --target wasm32-unknown-unknown \
+//!     --no-default-features --features wasm --release
+//! ```
+use alloc::collections::BTreeSet;
+use alloc::vec::Vec;
+
+use wasm_bindgen::prelude::wasm_bindgen;
+
+use crate::othello::{
+    east, flip, has_move, ne, north, nw, se, south, sw, validate_board, west, Board, CENTER_MASK,
+};
+use crate::prunings::connectivity::is_connected;
+use crate::prunings::occupancy::check_occupancy;
+use crate::prunings::passes_search_prunings;
+
+/// `is_reachable_shallow` の戻り値: 初期局面まで戻れなかった
+pub const SHALLOW_NOT_FOUND: u8 = 0;
+/// `is_reachable_shallow` の戻り値: 初期局面まで戻れた
+pub const SHALLOW_FOUND: u8 = 1;
+/// `is_reachable_shallow` の戻り値: ノード数の上限で打ち切った、または盤面文字列・盤面が不正
+pub const SHALLOW_UNKNOWN: u8 = 2;
+
+/// 盤面文字列の占有パターンが `check_occupancy` を通るか。文字列が不正なら `false`。
+#[wasm_bindgen]
+pub fn check_occupancy_str(board: &str) -> bool {
+    Board::parse(board).is_some_and(|b| check_occupancy(b.player | b.opponent))
+}
+
+/// 盤面文字列の占有マスが中央から連結しているか。文字列が不正なら `false`。
+#[wasm_bindgen]
+pub fn is_connected_str(board: &str) -> bool {
+    Board::parse(board).is_some_and(|b| is_connected(b.player | b.opponent))
+}
+
+/// 盤面文字列が `check_seg3_more` を含む探索用の枝刈りを通るか。文字列が不正なら `false`。
+#[wasm_bindgen]
+pub fn passes_prunings_str(board: &str) -> bool {
+    Board::parse(board).is_some_and(|b| passes_search_prunings(b.player, b.opponent))
+}
+
+/// 順方向のリーフ表を使わず、初期局面まで逆方向に DFS する簡易版の到達可能性判定。
+/// 展開したノード数が `max_nodes` を超えたら打ち切る。
+/// 戻り値は `SHALLOW_NOT_FOUND` / `SHALLOW_FOUND` / `SHALLOW_UNKNOWN`。
+#[wasm_bindgen]
+pub fn is_reachable_shallow(board: &str, max_nodes: u32) -> u8 {
+    let Some(board) = Board::parse(board).filter(|b| validate_board(b).is_ok()) else {
+        return SHALLOW_UNKNOWN;
+    };
+    let mut visited = BTreeSet::new();
+    let mut nodes = 0u32;
+    match shallow_search(&board, false, &mut visited, &mut nodes, max_nodes) {
+        Some(true) => SHALLOW_FOUND,
+        Some(false) => SHALLOW_NOT_FOUND,
+        None => SHALLOW_UNKNOWN,
+    }
+}
+
+/// `None` は打ち切り
+fn shallow_search(
+    board: &Board,
+    from_pass: bool,
+    visited: &mut BTreeSet<[u64; 2]>,
+    nodes: &mut u32,
+    max_nodes: u32,
+) -> Option<bool> {
+    let uni = board.unique();
+    if board.popcount() <= 4 {
+        return Some(uni == Board::initial().unique());
+    }
+    if !visited.insert(uni) {
+        return Some(false);
+    }
+    *nodes += 1;
+    if *nodes > max_nodes {
+        return None;
+    }
+    if !passes_search_prunings(board.player, board.opponent) {
+        return Some(false);
+    }
+
+    // パスの扱いは `retrospective_search` と同じく 1 回だけ遡る
+    if !from_pass && !has_move(board.opponent, board.player) {
+        let prev = Board::new(board.opponent, board.player);
+        if shallow_search(&prev, true, visited, nodes, max_nodes)? {
+            return Some(true);
+        }
+    }
+
+    let mut candidates = board.opponent & !CENTER_MASK;
+    let mut flips = Vec::new();
+    while candidates != 0 {
+        let index = candidates.trailing_zeros();
+        candidates &= candidates - 1;
+        flips.clear();
+        undo_flips(index, board.opponent, &mut flips);
+        for &flipped in &flips {
+            let prev = Board::new(
+                board.opponent ^ (flipped | (1u64 << index)),
+                board.player ^ flipped,
+            );
+            // 直前の着手で実際に返るのが `flipped` ちょうどでなければ前駆局面ではない
+            if flip(index as usize, prev.player, prev.opponent) != flipped {
+                continue;
+            }
+            if shallow_search(&prev, false, visited, nodes, max_nodes)? {
+                return Some(true);
+            }
+        }
+    }
+    Some(false)
+}
+
+/// `index` に置かれた `mover` の石が直前の着手だと仮定し、その着手で返りえた石の集合を
+/// `out` に列挙する (空集合は含めない)。各方向について、`index` から続く `mover` の石のうち
+/// 先端の 1 つを挟む側として残した手前の何個かが返ったものとみなす。
+fn undo_flips(index: u32, mover: u64, out: &mut Vec<u64>) {
+    out.push(0);
+    let start = 1u64 << index;
+    for step in [east, west, north, south, ne, nw, se, sw] {
+        let mut run = Vec::new();
+        let mut x = step(start);
+        while x & mover != 0 {
+            run.push(x);
+            x = step(x);
+        }
+        if run.len() < 2 {
+            continue;
+        }
+        let base = out.len();
+        let mut acc = 0u64;
+        for &sq in &run[..run.len() - 1] {
+            acc |= sq;
+            for i in 0..base {
+                out.push(out[i] | acc);
+            }
+        }
+    }
+    out.swap_remove(0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{play, XorShift};
+
+    /// 初期局面からの再現可能な乱択プレイアウトで現れる局面と、その石を 1 つ塗り替えた局面
+    fn sample_boards() -> Vec<Board> {
+        let mut rng = XorShift::new(0x5851_F42D_4C95_7F2D);
+        let mut boards = Vec::new();
+        let mut b = Board::initial();
+        while boards.len() < 400 {
+            let moves = crate::othello::get_moves(b.player, b.opponent);
+            if moves == 0 {
+                b = if has_move(b.opponent, b.player) {
+                    Board::new(b.opponent, b.player)
+                } else {
+                    Board::initial()
+                };
+                continue;
+            }
+            b = play(&b, rng.pick(moves));
+            boards.push(b);
+            let outer = (b.player | b.opponent) & !CENTER_MASK;
+            let bit = 1u64 << (rng.next_u64() % 64);
+            if outer & bit != 0 {
+                boards.push(Board::new(b.player ^ bit, b.opponent ^ bit));
+            }
+        }
+        boards
+    }
+
+    #[test]
+    fn string_wrappers_match_native_prunings() {
+        for b in sample_boards() {
+            let s = b.to_string();
+            let occupied = b.player | b.opponent;
+            assert_eq!(check_occupancy_str(&s), check_occupancy(occupied));
+            assert_eq!(is_connected_str(&s), is_connected(occupied));
+            assert_eq!(
+                passes_prunings_str(&s),
+                passes_search_prunings(b.player, b.opponent)
+            );
+        }
+        for bad in ["", "X", "Z".repeat(64).as_str()] {
+            assert!(!check_occupancy_str(bad));
+            assert!(!is_connected_str(bad));
+            assert!(!passes_prunings_str(bad));
+            assert_eq!(is_reachable_shallow(bad, 1000), SHALLOW_UNKNOWN);
+        }
+    }
+
+    #[test]
+    fn shallow_search_stops_at_the_node_limit() {
+        // f5 d6 c3 d3 c4 f4 f6 f3 e6 e7（14 石）
+        let board = Board::play_sequence(&[37, 43, 18, 19, 26, 29, 45, 21, 44, 52]).unwrap();
+        let s = board.to_string();
+        assert_eq!(is_reachable_shallow(&s, 0), SHALLOW_UNKNOWN);
+        assert_eq!(is_reachable_shallow(&s, u32::MAX), SHALLOW_FOUND);
+        assert_eq!(
+            is_reachable_shallow(&Board::initial().to_string(), 0),
+            SHALLOW_FOUND
+        );
+    }
+
+    /// 逆方向の前駆の列挙と到達可能性の判定が、ネイティブの探索と一致する
+    #[cfg(feature = "std")]
+    #[test]
+    fn shallow_search_matches_native_search() {
+        use crate::search::core::{
+            retrospective_flip, retrospective_search, search, Btable, SearchResult,
+        };
+        use std::collections::HashSet;
+
+        let mut retroflips = [0u64; 10_000];
+        for b in sample_boards().iter().filter(|b| b.popcount() <= 24) {
+            let mut candidates = b.opponent & !CENTER_MASK;
+            while candidates != 0 {
+                let index = candidates.trailing_zeros();
+                candidates &= candidates - 1;
+                let mut flips = Vec::new();
+                undo_flips(index, b.opponent, &mut flips);
+                let num = retrospective_flip(index, b.player, b.opponent, &mut retroflips);
+                let expected: HashSet<u64> = retroflips.iter().take(num).skip(1).copied().collect();
+                assert_eq!(flips.iter().copied().collect::<HashSet<u64>>(), expected);
+            }
+        }
+
+        let mut searched = HashSet::new();
+        let mut leafnode = HashSet::new();
+        search(&Board::initial(), &mut searched, &mut leafnode, 4);
+        for b in sample_boards().iter().filter(|b| b.popcount() <= 10) {
+            let native = retrospective_search(
+                b,
+                false,
+                4,
+                &leafnode,
+                &mut Btable::new(1 << 16, 1 << 8),
+                &mut vec![],
+                &mut 0,
+                usize::MAX,
+            )
+            .unwrap();
+            let expected = match native {
+                SearchResult::Found => SHALLOW_FOUND,
+                _ => SHALLOW_NOT_FOUND,
+            };
+            assert_eq!(
+                is_reachable_shallow(&b.to_string(), u32::MAX),
+                expected,
+                "{}",
+                b.to_string()
+            );
+        }
+    }
+}
@@ -0,0 +1,80 @@
+//! `estimate` バイナリを小さな標本で実行し、出力された信頼区間を確かめる。
+
+use std::process::Command;
+
+/// `prefix` で始まる行の残り
+fn field<'a>(stdout: &'a str, prefix: &str) -> &'a str {
+    stdout
+        .lines()
+        .find_map(|line| line.strip_prefix(prefix))
+        .unwrap_or_else(|| panic!("no line starting with {:?} in:\n{}", prefix, stdout))
+}
+
+/// `[lower, upper]` を数値の組にする
+fn interval(s: &str) -> (f64, f64) {
+    let (lower, upper) = s
+        .trim()
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .split_once(", ")
+        .unwrap();
+    (lower.parse().unwrap(), upper.parse().unwrap())
+}
+
+fn run_estimate() -> String {
+    // 中央以外に 1 石の盤面（5 石）: 初期局面から到達できるのは最初の 1 手の 4 通りだけ
+    let output = Command::new(env!("CARGO_BIN_EXE_estimate"))
+        .args([
+            "--stones",
+            "1",
+            "--count",
+            "400",
+            "--seed",
+            "7",
+            "--discs",
+            "5",
+            "--table-size",
+            "65536",
+            "-v",
+            "0",
+        ])
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn prints_a_wilson_interval_containing_the_true_count() {
+    let stdout = run_estimate();
+    assert_eq!(field(&stdout, "Seed = "), "7");
+    assert_eq!(field(&stdout, "Population size = "), "1920");
+
+    let counts = field(&stdout, "Sample size = ");
+    assert!(counts.starts_with("400 "), "{}", counts);
+    let total: u64 = ["OK = ", "NG = ", "UNKNOWN = "]
+        .iter()
+        .map(|key| {
+            let rest = &counts[counts.find(key).unwrap() + key.len()..];
+            let digits: String = rest.chars().take_while(char::is_ascii_digit).collect();
+            digits.parse::<u64>().unwrap()
+        })
+        .sum();
+    assert_eq!(total, 400);
+
+    let (lower, upper) = interval(field(&stdout, "99.5% Wilson CI: "));
+    assert!(0.0 <= lower && lower <= upper && upper <= 1.0);
+    let (expected_lower, expected_upper) = interval(field(&stdout, "Expected |R| interval: "));
+    assert!((expected_lower - lower * 1920.0).abs() < 1e-3);
+    assert!((expected_upper - upper * 1920.0).abs() < 1e-3);
+    assert!(expected_lower <= 4.0 && 4.0 <= expected_upper);
+}
+
+#[test]
+fn same_seed_gives_the_same_output() {
+    assert_eq!(run_estimate(), run_estimate());
+}
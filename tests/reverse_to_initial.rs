@@ -0,0 +1,163 @@
+//! `cargo run` で起動される既定のバイナリ `reverse_to_initial` を実際に実行して確かめる。
+
+use std::fs;
+use std::process::Command;
+
+use othello_complexity_rs::othello::{flip, get_moves, Board, Ruleset};
+use othello_complexity_rs::search::sized::{
+    board_to_string_sized, flip_sized, get_moves_sized, initial_board_sized,
+};
+
+#[path = "../src/test_util/scratch.rs"]
+mod scratch;
+use scratch::scratch_dir;
+
+#[test]
+fn default_run_binary_is_reverse_to_initial() {
+    let manifest = include_str!("../Cargo.toml");
+    assert!(manifest.contains("default-run = \"reverse_to_initial\""));
+}
+
+#[test]
+fn missing_subcommand_prints_usage_and_fails() {
+    let output = Command::new(env!("CARGO_BIN_EXE_reverse_to_initial"))
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Usage"), "{}", stderr);
+    assert!(stderr.contains("dfs"), "{}", stderr);
+}
+
+#[test]
+fn search_writes_each_board_to_its_verdict_file() {
+    let dir = scratch_dir("dfs");
+    // f5 d6 c3 d3 c4 は初期局面から到達可能、中央が空いた盤面は不正なので NG に書かれる
+    let reachable = Board::play_sequence(&[37, 43, 18, 19, 26])
+        .unwrap()
+        .to_string();
+    let invalid = format!("X{}", "-".repeat(63));
+    fs::write(
+        dir.join("input.txt"),
+        format!("{}\n{}\n", reachable, invalid),
+    )
+    .unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_reverse_to_initial"))
+        .current_dir(&dir)
+        // `dfs` は既訪問テーブルを大きく確保するので、表の大きさを指定できる並列版で確かめる
+        .args([
+            "dfs-parallel",
+            "input.txt",
+            "-o",
+            "out",
+            "--discs",
+            "7",
+            "--threads",
+            "1",
+            "--table-size",
+            "65536",
+            "-v",
+            "0",
+        ])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let ok = fs::read_to_string(dir.join("out/reverse_OK.txt")).unwrap();
+    let ng = fs::read_to_string(dir.join("out/reverse_NG.txt")).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(ok.lines().collect::<Vec<_>>(), [reachable.as_str()]);
+    assert_eq!(ng.lines().collect::<Vec<_>>(), [invalid.as_str()]);
+}
+
+#[test]
+fn dfs_gives_verdicts_on_6x6_boards() {
+    let dir = scratch_dir("dfs_6x6");
+    // 6x6 の初期局面から、毎回いちばん番号の小さい合法手を 6 手打った局面
+    let mut board = initial_board_sized(6);
+    for _ in 0..6 {
+        let pos = get_moves_sized(board.player, board.opponent, 6).trailing_zeros();
+        let flipped = flip_sized(pos, board.player, board.opponent, 6);
+        board = Board::new(
+            board.opponent ^ flipped,
+            board.player ^ flipped ^ (1u64 << pos),
+        );
+    }
+    let reachable = board_to_string_sized(&board, 6);
+    // 初期局面の隅に 1 石だけ離れて置かれた盤面は、どこからも挟めないので到達できない
+    let initial = initial_board_sized(6);
+    let island = Board::new(initial.player | 1, initial.opponent);
+    let unreachable = board_to_string_sized(&island, 6);
+    fs::write(
+        dir.join("input.txt"),
+        format!("{}\n{}\n", reachable, unreachable),
+    )
+    .unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_reverse_to_initial"))
+        .current_dir(&dir)
+        .args(["dfs", "--size", "6", "input.txt", "-o", "out", "-v", "0"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let ok = fs::read_to_string(dir.join("out/reverse_OK.txt")).unwrap();
+    let ng = fs::read_to_string(dir.join("out/reverse_NG.txt")).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(ok.lines().collect::<Vec<_>>(), [reachable.as_str()]);
+    assert_eq!(ng.lines().collect::<Vec<_>>(), [unreachable.as_str()]);
+}
+
+#[test]
+fn bfs_searches_back_to_the_chosen_initial_position() {
+    let dir = scratch_dir("bfs_ruleset");
+    // 平行配置から 1 手打った局面は、標準の初期局面からは 1 手で作れない
+    let initial = Ruleset::parallel().initial_board();
+    let pos = get_moves(initial.player, initial.opponent).trailing_zeros() as usize;
+    let flipped = flip(pos, initial.player, initial.opponent);
+    let board = Board::new(
+        initial.opponent ^ flipped,
+        initial.player ^ flipped ^ (1u64 << pos),
+    );
+    fs::write(dir.join("input.txt"), format!("{}\n", board.to_string())).unwrap();
+
+    let run = |ruleset: &str| {
+        let out = format!("out_{}", ruleset);
+        let status = Command::new(env!("CARGO_BIN_EXE_reverse_to_initial"))
+            .current_dir(&dir)
+            .args([
+                "bfs",
+                "input.txt",
+                "-o",
+                &out,
+                "-t",
+                &format!("tmp_{}", ruleset),
+            ])
+            .args(["--discs", "4", "-j", "1", "--ruleset", ruleset])
+            .status()
+            .unwrap();
+        assert!(status.success());
+        fs::read_to_string(dir.join(out).join("reverse_OK.txt"))
+            .unwrap()
+            .lines()
+            .count()
+    };
+    let parallel = run("parallel");
+    let standard = run("standard");
+    let rejected = Command::new(env!("CARGO_BIN_EXE_reverse_to_initial"))
+        .current_dir(&dir)
+        .args([
+            "bfs",
+            "input.txt",
+            &format!("--ruleset={}", board.to_string()),
+        ])
+        .output()
+        .unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!((parallel, standard), (1, 0));
+    // 中央4マス以外にも石がある配置は受け付けない
+    assert!(!rejected.status.success());
+    let stderr = String::from_utf8_lossy(&rejected.stderr);
+    assert!(stderr.contains("four center squares"), "{}", stderr);
+}